@@ -0,0 +1,14 @@
+#version 330 core
+// Bloom: add the blurred highlights back onto the scene
+in vec2 TexCoords;
+out vec4 FragColor;
+
+uniform sampler2D sceneColor;
+uniform sampler2D bloom;
+uniform float intensity;
+
+void main() {
+    vec3 color = texture(sceneColor, TexCoords).rgb;
+    color += texture(bloom, TexCoords).rgb * intensity;
+    FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 330 core
+// Bloom: 3x3 tent upsample of the smaller mip, added onto the larger one
+in vec2 TexCoords;
+out vec3 FragColor;
+
+uniform sampler2D source;
+uniform vec2 texelSize;   // of `source`
+uniform float radius;     // in source texels
+
+void main() {
+    vec2 r = texelSize * radius;
+    vec3 color = texture(source, TexCoords).rgb * 4.0;
+    color += texture(source, TexCoords + vec2(-r.x, 0.0)).rgb * 2.0;
+    color += texture(source, TexCoords + vec2( r.x, 0.0)).rgb * 2.0;
+    color += texture(source, TexCoords + vec2(0.0, -r.y)).rgb * 2.0;
+    color += texture(source, TexCoords + vec2(0.0,  r.y)).rgb * 2.0;
+    color += texture(source, TexCoords + vec2(-r.x, -r.y)).rgb;
+    color += texture(source, TexCoords + vec2( r.x, -r.y)).rgb;
+    color += texture(source, TexCoords + vec2(-r.x,  r.y)).rgb;
+    color += texture(source, TexCoords + vec2( r.x,  r.y)).rgb;
+    FragColor = color / 16.0;
+}
@@ -0,0 +1,17 @@
+#version 330 core
+// Chromatic aberration: red and blue sampled further apart towards the screen edges
+in vec2 TexCoords;
+out vec4 FragColor;
+
+uniform sampler2D sceneColor;
+uniform float intensity;   // channel offset at the corners, in UV units
+
+void main() {
+    vec2 offset = (TexCoords - 0.5) * 2.0;
+    vec2 shift = offset * dot(offset, offset) * 0.5 * intensity;
+
+    float r = texture(sceneColor, TexCoords - shift).r;
+    float g = texture(sceneColor, TexCoords).g;
+    float b = texture(sceneColor, TexCoords + shift).b;
+    FragColor = vec4(r, g, b, 1.0);
+}
@@ -0,0 +1,18 @@
+#version 330 core
+// Color grading: look every display color up in a 3D LUT
+in vec2 TexCoords;
+out vec4 FragColor;
+
+uniform sampler2D sceneColor;
+uniform sampler3D lut;
+uniform float lutSize;     // entries per axis
+uniform float strength;    // 0 = ungraded, 1 = fully graded
+
+void main() {
+    vec3 color = clamp(texture(sceneColor, TexCoords).rgb, 0.0, 1.0);
+
+    // Sample at texel centers, so 0 and 1 hit the first and last entries
+    vec3 coords = color * ((lutSize - 1.0) / lutSize) + 0.5 / lutSize;
+    vec3 graded = texture(lut, coords).rgb;
+    FragColor = vec4(mix(color, graded, strength), 1.0);
+}
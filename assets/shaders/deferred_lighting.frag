@@ -0,0 +1,132 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+// G-Buffer
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform sampler2D gAlbedoSpec;
+
+// Directional Light Properties
+uniform vec3 lightDir;
+uniform vec3 lightColor;
+uniform float ambientStrength;
+uniform float diffuseStrength;
+uniform float specularStrength;
+uniform float shininess;
+
+#include "clusters.glsl"
+
+uniform vec3 viewPos;
+uniform mat4 view;
+
+// View-space depth of the pixel being lit, selects the shadow cascade
+float ViewDepth;
+
+#include "cascades.glsl"
+#include "spot_shadows.glsl"
+
+#include "ibl.glsl"
+
+#include "ssao.glsl"
+float ambientOcclusion = 1.0;
+
+// Ambient light: the environment's irradiance, or a flat term without one
+vec3 ambientLight(vec3 norm) {
+    if (hasEnvironment == 0) return ambientStrength * lightColor * ambientOcclusion;
+    return ambientStrength * environmentIntensity * environmentIrradiance(norm) * ambientOcclusion;
+}
+
+// Environment reflection with dielectric Fresnel, as blurry as the highlight of `shininess`
+vec3 environmentReflection(vec3 norm, vec3 viewDir) {
+    if (hasEnvironment == 0) return vec3(0.0);
+    float roughness = sqrt(2.0 / (shininess + 2.0));
+    vec3 specular = environmentSpecular(norm, viewDir, roughness, vec3(0.04));
+    return ambientStrength * environmentIntensity * specularStrength * specular * ambientOcclusion;
+}
+
+// Directional Light Calculation
+vec3 calcDirLight(vec3 norm, vec3 viewDir, float shadow, float specMask) {
+    vec3 lightDirNorm = normalize(-lightDir);
+    float diff = max(dot(norm, lightDirNorm), 0.0);
+    vec3 reflectDir = reflect(-lightDirNorm, norm);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), shininess);
+    
+    vec3 ambient = ambientLight(norm);
+    vec3 diffuse = diffuseStrength * diff * lightColor;
+    vec3 specular = specularStrength * spec * lightColor * specMask;
+    
+    return ambient + (1.0 - shadow) * (diffuse + specular);
+}
+
+// Spot Light Calculation
+vec3 calcSpotLight(Light light, vec3 norm, vec3 fragPos, vec3 viewDir, float specMask, float shadow) {
+    vec3 lightDir = normalize(light.position - fragPos);
+    
+    // Diffuse shading
+    float diff = max(dot(norm, lightDir), 0.0);
+    
+    // Specular shading
+    vec3 reflectDir = reflect(-lightDir, norm);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), shininess);
+    
+    // Attenuation
+    float distance = length(light.position - fragPos);
+    float attenuation = 1.0 / (light.Constant + light.Linear * distance + light.Quadratic * (distance * distance));    
+    
+    // Spotlight intensity
+    float theta = dot(lightDir, normalize(-light.direction)); 
+    float epsilon = light.CutOff - light.OuterCutOff;
+    float intensity = clamp((theta - light.OuterCutOff) / epsilon, 0.0, 1.0);
+    
+    // Combine
+    vec3 ambient = light.Ambient * light.Color * attenuation * ambientOcclusion; // Ambient always present but attenuated
+    vec3 diffuse = light.Diffuse * diff * light.Color * intensity * attenuation;
+    vec3 specular = light.Specular * spec * light.Color * intensity * attenuation * specMask;
+    
+    return ambient + (1.0 - shadow) * (diffuse + specular);
+}
+
+void main() {
+    vec4 position = texture(gPosition, TexCoords);
+    // Nothing was drawn here; leave it to the skybox
+    if (position.w == 0.0) discard;
+    
+    vec3 FragPos = position.xyz;
+    vec4 normalShadow = texture(gNormal, TexCoords);
+    vec4 albedoSpec = texture(gAlbedoSpec, TexCoords);
+    vec3 norm = normalize(normalShadow.xyz);
+    vec3 viewDir = normalize(viewPos - FragPos);
+    ViewDepth = -(view * vec4(FragPos, 1.0)).z;
+    
+    vec3 lightDirNorm = normalize(-lightDir);
+    
+    // Directional Shadow
+    float NdotL = dot(norm, lightDirNorm);
+    float shadow = 0.0;
+    if (normalShadow.w > 0.5 && NdotL > 0.0) {
+        shadow = calcShadow(FragPos, norm, lightDirNorm);
+    }
+    
+    ambientOcclusion = screenOcclusion();
+
+    // Directional Light
+    vec3 result = calcDirLight(norm, viewDir, shadow, albedoSpec.a);
+    result += environmentReflection(norm, viewDir) * albedoSpec.a;
+    
+    // Spot Lights of this cluster (point lights are added by their light volumes)
+    uvec2 cluster = clusterLights(ViewDepth);
+    for(uint i = 0u; i < cluster.y; i++) {
+        Light light = fetchLight(int(texelFetch(lightClusters, int(cluster.x + i)).r));
+        if (light.type == 1) {
+            float sShadow = 0.0;
+            if (normalShadow.w > 0.5 && dot(norm, normalize(light.position - FragPos)) > 0.0) {
+                sShadow = calcSpotShadow(light, FragPos, norm);
+            }
+            result += calcSpotLight(light, norm, FragPos, viewDir, albedoSpec.a, sShadow);
+        }
+    }
+    
+    FragColor = vec4(result * albedoSpec.rgb, 1.0);
+}
@@ -0,0 +1,25 @@
+#version 330 core
+// Ease last frame's exposure towards the one that maps the average luminance to `key`
+out float Exposure;
+
+uniform sampler2D luminance;        // log luminance, averaged in its last mip
+uniform float luminanceLod;
+uniform sampler2D previousExposure; // 1x1
+uniform int hasHistory;
+uniform float adaptation;           // fraction of the way to move this frame
+
+uniform float key;
+uniform float minExposure;
+uniform float maxExposure;
+
+void main() {
+    float average = exp(textureLod(luminance, vec2(0.5), luminanceLod).r);
+    float target = clamp(key / average, minExposure, maxExposure);
+
+    if (hasHistory == 0) {
+        Exposure = target;
+        return;
+    }
+    float previous = texelFetch(previousExposure, ivec2(0), 0).r;
+    Exposure = mix(previous, target, adaptation);
+}
@@ -0,0 +1,47 @@
+#version 330 core
+// FXAA: blur along edges found from luma contrast (Lottes' compact variant)
+in vec2 TexCoords;
+out vec4 FragColor;
+
+uniform sampler2D sceneColor;
+uniform vec2 texelSize;
+uniform float spanMax;    // longest blur, in pixels
+uniform float reduceMul;  // shortens the span on low contrast
+uniform float reduceMin;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+void main() {
+    vec3 rgbNW = texture(sceneColor, TexCoords + vec2(-1.0, -1.0) * texelSize).rgb;
+    vec3 rgbNE = texture(sceneColor, TexCoords + vec2( 1.0, -1.0) * texelSize).rgb;
+    vec3 rgbSW = texture(sceneColor, TexCoords + vec2(-1.0,  1.0) * texelSize).rgb;
+    vec3 rgbSE = texture(sceneColor, TexCoords + vec2( 1.0,  1.0) * texelSize).rgb;
+    vec3 rgbM = texture(sceneColor, TexCoords).rgb;
+
+    float lumaNW = luma(rgbNW);
+    float lumaNE = luma(rgbNE);
+    float lumaSW = luma(rgbSW);
+    float lumaSE = luma(rgbSE);
+    float lumaM = luma(rgbM);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    // Blur direction: across the luma gradient
+    vec2 dir = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+                     ((lumaNW + lumaSW) - (lumaNE + lumaSE)));
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * reduceMul, reduceMin);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-spanMax), vec2(spanMax)) * texelSize;
+
+    vec3 rgbA = 0.5 * (texture(sceneColor, TexCoords + dir * (1.0 / 3.0 - 0.5)).rgb +
+                       texture(sceneColor, TexCoords + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (texture(sceneColor, TexCoords + dir * -0.5).rgb +
+                                     texture(sceneColor, TexCoords + dir * 0.5).rgb);
+
+    // The wider sample ran past the edge: keep the narrow one
+    float lumaB = luma(rgbB);
+    vec3 color = (lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB;
+    FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,29 @@
+#version 330 core
+layout (location = 0) out vec4 gPosition;   // xyz world position, w = 1 where covered
+layout (location = 1) out vec4 gNormal;     // xyz world normal, w = receives shadows
+layout (location = 2) out vec4 gAlbedoSpec; // rgb albedo, a = specular intensity
+
+in vec3 Normal;
+in vec3 FragPos;
+
+uniform vec3 viewPos;
+uniform vec3 objectColor;
+uniform float opacity;
+uniform float alphaCutoff;           // discard below this alpha (cutout materials), else 0
+uniform int u_UseShadows;
+
+void main() {
+    if (opacity < alphaCutoff) {
+        discard;
+    }
+    vec3 norm = normalize(Normal);
+    
+    // Two-sided lighting
+    if (dot(norm, viewPos - FragPos) < 0.0) {
+        norm = -norm;
+    }
+    
+    gPosition = vec4(FragPos, 1.0);
+    gNormal = vec4(norm, u_UseShadows != 0 ? 1.0 : 0.0);
+    gAlbedoSpec = vec4(objectColor, 1.0);
+}
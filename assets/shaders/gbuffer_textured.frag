@@ -0,0 +1,88 @@
+#version 330 core
+layout (location = 0) out vec4 gPosition;   // xyz world position, w = 1 where covered
+layout (location = 1) out vec4 gNormal;     // xyz world normal, w = receives shadows
+layout (location = 2) out vec4 gAlbedoSpec; // rgb albedo, a = specular intensity
+
+in vec3 Normal;
+in vec3 Tangent;
+in vec3 Bitangent;
+in vec3 FragPos;
+in vec2 TexCoord;
+
+uniform vec3 viewPos;
+uniform sampler2D u_Texture;
+uniform float opacity;
+uniform float alphaCutoff;           // discard below this alpha (cutout materials), else 0
+uniform sampler2D normalMap;   // tangent space
+uniform int hasNormalMap;
+uniform float normalScale;
+
+// Material factors; the maps multiply them where present
+uniform vec3 diffuseColor;           // tints u_Texture
+uniform vec3 specularColor;
+uniform sampler2D specularMap;
+uniform int hasSpecularMap;
+uniform int u_UseShadows;
+
+uniform int u_IsRepeated;
+uniform vec2 u_UVScale;
+
+// Tangent-space normal map applied around N, using the interpolated vertex tangent
+// frame, or one from screen-space derivatives when the mesh has no tangents
+vec3 perturbNormal(vec3 N, vec2 uv) {
+    vec3 tangentNormal = texture(normalMap, uv).xyz * 2.0 - 1.0;
+    tangentNormal.xy *= normalScale;
+
+    vec3 T = Tangent - N * dot(N, Tangent);
+    vec3 B;
+    if (dot(T, T) > 1e-8 && dot(Bitangent, Bitangent) > 1e-8) {
+        T = normalize(T);
+        B = cross(N, T) * (dot(cross(N, T), Bitangent) < 0.0 ? -1.0 : 1.0);
+    } else {
+        vec3 dp1 = dFdx(FragPos);
+        vec3 dp2 = dFdy(FragPos);
+        vec2 duv1 = dFdx(uv);
+        vec2 duv2 = dFdy(uv);
+
+        vec3 dp2perp = cross(dp2, N);
+        vec3 dp1perp = cross(N, dp1);
+        T = dp2perp * duv1.x + dp1perp * duv2.x;
+        B = dp2perp * duv1.y + dp1perp * duv2.y;
+        float invMax = inversesqrt(max(max(dot(T, T), dot(B, B)), 1e-12));
+        T *= invMax;
+        B *= invMax;
+    }
+    return normalize(mat3(T, B, N) * tangentNormal);
+}
+
+void main() {
+    vec2 coords = TexCoord;
+    if (u_IsRepeated != 0) {
+        coords.x *= u_UVScale.x;
+        coords.y *= u_UVScale.y;
+    }
+    vec4 texColor = texture(u_Texture, coords);
+    texColor.a *= opacity;
+    if (texColor.a < alphaCutoff) {
+        discard;
+    }
+    
+    vec3 norm = normalize(Normal);
+    
+    // Two-sided lighting
+    if (dot(norm, viewPos - FragPos) < 0.0) {
+        norm = -norm;
+    }
+    if (hasNormalMap != 0) {
+        norm = perturbNormal(norm, coords);
+    }
+    
+    vec3 specular = specularColor;
+    if (hasSpecularMap != 0) {
+        specular *= texture(specularMap, coords).rgb;
+    }
+    
+    gPosition = vec4(FragPos, 1.0);
+    gNormal = vec4(norm, u_UseShadows != 0 ? 1.0 : 0.0);
+    gAlbedoSpec = vec4(texColor.rgb * diffuseColor, dot(specular, vec3(1.0 / 3.0)));
+}
@@ -0,0 +1,75 @@
+#include "shadows.glsl"
+
+// Directional shadow cascades; the includer declares `ViewDepth`, the view-space depth
+// of the fragment, which picks the cascade
+uniform sampler2DArray shadowMap;
+uniform sampler2DArray shadowMoments; // blurred moments of the cascades
+uniform ShadowParams dirShadow;
+uniform mat4 lightSpaceMatrices[MAX_CASCADES];
+uniform float cascadeSplits[MAX_CASCADES];
+uniform float cascadeTexelSizes[MAX_CASCADES];
+uniform int cascadeCount;
+uniform float cascadeBlend;
+
+// Directional shadow from one cascade, filtered as `dirShadow` asks
+float calcCascadeShadow(int cascade, vec3 fragPos, vec3 normal, vec3 lightDirNorm) {
+    // Towards the light by the bias, and out along the normal by texels of this cascade
+    // (more at grazing angles)
+    float NdotL = clamp(dot(normal, lightDirNorm), 0.0, 1.0);
+    float texel = cascadeTexelSizes[cascade];
+    vec3 offsetPos = fragPos + lightDirNorm * dirShadow.bias
+        + normal * texel * dirShadow.normalOffset * (1.0 + 1.5 * (1.0 - NdotL));
+    mat4 lightSpaceMatrix = lightSpaceMatrices[cascade];
+    vec4 fragPosLightSpace = lightSpaceMatrix * vec4(offsetPos, 1.0);
+    vec3 projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
+    projCoords = projCoords * 0.5 + 0.5;
+    
+    if(projCoords.z > 1.0) return 0.0;
+    if(projCoords.x < 0.0 || projCoords.x > 1.0 || projCoords.y < 0.0 || projCoords.y > 1.0) return 0.0;
+    
+    vec3 coords = vec3(projCoords.xy, float(cascade));
+    float depth = projCoords.z;
+    if (dirShadow.filterMode == 3) return varianceShadow(texture(shadowMoments, coords).rg, depth);
+    if (dirShadow.filterMode == 4) return exponentialShadow(texture(shadowMoments, coords).r, depth);
+    
+    float radius = dirShadow.radius;
+    if (dirShadow.filterMode == 2) {
+        // Penumbra grows with the distance from the blockers to the receiver: the world
+        // depth the cascade spans, scaled by the light size
+        float blocker = blockerDepth(shadowMap, coords, depth, PCSS_SEARCH_TEXELS);
+        if (blocker < 0.0) return 0.0;
+        vec3 depthRow = vec3(lightSpaceMatrix[0][2], lightSpaceMatrix[1][2], lightSpaceMatrix[2][2]);
+        float depthRange = 2.0 / length(depthRow);
+        float penumbra = (depth - blocker) * depthRange * dirShadow.lightSize;
+        radius = min(radius + penumbra / texel, PCSS_MAX_TEXELS);
+    }
+    return pcfShadow(shadowMap, coords, depth, radius, dirShadow.filterMode == 0);
+}
+
+// Calculate Directional Shadow: pick the cascade by view depth, blending into the
+// next one near its far end (and fading out past the last one)
+float calcShadow(vec3 fragPos, vec3 normal, vec3 lightDirNorm) {
+    int cascade = -1;
+    for(int i = 0; i < cascadeCount; ++i) {
+        if(ViewDepth < cascadeSplits[i]) {
+            cascade = i;
+            break;
+        }
+    }
+    if(cascade < 0) return 0.0;
+    
+    float shadow = calcCascadeShadow(cascade, fragPos, normal, lightDirNorm);
+    
+    float splitNear = cascade == 0 ? 0.0 : cascadeSplits[cascade - 1];
+    float splitFar = cascadeSplits[cascade];
+    float blendStart = splitFar - (splitFar - splitNear) * cascadeBlend;
+    if(ViewDepth > blendStart) {
+        float next = 0.0;
+        if(cascade + 1 < cascadeCount) {
+            next = calcCascadeShadow(cascade + 1, fragPos, normal, lightDirNorm);
+        }
+        shadow = mix(shadow, next, (ViewDepth - blendStart) / (splitFar - blendStart));
+    }
+    
+    return shadow;
+}
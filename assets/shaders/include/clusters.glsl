@@ -0,0 +1,75 @@
+#include "shadows.glsl"
+
+// Clustered Lights: point and spot lights binned per view-space cluster
+uniform samplerBuffer lightData;     // 7 texels per light, see fetchLight
+uniform usamplerBuffer lightClusters; // per cluster: offset of its light indices, count; then the indices
+uniform vec3 clusterDims;            // tiles across, tiles down, depth slices
+uniform vec2 clusterScreenSize;
+uniform float clusterNear;
+uniform float clusterSliceScale;
+
+struct Light {
+    vec3 position;
+    int type; // 0 = point, 1 = spot
+    vec3 direction;
+    float CutOff;
+    float OuterCutOff;
+  
+    float Constant;
+    float Linear;
+    float Quadratic;
+  
+    vec3 Color;
+    float Ambient;
+    float Diffuse;
+    float Specular;
+    float Shininess;
+    int shadowSlot;    // point light cube map or spot light shadow layer, -1 for none
+    float shadowRange; // far plane of the light's shadow map
+    float shadowTexel; // shadow texel size one unit from the light
+    vec3 shadowOrigin; // point light: where its cube map was rendered from
+    ShadowParams shadow;
+};
+
+Light fetchLight(int index) {
+    int base = index * 7;
+    vec4 t0 = texelFetch(lightData, base);     // position, type
+    vec4 t1 = texelFetch(lightData, base + 1); // color, range
+    vec4 t2 = texelFetch(lightData, base + 2); // ambient, diffuse, specular, shininess
+    vec4 t3 = texelFetch(lightData, base + 3); // constant, linear, quadratic, outer cutoff
+    vec4 t4 = texelFetch(lightData, base + 4); // spot: direction, cutoff; point: shadow origin
+    vec4 t5 = texelFetch(lightData, base + 5); // shadow slot, shadow range, shadow texel size, shadow filter
+    vec4 t6 = texelFetch(lightData, base + 6); // shadow bias, normal offset, filter radius, light size
+    
+    Light light;
+    light.position = t0.xyz;
+    light.type = int(t0.w);
+    light.direction = t4.xyz;
+    light.CutOff = t4.w;
+    light.OuterCutOff = t3.w;
+    light.Constant = t3.x;
+    light.Linear = t3.y;
+    light.Quadratic = t3.z;
+    light.Color = t1.rgb;
+    light.Ambient = t2.x;
+    light.Diffuse = t2.y;
+    light.Specular = t2.z;
+    light.Shininess = t2.w;
+    light.shadowSlot = int(t5.x);
+    light.shadowRange = t5.y;
+    light.shadowTexel = t5.z;
+    light.shadowOrigin = t4.xyz;
+    light.shadow = ShadowParams(int(t5.w), t6.x, t6.y, t6.z, t6.w);
+    return light;
+}
+
+// Offset and count of this fragment's light indices in lightClusters
+uvec2 clusterLights(float viewDepth) {
+    ivec3 dims = ivec3(clusterDims);
+    ivec2 tile = ivec2(gl_FragCoord.xy / clusterScreenSize * vec2(dims.xy));
+    tile = clamp(tile, ivec2(0), dims.xy - 1);
+    int slice = int(floor(log(viewDepth / clusterNear) * clusterSliceScale));
+    slice = clamp(slice, 0, dims.z - 1);
+    int cluster = tile.x + tile.y * dims.x + slice * dims.x * dims.y;
+    return uvec2(texelFetch(lightClusters, 2 * cluster).r, texelFetch(lightClusters, 2 * cluster + 1).r);
+}
@@ -0,0 +1,22 @@
+// Image-Based Lighting: irradiance and prefiltered maps baked from the skybox
+uniform samplerCube irradianceMap;
+uniform samplerCube prefilteredMap;  // one mip per roughness step
+uniform sampler2D brdfLUT;           // specular scale and bias over NdotV and roughness
+uniform int hasEnvironment;
+uniform float prefilteredMaxLod;
+uniform float environmentIntensity;
+
+// Diffuse light reaching normal `N` from the environment
+vec3 environmentIrradiance(vec3 N) {
+    return texture(irradianceMap, N).rgb;
+}
+
+// Specular light reflected towards `V` from the environment at `roughness`, with Fresnel
+// reflectance `F` at normal incidence (the split-sum approximation)
+vec3 environmentSpecular(vec3 N, vec3 V, float roughness, vec3 F) {
+    float NdotV = max(dot(N, V), 0.0);
+    vec3 R = reflect(-V, N);
+    vec3 prefiltered = textureLod(prefilteredMap, R, roughness * prefilteredMaxLod).rgb;
+    vec2 brdf = texture(brdfLUT, vec2(NdotV, roughness)).rg;
+    return prefiltered * (F * brdf.x + brdf.y);
+}
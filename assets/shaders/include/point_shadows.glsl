@@ -0,0 +1,37 @@
+#include "clusters.glsl"
+
+// Point light shadows: one cube map per shadow-casting point light (as many as the
+// context has texture units for, see config::rendering::texture_units)
+#if MAX_POINT_SHADOWS > 0
+uniform samplerCube pointShadowMaps[MAX_POINT_SHADOWS];
+#endif
+
+// Point light shadow from its cube map, filtered as `light.shadow` asks
+float calcPointShadow(vec3 fragPos, vec3 normal, Light light, samplerCube depthMap) {
+    return cubeShadow(depthMap, light.shadowOrigin, light.shadowRange, light.shadowTexel, light.shadow,
+                      fragPos, normal);
+}
+
+// Shadow of `light` from its point shadow cube map (none for `shadowSlot` -1). Sampler
+// arrays can only be indexed by constants.
+float calcPointShadowSlot(Light light, vec3 fragPos, vec3 normal) {
+#if MAX_POINT_SHADOWS > 0
+    if (light.shadowSlot == 0) return calcPointShadow(fragPos, normal, light, pointShadowMaps[0]);
+#endif
+#if MAX_POINT_SHADOWS > 1
+    if (light.shadowSlot == 1) return calcPointShadow(fragPos, normal, light, pointShadowMaps[1]);
+#endif
+#if MAX_POINT_SHADOWS > 2
+    if (light.shadowSlot == 2) return calcPointShadow(fragPos, normal, light, pointShadowMaps[2]);
+#endif
+#if MAX_POINT_SHADOWS > 3
+    if (light.shadowSlot == 3) return calcPointShadow(fragPos, normal, light, pointShadowMaps[3]);
+#endif
+#if MAX_POINT_SHADOWS > 4
+    if (light.shadowSlot == 4) return calcPointShadow(fragPos, normal, light, pointShadowMaps[4]);
+#endif
+#if MAX_POINT_SHADOWS > 5
+    if (light.shadowSlot == 5) return calcPointShadow(fragPos, normal, light, pointShadowMaps[5]);
+#endif
+    return 0.0;
+}
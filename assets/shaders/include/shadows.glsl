@@ -0,0 +1,202 @@
+// Shadow filtering shared by the lit shaders: the per-light filter parameters, PCF
+// (grid and rotated Poisson disk), the PCSS blocker search, variance and exponential
+// shadows, and the filtered lookup of a point light's cube map. Depth is compared here
+// rather than through comparison samplers, which would take a texture unit per map.
+
+// Shadow filtering of one light (see ShadowSettings)
+struct ShadowParams {
+    int filterMode;     // 0 = grid PCF, 1 = Poisson PCF, 2 = PCSS, 3 = variance, 4 = exponential
+    float bias;         // world distance the receiver is moved towards the light
+    float normalOffset; // shadow map texels the receiver is moved along its normal
+    float radius;       // filter radius in texels
+    float lightSize;    // PCSS: light size, widening the penumbra with blocker distance
+};
+
+// PCSS blocker search radius and widest penumbra, in texels
+const float PCSS_SEARCH_TEXELS = 8.0;
+const float PCSS_MAX_TEXELS = 16.0;
+
+const vec2 poissonDisk[16] = vec2[](
+    vec2(-0.94201624, -0.39906216), vec2( 0.94558609, -0.76890725),
+    vec2(-0.09418410, -0.92938870), vec2( 0.34495938,  0.29387760),
+    vec2(-0.91588581,  0.45771432), vec2(-0.81544232, -0.87912464),
+    vec2(-0.38277543,  0.27676845), vec2( 0.97484398,  0.75648379),
+    vec2( 0.44323325, -0.97511554), vec2( 0.53742981, -0.47373420),
+    vec2(-0.26496911, -0.41893023), vec2( 0.79197514,  0.19090188),
+    vec2(-0.24188840,  0.99706507), vec2(-0.81409955,  0.91437590),
+    vec2( 0.19984126,  0.78641367), vec2( 0.14383161, -0.14100790)
+);
+
+// Per-pixel rotation of the Poisson disk (interleaved gradient noise), trading banding
+// for noise
+mat2 poissonRotation() {
+    float angle = 6.2831853 * fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+    float s = sin(angle);
+    float c = cos(angle);
+    return mat2(c, s, -s, c);
+}
+
+// Whether texel `texel` of `layer` is at or behind `depth`; outside the map counts as lit
+float compareTexel(sampler2DArray map, ivec2 texel, int layer, ivec2 size, float depth) {
+    if (any(lessThan(texel, ivec2(0))) || any(greaterThanEqual(texel, size))) return 1.0;
+    return step(depth, texelFetch(map, ivec3(texel, layer), 0).r);
+}
+
+// Lit fraction at `coords` (uv, layer) against `depth`: the four nearest texels compared
+// and blended bilinearly, as a comparison sampler with linear filtering does
+float compareBilinear(sampler2DArray map, vec3 coords, float depth) {
+    ivec2 size = textureSize(map, 0).xy;
+    vec2 pos = coords.xy * vec2(size) - 0.5;
+    ivec2 base = ivec2(floor(pos));
+    vec2 f = pos - floor(pos);
+    int layer = int(coords.z);
+    float l00 = compareTexel(map, base, layer, size, depth);
+    float l10 = compareTexel(map, base + ivec2(1, 0), layer, size, depth);
+    float l01 = compareTexel(map, base + ivec2(0, 1), layer, size, depth);
+    float l11 = compareTexel(map, base + ivec2(1, 1), layer, size, depth);
+    return mix(mix(l00, l10, f.x), mix(l01, l11, f.x), f.y);
+}
+
+// Shadowed fraction within `radius` texels of `coords` (uv, layer) against `depth`: a
+// 3x3 grid of bilinear comparisons, or the rotated Poisson disk
+float pcfShadow(sampler2DArray map, vec3 coords, float depth, float radius, bool grid) {
+    vec2 texel = radius / vec2(textureSize(map, 0).xy);
+    float lit = 0.0;
+    if (grid) {
+        for(int x = -1; x <= 1; ++x) {
+            for(int y = -1; y <= 1; ++y) {
+                lit += compareBilinear(map, vec3(coords.xy + vec2(x, y) * texel, coords.z), depth);
+            }
+        }
+        return 1.0 - lit / 9.0;
+    }
+    mat2 rotation = poissonRotation();
+    for(int i = 0; i < 16; ++i) {
+        lit += compareBilinear(map, vec3(coords.xy + rotation * poissonDisk[i] * texel, coords.z), depth);
+    }
+    return 1.0 - lit / 16.0;
+}
+
+// Average depth of the blockers within `radius` texels of `coords`, -1 for none
+float blockerDepth(sampler2DArray map, vec3 coords, float depth, float radius) {
+    vec2 texel = radius / vec2(textureSize(map, 0).xy);
+    mat2 rotation = poissonRotation();
+    float sum = 0.0;
+    int count = 0;
+    for(int i = 0; i < 16; ++i) {
+        float blocker = texture(map, vec3(coords.xy + rotation * poissonDisk[i] * texel, coords.z)).r;
+        if (blocker < depth) {
+            sum += blocker;
+            count++;
+        }
+    }
+    return count == 0 ? -1.0 : sum / float(count);
+}
+
+// Variance shadow: Chebyshev's bound on the lit fraction, with the lowest
+// VSM_BLEED_REDUCTION of it cut off against light bleeding
+float varianceShadow(vec2 moments, float depth) {
+    if (depth <= moments.x) return 0.0;
+    float variance = max(moments.y - moments.x * moments.x, 0.00002);
+    float d = depth - moments.x;
+    float lit = variance / (variance + d * d);
+    return 1.0 - clamp((lit - VSM_BLEED_REDUCTION) / (1.0 - VSM_BLEED_REDUCTION), 0.0, 1.0);
+}
+
+// Exponential shadow from the blurred exp(ESM_EXPONENT * occluder depth)
+float exponentialShadow(float moment, float depth) {
+    return 1.0 - clamp(moment * exp(-ESM_EXPONENT * depth), 0.0, 1.0);
+}
+
+// Lit fraction along `dir` against `reference` (depth over the range): the four texels
+// of the cube face around it compared and blended bilinearly. Neighbors past the face's
+// edge are read from the adjacent face.
+float compareCube(samplerCube map, vec3 dir, float reference) {
+    // Axes spanning the face `dir` points at
+    vec3 a = abs(dir);
+    vec3 s;
+    vec3 t;
+    float major;
+    if (a.x >= a.y && a.x >= a.z) {
+        major = a.x; s = vec3(0.0, 0.0, 1.0); t = vec3(0.0, 1.0, 0.0);
+    } else if (a.y >= a.z) {
+        major = a.y; s = vec3(1.0, 0.0, 0.0); t = vec3(0.0, 0.0, 1.0);
+    } else {
+        major = a.z; s = vec3(1.0, 0.0, 0.0); t = vec3(0.0, 1.0, 0.0);
+    }
+    vec3 onFace = dir / major;
+    vec3 axis = onFace - s * dot(onFace, s) - t * dot(onFace, t);
+    
+    // Texel centers around the point, in the face's [-1, 1] coordinates
+    float size = float(textureSize(map, 0).x);
+    vec2 pos = (vec2(dot(onFace, s), dot(onFace, t)) * 0.5 + 0.5) * size - 0.5;
+    vec2 base = floor(pos);
+    vec2 f = pos - base;
+    vec2 c0 = (base + 0.5) / size * 2.0 - 1.0;
+    vec2 c1 = c0 + 2.0 / size;
+    float l00 = step(reference, textureLod(map, axis + s * c0.x + t * c0.y, 0.0).r);
+    float l10 = step(reference, textureLod(map, axis + s * c1.x + t * c0.y, 0.0).r);
+    float l01 = step(reference, textureLod(map, axis + s * c0.x + t * c1.y, 0.0).r);
+    float l11 = step(reference, textureLod(map, axis + s * c1.x + t * c1.y, 0.0).r);
+    return mix(mix(l00, l10, f.x), mix(l01, l11, f.x), f.y);
+}
+
+// Shadow of `fragPos` from a point light cube map rendered from `origin` out to `range`,
+// filtered as `params` asks. Cube maps keep no moments, so their lights are sent Poisson
+// PCF instead of variance or exponential.
+float cubeShadow(samplerCube depthMap, vec3 origin, float range, float shadowTexel,
+                 ShadowParams params, vec3 fragPos, vec3 normal) {
+    vec3 toLight = origin - fragPos;
+    float distance = length(toLight);
+    
+    // Skip if out of range
+    if (distance > range) return 0.0;
+    
+    // Normal offset in texels at this distance from the light (more at grazing angles)
+    float NdotL = clamp(dot(normal, toLight / distance), 0.0, 1.0);
+    float texel = shadowTexel * distance;
+    vec3 offsetPos = fragPos + normal * texel * params.normalOffset * (1.0 + 1.5 * (1.0 - NdotL));
+    vec3 fragToLight = offsetPos - origin;
+    float depth = length(fragToLight) - params.bias;
+    
+    // Taps spread over the plane facing the light, `shadowTexel` per texel
+    vec3 axis = normalize(fragToLight);
+    vec3 tangent = normalize(cross(axis, abs(axis.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0)));
+    vec3 bitangent = cross(axis, tangent);
+    mat2 rotation = poissonRotation();
+    
+    float radius = params.radius;
+    if (params.filterMode == 2) {
+        // Penumbra grows with the distance from the blockers to the receiver
+        float blockers = 0.0;
+        int count = 0;
+        for(int i = 0; i < 16; ++i) {
+            vec2 offset = rotation * poissonDisk[i] * PCSS_SEARCH_TEXELS * shadowTexel;
+            float blocker = texture(depthMap, axis + tangent * offset.x + bitangent * offset.y).r * range;
+            if (blocker < depth) {
+                blockers += blocker;
+                count++;
+            }
+        }
+        if (count == 0) return 0.0;
+        float blocker = blockers / float(count);
+        radius = min(radius + (depth - blocker) / blocker * params.lightSize / texel, PCSS_MAX_TEXELS);
+    }
+    
+    float reference = depth / range;
+    float lit = 0.0;
+    if (params.filterMode == 0) {
+        for(int x = -1; x <= 1; ++x) {
+            for(int y = -1; y <= 1; ++y) {
+                vec3 offset = (tangent * float(x) + bitangent * float(y)) * radius * shadowTexel;
+                lit += compareCube(depthMap, axis + offset, reference);
+            }
+        }
+        return 1.0 - lit / 9.0;
+    }
+    for(int i = 0; i < 16; ++i) {
+        vec2 offset = rotation * poissonDisk[i] * radius * shadowTexel;
+        lit += compareCube(depthMap, axis + tangent * offset.x + bitangent * offset.y, reference);
+    }
+    return 1.0 - lit / 16.0;
+}
@@ -0,0 +1,58 @@
+#include "clusters.glsl"
+
+// Spot light shadows: one perspective depth layer per shadow-casting spot light
+uniform sampler2DArray spotShadowMaps;
+uniform sampler2DArray spotShadowMoments;
+uniform mat4 spotLightSpaceMatrices[MAX_SPOT_SHADOWS];
+
+// View depth of depth `depth` of a spot shadow layer reaching `range` (same near plane as
+// the layer's projection)
+float spotViewDepth(float depth, float range) {
+    float near = min(range * 0.05, 1.0);
+    float z = depth * 2.0 - 1.0;
+    return 2.0 * near * range / (range + near - z * (range - near));
+}
+
+// Shadow of `light` from its spot shadow layer (none for `shadowSlot` -1), filtered as
+// `light.shadow` asks
+float calcSpotShadow(Light light, vec3 fragPos, vec3 normal) {
+    int slot = light.shadowSlot;
+    if (slot < 0) return 0.0;
+    ShadowParams params = light.shadow;
+    
+    // Towards the light by the bias, and out along the normal by texels at this distance
+    // from the light (more at grazing angles)
+    vec3 toLight = light.position - fragPos;
+    float distance = length(toLight);
+    float NdotL = clamp(dot(normal, toLight / distance), 0.0, 1.0);
+    float texel = light.shadowTexel * distance;
+    vec3 offsetPos = fragPos + toLight / distance * params.bias
+        + normal * texel * params.normalOffset * (1.0 + 1.5 * (1.0 - NdotL));
+    vec4 fragPosLightSpace = spotLightSpaceMatrices[slot] * vec4(offsetPos, 1.0);
+    if (fragPosLightSpace.w <= 0.0) return 0.0;
+    vec3 projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
+    projCoords = projCoords * 0.5 + 0.5;
+    
+    if(projCoords.z > 1.0) return 0.0;
+    
+    // Moments are of view depth over the range; outside the map reads as lit
+    vec3 coords = vec3(projCoords.xy, float(slot));
+    if (params.filterMode == 3 || params.filterMode == 4) {
+        vec2 moments = texture(spotShadowMoments, coords).rg;
+        float depth = fragPosLightSpace.w / light.shadowRange;
+        if (params.filterMode == 3) return varianceShadow(moments, depth);
+        return exponentialShadow(moments.x, depth);
+    }
+    
+    float radius = params.radius;
+    if (params.filterMode == 2) {
+        // Penumbra grows with the distance from the blockers to the receiver
+        float blocker = blockerDepth(spotShadowMaps, coords, projCoords.z, PCSS_SEARCH_TEXELS);
+        if (blocker < 0.0) return 0.0;
+        float receiverDepth = fragPosLightSpace.w;
+        float blockerViewDepth = spotViewDepth(blocker, light.shadowRange);
+        float penumbra = (receiverDepth - blockerViewDepth) / blockerViewDepth * params.lightSize;
+        radius = min(radius + penumbra / texel, PCSS_MAX_TEXELS);
+    }
+    return pcfShadow(spotShadowMaps, coords, projCoords.z, radius, params.filterMode == 0);
+}
@@ -0,0 +1,8 @@
+// Screen-space ambient occlusion of the opaque scene (1.0 = unoccluded)
+uniform sampler2D ssaoMap;
+uniform int hasSsao;
+
+float screenOcclusion() {
+    if (hasSsao == 0) return 1.0;
+    return texture(ssaoMap, gl_FragCoord.xy / vec2(textureSize(ssaoMap, 0))).r;
+}
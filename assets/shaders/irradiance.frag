@@ -0,0 +1,34 @@
+#version 330 core
+// Diffuse irradiance: the environment convolved with a cosine lobe around each direction
+in vec3 TexCoords;
+out vec4 FragColor;
+
+uniform samplerCube environmentMap;
+
+const float PI = 3.14159265359;
+const float SAMPLE_DELTA = 0.025;
+
+void main() {
+    vec3 N = normalize(TexCoords);
+    vec3 up = abs(N.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(up, N));
+    up = cross(N, right);
+
+    // Read from the mip whose texels are about as far apart as the samples
+    float faceSize = float(textureSize(environmentMap, 0).x);
+    float lod = max(log2(SAMPLE_DELTA * faceSize * 2.0 / PI), 0.0);
+
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+            vec3 tangentSample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 dir = tangentSample.x * right + tangentSample.y * up + tangentSample.z * N;
+            irradiance += textureLod(environmentMap, dir, lod).rgb * cos(theta) * sin(theta);
+            samples += 1.0;
+        }
+    }
+
+    // Scaled so a uniform environment of radiance L gives L (diffuse = irradiance * albedo)
+    FragColor = vec4(PI * irradiance / samples, 1.0);
+}
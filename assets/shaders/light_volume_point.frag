@@ -0,0 +1,85 @@
+#version 330 core
+out vec4 FragColor;
+
+// G-Buffer
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform sampler2D gAlbedoSpec;
+uniform vec2 screenSize;
+
+// Point Light Structure
+struct PointLight {
+    vec3 position;
+    vec3 Color;
+    float Ambient;
+    float Diffuse;
+    float Specular;
+    float Shininess;
+    float Constant;
+    float Linear;
+    float Quadratic;
+};
+uniform PointLight light;
+// Radius of the light volume; nothing past it is lit
+uniform float lightRange;
+
+uniform vec3 viewPos;
+
+#include "shadows.glsl"
+
+// Cube shadow map, only for the lights that have one
+uniform int hasShadow;
+uniform samplerCube pointShadowMap;
+uniform vec3 shadowOrigin; // light position the cube map was rendered from
+uniform float shadowRange; // far plane the cube map was rendered with
+uniform float shadowTexel; // shadow texel size one unit from the light
+uniform ShadowParams lightShadow;
+
+#include "ssao.glsl"
+
+// Point light shadow from the cube map, filtered as `lightShadow` asks
+float calcPointShadow(vec3 fragPos, vec3 normal) {
+    return cubeShadow(pointShadowMap, shadowOrigin, shadowRange, shadowTexel, lightShadow, fragPos, normal);
+}
+
+// Point Light Calculation
+vec3 calcPointLight(vec3 fragPos, vec3 norm, vec3 viewDir, float shadow, float specMask, float occlusion) {
+    vec3 lightDirNorm = normalize(light.position - fragPos);
+    float diff = max(dot(norm, lightDirNorm), 0.0);
+    vec3 reflectDir = reflect(-lightDirNorm, norm);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), light.Shininess);
+    float distance = length(light.position - fragPos);
+    float attenuation = 1.0 / (light.Constant + light.Linear * distance + light.Quadratic * distance * distance);
+
+    vec3 ambient = light.Ambient * light.Color * attenuation * occlusion;
+    vec3 diffuse = light.Diffuse * diff * light.Color * attenuation;
+    vec3 specular = light.Specular * spec * light.Color * attenuation * specMask;
+    
+    return ambient + (1.0 - shadow) * (diffuse + specular);
+}
+
+void main() {
+    vec2 texCoords = gl_FragCoord.xy / screenSize;
+    vec4 position = texture(gPosition, texCoords);
+    if (position.w == 0.0) discard;
+    
+    vec3 FragPos = position.xyz;
+    if (length(light.position - FragPos) > lightRange) discard;
+    
+    vec4 normalShadow = texture(gNormal, texCoords);
+    vec4 albedoSpec = texture(gAlbedoSpec, texCoords);
+    vec3 norm = normalize(normalShadow.xyz);
+    vec3 viewDir = normalize(viewPos - FragPos);
+    
+    float shadow = 0.0;
+    if (hasShadow != 0 && normalShadow.w > 0.5) {
+        vec3 lightToFrag = normalize(FragPos - light.position);
+        if (dot(norm, -lightToFrag) > 0.0) {
+            shadow = calcPointShadow(FragPos, norm);
+        }
+    }
+    
+    float occlusion = screenOcclusion();
+    vec3 result = calcPointLight(FragPos, norm, viewDir, shadow, albedoSpec.a, occlusion);
+    FragColor = vec4(result * albedoSpec.rgb, 1.0);
+}
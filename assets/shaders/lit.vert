@@ -2,21 +2,27 @@
 layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec3 aNormal;
 layout (location = 2) in vec2 aTexCoord;
+layout (location = 3) in mat4 aModel; // per instance, locations 3-6
+layout (location = 7) in vec3 aTangent;
+layout (location = 8) in vec3 aBitangent;
 
 out vec3 Normal;
+out vec3 Tangent;
+out vec3 Bitangent;
 out vec3 FragPos;
 out vec2 TexCoord;
-out vec4 FragPosLightSpace;
+out float ViewDepth;
 
-uniform mat4 model;
 uniform mat4 view;
 uniform mat4 projection;
-uniform mat4 lightSpaceMatrix;
 
 void main() {
-    FragPos = vec3(model * vec4(aPos, 1.0));
-    Normal = mat3(transpose(inverse(model))) * aNormal;
+    FragPos = vec3(aModel * vec4(aPos, 1.0));
+    Normal = mat3(transpose(inverse(aModel))) * aNormal;
+    // Tangents lie in the surface, so they follow the model matrix itself
+    Tangent = mat3(aModel) * aTangent;
+    Bitangent = mat3(aModel) * aBitangent;
     TexCoord = aTexCoord;
-    FragPosLightSpace = lightSpaceMatrix * vec4(FragPos, 1.0);
+    ViewDepth = -(view * vec4(FragPos, 1.0)).z; // selects the shadow cascade
     gl_Position = projection * view * vec4(FragPos, 1.0);
 }
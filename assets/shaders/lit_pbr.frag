@@ -0,0 +1,282 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec3 Normal;
+in vec3 Tangent;
+in vec3 Bitangent;
+in vec3 FragPos;
+in vec2 TexCoord;
+in float ViewDepth;
+
+// Directional Light Properties
+uniform vec3 lightDir;
+uniform vec3 lightColor;
+uniform float ambientStrength;
+uniform float diffuseStrength;
+uniform float specularStrength;
+uniform float shininess;
+
+#include "clusters.glsl"
+
+uniform vec3 viewPos;
+
+#include "cascades.glsl"
+#include "spot_shadows.glsl"
+#include "point_shadows.glsl"
+
+// Cook-Torrance BRDF (GGX distribution, Smith-Schlick geometry, Schlick Fresnel)
+const float PI = 3.14159265359;
+
+float distributionGGX(float NdotH, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+float geometrySchlickGGX(float NdotX, float k) {
+    return NdotX / (NdotX * (1.0 - k) + k);
+}
+
+float geometrySmith(float NdotV, float NdotL, float roughness) {
+    float r = roughness + 1.0;
+    float k = (r * r) / 8.0;
+    return geometrySchlickGGX(NdotV, k) * geometrySchlickGGX(NdotL, k);
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 F0) {
+    return F0 + (1.0 - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+struct Surface {
+    vec3 albedo;
+    float metallic;
+    float roughness;
+    vec3 F0;
+    vec3 N;
+    vec3 V;
+};
+
+// Light leaving the surface towards the eye from one light of `radiance` in direction L.
+// Radiance is scaled by PI so a white dielectric facing a light reaches the same
+// brightness as with the Blinn-Phong shaders' diffuse term.
+vec3 shade(Surface s, vec3 L, vec3 radiance) {
+    vec3 H = normalize(s.V + L);
+    float NdotL = max(dot(s.N, L), 0.0);
+    float NdotV = max(dot(s.N, s.V), 0.0001);
+
+    float D = distributionGGX(max(dot(s.N, H), 0.0), s.roughness);
+    float G = geometrySmith(NdotV, NdotL, s.roughness);
+    vec3 F = fresnelSchlick(max(dot(H, s.V), 0.0), s.F0);
+
+    vec3 specular = D * G * F / (4.0 * NdotV * NdotL + 0.0001);
+    vec3 kD = (vec3(1.0) - F) * (1.0 - s.metallic);
+    return (kD * s.albedo / PI + specular) * radiance * PI * NdotL;
+}
+
+#include "ibl.glsl"
+
+vec3 fresnelSchlickRoughness(float cosTheta, vec3 F0, float roughness) {
+    return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+#include "ssao.glsl"
+
+// Ambient light: diffuse irradiance plus prefiltered specular from the environment
+// (the split-sum approximation), or a flat term without one
+vec3 calcAmbient(Surface s, float ao) {
+    if (hasEnvironment == 0) return ambientStrength * lightColor * s.albedo * ao;
+
+    float NdotV = max(dot(s.N, s.V), 0.0);
+    vec3 F = fresnelSchlickRoughness(NdotV, s.F0, s.roughness);
+    vec3 kD = (vec3(1.0) - F) * (1.0 - s.metallic);
+    vec3 diffuse = environmentIrradiance(s.N) * s.albedo;
+
+    vec3 specular = environmentSpecular(s.N, s.V, s.roughness, F);
+
+    return ambientStrength * environmentIntensity * (kD * diffuse + specular) * ao;
+}
+
+// Directional Light
+vec3 calcDirLight(Surface s, float shadow, float ao) {
+    vec3 L = normalize(-lightDir);
+    return calcAmbient(s, ao) + (1.0 - shadow) * shade(s, L, diffuseStrength * lightColor);
+}
+
+// Point Light
+vec3 calcPointLight(Light light, Surface s, float shadow, float ao) {
+    vec3 L = normalize(light.position - FragPos);
+    float distance = length(light.position - FragPos);
+    float attenuation = 1.0 / (light.Constant + light.Linear * distance + light.Quadratic * distance * distance);
+    vec3 radiance = light.Diffuse * light.Color * attenuation;
+
+    vec3 ambient = light.Ambient * light.Color * attenuation * s.albedo * ao;
+    return ambient + (1.0 - shadow) * shade(s, L, radiance);
+}
+
+// Spot Light
+vec3 calcSpotLight(Light light, Surface s, float ao, float shadow) {
+    vec3 L = normalize(light.position - FragPos);
+    float distance = length(light.position - FragPos);
+    float attenuation = 1.0 / (light.Constant + light.Linear * distance + light.Quadratic * distance * distance);
+
+    float theta = dot(L, normalize(-light.direction));
+    float epsilon = light.CutOff - light.OuterCutOff;
+    float intensity = clamp((theta - light.OuterCutOff) / epsilon, 0.0, 1.0);
+    vec3 radiance = light.Diffuse * light.Color * attenuation * intensity;
+
+    vec3 ambient = light.Ambient * light.Color * attenuation * s.albedo * ao;
+    return ambient + (1.0 - shadow) * shade(s, L, radiance);
+}
+
+// Material: factors, multiplied by the maps that are present
+uniform vec3 baseColorFactor;
+uniform float metallicFactor;
+uniform float roughnessFactor;
+uniform vec3 emissiveFactor;
+uniform float normalScale;
+uniform float occlusionStrength;
+
+uniform sampler2D baseColorMap;         // color (sRGB texture), alpha
+uniform sampler2D metallicRoughnessMap; // roughness in G, metallic in B
+uniform sampler2D normalMap;            // tangent space
+uniform sampler2D occlusionMap;         // occlusion in R
+uniform sampler2D emissiveMap;          // sRGB texture
+uniform int hasBaseColorMap;
+uniform int hasMetallicRoughnessMap;
+uniform int hasNormalMap;
+uniform int hasOcclusionMap;
+uniform int hasEmissiveMap;
+
+uniform vec2 u_UVScale;
+uniform float opacity;
+uniform float alphaCutoff;           // discard below this alpha (cutout materials), else 0
+
+uniform int u_UseLighting;
+uniform int u_UseShadows;
+
+// Tangent-space normal map applied around N, using the interpolated vertex tangent
+// frame, or one from screen-space derivatives of the position and UVs when the mesh
+// has no tangents
+vec3 perturbNormal(vec3 N, vec2 uv) {
+    vec3 tangentNormal = texture(normalMap, uv).xyz * 2.0 - 1.0;
+    tangentNormal.xy *= normalScale;
+
+    vec3 T = Tangent - N * dot(N, Tangent);
+    vec3 B;
+    if (dot(T, T) > 1e-8 && dot(Bitangent, Bitangent) > 1e-8) {
+        T = normalize(T);
+        B = cross(N, T) * (dot(cross(N, T), Bitangent) < 0.0 ? -1.0 : 1.0);
+    } else {
+        vec3 dp1 = dFdx(FragPos);
+        vec3 dp2 = dFdy(FragPos);
+        vec2 duv1 = dFdx(uv);
+        vec2 duv2 = dFdy(uv);
+
+        vec3 dp2perp = cross(dp2, N);
+        vec3 dp1perp = cross(N, dp1);
+        T = dp2perp * duv1.x + dp1perp * duv2.x;
+        B = dp2perp * duv1.y + dp1perp * duv2.y;
+        float invMax = inversesqrt(max(max(dot(T, T), dot(B, B)), 1e-12));
+        T *= invMax;
+        B *= invMax;
+    }
+    return normalize(mat3(T, B, N) * tangentNormal);
+}
+
+void main() {
+    vec2 uv = TexCoord * u_UVScale;
+
+    vec3 albedo = baseColorFactor;
+    float alpha = 1.0;
+    if (hasBaseColorMap != 0) {
+        vec4 texColor = texture(baseColorMap, uv);
+        albedo *= texColor.rgb;
+        alpha = texColor.a;
+    }
+    alpha *= opacity;
+    if (alpha < alphaCutoff) {
+        discard;
+    }
+
+    float metallic = metallicFactor;
+    float roughness = roughnessFactor;
+    if (hasMetallicRoughnessMap != 0) {
+        vec4 mr = texture(metallicRoughnessMap, uv);
+        roughness *= mr.g;
+        metallic *= mr.b;
+    }
+    roughness = clamp(roughness, 0.04, 1.0);
+    metallic = clamp(metallic, 0.0, 1.0);
+
+    float ao = 1.0;
+    if (hasOcclusionMap != 0) {
+        ao = mix(1.0, texture(occlusionMap, uv).r, occlusionStrength);
+    }
+
+    vec3 emissive = emissiveFactor;
+    if (hasEmissiveMap != 0) {
+        emissive *= texture(emissiveMap, uv).rgb;
+    }
+
+    vec3 result;
+    if (u_UseLighting == 0) {
+        result = albedo;
+    } else {
+        vec3 V = normalize(viewPos - FragPos);
+        vec3 geomNormal = normalize(Normal);
+
+        // Two-sided lighting
+        if (dot(geomNormal, V) < 0.0) {
+            geomNormal = -geomNormal;
+        }
+        vec3 N = geomNormal;
+        if (hasNormalMap != 0) {
+            N = perturbNormal(geomNormal, uv);
+        }
+
+        Surface s;
+        s.albedo = albedo;
+        s.metallic = metallic;
+        s.roughness = roughness;
+        s.F0 = mix(vec3(0.04), albedo, metallic);
+        s.N = N;
+        s.V = V;
+
+        // Directional Shadow (biased along the geometric normal)
+        vec3 lightDirNorm = normalize(-lightDir);
+        float shadow = 0.0;
+        if (u_UseShadows != 0 && dot(geomNormal, lightDirNorm) > 0.0) {
+            shadow = calcShadow(FragPos, geomNormal, lightDirNorm);
+        }
+
+        // Material occlusion times the screen-space term, on the ambient light only
+        ao *= screenOcclusion();
+
+        // 1. Directional Light
+        result = calcDirLight(s, shadow, ao);
+
+        // 2. Point and Spot Lights of this cluster
+        uvec2 cluster = clusterLights(ViewDepth);
+        for(uint i = 0u; i < cluster.y; i++) {
+            int index = int(texelFetch(lightClusters, int(cluster.x + i)).r);
+            Light light = fetchLight(index);
+            if (light.type == 1) {
+                float sShadow = 0.0;
+                if (u_UseShadows != 0 && dot(geomNormal, normalize(light.position - FragPos)) > 0.0) {
+                    sShadow = calcSpotShadow(light, FragPos, geomNormal);
+                }
+                result += calcSpotLight(light, s, ao, sShadow);
+            } else {
+                float pShadow = 0.0;
+                if (u_UseShadows != 0 && dot(geomNormal, normalize(light.position - FragPos)) > 0.0) {
+                    pShadow = calcPointShadowSlot(light, FragPos, geomNormal);
+                }
+                result += calcPointLight(light, s, pShadow, ao);
+            }
+        }
+    }
+
+    result += emissive;
+    FragColor = vec4(result, alpha);
+}
@@ -2,9 +2,11 @@
 out vec4 FragColor;
 
 in vec3 Normal;
+in vec3 Tangent;
+in vec3 Bitangent;
 in vec3 FragPos;
 in vec2 TexCoord;
-in vec4 FragPosLightSpace;
+in float ViewDepth;
 
 // Directional Light Properties
 uniform vec3 lightDir;
@@ -14,104 +16,49 @@ uniform float diffuseStrength;
 uniform float specularStrength;
 uniform float shininess;
 
-// Point Light Structure
-struct PointLight {
-    vec3 position;
-    vec3 Color;
-    float Ambient;
-    float Diffuse;
-    float Specular;
-    float Shininess; // Use light shininess if specialized
-    float Constant;
-    float Linear;
-    float Quadratic;
-};
-#define NR_POINT_LIGHTS 4
-uniform PointLight pointLights[NR_POINT_LIGHTS];
-
-// Spot Light Structure
-// Spot Light Structure
-struct SpotLight {
-    vec3 position;
-    vec3 direction;
-    float CutOff;
-    float OuterCutOff;
-  
-    float Constant;
-    float Linear;
-    float Quadratic;
-  
-    vec3 Color;
-    float Ambient;
-    float Diffuse;
-    float Specular;
-};
-#define NR_SPOT_LIGHTS 4
-uniform SpotLight spotLights[NR_SPOT_LIGHTS];
+#include "clusters.glsl"
 
 uniform vec3 viewPos;
 uniform sampler2D u_Texture;
-
-// Shadow Maps
-uniform sampler2D shadowMap;
-uniform samplerCube pointShadowMaps[NR_POINT_LIGHTS];
-uniform float farPlane;
-uniform int nrPointLights;
-uniform int nrSpotLights;
-
-// Calculate Point Shadow (with PCF)
-float calcPointShadow(vec3 fragPos, vec3 lightPos, samplerCube shadowMap, float lightRange) {
-    vec3 fragToLight = fragPos - lightPos;
-    float currentDepth = length(fragToLight);
-    
-    // Skip if out of range
-    if (currentDepth > lightRange) return 0.0;
-
-    float shadow = 0.0;
-    float bias = 0.15; 
-    int samples = 8;
-    vec3 sampleOffsetDirections[8] = vec3[]
-    (
-       vec3( 1,  1,  1), vec3( 1, -1,  1), vec3(-1, -1,  1), vec3(-1,  1,  1), 
-       vec3( 1,  1, -1), vec3( 1, -1, -1), vec3(-1, -1, -1), vec3(-1,  1, -1)
-    );
-    
-    float viewDistance = length(viewPos - fragPos);
-    float diskRadius = (1.0 + (viewDistance / farPlane)) / 50.0;
-    
-    for(int i = 0; i < samples; ++i) {
-        float closestDepth = texture(shadowMap, fragToLight + sampleOffsetDirections[i] * diskRadius).r;
-        closestDepth *= farPlane;
-        if(currentDepth - bias > closestDepth) {
-            shadow += 1.0;
-        }
-    }
-    
-    return shadow / float(samples);
+uniform float opacity;
+uniform float alphaCutoff;           // discard below this alpha (cutout materials), else 0
+uniform sampler2D normalMap;   // tangent space
+uniform int hasNormalMap;
+uniform float normalScale;
+
+// Material factors; the maps multiply them where present
+uniform vec3 diffuseColor;           // tints u_Texture
+uniform vec3 specularColor;
+uniform sampler2D specularMap;
+uniform int hasSpecularMap;
+uniform vec3 emissiveColor;
+uniform sampler2D emissiveMap;
+uniform int hasEmissiveMap;
+
+// Material specular scale for the light functions, set at the start of main
+vec3 specularScale = vec3(1.0);
+
+#include "cascades.glsl"
+#include "spot_shadows.glsl"
+#include "point_shadows.glsl"
+
+#include "ibl.glsl"
+
+#include "ssao.glsl"
+float ambientOcclusion = 1.0;
+
+// Ambient light: the environment's irradiance, or a flat term without one
+vec3 ambientLight(vec3 norm) {
+    if (hasEnvironment == 0) return ambientStrength * lightColor * ambientOcclusion;
+    return ambientStrength * environmentIntensity * environmentIrradiance(norm) * ambientOcclusion;
 }
 
-// Calculate Directional Shadow
-float calcShadow(vec4 fragPosLightSpace, vec3 normal, vec3 lightDirNorm) {
-    vec3 projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
-    projCoords = projCoords * 0.5 + 0.5;
-    
-    if(projCoords.z > 1.0) return 0.0;
-    if(projCoords.x < 0.0 || projCoords.x > 1.0 || projCoords.y < 0.0 || projCoords.y > 1.0) return 0.0;
-    
-    float currentDepth = projCoords.z;
-    float bias = max(0.05 * (1.0 - dot(normal, lightDirNorm)), 0.005);
-    
-    // PCF (3x3 sampling)
-    float shadow = 0.0;
-    vec2 texelSize = 1.0 / textureSize(shadowMap, 0);
-    for(int x = -1; x <= 1; ++x) {
-        for(int y = -1; y <= 1; ++y) {
-            float pcfDepth = texture(shadowMap, projCoords.xy + vec2(x, y) * texelSize).r;
-            shadow += currentDepth - bias > pcfDepth ? 1.0 : 0.0;
-        }
-    }
-    
-    return shadow / 9.0;
+// Environment reflection with dielectric Fresnel, as blurry as the highlight of `shininess`
+vec3 environmentReflection(vec3 norm, vec3 viewDir) {
+    if (hasEnvironment == 0) return vec3(0.0);
+    float roughness = sqrt(2.0 / (shininess + 2.0));
+    vec3 specular = environmentSpecular(norm, viewDir, roughness, vec3(0.04));
+    return ambientStrength * environmentIntensity * specularStrength * specularScale * specular * ambientOcclusion;
 }
 
 // Directional Light Calculation
@@ -121,15 +68,15 @@ vec3 calcDirLight(vec3 norm, vec3 viewDir, float shadow) {
     vec3 reflectDir = reflect(-lightDirNorm, norm);
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), shininess);
     
-    vec3 ambient = ambientStrength * lightColor;
+    vec3 ambient = ambientLight(norm);
     vec3 diffuse = diffuseStrength * diff * lightColor;
-    vec3 specular = specularStrength * spec * lightColor;
+    vec3 specular = specularStrength * spec * lightColor * specularScale;
     
     return ambient + (1.0 - shadow) * (diffuse + specular);
 }
 
 // Point Light Calculation
-vec3 calcPointLight(PointLight light, vec3 norm, vec3 viewDir, float shadow) {
+vec3 calcPointLight(Light light, vec3 norm, vec3 viewDir, float shadow) {
     vec3 lightDirNorm = normalize(light.position - FragPos);
     float diff = max(dot(norm, lightDirNorm), 0.0);
     vec3 reflectDir = reflect(-lightDirNorm, norm);
@@ -137,15 +84,15 @@ vec3 calcPointLight(PointLight light, vec3 norm, vec3 viewDir, float shadow) {
     float distance = length(light.position - FragPos);
     float attenuation = 1.0 / (light.Constant + light.Linear * distance + light.Quadratic * distance * distance);
 
-    vec3 ambient = light.Ambient * light.Color * attenuation;
+    vec3 ambient = light.Ambient * light.Color * attenuation * ambientOcclusion;
     vec3 diffuse = light.Diffuse * diff * light.Color * attenuation;
-    vec3 specular = light.Specular * spec * light.Color * attenuation;
+    vec3 specular = light.Specular * spec * light.Color * attenuation * specularScale;
     
     return ambient + (1.0 - shadow) * (diffuse + specular);
 }
 
 // Spot Light Calculation
-vec3 calcSpotLight(SpotLight light, vec3 norm, vec3 fragPos, vec3 viewDir) {
+vec3 calcSpotLight(Light light, vec3 norm, vec3 fragPos, vec3 viewDir, float shadow) {
     vec3 lightDir = normalize(light.position - fragPos);
     
     // Diffuse shading
@@ -165,11 +112,11 @@ vec3 calcSpotLight(SpotLight light, vec3 norm, vec3 fragPos, vec3 viewDir) {
     float intensity = clamp((theta - light.OuterCutOff) / epsilon, 0.0, 1.0);
     
     // Combine
-    vec3 ambient = light.Ambient * light.Color * attenuation; // Ambient always present but attenuated
+    vec3 ambient = light.Ambient * light.Color * attenuation * ambientOcclusion; // Ambient always present but attenuated
     vec3 diffuse = light.Diffuse * diff * light.Color * intensity * attenuation;
-    vec3 specular = light.Specular * spec * light.Color * intensity * attenuation;
+    vec3 specular = light.Specular * spec * light.Color * intensity * attenuation * specularScale;
     
-    return ambient + diffuse + specular;
+    return ambient + (1.0 - shadow) * (diffuse + specular);
 }
 
 uniform int u_UseLighting;
@@ -178,6 +125,34 @@ uniform int u_UseShadows;
 uniform int u_IsRepeated;
 uniform vec2 u_UVScale;
 
+// Tangent-space normal map applied around N, using the interpolated vertex tangent
+// frame, or one from screen-space derivatives when the mesh has no tangents
+vec3 perturbNormal(vec3 N, vec2 uv) {
+    vec3 tangentNormal = texture(normalMap, uv).xyz * 2.0 - 1.0;
+    tangentNormal.xy *= normalScale;
+
+    vec3 T = Tangent - N * dot(N, Tangent);
+    vec3 B;
+    if (dot(T, T) > 1e-8 && dot(Bitangent, Bitangent) > 1e-8) {
+        T = normalize(T);
+        B = cross(N, T) * (dot(cross(N, T), Bitangent) < 0.0 ? -1.0 : 1.0);
+    } else {
+        vec3 dp1 = dFdx(FragPos);
+        vec3 dp2 = dFdy(FragPos);
+        vec2 duv1 = dFdx(uv);
+        vec2 duv2 = dFdy(uv);
+
+        vec3 dp2perp = cross(dp2, N);
+        vec3 dp1perp = cross(N, dp1);
+        T = dp2perp * duv1.x + dp1perp * duv2.x;
+        B = dp2perp * duv1.y + dp1perp * duv2.y;
+        float invMax = inversesqrt(max(max(dot(T, T), dot(B, B)), 1e-12));
+        T *= invMax;
+        B *= invMax;
+    }
+    return normalize(mat3(T, B, N) * tangentNormal);
+}
+
 void main() {
     vec2 coords = TexCoord;
     if (u_IsRepeated != 0) {
@@ -186,6 +161,15 @@ void main() {
     }
     
     vec4 texColor = texture(u_Texture, coords);
+    texColor.a *= opacity;
+    if (texColor.a < alphaCutoff) {
+        discard;
+    }
+    texColor.rgb *= diffuseColor;
+    specularScale = specularColor;
+    if (hasSpecularMap != 0) {
+        specularScale *= texture(specularMap, coords).rgb;
+    }
     vec3 result;
 
     if (u_UseLighting == 0) {
@@ -198,31 +182,52 @@ void main() {
         if (dot(norm, viewDir) < 0.0) {
             norm = -norm;
         }
+        vec3 geomNorm = norm;
+        if (hasNormalMap != 0) {
+            norm = perturbNormal(geomNorm, coords);
+        }
         vec3 lightDirNorm = normalize(-lightDir);
         
-        // Directional Shadow
-        float NdotL = dot(norm, lightDirNorm);
+        // Directional Shadow (offset along the surface, not the mapped normal)
+        float NdotL = dot(geomNorm, lightDirNorm);
         float shadow = 0.0;
         if (u_UseShadows != 0 && NdotL > 0.0) {
-            shadow = calcShadow(FragPosLightSpace, norm, lightDirNorm);
+            shadow = calcShadow(FragPos, geomNorm, lightDirNorm);
         }
 
+        ambientOcclusion = screenOcclusion();
+
         // 1. Directional Light
         result = calcDirLight(norm, viewDir, shadow);
-        
-
-
-        // 2. Point Lights
-        // {{POINT_SHADOW_LOOP}}
-
-
-
-        // 3. Spot Lights
-        for(int i = 0; i < nrSpotLights; i++) {
-            result += calcSpotLight(spotLights[i], norm, FragPos, viewDir);
+        result += environmentReflection(norm, viewDir);
+
+        // 2. Point and Spot Lights of this cluster
+        uvec2 cluster = clusterLights(ViewDepth);
+        for(uint i = 0u; i < cluster.y; i++) {
+            int index = int(texelFetch(lightClusters, int(cluster.x + i)).r);
+            Light light = fetchLight(index);
+            if (light.type == 1) {
+                float sShadow = 0.0;
+                if (u_UseShadows != 0 && dot(geomNorm, normalize(light.position - FragPos)) > 0.0) {
+                    sShadow = calcSpotShadow(light, FragPos, geomNorm);
+                }
+                result += calcSpotLight(light, norm, FragPos, viewDir, sShadow);
+            } else {
+                float pShadow = 0.0;
+                if (u_UseShadows != 0 && dot(norm, normalize(light.position - FragPos)) > 0.0) {
+                    pShadow = calcPointShadowSlot(light, FragPos, geomNorm);
+                }
+                result += calcPointLight(light, norm, viewDir, pShadow);
+            }
         }
     }
     
     result *= texColor.rgb;
+
+    vec3 emissive = emissiveColor;
+    if (hasEmissiveMap != 0) {
+        emissive *= texture(emissiveMap, coords).rgb;
+    }
+    result += emissive;
     FragColor = vec4(result, texColor.a);
 }
@@ -0,0 +1,12 @@
+#version 330 core
+// Log luminance of the HDR scene, mipmapped down to its average afterwards
+in vec2 TexCoords;
+out float LogLuminance;
+
+uniform sampler2D hdrColor;
+
+void main() {
+    vec3 color = texture(hdrColor, TexCoords).rgb;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    LogLuminance = log(max(luminance, 0.0001));
+}
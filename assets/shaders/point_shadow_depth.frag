@@ -1,11 +1,27 @@
 #version 330 core
 in vec4 FragPos;
+in vec2 TexCoord;
 
 uniform vec3 lightPos;
 uniform float far_plane;
 
+// Cutout materials only: texels whose alpha (map alpha times opacity, as in the
+// lit shaders) falls below the cutoff cast no shadow
+uniform sampler2D alphaMap;
+uniform int hasAlphaMap;
+uniform float opacity;
+uniform float alphaCutoff;
+
 void main()
 {
+    float alpha = opacity;
+    if (hasAlphaMap != 0) {
+        alpha *= texture(alphaMap, TexCoord).a;
+    }
+    if (alpha < alphaCutoff) {
+        discard;
+    }
+
     float lightDistance = length(FragPos.xyz - lightPos);
     
     // Normalize distance to [0, 1]
@@ -1,10 +1,15 @@
 #version 330 core
 layout (location = 0) in vec3 aPos;
+layout (location = 2) in vec2 aTexCoord;
+layout (location = 3) in mat4 aModel; // per instance, locations 3-6
 
-uniform mat4 model;
+uniform vec2 u_UVScale;
+
+out vec2 vTexCoord;
 
 void main()
 {
+    vTexCoord = aTexCoord * u_UVScale;
     // Only model transform (View/Proj in Geometry Shader)
-    gl_Position = model * vec4(aPos, 1.0);
+    gl_Position = aModel * vec4(aPos, 1.0);
 }
@@ -0,0 +1,74 @@
+#version 330 core
+// Specular prefilter: the environment convolved with the GGX lobe of one roughness
+// (one mip level per roughness), importance sampled
+in vec3 TexCoords;
+out vec4 FragColor;
+
+uniform samplerCube environmentMap;
+uniform float roughness;
+uniform int sampleCount;
+
+const float PI = 3.14159265359;
+
+float distributionGGX(float NdotH, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Low-discrepancy point i of n
+vec2 hammersley(uint i, uint n) {
+    uint bits = i;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return vec2(float(i) / float(n), float(bits) * 2.3283064365386963e-10);
+}
+
+// Half vector around N distributed like the GGX lobe
+vec3 importanceSampleGGX(vec2 xi, vec3 N, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    vec3 H = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+
+    vec3 up = abs(N.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, N));
+    vec3 bitangent = cross(N, tangent);
+    return normalize(tangent * H.x + bitangent * H.y + N * H.z);
+}
+
+void main() {
+    // Assume the view direction is the normal (the split-sum approximation)
+    vec3 N = normalize(TexCoords);
+    vec3 V = N;
+
+    float faceSize = float(textureSize(environmentMap, 0).x);
+    float texelSolidAngle = 4.0 * PI / (6.0 * faceSize * faceSize);
+
+    vec3 color = vec3(0.0);
+    float totalWeight = 0.0;
+    uint count = uint(sampleCount);
+    for (uint i = 0u; i < count; ++i) {
+        vec3 H = importanceSampleGGX(hammersley(i, count), N, roughness);
+        vec3 L = normalize(2.0 * dot(V, H) * H - V);
+        float NdotL = dot(N, L);
+        if (NdotL > 0.0) {
+            // Read from the mip matching the solid angle this sample covers, which
+            // removes the bright speckles of undersampled highlights
+            float NdotH = max(dot(N, H), 0.0);
+            float pdf = distributionGGX(NdotH, roughness) / 4.0 + 0.0001;
+            float sampleSolidAngle = 1.0 / (float(count) * pdf + 0.0001);
+            float lod = roughness == 0.0 ? 0.0 : 0.5 * log2(sampleSolidAngle / texelSolidAngle);
+
+            color += textureLod(environmentMap, L, max(lod, 0.0)).rgb * NdotL;
+            totalWeight += NdotL;
+        }
+    }
+
+    FragColor = vec4(color / max(totalWeight, 0.0001), 1.0);
+}
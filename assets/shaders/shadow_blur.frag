@@ -0,0 +1,21 @@
+#version 330 core
+// One direction of the separable Gaussian blur of shadow moments
+in vec2 TexCoords;
+out vec2 FragColor;
+
+uniform sampler2D momentsMap;
+uniform int taps;           // on each side of the center
+uniform vec2 direction;     // one texel along the blur
+
+void main() {
+    float sigma = float(taps) * 0.5;
+    vec2 sum = texture(momentsMap, TexCoords).rg;
+    float weight = 1.0;
+    for (int i = 1; i <= taps; ++i) {
+        float w = exp(-float(i * i) / (2.0 * sigma * sigma));
+        vec2 offset = direction * float(i);
+        sum += (texture(momentsMap, TexCoords + offset).rg + texture(momentsMap, TexCoords - offset).rg) * w;
+        weight += 2.0 * w;
+    }
+    FragColor = sum / weight;
+}
@@ -1,6 +1,21 @@
 #version 330 core
+in vec2 TexCoord;
+
+// Cutout materials only: texels whose alpha (map alpha times opacity, as in the
+// lit shaders) falls below the cutoff cast no shadow
+uniform sampler2D alphaMap;
+uniform int hasAlphaMap;
+uniform float opacity;
+uniform float alphaCutoff;
 
 void main() {
+    float alpha = opacity;
+    if (hasAlphaMap != 0) {
+        alpha *= texture(alphaMap, TexCoord).a;
+    }
+    if (alpha < alphaCutoff) {
+        discard;
+    }
     // Depth is written automatically
     // gl_FragDepth = gl_FragCoord.z;
 }
@@ -1,10 +1,15 @@
 #version 330 core
 layout (location = 0) in vec3 aPos;
+layout (location = 2) in vec2 aTexCoord;
+layout (location = 3) in mat4 aModel; // per instance, locations 3-6
 
 uniform mat4 lightSpaceMatrix;
-uniform mat4 model;
+uniform vec2 u_UVScale;
+
+out vec2 TexCoord;
 
 void main() {
+    TexCoord = aTexCoord * u_UVScale;
     // Transform vertex to light space
-    gl_Position = lightSpaceMatrix * model * vec4(aPos, 1.0);
+    gl_Position = lightSpaceMatrix * aModel * vec4(aPos, 1.0);
 }
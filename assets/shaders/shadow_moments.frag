@@ -0,0 +1,36 @@
+#version 330 core
+// Moments of one shadow map layer at a lower resolution: the average of depth and depth
+// squared for variance shadows, or of exp(ESM_EXPONENT * depth) for exponential ones.
+// Perspective depth is linearized (view depth over the far plane) first.
+in vec2 TexCoords;
+out vec2 FragColor;
+
+uniform sampler2DArray depthMap;
+uniform int layer;
+uniform int downscale;      // depth texels across each moments texel
+uniform int exponential;
+uniform int perspective;
+uniform float nearPlane;
+uniform float farPlane;
+
+float linearDepth(float depth) {
+    if (perspective == 0) return depth;
+    float z = depth * 2.0 - 1.0;
+    return 2.0 * nearPlane / (farPlane + nearPlane - z * (farPlane - nearPlane));
+}
+
+void main() {
+    ivec2 origin = ivec2(gl_FragCoord.xy) * downscale;
+    vec2 moments = vec2(0.0);
+    for (int y = 0; y < downscale; ++y) {
+        for (int x = 0; x < downscale; ++x) {
+            float depth = linearDepth(texelFetch(depthMap, ivec3(origin + ivec2(x, y), layer), 0).r);
+            if (exponential != 0) {
+                moments.x += exp(ESM_EXPONENT * depth);
+            } else {
+                moments += vec2(depth, depth * depth);
+            }
+        }
+    }
+    FragColor = moments / float(downscale * downscale);
+}
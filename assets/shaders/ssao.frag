@@ -0,0 +1,70 @@
+#version 330 core
+// Ambient occlusion: hemisphere samples around each pixel's view-space position, tested
+// against the camera depth (1.0 = unoccluded)
+in vec2 TexCoords;
+out float FragColor;
+
+uniform sampler2D depthMap;
+uniform sampler2D noiseMap;      // rotations around the normal, tiled over the screen
+uniform vec3 samples[SSAO_MAX_SAMPLES]; // hemisphere kernel around +Z, inside the unit sphere
+uniform int sampleCount;
+uniform float radius;            // view-space size of the kernel
+uniform float bias;              // depth difference that doesn't count as occlusion
+uniform float intensity;         // exponent applied to the result
+uniform mat4 projection;
+uniform mat4 invProjection;
+uniform vec2 noiseScale;         // screen size / noise tile size
+
+vec3 viewPosition(vec2 uv) {
+    float depth = texture(depthMap, uv).r;
+    vec4 position = invProjection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    return position.xyz / position.w;
+}
+
+void main() {
+    // Background: nothing to occlude
+    if (texture(depthMap, TexCoords).r >= 1.0) {
+        FragColor = 1.0;
+        return;
+    }
+    vec3 P = viewPosition(TexCoords);
+
+    // Normal from the neighbouring depths, taking the nearer side on each axis so
+    // silhouettes don't bend it towards the background
+    vec2 texel = 1.0 / vec2(textureSize(depthMap, 0));
+    vec3 right = viewPosition(TexCoords + vec2(texel.x, 0.0)) - P;
+    vec3 left = P - viewPosition(TexCoords - vec2(texel.x, 0.0));
+    vec3 up = viewPosition(TexCoords + vec2(0.0, texel.y)) - P;
+    vec3 down = P - viewPosition(TexCoords - vec2(0.0, texel.y));
+    vec3 dx = abs(right.z) < abs(left.z) ? right : left;
+    vec3 dy = abs(up.z) < abs(down.z) ? up : down;
+    vec3 N = normalize(cross(dx, dy));
+
+    // Kernel frame, turned around the normal by this pixel's noise
+    vec3 rotation = vec3(texture(noiseMap, TexCoords * noiseScale).xy, 0.0);
+    vec3 T = rotation - N * dot(rotation, N);
+    if (dot(T, T) < 1e-6) {
+        T = abs(N.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0);
+        T -= N * dot(T, N);
+    }
+    T = normalize(T);
+    mat3 TBN = mat3(T, cross(N, T), N);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < sampleCount; ++i) {
+        vec3 samplePos = P + TBN * samples[i] * radius;
+
+        vec4 offset = projection * vec4(samplePos, 1.0);
+        vec2 uv = offset.xy / offset.w * 0.5 + 0.5;
+        if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+            continue;
+        }
+        float sceneDepth = viewPosition(uv).z;
+
+        // Surfaces much closer to the camera than the radius don't shade this one
+        float range = smoothstep(0.0, 1.0, radius / abs(P.z - sceneDepth));
+        occlusion += (sceneDepth >= samplePos.z + bias ? 1.0 : 0.0) * range;
+    }
+
+    FragColor = pow(1.0 - occlusion / float(sampleCount), intensity);
+}
@@ -0,0 +1,34 @@
+#version 330 core
+// Box blur over the noise tile, skipping texels whose depth is further than the kernel
+// radius from the center (they belong to another surface)
+in vec2 TexCoords;
+out float FragColor;
+
+uniform sampler2D depthMap;
+uniform sampler2D occlusionMap;
+uniform int blurSize;            // noise tile size
+uniform float radius;
+uniform mat4 invProjection;
+
+float viewDepth(vec2 uv) {
+    float depth = texture(depthMap, uv).r;
+    vec4 position = invProjection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    return position.z / position.w;
+}
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(occlusionMap, 0));
+    float center = viewDepth(TexCoords);
+
+    float sum = 0.0;
+    float weight = 0.0;
+    for (int x = 0; x < blurSize; ++x) {
+        for (int y = 0; y < blurSize; ++y) {
+            vec2 uv = TexCoords + (vec2(x, y) - float(blurSize / 2)) * texel;
+            float w = step(abs(viewDepth(uv) - center), radius);
+            sum += texture(occlusionMap, uv).r * w;
+            weight += w;
+        }
+    }
+    FragColor = weight > 0.0 ? sum / weight : texture(occlusionMap, TexCoords).r;
+}
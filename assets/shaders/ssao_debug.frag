@@ -0,0 +1,10 @@
+#version 330 core
+// Occlusion buffer as greyscale, for tuning the SSAO settings
+in vec2 TexCoords;
+out vec4 FragColor;
+
+uniform sampler2D occlusionMap;
+
+void main() {
+    FragColor = vec4(vec3(texture(occlusionMap, TexCoords).r), 1.0);
+}
@@ -0,0 +1,56 @@
+#version 330 core
+// Expose the HDR scene, compress it with a tone mapping curve and encode gamma
+in vec2 TexCoords;
+out vec4 FragColor;
+
+uniform sampler2D hdrColor;
+uniform sampler2D exposureMap; // 1x1, from the exposure pass
+uniform int autoExposure;
+uniform float exposure;
+uniform int toneMapOperator;   // 0 linear, 1 Reinhard, 2 ACES, 3 filmic
+uniform float gamma;
+
+vec3 reinhard(vec3 x) {
+    return x / (1.0 + x);
+}
+
+// Narkowicz's fit of the ACES reference rendering transform
+vec3 aces(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+// Hable's Uncharted 2 curve, normalized to a white point of 11.2
+vec3 hable(vec3 x) {
+    const float A = 0.15;
+    const float B = 0.50;
+    const float C = 0.10;
+    const float D = 0.20;
+    const float E = 0.02;
+    const float F = 0.30;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 filmic(vec3 x) {
+    return hable(x * 2.0) / hable(vec3(11.2));
+}
+
+void main() {
+    vec3 color = texture(hdrColor, TexCoords).rgb;
+    color *= autoExposure != 0 ? texelFetch(exposureMap, ivec2(0), 0).r : exposure;
+
+    if (toneMapOperator == 1) {
+        color = reinhard(color);
+    } else if (toneMapOperator == 2) {
+        color = aces(color);
+    } else if (toneMapOperator == 3) {
+        color = filmic(color);
+    }
+
+    color = pow(max(color, vec3(0.0)), vec3(1.0 / gamma));
+    FragColor = vec4(color, 1.0);
+}
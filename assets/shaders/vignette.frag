@@ -0,0 +1,22 @@
+#version 330 core
+// Vignette: darken towards the corners
+in vec2 TexCoords;
+out vec4 FragColor;
+
+uniform sampler2D sceneColor;
+uniform vec2 texelSize;
+uniform float intensity;   // darkening at full strength
+uniform float radius;      // distance from the center where it is reached (0.5 = top edge)
+uniform float smoothness;  // width of the falloff inside `radius`
+
+void main() {
+    vec3 color = texture(sceneColor, TexCoords).rgb;
+
+    // Measured in screen heights, so the vignette stays round on wide screens
+    vec2 offset = TexCoords - 0.5;
+    offset.x *= texelSize.y / texelSize.x;
+    float falloff = smoothstep(radius - smoothness, radius, length(offset));
+
+    color *= 1.0 - intensity * falloff;
+    FragColor = vec4(color, 1.0);
+}
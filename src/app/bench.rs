@@ -0,0 +1,248 @@
+//! Raycast Benchmark - Scene BVH queries against a linear scan over every collider
+//!
+//! Needs no window or GL context: the scene only holds transforms and colliders.
+
+use crate::math::bounds::Aabb;
+use crate::math::ray::Ray;
+use crate::scene::bvh::Bvh;
+use crate::scene::collider::Collider;
+use crate::scene::components::GlobalTransform;
+use crate::scene::handle::ObjectId;
+use crate::scene::manager::Scene;
+use crate::scene::systems;
+use crate::scene::transform::Transform;
+use glam::{Quat, Vec3};
+use std::f32::consts::TAU;
+use std::time::{Duration, Instant};
+
+pub const USAGE: &str = "\
+Usage: opengl_renderer bench [OPTIONS]
+
+Times scene raycasts through the BVH against a linear scan over every collider.
+
+Options:
+  --objects N         Objects with colliders to scatter (default: 1000)
+  --rays N            Rays per query type (default: 1000)
+  --seed N            Random seed for the layout and rays (default: 1)";
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub objects: u32,
+    pub rays: u32,
+    pub seed: u64,
+}
+
+impl BenchOptions {
+    /// Parse the arguments that follow the `bench` subcommand
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            objects: 1000,
+            rays: 1000,
+            seed: 1,
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut number = |name: &str| -> Result<u64, String> {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("Missing value for {}\n\n{}", name, USAGE))?;
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid {} '{}'", name, value))
+            };
+
+            match arg.as_str() {
+                "--objects" => options.objects = number("--objects")? as u32,
+                "--rays" => options.rays = number("--rays")?.max(1) as u32,
+                "--seed" => options.seed = number("--seed")?,
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                other => return Err(format!("Unknown option '{}'\n\n{}", other, USAGE)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Build the scene, run every query both ways and print the timings.
+/// Fails if the BVH and the linear scan disagree on any result.
+pub fn run(options: &BenchOptions) -> Result<(), String> {
+    let mut rng = Rng(options.seed.max(1));
+    // Keep the density roughly constant as the object count grows
+    let extent = (options.objects as f32).cbrt() * 4.0;
+
+    let mut scene = Scene::new();
+    for _ in 0..options.objects {
+        let id = scene.world.spawn();
+        let transform = Transform::new(
+            rng.vec3(extent),
+            Quat::from_euler(
+                glam::EulerRot::XYZ,
+                rng.range(0.0, TAU),
+                rng.range(0.0, TAU),
+                0.0,
+            ),
+            Vec3::splat(rng.range(0.5, 2.0)),
+        );
+        let collider = if rng.next() < 0.5 {
+            Collider::new_sphere(rng.range(0.3, 1.0))
+        } else {
+            Collider::new_cube(rng.range(0.5, 1.5))
+        };
+        scene.world.insert(id, transform);
+        scene.world.insert(id, collider);
+    }
+    scene.update_world_transforms();
+
+    let rays: Vec<Ray> = (0..options.rays)
+        .map(|_| Ray::new(rng.vec3(extent * 1.5), rng.vec3(1.0)))
+        .collect();
+
+    println!(
+        "[bench] {} objects, {} rays, seed {}",
+        options.objects, options.rays, options.seed
+    );
+
+    // Tree maintenance
+    let items = systems::collider_bounds(&scene.world);
+    let (mut bvh, build_time) = timed(|| Bvh::build(&items));
+    let moved: Vec<(ObjectId, Aabb)> = items
+        .iter()
+        .map(|&(id, aabb)| {
+            let offset = rng.vec3(0.1);
+            (id, Aabb::new(aabb.min + offset, aabb.max + offset))
+        })
+        .collect();
+    let (rebuilt, refit_time) = timed(|| bvh.update(&moved));
+    println!("  build             {:>10.3} ms", ms(build_time));
+    println!(
+        "  refit (all moved) {:>10.3} ms{}",
+        ms(refit_time),
+        if rebuilt { " (rebuilt)" } else { "" }
+    );
+
+    // Nearest hit
+    let (linear, linear_time) = timed(|| {
+        rays.iter()
+            .map(|ray| linear_nearest(&scene, ray))
+            .collect::<Vec<_>>()
+    });
+    let (accelerated, bvh_time) = timed(|| {
+        rays.iter()
+            .map(|ray| scene.nearest_hit(ray))
+            .collect::<Vec<_>>()
+    });
+    report("nearest hit", linear_time, bvh_time);
+    let nearest_mismatches = linear
+        .iter()
+        .zip(&accelerated)
+        .filter(|(a, b)| a.map(|(id, _)| id) != b.map(|(id, _)| id))
+        .count();
+    let hit_count = accelerated.iter().filter(|hit| hit.is_some()).count();
+
+    // All hits
+    let (linear, linear_time) = timed(|| {
+        rays.iter()
+            .map(|ray| linear_all(&scene, ray))
+            .collect::<Vec<_>>()
+    });
+    let (accelerated, bvh_time) = timed(|| {
+        rays.iter()
+            .map(|ray| ids(&scene.all_hits(ray)))
+            .collect::<Vec<_>>()
+    });
+    report("all hits", linear_time, bvh_time);
+    let all_mismatches = linear
+        .iter()
+        .zip(&accelerated)
+        .filter(|(a, b)| a != b)
+        .count();
+
+    println!("  {} of {} rays hit something", hit_count, options.rays);
+    if nearest_mismatches + all_mismatches > 0 {
+        return Err(format!(
+            "BVH results differ from the linear scan: {} nearest, {} all-hits",
+            nearest_mismatches, all_mismatches
+        ));
+    }
+    Ok(())
+}
+
+/// The pre-BVH query: test every collider
+fn linear_nearest(scene: &Scene, ray: &Ray) -> Option<(ObjectId, f32)> {
+    let mut hit: Option<(ObjectId, f32)> = None;
+    scene
+        .world
+        .query::<(&GlobalTransform, &Collider)>(|id, (global, collider)| {
+            if let Some(dist) = collider.intersect(ray, &global.0) {
+                if hit.is_none_or(|(_, min_dist)| dist < min_dist) {
+                    hit = Some((id, dist));
+                }
+            }
+        });
+    hit
+}
+
+fn linear_all(scene: &Scene, ray: &Ray) -> Vec<ObjectId> {
+    let mut hits = Vec::new();
+    scene
+        .world
+        .query::<(&GlobalTransform, &Collider)>(|id, (global, collider)| {
+            if let Some(dist) = collider.intersect(ray, &global.0) {
+                hits.push((id, dist));
+            }
+        });
+    hits.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    ids(&hits)
+}
+
+fn ids(hits: &[(ObjectId, f32)]) -> Vec<ObjectId> {
+    hits.iter().map(|&(id, _)| id).collect()
+}
+
+fn report(query: &str, linear: Duration, bvh: Duration) {
+    println!(
+        "  {:<17} linear {:>10.3} ms   bvh {:>10.3} ms   {:>6.1}x",
+        query,
+        ms(linear),
+        ms(bvh),
+        linear.as_secs_f64() / bvh.as_secs_f64().max(1e-9)
+    );
+}
+
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Small xorshift generator so runs are repeatable for a given seed
+struct Rng(u64);
+
+impl Rng {
+    /// Uniform in [0, 1)
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next()
+    }
+
+    /// Uniform in the cube [-extent, extent]^3
+    fn vec3(&mut self, extent: f32) -> Vec3 {
+        Vec3::new(
+            self.range(-extent, extent),
+            self.range(-extent, extent),
+            self.range(-extent, extent),
+        )
+    }
+}
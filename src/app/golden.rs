@@ -0,0 +1,452 @@
+//! Golden Images - Renderer regression checks against stored reference images
+//!
+//! Each case in `assets/golden/cases.ron` renders a scene offscreen through the normal
+//! `Game`/`Renderer` path and compares it with `assets/golden/<name>.png`. Pixels are
+//! compared in CIELAB, so the tolerance is in perceptual (ΔE) units rather than raw RGB.
+
+use crate::app::headless::{create_parent_dir, render_frames};
+use crate::assets::paths::golden;
+use crate::game::{Game, RenderMode};
+use crate::renderer::target::{RenderTarget, TargetFormat};
+use crate::window::GlWindow;
+use glfw::ContextCreationApi;
+use image::{Rgb, RgbImage};
+use serde::Deserialize;
+
+pub const USAGE: &str = "\
+Usage: opengl_renderer golden [OPTIONS]
+
+Renders every case in the golden manifest and compares it with its stored image.
+
+Options:
+  --update            Overwrite the stored images instead of comparing
+  --cases PATH        Case manifest (default: assets/golden/cases.ron)
+  --diff-dir DIR      Where actual/diff images of failed cases go (default: target/golden)
+  --context API       native | egl | osmesa (default: native)";
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Tolerance {
+    /// Largest CIE76 ΔE a pixel may differ by (≈2.3 is a just-noticeable difference)
+    #[serde(default = "default_max_delta_e")]
+    pub max_delta_e: f32,
+    /// Fraction of pixels allowed to exceed `max_delta_e` (rasterization differences on edges)
+    #[serde(default = "default_max_failing_fraction")]
+    pub max_failing_fraction: f32,
+}
+
+fn default_max_delta_e() -> f32 {
+    3.0
+}
+
+fn default_max_failing_fraction() -> f32 {
+    0.002
+}
+
+fn default_frames() -> u32 {
+    3
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            max_delta_e: default_max_delta_e(),
+            max_failing_fraction: default_max_failing_fraction(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GoldenCase {
+    /// Golden image is stored as `<name>.png` next to the manifest
+    pub name: String,
+    pub scene: String,
+    pub width: u32,
+    pub height: u32,
+    /// Frames to simulate before capturing; the point shadow maps need a few to all update
+    #[serde(default = "default_frames")]
+    pub frames: u32,
+    #[serde(default)]
+    pub tolerance: Tolerance,
+}
+
+#[derive(Debug, Clone)]
+pub struct GoldenOptions {
+    pub update: bool,
+    pub cases: String,
+    pub diff_dir: String,
+    pub context_api: ContextCreationApi,
+}
+
+impl GoldenOptions {
+    /// Parse the arguments that follow the `golden` subcommand
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            update: false,
+            cases: golden::CASES.to_string(),
+            diff_dir: golden::DIFF_DIR.to_string(),
+            context_api: ContextCreationApi::Native,
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = |name: &str| {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for {}\n\n{}", name, USAGE))
+            };
+
+            match arg.as_str() {
+                "--update" => options.update = true,
+                "--cases" => options.cases = value("--cases")?,
+                "--diff-dir" => options.diff_dir = value("--diff-dir")?,
+                "--context" => {
+                    options.context_api = match value("--context")?.as_str() {
+                        "native" => ContextCreationApi::Native,
+                        "egl" => ContextCreationApi::Egl,
+                        "osmesa" => ContextCreationApi::OsMesa,
+                        other => return Err(format!("Unknown --context '{}'", other)),
+                    }
+                }
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                other => return Err(format!("Unknown option '{}'\n\n{}", other, USAGE)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Outcome of comparing one image against its golden
+#[derive(Debug, Clone, Copy)]
+pub struct Comparison {
+    pub failing_pixels: usize,
+    pub total_pixels: usize,
+    pub max_delta_e: f32,
+}
+
+impl Comparison {
+    pub fn failing_fraction(&self) -> f32 {
+        self.failing_pixels as f32 / self.total_pixels.max(1) as f32
+    }
+
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.failing_fraction() <= tolerance.max_failing_fraction
+    }
+}
+
+/// Render (and check or update) every case. Returns `Ok(true)` if all cases passed.
+pub fn run(options: &GoldenOptions) -> Result<bool, String> {
+    let source = std::fs::read_to_string(&options.cases)
+        .map_err(|e| format!("Failed to read golden cases '{}': {}", options.cases, e))?;
+    let cases: Vec<GoldenCase> = ron::from_str(&source)
+        .map_err(|e| format!("Failed to parse golden cases '{}': {}", options.cases, e))?;
+    let golden_dir = std::path::Path::new(&options.cases)
+        .parent()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    // One hidden context for every case; each case builds its own Game on it
+    let mut window = GlWindow::new_hidden(64, 64, options.context_api);
+    window.init_gl();
+
+    let mut failed = Vec::new();
+    for case in &cases {
+        let golden_path = format!("{}/{}.png", golden_dir, case.name);
+        let actual = render_case(case)?;
+
+        if options.update {
+            create_parent_dir(&golden_path)?;
+            actual
+                .save(&golden_path)
+                .map_err(|e| format!("Failed to write golden '{}': {}", golden_path, e))?;
+            println!("[golden] {}: updated {}", case.name, golden_path);
+            continue;
+        }
+
+        let expected = match image::open(&golden_path) {
+            Ok(image) => image.into_rgb8(),
+            Err(e) => {
+                println!(
+                    "[golden] {}: FAILED, no golden at {} ({}); run `golden --update`",
+                    case.name, golden_path, e
+                );
+                write_failure(options, case, &actual, None)?;
+                failed.push(case.name.clone());
+                continue;
+            }
+        };
+
+        if expected.dimensions() != actual.dimensions() {
+            println!(
+                "[golden] {}: FAILED, size {:?} != golden {:?}",
+                case.name,
+                actual.dimensions(),
+                expected.dimensions()
+            );
+            write_failure(options, case, &actual, None)?;
+            failed.push(case.name.clone());
+            continue;
+        }
+
+        let (comparison, diff) = compare(&expected, &actual, &case.tolerance);
+        let summary = format!(
+            "{:.3}% pixels over ΔE {:.1} (max ΔE {:.2})",
+            comparison.failing_fraction() * 100.0,
+            case.tolerance.max_delta_e,
+            comparison.max_delta_e
+        );
+
+        if comparison.passes(&case.tolerance) {
+            println!("[golden] {}: ok, {}", case.name, summary);
+        } else {
+            println!("[golden] {}: FAILED, {}", case.name, summary);
+            write_failure(options, case, &actual, Some(&diff))?;
+            failed.push(case.name.clone());
+        }
+    }
+
+    if !options.update {
+        println!(
+            "[golden] {} passed, {} failed",
+            cases.len() - failed.len(),
+            failed.len()
+        );
+    }
+    Ok(failed.is_empty())
+}
+
+/// Render one case offscreen and read back the final frame
+fn render_case(case: &GoldenCase) -> Result<RgbImage, String> {
+    let target = RenderTarget::new(case.width, case.height, TargetFormat::Rgba8)?;
+
+    let mut game = Game::from_scene_file(&case.scene)?;
+    game.set_ui_visible(false);
+    game.set_output(target.viewport());
+
+    render_frames(&mut game, &target, case.frames, 1.0 / 60.0, |_| Ok(()))?;
+    Ok(image::DynamicImage::ImageRgba8(target.read_rgba8()).into_rgb8())
+}
+
+fn write_failure(
+    options: &GoldenOptions,
+    case: &GoldenCase,
+    actual: &RgbImage,
+    diff: Option<&RgbImage>,
+) -> Result<(), String> {
+    let actual_path = format!("{}/{}.actual.png", options.diff_dir, case.name);
+    create_parent_dir(&actual_path)?;
+    actual
+        .save(&actual_path)
+        .map_err(|e| format!("Failed to write '{}': {}", actual_path, e))?;
+    println!("         actual: {}", actual_path);
+
+    if let Some(diff) = diff {
+        let diff_path = format!("{}/{}.diff.png", options.diff_dir, case.name);
+        diff.save(&diff_path)
+            .map_err(|e| format!("Failed to write '{}': {}", diff_path, e))?;
+        println!("         diff:   {}", diff_path);
+    }
+    Ok(())
+}
+
+/// Per-pixel ΔE comparison. The diff image shows the golden dimmed to grey, with pixels
+/// over tolerance in red (brighter = larger difference).
+pub fn compare(
+    expected: &RgbImage,
+    actual: &RgbImage,
+    tolerance: &Tolerance,
+) -> (Comparison, RgbImage) {
+    let (width, height) = expected.dimensions();
+    let mut diff = RgbImage::new(width, height);
+    let mut comparison = Comparison {
+        failing_pixels: 0,
+        total_pixels: (width * height) as usize,
+        max_delta_e: 0.0,
+    };
+
+    for (x, y, expected_px) in expected.enumerate_pixels() {
+        let actual_px = actual.get_pixel(x, y);
+        let delta_e = delta_e(expected_px, actual_px);
+        comparison.max_delta_e = comparison.max_delta_e.max(delta_e);
+
+        let out = if delta_e > tolerance.max_delta_e {
+            comparison.failing_pixels += 1;
+            let intensity = (128.0 + delta_e * 4.0).min(255.0) as u8;
+            Rgb([intensity, 0, 0])
+        } else {
+            let [r, g, b] = expected_px.0;
+            let grey = (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32) * 0.3;
+            Rgb([grey as u8; 3])
+        };
+        diff.put_pixel(x, y, out);
+    }
+
+    (comparison, diff)
+}
+
+/// CIE76 color difference between two sRGB pixels
+fn delta_e(a: &Rgb<u8>, b: &Rgb<u8>) -> f32 {
+    let (l1, a1, b1) = srgb_to_lab(a);
+    let (l2, a2, b2) = srgb_to_lab(b);
+    ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+}
+
+fn srgb_to_lab(pixel: &Rgb<u8>) -> (f32, f32, f32) {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let [r, g, b] = pixel.0.map(linear);
+
+    // Linear sRGB -> XYZ (D65), normalized by the reference white
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Components of `actual` within `eps` of `expected`
+    fn assert_lab_near(actual: (f32, f32, f32), expected: (f32, f32, f32), eps: f32) {
+        let (l, a, b) = actual;
+        let (el, ea, eb) = expected;
+        assert!(
+            (l - el).abs() < eps && (a - ea).abs() < eps && (b - eb).abs() < eps,
+            "{:?} is not within {} of {:?}",
+            actual,
+            eps,
+            expected
+        );
+    }
+
+    /// 10x10 image of `color`
+    fn solid(color: [u8; 3]) -> RgbImage {
+        RgbImage::from_pixel(10, 10, Rgb(color))
+    }
+
+    #[test]
+    fn srgb_converts_to_reference_lab_values() {
+        assert_lab_near(srgb_to_lab(&Rgb([0, 0, 0])), (0.0, 0.0, 0.0), 0.01);
+        assert_lab_near(srgb_to_lab(&Rgb([255, 255, 255])), (100.0, 0.0, 0.0), 0.05);
+        assert_lab_near(srgb_to_lab(&Rgb([128, 128, 128])), (53.59, 0.0, 0.0), 0.05);
+        assert_lab_near(srgb_to_lab(&Rgb([255, 0, 0])), (53.24, 80.09, 67.20), 0.1);
+        assert_lab_near(srgb_to_lab(&Rgb([0, 255, 0])), (87.73, -86.18, 83.18), 0.1);
+        assert_lab_near(srgb_to_lab(&Rgb([0, 0, 255])), (32.30, 79.19, -107.86), 0.1);
+    }
+
+    #[test]
+    fn delta_e_is_the_lab_distance() {
+        let red = Rgb([255, 0, 0]);
+        let grey = Rgb([128, 128, 128]);
+        assert_eq!(delta_e(&red, &red), 0.0);
+        assert!((delta_e(&Rgb([0, 0, 0]), &Rgb([255, 255, 255])) - 100.0).abs() < 0.05);
+        assert_eq!(delta_e(&red, &grey), delta_e(&grey, &red));
+
+        // One step of 8-bit grey is well under a just-noticeable difference
+        let step = delta_e(&grey, &Rgb([129, 129, 129]));
+        assert!(step > 0.0 && step < 1.0, "{}", step);
+    }
+
+    #[test]
+    fn identical_images_pass_with_a_grey_diff() {
+        let image = solid([200, 100, 50]);
+        let (comparison, diff) = compare(&image, &image, &Tolerance::default());
+
+        assert_eq!(comparison.failing_pixels, 0);
+        assert_eq!(comparison.total_pixels, 100);
+        assert_eq!(comparison.max_delta_e, 0.0);
+        assert!(comparison.passes(&Tolerance::default()));
+        assert!(diff.pixels().all(|px| px[0] == px[1] && px[1] == px[2]));
+    }
+
+    #[test]
+    fn pixels_over_max_delta_e_fail_and_show_red() {
+        let expected = solid([128, 128, 128]);
+        let mut actual = expected.clone();
+        actual.put_pixel(3, 4, Rgb([255, 0, 0]));
+        // Just noticeable: under the default tolerance
+        actual.put_pixel(5, 5, Rgb([130, 128, 128]));
+        let (comparison, diff) = compare(&expected, &actual, &Tolerance::default());
+
+        assert_eq!(comparison.failing_pixels, 1);
+        assert_eq!(
+            comparison.max_delta_e,
+            delta_e(&Rgb([128, 128, 128]), &Rgb([255, 0, 0]))
+        );
+        let marked = diff.get_pixel(3, 4);
+        assert!(marked[0] >= 128 && marked[1] == 0 && marked[2] == 0);
+        assert_eq!(diff.get_pixel(5, 5), diff.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn a_pixel_at_exactly_max_delta_e_does_not_fail() {
+        let expected = solid([128, 128, 128]);
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 0, Rgb([140, 128, 128]));
+        let tolerance = Tolerance {
+            max_delta_e: delta_e(&Rgb([128, 128, 128]), &Rgb([140, 128, 128])),
+            ..Tolerance::default()
+        };
+
+        let (comparison, _) = compare(&expected, &actual, &tolerance);
+        assert_eq!(comparison.failing_pixels, 0);
+    }
+
+    #[test]
+    fn cases_pass_up_to_the_failing_fraction() {
+        let tolerance = Tolerance {
+            max_delta_e: 3.0,
+            max_failing_fraction: 0.01,
+        };
+        let comparison = |failing_pixels| Comparison {
+            failing_pixels,
+            total_pixels: 1000,
+            max_delta_e: 50.0,
+        };
+
+        assert!(comparison(0).passes(&tolerance));
+        assert!(comparison(10).passes(&tolerance));
+        assert!(!comparison(11).passes(&tolerance));
+        assert_eq!(comparison(10).failing_fraction(), 0.01);
+
+        // An empty image doesn't divide by zero
+        let empty = Comparison {
+            failing_pixels: 0,
+            total_pixels: 0,
+            max_delta_e: 0.0,
+        };
+        assert_eq!(empty.failing_fraction(), 0.0);
+        assert!(empty.passes(&tolerance));
+    }
+
+    #[test]
+    fn omitted_tolerance_fields_take_the_defaults() {
+        let tolerance: Tolerance = ron::from_str("(max_delta_e: 5.0)").unwrap();
+        assert_eq!(tolerance.max_delta_e, 5.0);
+        assert_eq!(
+            tolerance.max_failing_fraction,
+            default_max_failing_fraction()
+        );
+
+        let case: GoldenCase =
+            ron::from_str("(name: \"a\", scene: \"a.ron\", width: 64, height: 32)").unwrap();
+        assert_eq!(case.tolerance.max_delta_e, default_max_delta_e());
+        assert_eq!(case.frames, default_frames());
+    }
+}
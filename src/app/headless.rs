@@ -0,0 +1,242 @@
+//! Headless Application - Renders a mode offscreen and writes frames to image files
+//!
+//! Uses a hidden window only to own the GL context, so it runs on machines without a
+//! visible desktop (e.g. `xvfb-run` + Mesa llvmpipe, or an EGL/OSMesa context).
+
+use crate::config::window as win_cfg;
+use crate::game::RenderMode;
+use crate::renderer::target::{RenderTarget, TargetFormat};
+use crate::time::Time;
+use crate::window::GlWindow;
+use glfw::ContextCreationApi;
+use image::DynamicImage;
+use std::path::Path;
+
+pub const USAGE: &str = "\
+Usage: opengl_renderer [SCENE] [--headless [OPTIONS]]
+
+Headless options:
+  --size WxH          Output resolution (default: window size)
+  --frames N          Frames to simulate and render (default: 1)
+  --frame-time SECS   Simulated seconds per frame (default: 1/60)
+  --output PATH       .png or .exr; \"{frame}\" in the name writes every frame,
+                      otherwise only the last one (default: frame.png)
+  --context API       native | egl | osmesa (default: native)
+  --no-ui             Hide the HUD and inspector
+  --deferred          Use the deferred shading path";
+
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    pub frame_time: f32,
+    pub output: String,
+    pub context_api: ContextCreationApi,
+    pub show_ui: bool,
+    pub deferred: bool,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            width: win_cfg::WIDTH,
+            height: win_cfg::HEIGHT,
+            frames: 1,
+            frame_time: 1.0 / 60.0,
+            output: "frame.png".to_string(),
+            context_api: ContextCreationApi::Native,
+            show_ui: true,
+            deferred: false,
+        }
+    }
+}
+
+impl HeadlessOptions {
+    /// Split command line arguments into headless options (if `--headless` is given)
+    /// and the remaining positional arguments
+    pub fn parse(args: &[String]) -> Result<(Option<Self>, Vec<String>), String> {
+        let mut headless = false;
+        let mut options = Self::default();
+        let mut positional = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = |name: &str| {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for {}\n\n{}", name, USAGE))
+            };
+
+            match arg.as_str() {
+                "--headless" => headless = true,
+                "--size" => {
+                    let size = value("--size")?;
+                    let (w, h) = size
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .filter(|&(w, h): &(u32, u32)| w > 0 && h > 0)
+                        .ok_or_else(|| format!("Invalid --size '{}', expected WxH", size))?;
+                    options.width = w;
+                    options.height = h;
+                }
+                "--frames" => {
+                    let frames = value("--frames")?;
+                    options.frames = frames
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("Invalid --frames '{}'", frames))?;
+                }
+                "--frame-time" => {
+                    let secs = value("--frame-time")?;
+                    options.frame_time = secs
+                        .parse()
+                        .map_err(|_| format!("Invalid --frame-time '{}'", secs))?;
+                }
+                "--output" => options.output = value("--output")?,
+                "--context" => {
+                    options.context_api = match value("--context")?.as_str() {
+                        "native" => ContextCreationApi::Native,
+                        "egl" => ContextCreationApi::Egl,
+                        "osmesa" => ContextCreationApi::OsMesa,
+                        other => return Err(format!("Unknown --context '{}'", other)),
+                    }
+                }
+                "--no-ui" => options.show_ui = false,
+                "--deferred" => options.deferred = true,
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                other if other.starts_with("--") => {
+                    return Err(format!("Unknown option '{}'\n\n{}", other, USAGE))
+                }
+                other => positional.push(other.to_string()),
+            }
+        }
+
+        if !headless
+            && args
+                .iter()
+                .any(|a| a.starts_with("--") && a != "--headless")
+        {
+            return Err(format!("Rendering options require --headless\n\n{}", USAGE));
+        }
+
+        Ok((headless.then_some(options), positional))
+    }
+
+    /// EXR output keeps float values; everything else is written as 8-bit
+    pub fn target_format(&self) -> TargetFormat {
+        let is_exr = Path::new(&self.output)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"));
+        if is_exr {
+            TargetFormat::Rgba32F
+        } else {
+            TargetFormat::Rgba8
+        }
+    }
+}
+
+pub struct HeadlessApplication {
+    mode: Box<dyn RenderMode>,
+    // Owns the GL context; never shown
+    _window: GlWindow,
+    target: RenderTarget,
+    options: HeadlessOptions,
+}
+
+impl HeadlessApplication {
+    /// `window` must be the (hidden) window whose context `mode` was created with
+    pub fn new(
+        window: GlWindow,
+        mut mode: Box<dyn RenderMode>,
+        options: HeadlessOptions,
+    ) -> Result<Self, String> {
+        let target = RenderTarget::new(options.width, options.height, options.target_format())?;
+        mode.set_output(target.viewport());
+
+        Ok(Self {
+            mode,
+            _window: window,
+            target,
+            options,
+        })
+    }
+
+    /// Simulate and render every frame, writing the requested images
+    pub fn run(&mut self) -> Result<(), String> {
+        let per_frame = self.options.output.contains("{frame}");
+        let frames = self.options.frames;
+        let output = &self.options.output;
+        let target = &self.target;
+
+        render_frames(
+            self.mode.as_mut(),
+            target,
+            frames,
+            self.options.frame_time,
+            |frame| {
+                if per_frame || frame + 1 == frames {
+                    let path = output.replace("{frame}", &format!("{:04}", frame));
+                    save_target(target, &path)?;
+                    println!("Wrote frame: {}", path);
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Simulate `frames` fixed steps of `mode`, rendering each into `target`.
+/// `on_frame` runs after every frame with the frame index, while the image is still in `target`.
+pub fn render_frames(
+    mode: &mut dyn RenderMode,
+    target: &RenderTarget,
+    frames: u32,
+    frame_time: f32,
+    mut on_frame: impl FnMut(u32) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut time = Time::new();
+
+    for frame in 0..frames {
+        time.advance(if frame == 0 { 0.0 } else { frame_time });
+
+        target.viewport().bind();
+        unsafe {
+            gl::ClearColor(0.2, 0.3, 0.3, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+
+        mode.update(&time);
+        mode.render();
+
+        on_frame(frame)?;
+    }
+
+    Ok(())
+}
+
+/// Read `target` back and save it; alpha is dropped like on a window
+pub fn save_target(target: &RenderTarget, path: &str) -> Result<(), String> {
+    create_parent_dir(path)?;
+
+    let result = match target.format {
+        TargetFormat::Rgba8 => DynamicImage::ImageRgba8(target.read_rgba8())
+            .into_rgb8()
+            .save(path),
+        TargetFormat::Rgba32F | TargetFormat::Rgba16F => {
+            DynamicImage::ImageRgba32F(target.read_rgba32f())
+                .into_rgb32f()
+                .save(path)
+        }
+    };
+    result.map_err(|e| format!("Failed to write frame '{}': {}", path, e))
+}
+
+pub fn create_parent_dir(path: &str) -> Result<(), String> {
+    match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create '{}': {}", dir.display(), e)),
+        _ => Ok(()),
+    }
+}
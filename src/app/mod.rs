@@ -1,3 +1,7 @@
+pub mod bench;
+pub mod golden;
+pub mod headless;
+
 use glfw::Context;
 use crate::window::GlWindow;
 use crate::game::RenderMode;
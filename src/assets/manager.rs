@@ -1,14 +1,24 @@
+use crate::assets::paths::{names, shaders};
 use crate::importer::AssetImporter;
-use crate::scene::model::Model;
+use crate::scene::material::{BlendMode, Material};
+use crate::scene::material_factory::MaterialFactory;
+use crate::scene::model::{Model, ModelMaterial};
 use crate::shaders::{CubeMap, Shader, Texture};
-use std::collections::HashMap;
+use glam::Vec3;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 pub struct AssetManager {
     shaders: HashMap<String, Rc<Shader>>,
-    textures: HashMap<String, Rc<Texture>>,
+    /// By name and color space: the same file read as sRGB color and as linear data
+    /// are two different textures
+    textures: HashMap<(String, bool), Rc<Texture>>,
     models: HashMap<String, Rc<Model>>,
+    /// Per-mesh materials of the models, by model name
+    model_materials: HashMap<String, Vec<Rc<dyn Material>>>,
     cubemaps: HashMap<String, Rc<CubeMap>>,
+    /// Point shadow cube maps the lit shaders are compiled for (MAX_POINT_SHADOWS)
+    point_shadow_slots: usize,
 }
 
 impl AssetManager {
@@ -17,10 +27,19 @@ impl AssetManager {
             shaders: HashMap::new(),
             textures: HashMap::new(),
             models: HashMap::new(),
+            model_materials: HashMap::new(),
             cubemaps: HashMap::new(),
+            point_shadow_slots: crate::config::rendering::MAX_POINT_SHADOWS,
         }
     }
 
+    /// Compile the lit shaders for `slots` point shadow cube maps instead of the
+    /// configured number
+    pub fn with_point_shadow_slots(mut self, slots: usize) -> Self {
+        self.point_shadow_slots = slots;
+        self
+    }
+
     pub fn load_shader(&mut self, name: &str, vert: &str, frag: &str) -> Rc<Shader> {
         let vs_source = std::fs::read_to_string(vert)
             .expect(&format!("Failed to read vertex shader: {}", vert));
@@ -36,78 +55,173 @@ impl AssetManager {
         shader
     }
 
+    /// Expand the `#include`s, and define the engine limits the shaders size their arrays
+    /// by and the shadow filter constants they share right after `#version`
     fn preprocess_shader(&self, source: &str) -> String {
-        let max_lights = crate::config::rendering::MAX_POINT_LIGHTS;
-
-        // 1. Inject MAX_POINT_LIGHTS
-        let source = source.replace(
-            "#define NR_POINT_LIGHTS 4",
-            &format!("#define NR_POINT_LIGHTS {}", max_lights),
+        let source = expand_includes(source, &mut HashSet::new());
+        let defines = format!(
+            "#define MAX_CASCADES {}\n#define MAX_POINT_SHADOWS {}\n#define MAX_SPOT_SHADOWS {}\n\
+             #define SSAO_MAX_SAMPLES {}\n\
+             #define ESM_EXPONENT {:?}\n#define VSM_BLEED_REDUCTION {:?}\n",
+            crate::config::rendering::MAX_SHADOW_CASCADES,
+            self.point_shadow_slots,
+            crate::config::rendering::MAX_SPOT_SHADOWS,
+            crate::config::rendering::SSAO_MAX_SAMPLES,
+            crate::config::rendering::SHADOW_ESM_EXPONENT,
+            crate::config::rendering::SHADOW_VSM_BLEED_REDUCTION
         );
 
-        // 2. Unroll Point Shadow Loop if placeholder exists
-        if source.contains("{{POINT_SHADOW_LOOP}}") {
-            let unrolled_checks = (0..max_lights)
-                .map(|i| {
-                    format!(
-                        "                {} (i == {}) pShadow = calcPointShadow(FragPos, pointLights[{}].position, pointShadowMaps[{}], 15.0);",
-                        if i == 0 { "if" } else { "else if" },
-                        i, i, i
-                    )
-                })
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            let loop_code = format!(
-                r#"
-        // Generated Unrolled Point Shadow Loop
-        for(int i = 0; i < nrPointLights; i++) {{
-            float pShadow = 0.0;
-            if (u_UseShadows != 0) {{
-                vec3 lightToFrag = normalize(FragPos - pointLights[i].position);
-                if (dot(norm, -lightToFrag) > 0.0) {{
-{}
-                }}
-            }}
-            result += calcPointLight(pointLights[i], norm, viewDir, pShadow);
-        }}
-"#,
-                unrolled_checks
-            );
-
-            return source.replace("// {{POINT_SHADOW_LOOP}}", &loop_code);
+        match source.split_once('\n') {
+            Some((version, rest)) if version.starts_with("#version") => {
+                format!("{}\n{}{}", version, defines, rest)
+            }
+            _ => format!("{}{}", defines, source),
         }
-
-        source
     }
 
     pub fn get_shader(&self, name: &str) -> Option<Rc<Shader>> {
         self.shaders.get(name).cloned()
     }
 
-    pub fn load_texture(&mut self, name: &str, path: &str) -> Rc<Texture> {
-        let texture =
-            Rc::new(Texture::from_file(path).expect(&format!("Failed to load texture: {}", name)));
-        self.textures.insert(name.to_string(), texture.clone());
-        texture
+    /// Load a color texture (diffuse, base color, emissive), sampled as linear from sRGB
+    pub fn load_texture(&mut self, name: &str, path: &str) -> Result<Rc<Texture>, String> {
+        let texture = Rc::new(
+            Texture::from_file_srgb(path)
+                .map_err(|e| format!("Failed to load texture '{}': {}", name, e))?,
+        );
+        self.textures
+            .insert((name.to_string(), true), texture.clone());
+        Ok(texture)
+    }
+
+    /// Load a data texture (normal, metallic-roughness, occlusion) as is
+    pub fn load_linear_texture(&mut self, name: &str, path: &str) -> Result<Rc<Texture>, String> {
+        let texture = Rc::new(
+            Texture::from_file(path)
+                .map_err(|e| format!("Failed to load texture '{}': {}", name, e))?,
+        );
+        self.textures
+            .insert((name.to_string(), false), texture.clone());
+        Ok(texture)
+    }
+
+    /// Texture loaded as `name`, by `load_texture` if `srgb`, else by `load_linear_texture`
+    pub fn get_texture(&self, name: &str, srgb: bool) -> Option<Rc<Texture>> {
+        self.textures.get(&(name.to_string(), srgb)).cloned()
     }
 
-    pub fn get_texture(&self, name: &str) -> Option<Rc<Texture>> {
-        self.textures.get(name).cloned()
+    /// 1x1 white texture, created on first use
+    pub fn white_texture(&mut self) -> Rc<Texture> {
+        self.textures
+            .entry((names::TEX_WHITE.to_string(), true))
+            .or_insert_with(|| Rc::new(Texture::new(1, 1, &[255; 4], gl::RGBA)))
+            .clone()
     }
 
-    pub fn load_model(&mut self, name: &str, path: &str) -> Rc<Model> {
+    pub fn load_model(&mut self, name: &str, path: &str) -> Result<Rc<Model>, String> {
         let model = Rc::new(
-            AssetImporter::load_model(path).expect(&format!("Failed to load model: {}", name)),
+            AssetImporter::load_model(path)
+                .map_err(|e| format!("Failed to load model '{}': {}", name, e))?,
         );
         self.models.insert(name.to_string(), model.clone());
-        model
+        Ok(model)
     }
 
     pub fn get_model(&self, name: &str) -> Option<Rc<Model>> {
         self.models.get(name).cloned()
     }
 
+    /// One material per mesh of the model loaded as `name`, built from the file's colors
+    /// and textures on first use. `None` if no such model is loaded.
+    pub fn model_materials(
+        &mut self,
+        name: &str,
+        factory: &MaterialFactory,
+    ) -> Option<Vec<Rc<dyn Material>>> {
+        if let Some(materials) = self.model_materials.get(name) {
+            return Some(materials.clone());
+        }
+        let model = self.get_model(name)?;
+
+        // Meshes sharing a file material share the `Rc`, so they still batch together
+        let built: Vec<Rc<dyn Material>> = model
+            .materials
+            .iter()
+            .map(|source| self.build_model_material(source, factory))
+            .collect();
+        let fallback = self.build_model_material(&ModelMaterial::default(), factory);
+        let materials: Vec<Rc<dyn Material>> = model
+            .meshes
+            .iter()
+            .map(|mesh| built.get(mesh.material_index).unwrap_or(&fallback).clone())
+            .collect();
+
+        self.model_materials
+            .insert(name.to_string(), materials.clone());
+        Some(materials)
+    }
+
+    fn build_model_material(
+        &mut self,
+        source: &ModelMaterial,
+        factory: &MaterialFactory,
+    ) -> Rc<dyn Material> {
+        let diffuse = match self.model_texture(&source.diffuse_map, true) {
+            Some(texture) => texture,
+            None => self.white_texture(),
+        };
+        let mut material = factory.textured_material(diffuse, None, true, true);
+        material.color = source.diffuse;
+        material.specular = source.specular;
+        material.specular_map = self.model_texture(&source.specular_map, false);
+        material.normal_map = self.model_texture(&source.normal_map, false);
+        material.emissive_map = self.model_texture(&source.emissive_map, true);
+        material.emissive = match material.emissive_map {
+            // An emissive map without a color of its own shows as is
+            Some(_) if source.emissive == Vec3::ZERO => Vec3::ONE,
+            _ => source.emissive,
+        };
+        // The G-buffer has no room for emitted light
+        if material.emissive != Vec3::ZERO {
+            material.deferred_shader = None;
+        }
+        // Translucent materials blend; diffuse textures with alpha (foliage) are cut out
+        material.opacity = source.opacity;
+        if source.opacity < 1.0 {
+            material.blend_mode = BlendMode::Blend;
+        } else if source.diffuse_map.is_some() && material.texture.has_alpha {
+            material.blend_mode = BlendMode::Cutout {
+                threshold: crate::config::rendering::ALPHA_CUTOFF,
+            };
+        }
+        Rc::new(material)
+    }
+
+    /// Texture a model file refers to, cached by path and color space. Missing or broken files are
+    /// reported and left out, so the rest of the model still loads.
+    fn model_texture(&mut self, path: &Option<String>, srgb: bool) -> Option<Rc<Texture>> {
+        let path = path.as_ref()?;
+        if let Some(texture) = self.get_texture(path, srgb) {
+            return Some(texture);
+        }
+        let loaded = if srgb {
+            Texture::from_file_srgb(path)
+        } else {
+            Texture::from_file(path)
+        };
+        match loaded {
+            Ok(texture) => {
+                let texture = Rc::new(texture);
+                self.textures.insert((path.clone(), srgb), texture.clone());
+                Some(texture)
+            }
+            Err(e) => {
+                eprintln!("Skipping model texture '{}': {}", path, e);
+                None
+            }
+        }
+    }
+
     pub fn load_cubemap(&mut self, name: &str, path: &str) -> Rc<CubeMap> {
         let cubemap = Rc::new(
             CubeMap::from_cross_file(path).expect(&format!("Failed to load cubemap: {}", name)),
@@ -120,3 +234,62 @@ impl AssetManager {
         self.cubemaps.get(name).cloned()
     }
 }
+
+/// Replace each `#include "<file>"` line with that file from the shader include directory,
+/// its own includes expanded. A file already in `included` is left out, so includes can
+/// pull in what they depend on without it being defined twice.
+fn expand_includes(source: &str, included: &mut HashSet<String>) -> String {
+    let mut expanded = String::with_capacity(source.len());
+    for line in source.lines() {
+        let Some(file) = line.trim().strip_prefix("#include") else {
+            expanded.push_str(line);
+            expanded.push('\n');
+            continue;
+        };
+        let file = file.trim().trim_matches('"');
+        if included.insert(file.to_string()) {
+            let path = format!("{}/{}", shaders::INCLUDE_DIR, file);
+            let include = std::fs::read_to_string(&path)
+                .expect(&format!("Failed to read shader include: {}", path));
+            expanded.push_str(&expand_includes(&include, included));
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_are_expanded_once() {
+        let source = "#version 330 core\n\
+                      #include \"clusters.glsl\"\n\
+                      #include \"shadows.glsl\"\n\
+                      void main() {}\n";
+        let expanded = expand_includes(source, &mut HashSet::new());
+
+        assert!(expanded.starts_with("#version 330 core\n"));
+        assert!(!expanded.contains("#include"));
+        assert_eq!(expanded.matches("struct ShadowParams").count(), 1);
+        assert_eq!(expanded.matches("Light fetchLight(").count(), 1);
+        assert!(expanded.ends_with("void main() {}\n"));
+    }
+
+    #[test]
+    fn lit_shaders_expand_without_duplicates() {
+        for frag in [
+            shaders::LIT_COLOR_FRAG,
+            shaders::LIT_TEXTURED_FRAG,
+            shaders::LIT_PBR_FRAG,
+            shaders::DEFERRED_LIGHTING_FRAG,
+            shaders::LIGHT_VOLUME_POINT_FRAG,
+        ] {
+            let source = std::fs::read_to_string(frag).unwrap();
+            let expanded = expand_includes(&source, &mut HashSet::new());
+            assert!(!expanded.contains("#include"), "{}", frag);
+            let rotations = expanded.matches("mat2 poissonRotation()").count();
+            assert_eq!(rotations, 1, "{}", frag);
+        }
+    }
+}
@@ -3,12 +3,55 @@
 pub const SHADERS_DIR: &str = "assets/shaders";
 pub const TEXTURES_DIR: &str = "assets/resources/textures";
 pub const MODELS_DIR: &str = "assets/resources/models";
+pub const SCENES_DIR: &str = "assets/scenes";
 
 pub mod shaders {
+    // Shared GLSL pulled into fragment shaders by `#include "<file>"`
+    pub const INCLUDE_DIR: &str = concat!("assets/shaders", "/include");
+
     // Lit shaders (with lighting)
     pub const LIT_VERT: &str = concat!("assets/shaders", "/lit.vert");
     pub const LIT_COLOR_FRAG: &str = concat!("assets/shaders", "/lit_color.frag");
     pub const LIT_TEXTURED_FRAG: &str = concat!("assets/shaders", "/lit_textured.frag");
+    pub const LIT_PBR_FRAG: &str = concat!("assets/shaders", "/lit_pbr.frag");
+
+    // G-buffer shaders (deferred geometry pass, with LIT_VERT)
+    pub const GBUFFER_COLOR_FRAG: &str = concat!("assets/shaders", "/gbuffer_color.frag");
+    pub const GBUFFER_TEXTURED_FRAG: &str = concat!("assets/shaders", "/gbuffer_textured.frag");
+
+    // Fullscreen triangle, shared by the screen-space passes
+    pub const FULLSCREEN_VERT: &str = concat!("assets/shaders", "/fullscreen.vert");
+
+    // Deferred lighting shaders (with FULLSCREEN_VERT, and the light volume vertex shader)
+    pub const DEFERRED_LIGHTING_FRAG: &str = concat!("assets/shaders", "/deferred_lighting.frag");
+    pub const LIGHT_VOLUME_VERT: &str = concat!("assets/shaders", "/light_volume.vert");
+    pub const LIGHT_VOLUME_POINT_FRAG: &str = concat!("assets/shaders", "/light_volume_point.frag");
+
+    // HDR resolve shaders (with FULLSCREEN_VERT)
+    pub const LUMINANCE_FRAG: &str = concat!("assets/shaders", "/luminance.frag");
+    pub const EXPOSURE_FRAG: &str = concat!("assets/shaders", "/exposure.frag");
+    pub const TONEMAP_FRAG: &str = concat!("assets/shaders", "/tonemap.frag");
+
+    // Post-processing shaders (with FULLSCREEN_VERT)
+    pub const BLOOM_DOWNSAMPLE_FRAG: &str = concat!("assets/shaders", "/bloom_downsample.frag");
+    pub const BLOOM_UPSAMPLE_FRAG: &str = concat!("assets/shaders", "/bloom_upsample.frag");
+    pub const BLOOM_COMPOSITE_FRAG: &str = concat!("assets/shaders", "/bloom_composite.frag");
+    pub const FXAA_FRAG: &str = concat!("assets/shaders", "/fxaa.frag");
+    pub const VIGNETTE_FRAG: &str = concat!("assets/shaders", "/vignette.frag");
+    pub const CHROMATIC_ABERRATION_FRAG: &str =
+        concat!("assets/shaders", "/chromatic_aberration.frag");
+    pub const COLOR_GRADING_FRAG: &str = concat!("assets/shaders", "/color_grading.frag");
+
+    // Ambient occlusion shaders (with FULLSCREEN_VERT; the depth prepass uses the
+    // directional shadow shaders)
+    pub const SSAO_FRAG: &str = concat!("assets/shaders", "/ssao.frag");
+    pub const SSAO_BLUR_FRAG: &str = concat!("assets/shaders", "/ssao_blur.frag");
+    pub const SSAO_DEBUG_FRAG: &str = concat!("assets/shaders", "/ssao_debug.frag");
+
+    // Image-based lighting bake shaders (with SKYBOX_VERT, the BRDF table with FULLSCREEN_VERT)
+    pub const IRRADIANCE_FRAG: &str = concat!("assets/shaders", "/irradiance.frag");
+    pub const PREFILTER_FRAG: &str = concat!("assets/shaders", "/prefilter.frag");
+    pub const BRDF_LUT_FRAG: &str = concat!("assets/shaders", "/brdf_lut.frag");
 
     // UI shaders
     pub const UI_VERT: &str = concat!("assets/shaders", "/ui.vert");
@@ -27,36 +70,76 @@ pub mod shaders {
     pub const POINT_SHADOW_VERT: &str = concat!("assets/shaders", "/point_shadow_depth.vert");
     pub const POINT_SHADOW_GEOM: &str = concat!("assets/shaders", "/point_shadow_depth.geom");
     pub const POINT_SHADOW_FRAG: &str = concat!("assets/shaders", "/point_shadow_depth.frag");
+
+    // Variance and exponential shadow map filtering (with FULLSCREEN_VERT)
+    pub const SHADOW_MOMENTS_FRAG: &str = concat!("assets/shaders", "/shadow_moments.frag");
+    pub const SHADOW_BLUR_FRAG: &str = concat!("assets/shaders", "/shadow_blur.frag");
 }
 
 pub mod textures {
-    pub const GRASS: &str =
-        "assets/resources/textures/Poliigon_GrassPatchyGround_4585_BaseColor.jpg";
-    pub const STONE_BRICKS: &str = "assets/resources/textures/StoneBricks_1K.tiff";
     pub const SKYBOX: &str = "assets/resources/textures/Cubemap_Sky_22-512x512.png";
 }
 
-pub mod models {
-    pub const TREE: &str = "assets/resources/models/Tree2/trees9.obj";
-    pub const XWING: &str = "assets/resources/models/Xwing/x-wing.obj";
-    pub const STATUE: &str = "assets/resources/models/Statue/12334_statue_v1_l3.obj";
+/// Color grading lookup tables (strips of N slices of NxN)
+pub mod luts {
+    pub const WARM: &str = "assets/resources/luts/warm.png";
+}
+
+pub mod scenes {
+    pub const DEMO: &str = concat!("assets/scenes", "/demo.ron");
+    pub const SAVED: &str = concat!("assets/scenes", "/saved.ron");
+}
+
+pub mod config {
+    /// Post-processing chain loaded at startup
+    pub const POST_PROCESS: &str = "assets/post_process.ron";
+}
+
+pub mod cache {
+    /// Baked image-based lighting maps
+    pub const IBL_DIR: &str = "target/ibl_cache";
+}
+
+pub mod golden {
+    pub const CASES: &str = concat!("assets/golden", "/cases.ron");
+    /// Actual/diff images of failed cases
+    pub const DIFF_DIR: &str = "target/golden";
 }
 
 pub mod names {
     // Shaders
     pub const SHADER_COLORED: &str = "colored";
     pub const SHADER_TEXTURED: &str = "textured";
+    pub const SHADER_PBR: &str = "pbr";
+    pub const SHADER_GBUFFER_COLORED: &str = "gbuffer_colored";
+    pub const SHADER_GBUFFER_TEXTURED: &str = "gbuffer_textured";
+    pub const SHADER_DEFERRED_LIGHTING: &str = "deferred_lighting";
+    pub const SHADER_DEFERRED_POINT: &str = "deferred_point";
+    pub const SHADER_LUMINANCE: &str = "luminance";
+    pub const SHADER_EXPOSURE: &str = "exposure";
+    pub const SHADER_TONEMAP: &str = "tonemap";
+    pub const SHADER_BLOOM_DOWNSAMPLE: &str = "bloom_downsample";
+    pub const SHADER_BLOOM_UPSAMPLE: &str = "bloom_upsample";
+    pub const SHADER_BLOOM_COMPOSITE: &str = "bloom_composite";
+    pub const SHADER_FXAA: &str = "fxaa";
+    pub const SHADER_VIGNETTE: &str = "vignette";
+    pub const SHADER_CHROMATIC_ABERRATION: &str = "chromatic_aberration";
+    pub const SHADER_COLOR_GRADING: &str = "color_grading";
+    pub const SHADER_SSAO_DEPTH: &str = "ssao_depth";
+    pub const SHADER_SSAO: &str = "ssao";
+    pub const SHADER_SSAO_BLUR: &str = "ssao_blur";
+    pub const SHADER_SSAO_DEBUG: &str = "ssao_debug";
+    pub const SHADER_SHADOW_MOMENTS: &str = "shadow_moments";
+    pub const SHADER_SHADOW_BLUR: &str = "shadow_blur";
+    pub const SHADER_IRRADIANCE: &str = "irradiance";
+    pub const SHADER_PREFILTER: &str = "prefilter";
+    pub const SHADER_BRDF_LUT: &str = "brdf_lut";
     pub const SHADER_UI_TEXT: &str = "ui_text";
     pub const SHADER_UI_COLOR: &str = "ui_color";
     pub const SHADER_SKYBOX: &str = "skybox";
 
     // Textures
-    pub const TEX_GRASS: &str = "grass";
-    pub const TEX_STONE: &str = "stone";
     pub const TEX_SKYBOX: &str = "skybox";
-
-    // Models
-    pub const MODEL_TREE: &str = "tree";
-    pub const MODEL_XWING: &str = "xwing";
-    pub const MODEL_STATUE: &str = "statue";
+    /// 1x1 white, for textured materials without an image
+    pub const TEX_WHITE: &str = "white";
 }
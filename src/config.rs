@@ -52,20 +52,164 @@ pub mod camera {
 }
 
 pub mod rendering {
-    /// Shadow map resolution
+    /// Shadow map resolution (per cascade)
     pub const SHADOW_MAP_SIZE: u32 = 2048;
 
-    /// Point shadow map resolution (per face)
-    pub const POINT_SHADOW_SIZE: u32 = 512;
+    /// Directional shadow cascades in use
+    pub const SHADOW_CASCADES: usize = 4;
+
+    /// Cascade layers allocated (injected into the lit shaders as MAX_CASCADES)
+    pub const MAX_SHADOW_CASCADES: usize = 4;
+
+    /// Cascade split blend between uniform (0.0) and logarithmic (1.0) splits
+    pub const SHADOW_SPLIT_LAMBDA: f32 = 0.75;
 
-    /// Far plane for point light shadows
-    pub const SHADOW_FAR_PLANE: f32 = 25.0;
+    /// Distance from the camera that receives directional shadows
+    pub const SHADOW_DISTANCE: f32 = 120.0;
 
-    /// Number of point lights supported
-    pub const MAX_POINT_LIGHTS: usize = 5;
+    /// Extra depth towards the light in which casters are captured
+    pub const SHADOW_CASTER_DISTANCE: f32 = 60.0;
+
+    /// Fraction of each cascade blended into the next one
+    pub const SHADOW_CASCADE_BLEND: f32 = 0.1;
+
+    /// Point shadow map resolution (per face)
+    pub const POINT_SHADOW_SIZE: u32 = 512;
 
-    /// Number of spot lights supported
-    pub const MAX_SPOT_LIGHTS: usize = 4;
+    /// Point lights that cast shadows, one cube map each (1 to 6; the lit shaders unroll
+    /// that many lookups). Contexts with too few texture units for them all get fewer
+    /// (see `texture_units::point_shadow_slots`); the count in use is injected into the
+    /// shaders as MAX_POINT_SHADOWS.
+    pub const MAX_POINT_SHADOWS: usize = 5;
+
+    /// Cube map faces re-rendered per frame at most (the most important light's update is
+    /// always made, even if it alone goes over)
+    pub const POINT_SHADOW_FACE_BUDGET: u32 = 12;
+
+    /// How much more important a light without a cube map has to be to take one over
+    pub const POINT_SHADOW_SWAP_MARGIN: f32 = 1.5;
+
+    /// Spot light shadow map resolution (per light)
+    pub const SPOT_SHADOW_SIZE: u32 = 1024;
+
+    /// Spot lights that cast shadows at once, one depth layer each (injected into the lit
+    /// shaders as MAX_SPOT_SHADOWS)
+    pub const MAX_SPOT_SHADOWS: usize = 4;
+
+    /// Shadow filtering lights start with (see `ShadowSettings`): world-space distance the
+    /// receiver is moved towards the light, shadow map texels it is moved along its normal,
+    /// filter radius in texels and PCSS light size
+    pub const SHADOW_BIAS: f32 = 0.05;
+    pub const SHADOW_NORMAL_OFFSET: f32 = 1.0;
+    pub const SHADOW_FILTER_RADIUS: f32 = 1.0;
+    pub const SHADOW_LIGHT_SIZE: f32 = 0.1;
+
+    /// Variance and exponential shadow maps: moments kept at 1/N of the depth map size,
+    /// exponent of the exponential maps (exp(c) must fit a float) and the lit fraction
+    /// variance maps cut off against light bleeding (injected into the shaders)
+    pub const SHADOW_MOMENTS_DOWNSCALE: u32 = 2;
+    pub const SHADOW_ESM_EXPONENT: f32 = 80.0;
+    pub const SHADOW_VSM_BLEED_REDUCTION: f32 = 0.2;
+
+    /// Light clusters: screen tiles across and down, and logarithmic depth slices
+    pub const CLUSTER_TILES_X: u32 = 16;
+    pub const CLUSTER_TILES_Y: u32 = 9;
+    pub const CLUSTER_SLICES: u32 = 24;
+
+    /// Point and spot lights end where they drop below this fraction of their peak
+    /// (sizes deferred light volumes and cluster assignment)
+    pub const LIGHT_VOLUME_CUTOFF: f32 = 5.0 / 256.0;
+
+    /// Largest light range (for lights with no distance falloff)
+    pub const MAX_LIGHT_RANGE: f32 = 500.0;
+
+    /// Exposure multiplier when automatic exposure is off
+    pub const EXPOSURE: f32 = 1.0;
+
+    /// Adapt exposure to the average scene luminance
+    pub const AUTO_EXPOSURE: bool = true;
+
+    /// Middle grey the average luminance is exposed to, and the exposure range allowed
+    pub const EXPOSURE_KEY: f32 = 0.18;
+    pub const MIN_EXPOSURE: f32 = 0.05;
+    pub const MAX_EXPOSURE: f32 = 8.0;
+
+    /// How quickly automatic exposure follows the scene (per second)
+    pub const EXPOSURE_ADAPTATION_SPEED: f32 = 1.5;
+
+    /// Display gamma the tone mapped image is encoded for
+    pub const GAMMA: f32 = 2.2;
+
+    /// Size of the square log luminance texture the average is reduced from
+    pub const LUMINANCE_SIZE: u32 = 256;
+
+    /// Image-based lighting: face size of the diffuse irradiance map, face size and mip
+    /// levels (roughness steps) of the prefiltered specular map, and BRDF table size
+    pub const IBL_IRRADIANCE_SIZE: u32 = 32;
+    pub const IBL_PREFILTERED_SIZE: u32 = 128;
+    pub const IBL_PREFILTERED_LEVELS: u32 = 5;
+    pub const IBL_BRDF_LUT_SIZE: u32 = 256;
+
+    /// Importance samples per texel of the prefiltered map and the BRDF table
+    pub const IBL_SAMPLES: u32 = 1024;
+
+    /// Scales the environment's light, on top of the directional light's ambient strength
+    pub const IBL_INTENSITY: f32 = 1.0;
+
+    /// Alpha below which imported cutout materials discard fragments
+    pub const ALPHA_CUTOFF: f32 = 0.5;
+
+    /// Screen-space ambient occlusion: on at startup, world-space radius of the sampled
+    /// hemisphere, depth bias against self-occlusion, samples per pixel (at most
+    /// `SSAO_MAX_SAMPLES`, injected into the SSAO shader as the size of its kernel) and the
+    /// exponent darkening the result
+    pub const SSAO_ENABLED: bool = true;
+    pub const SSAO_RADIUS: f32 = 0.5;
+    pub const SSAO_BIAS: f32 = 0.025;
+    pub const SSAO_SAMPLES: u32 = 32;
+    pub const SSAO_MAX_SAMPLES: u32 = 64;
+    pub const SSAO_INTENSITY: f32 = 1.5;
+
+    /// Texture units of the lit shaders' samplers (forward and deferred). Shadows are
+    /// compared in the shaders rather than through separate comparison views, so the fixed
+    /// units fit in the 16 every GL 3.3 context offers with room for a point shadow.
+    pub mod texture_units {
+        use super::MAX_POINT_SHADOWS;
+
+        /// Material maps, and the G-buffer attachments in the deferred lighting pass,
+        /// take the units below this one
+        pub const MATERIAL_MAPS: u32 = 5;
+
+        /// Directional shadow cascades: depth and moments
+        pub const CASCADE_DEPTH: u32 = MATERIAL_MAPS;
+        pub const CASCADE_MOMENTS: u32 = 6;
+
+        /// Spot light shadow layers: depth and moments
+        pub const SPOT_DEPTH: u32 = 7;
+        pub const SPOT_MOMENTS: u32 = 8;
+
+        /// Clustered lights: light data, and the cluster grid followed by its light indices
+        pub const LIGHT_DATA: u32 = 9;
+        pub const LIGHT_CLUSTERS: u32 = 10;
+
+        /// Image-based lighting: irradiance map, prefiltered map and BRDF lookup table
+        pub const IRRADIANCE: u32 = 11;
+        pub const PREFILTERED: u32 = 12;
+        pub const BRDF_LUT: u32 = 13;
+
+        /// Blurred screen-space ambient occlusion
+        pub const OCCLUSION: u32 = 14;
+
+        /// Point shadow cube maps, one unit per slot (the deferred light volumes bind
+        /// their light's map to the first)
+        pub const POINT_SHADOW_MAPS: u32 = 15;
+
+        /// Point shadow slots a context offering `units` fragment texture units has room
+        /// for next to the units above, at most `MAX_POINT_SHADOWS`
+        pub fn point_shadow_slots(units: u32) -> usize {
+            (units.saturating_sub(POINT_SHADOW_MAPS) as usize).min(MAX_POINT_SHADOWS)
+        }
+    }
 }
 
 pub mod ui {
@@ -1,26 +1,25 @@
-use glam::{Quat, Vec2, Vec3};
+use glam::Vec3;
 use glfw::{Action, WindowEvent};
-use std::rc::Rc;
 
-use crate::assets::paths::{models, names, shaders, textures};
+use crate::assets::paths::{config, names, scenes, shaders, textures};
 use crate::assets::AssetManager;
 use crate::camera::OrbitCamera;
-use crate::config::{ui as ui_cfg, window as win_cfg};
+use crate::config::{rendering as render_cfg, ui as ui_cfg, window as win_cfg};
 use crate::input::Input;
-use crate::light::{
-    components::{Attenuation, LightProperties, SpotCone},
-    DirectionalLight, PointLight, SpotLight,
-};
-use crate::logic::controller::{
-    FloatingController, OrbitController, OscillationController, RotationController,
-};
 use crate::math::ray::Ray;
-use crate::primitives::{Capsule, Cube, Plane, Sphere};
-use crate::renderer::Renderer;
-use crate::scene::collider::Collider;
-use crate::scene::manager::Scene;
-use crate::scene::material_factory::MaterialFactory;
-use crate::scene::object::SceneObject3D;
+use crate::renderer::deferred::DeferredRenderer;
+use crate::renderer::hdr::{HdrPipeline, ToneMapSettings};
+use crate::renderer::ibl::Environment;
+use crate::renderer::post::{PostChain, PostProcessor};
+use crate::renderer::ssao::Ssao;
+use crate::renderer::target::Viewport;
+use crate::renderer::{available_point_shadow_slots, RenderPath, Renderer};
+use crate::scene::components::GlobalTransform;
+use crate::scene::handle::ObjectId;
+use crate::scene::manager::{ChildPolicy, Scene};
+use crate::scene::systems;
+use crate::scene::transform::Transform;
+use crate::shadow::moments::ShadowMoments;
 
 use crate::time::Time;
 use crate::ui::Button;
@@ -31,6 +30,9 @@ pub trait RenderMode {
     fn update(&mut self, time: &Time);
     fn render(&mut self);
     fn handle_event(&mut self, event: &WindowEvent, time: &mut Time);
+
+    /// Redirect rendering to another framebuffer/size (e.g. an offscreen target)
+    fn set_output(&mut self, _output: Viewport) {}
 }
 pub struct Game {
     // Assets
@@ -42,38 +44,34 @@ pub struct Game {
     // UI
     ui_manager: UIManager,
     pause_button: Button,
-    selected_object_id: Option<usize>,
+    selected_object_id: Option<ObjectId>,
 
     // Systems
     renderer: Renderer,
     input: Input,
     camera: OrbitCamera,
 
-    // Lights
-    light: DirectionalLight,
-    point_lights: Vec<PointLight>,
-    spot_lights: Vec<SpotLight>,
-
     // State
     is_paused: bool,
+    show_ui: bool,
 }
 
 impl Game {
-    pub fn new() -> Self {
+    /// Set up the renderer and UI and load the scene at `scene_path`
+    pub fn from_scene_file(scene_path: &str) -> Result<Self, String> {
         println!("Initializing Game...");
-        let mut assets = AssetManager::new();
+        // Contexts short of texture units get fewer point shadows
+        let point_shadow_slots = available_point_shadow_slots();
+        if point_shadow_slots < render_cfg::MAX_POINT_SHADOWS {
+            eprintln!(
+                "The context has texture units for {} of {} point shadows",
+                point_shadow_slots,
+                render_cfg::MAX_POINT_SHADOWS
+            );
+        }
+        let mut assets = AssetManager::new().with_point_shadow_slots(point_shadow_slots);
 
         // 1. Shaders
-        let colored_shader = assets.load_shader(
-            names::SHADER_COLORED,
-            shaders::LIT_VERT,
-            shaders::LIT_COLOR_FRAG,
-        );
-        let textured_shader = assets.load_shader(
-            names::SHADER_TEXTURED,
-            shaders::LIT_VERT,
-            shaders::LIT_TEXTURED_FRAG,
-        );
         let ui_shader = assets.load_shader(
             names::SHADER_UI_TEXT,
             shaders::UI_VERT,
@@ -90,237 +88,32 @@ impl Game {
             shaders::SKYBOX_FRAG,
         );
 
-        // 2. Textures
-        let texture = assets.load_texture(names::TEX_GRASS, textures::GRASS);
-        let sphere_texture = assets.load_texture(names::TEX_STONE, textures::STONE_BRICKS);
-
-        // 3. Cubemap
+        // 2. Cubemap
         let skybox_cubemap = assets.load_cubemap(names::TEX_SKYBOX, textures::SKYBOX);
 
         let text_renderer = TextRenderer::new(ui_shader);
         let ui_manager = UIManager::new(text_renderer, ui_rect_shader);
 
         // Renderer
-        let renderer = Renderer::new(skybox_shader, skybox_cubemap);
-
-        let light = DirectionalLight::simple(Vec3::new(-0.2, -1.0, -0.3), 0.1, 0.3, 1.0, 32.0);
-
-        // Models
-        let tree2_model = assets.load_model(names::MODEL_TREE, models::TREE);
-        let xwing_model = assets.load_model(names::MODEL_XWING, models::XWING);
-        let statue_model = assets.load_model(names::MODEL_STATUE, models::STATUE);
-
-        // Shared Meshes
-        let cube_mesh = Rc::new(Cube::new(1.0));
-        let sphere_mesh = Rc::new(Sphere::new(0.6, 32, 32));
-        let capsule_mesh = Rc::new(Capsule::new(0.4, 1.2, 32, 16, 16));
-        let plane_mesh = Rc::new(Plane::new(80.0));
-
-        // Create Material Factory
-        let materials = MaterialFactory::new(colored_shader.clone(), textured_shader.clone());
-
-        // Create Materials using Factory
-        let grass_material = materials.textured(texture.clone());
-        let stone_material = materials.textured(sphere_texture.clone());
-        let green_material = materials.grass_green();
-        let red_material = materials.red();
-        let grey_material = materials.light_grey();
-
-        let mut scene = Scene::new();
-
-        // Create Scene Objects
-        let center_cube = SceneObject3D::new(Box::new(cube_mesh.clone()), grass_material.clone())
-            .with_name("Center Cube")
-            .with_collider(Collider::new_cube(1.0));
-        scene.add_object(center_cube);
-
-        let mut green_cube =
-            SceneObject3D::new(Box::new(cube_mesh.clone()), green_material.clone())
-                .with_name("Green Cube")
-                .with_collider(Collider::new_cube(1.0))
-                .with_controller(Box::new(RotationController::new(Vec3::Y, 1.0)));
-        green_cube.transform.translate(Vec3::new(0.0, 2.0, 0.0));
-        scene.green_cube_id = scene.add_object(green_cube);
-
-        let mut red_cube = SceneObject3D::new(Box::new(cube_mesh.clone()), red_material.clone())
-            .with_name("Red Cube")
-            .with_collider(Collider::new_cube(1.0))
-            .with_controller(Box::new(RotationController::new(Vec3::Y, -1.0)));
-        red_cube.transform.translate(Vec3::new(0.0, -2.0, 0.0));
-        scene.red_cube_id = scene.add_object(red_cube);
-
-        let configs = [(2.5, 1.2), (4.0, 0.8)];
-        for i in 0..2 {
-            let (radius, speed) = configs[i];
-            let sphere = SceneObject3D::new(Box::new(sphere_mesh.clone()), stone_material.clone())
-                .with_name(&format!("Orbiting Sphere {}", i))
-                .with_collider(Collider::new_sphere(0.6))
-                .with_controller(Box::new(OrbitController::new(
-                    Vec3::ZERO,
-                    radius,
-                    speed,
-                    i as f32 * std::f32::consts::PI,
-                )));
-            scene.orbiting_sphere_ids.push(sphere.id);
-            scene.add_object(sphere);
+        let environment = Environment::load_or_bake(&mut assets, &skybox_cubemap, textures::SKYBOX);
+        let mut renderer = Renderer::new(skybox_shader, skybox_cubemap, point_shadow_slots)
+            .with_deferred(DeferredRenderer::load(&mut assets))
+            .with_hdr(HdrPipeline::load(&mut assets))
+            .with_post_processing(PostProcessor::load(&mut assets))
+            .with_ssao(Ssao::load(&mut assets))
+            .with_shadow_moments(ShadowMoments::load(&mut assets));
+        match environment {
+            Ok(environment) => renderer = renderer.with_environment(environment),
+            Err(e) => eprintln!("{}; using flat ambient light", e),
         }
+        renderer.post_process = load_post_chain();
 
-        for i in 0..2 {
-            let offset = i as f32 * std::f32::consts::PI;
-            let capsule =
-                SceneObject3D::new(Box::new(capsule_mesh.clone()), grass_material.clone())
-                    .with_name(&format!("Floating Capsule {}", i))
-                    .with_collider(Collider::new_box(
-                        Vec3::new(-0.4, -1.0, -0.4),
-                        Vec3::new(0.4, 1.0, 0.4),
-                    ))
-                    .with_controller(Box::new(FloatingController::new(0.7, offset)));
-            scene.capsule_ids.push(capsule.id);
-            scene.add_object(capsule);
-        }
-
-        let mut floor = SceneObject3D::new(Box::new(plane_mesh), grass_material.clone())
-            .with_name("Floor")
-            .with_collider(Collider::new_box(
-                Vec3::new(-40.0, -0.01, -40.0),
-                Vec3::new(40.0, 0.01, 40.0),
-            ));
-        floor.transform.translate(Vec3::new(0.0, -4.0, 0.0));
-        scene.add_object(floor);
-
-        // Walls
-        let wall_height = 8.0;
-        let plane_size = 80.0;
-        let half_size = plane_size / 2.0;
-        let wall_thickness = 1.0;
-
-        let wall_mat_x = materials.textured_tiled(
-            sphere_texture.clone(),
-            Vec2::new(wall_height / 8.0, plane_size / 8.0),
-        );
-
-        let wall_mat_z = materials.textured_tiled(
-            sphere_texture.clone(),
-            Vec2::new(plane_size / 8.0, wall_height / 8.0),
-        );
-
-        let mut w1 = SceneObject3D::new(Box::new(cube_mesh.clone()), wall_mat_x.clone())
-            .with_name("Wall +X")
-            .with_collider(Collider::new_cube(1.0));
-        w1.transform
-            .translate(Vec3::new(half_size, -4.0 + wall_height / 2.0, 0.0));
-        w1.transform
-            .scale(Vec3::new(wall_thickness, wall_height, plane_size));
-        scene.add_object(w1);
-
-        let mut w2 = SceneObject3D::new(Box::new(cube_mesh.clone()), wall_mat_x.clone())
-            .with_name("Wall -X")
-            .with_collider(Collider::new_cube(1.0));
-        w2.transform
-            .translate(Vec3::new(-half_size, -4.0 + wall_height / 2.0, 0.0));
-        w2.transform
-            .scale(Vec3::new(wall_thickness, wall_height, plane_size));
-        scene.add_object(w2);
-
-        let mut w3 = SceneObject3D::new(Box::new(cube_mesh.clone()), wall_mat_z.clone())
-            .with_name("Wall +Z")
-            .with_collider(Collider::new_cube(1.0));
-        w3.transform
-            .translate(Vec3::new(0.0, -4.0 + wall_height / 2.0, half_size));
-        w3.transform
-            .scale(Vec3::new(plane_size, wall_height, wall_thickness));
-        scene.add_object(w3);
-
-        let mut w4 = SceneObject3D::new(Box::new(cube_mesh.clone()), wall_mat_z.clone())
-            .with_name("Wall -Z")
-            .with_collider(Collider::new_cube(1.0));
-        w4.transform
-            .translate(Vec3::new(0.0, -4.0 + wall_height / 2.0, -half_size));
-        w4.transform
-            .scale(Vec3::new(plane_size, wall_height, wall_thickness));
-        scene.add_object(w4);
-
-        for (i, pos) in [Vec3::new(-8.0, -4.0, -8.0), Vec3::new(8.0, -4.0, 8.0)]
-            .iter()
-            .enumerate()
-        {
-            let mut tree =
-                SceneObject3D::new(Box::new(tree2_model.clone()), green_material.clone())
-                    .with_name(&format!("Tree {}", i))
-                    .with_collider(Collider::new_box(
-                        Vec3::new(-0.5, 0.0, -0.5),
-                        Vec3::new(0.5, 3.0, 0.5),
-                    ));
-            tree.transform.translate(*pos);
-            tree.transform.scale(Vec3::splat(0.8));
-            scene.add_object(tree);
-        }
-
-        let mut xwing = SceneObject3D::new(Box::new(xwing_model), grey_material.clone())
-            .with_name("X-Wing")
-            .with_collider(Collider::new_sphere(2.0))
-            .with_controller(Box::new(OscillationController::new(50.0, 2.0, 0.5)));
-        xwing.transform.translate(Vec3::new(0.0, 50.0, 10.0));
-        xwing.transform.scale(Vec3::splat(1.0));
-        scene.xwing_id = scene.add_object(xwing);
-
-        let statue_configs = [
-            (Vec3::new(0.0, -3.9, 20.0), 180.0f32),
-            (Vec3::new(0.0, -3.9, -20.0), 0.0f32),
-            (Vec3::new(20.0, -3.9, 0.0), 90.0f32),
-            (Vec3::new(-20.0, -3.9, 0.0), -90.0f32),
-        ];
-
-        for (i, (pos, yaw_deg)) in statue_configs.iter().enumerate() {
-            let mut s = SceneObject3D::new(Box::new(statue_model.clone()), grey_material.clone())
-                .with_name(&format!("Statue {}", i))
-                .with_collider(Collider::new_sphere(500.0));
-            s.transform.translate(*pos);
-            s.transform.scale(Vec3::splat(0.01));
-            s.transform.rotate(
-                Quat::from_rotation_y(yaw_deg.to_radians())
-                    * Quat::from_rotation_x(-90.0f32.to_radians()),
-            );
-            scene.statue_ids.push(s.id);
-            scene.add_object(s);
-        }
-
-        let mut point_lights = Vec::new();
-        let colors = [
-            Vec3::new(1.0, 0.0, 0.0),
-            Vec3::new(0.0, 1.0, 0.0),
-            Vec3::new(0.0, 0.0, 1.0),
-            Vec3::new(1.0, 0.5, 0.0),
-        ];
-
-        for i in 0..4 {
-            point_lights.push(
-                PointLight::new(
-                    Vec3::ZERO,
-                    LightProperties::new(0.2, 2.5, 3.0, 32.0).with_color(colors[i % 4]),
-                )
-                .with_attenuation(Attenuation::new(1.0, 0.35, 0.44)),
-            );
-        }
-
-        // Add a SpotLight pointing down from above the center
-        let mut spot_lights = Vec::new();
-        spot_lights.push(
-            SpotLight::simple(
-                Vec3::new(0.0, 10.0, 0.0),
-                Vec3::new(0.0, -1.0, 0.0),
-                0.0,
-                1.0,
-                1.0,
-                32.0,
-            )
-            .with_cone(SpotCone::from_degrees(12.5, 17.5))
-            .with_attenuation(Attenuation::new(1.0, 0.09, 0.032)),
-        );
+        // 3. Scene (objects, materials and lights come from the scene file)
+        let scene = Scene::load(scene_path, &mut assets)?;
 
         let pause_button = Button::new("Pause", 1170.0, 660.0, 100.0, 40.0);
 
-        Self {
+        Ok(Self {
             assets,
             scene,
             ui_manager,
@@ -329,20 +122,35 @@ impl Game {
             renderer,
             input: Input::new(),
             camera: OrbitCamera::new(),
-            light,
-            point_lights,
-            spot_lights,
             is_paused: false,
-        }
+            show_ui: true,
+        })
+    }
+
+    /// Hide the HUD and inspector (e.g. for offscreen captures)
+    pub fn set_ui_visible(&mut self, visible: bool) {
+        self.show_ui = visible;
+    }
+
+    pub fn set_render_path(&mut self, path: RenderPath) {
+        self.renderer.path = path;
+    }
+
+    pub fn set_tone_mapping(&mut self, settings: ToneMapSettings) {
+        self.renderer.tone_mapping = settings;
+    }
+
+    pub fn set_post_processing(&mut self, chain: PostChain) {
+        self.renderer.post_process = chain;
     }
 
-    fn cast_ray(&self, ray: &Ray) -> Option<usize> {
+    fn cast_ray(&self, ray: &Ray) -> Option<ObjectId> {
         self.scene.cast_ray(ray)
     }
 
-    fn apply_transform_delta(&mut self, id: usize, delta: Vec3) {
-        if let Some(obj) = self.scene.get_object_mut(id) {
-            obj.transform.translate(delta);
+    fn apply_transform_delta(&mut self, id: ObjectId, delta: Vec3) {
+        if let Some(transform) = self.scene.world.get_mut::<Transform>(id) {
+            transform.translate(delta);
         }
     }
 
@@ -355,21 +163,25 @@ impl RenderMode for Game {
     fn update(&mut self, time: &Time) {
         let current_time = time.time();
         let delta_time = time.delta_time;
+        self.renderer.frame_time = delta_time;
 
         self.camera.update(&self.input, delta_time);
 
         self.input.reset_delta();
 
         // Animated Objects logic
-        for obj in &mut self.scene.objects {
-            obj.update(current_time, delta_time);
-        }
+        systems::run_controllers(&self.scene.world, current_time, delta_time);
+        self.scene.update_world_transforms();
 
         // Update Point Lights based on statue positions
-        for i in 0..4 {
-            let s_id = self.scene.statue_ids[i];
-            if let Some(statue) = self.scene.objects.iter().find(|o| o.id == s_id) {
-                let statue_pos = statue.transform.position;
+        let statues = self.scene.group("statues").to_vec();
+        for (i, s_id) in statues.into_iter().enumerate() {
+            let statue_pos = self
+                .scene
+                .world
+                .get::<GlobalTransform>(s_id)
+                .map(|g| g.position());
+            if let Some(statue_pos) = statue_pos {
                 let light_radius = 2.0;
                 let light_speed = 1.0;
                 let light_angle =
@@ -379,30 +191,30 @@ impl RenderMode for Game {
                 let light_z = statue_pos.z + light_angle.sin() * light_radius;
                 let light_y = statue_pos.y + 1.5;
 
-                self.point_lights[i].position = Vec3::new(light_x, light_y, light_z);
+                if let Some(light) = self.scene.point_lights.get_mut(i) {
+                    light.position = Vec3::new(light_x, light_y, light_z);
+                }
             }
         }
     }
 
     fn render(&mut self) {
-        self.renderer.render(
-            &self.scene,
-            &self.camera,
-            &self.light,
-            &self.point_lights,
-            &self.spot_lights,
-        );
+        self.renderer.render(&self.scene, &self.camera);
+
+        if !self.show_ui {
+            return;
+        }
 
         // 1. Game Specific UI: Top Panel
-        let w = win_cfg::WIDTH as f32;
-        let h = win_cfg::HEIGHT as f32;
+        let w = self.renderer.output.width as f32;
+        let h = self.renderer.output.height as f32;
 
         self.ui_manager.text_renderer.render_rect(
             &self.ui_manager.ui_rect_shader,
             10.0,
-            h - 60.0,
-            180.0,
-            50.0,
+            h - 85.0,
+            260.0,
+            75.0,
             glam::Vec4::new(0.0, 0.0, 0.0, ui_cfg::PANEL_OPACITY),
             w,
             h,
@@ -416,6 +228,26 @@ impl RenderMode for Game {
             w,
             h,
         );
+        let stats = self.renderer.stats;
+        let mut stats_line = format!(
+            "Drawn {}  Culled {}  Calls {}",
+            stats.drawn, stats.culled, stats.draw_calls
+        );
+        if stats.shadow_faces > 0 {
+            stats_line.push_str(&format!("  Shadow faces {}", stats.shadow_faces));
+        }
+        if self.renderer.path == RenderPath::Deferred {
+            stats_line.push_str(&format!("  Lights {}", stats.light_volumes));
+        }
+        self.ui_manager.text_renderer.render_text(
+            &stats_line,
+            20.0,
+            h - 80.0,
+            18.0,
+            Vec3::new(0.8, 0.8, 0.8),
+            w,
+            h,
+        );
 
         // 2. Game Specific UI: Pause Button
         let mut pause_btn = self.pause_button.clone();
@@ -433,9 +265,140 @@ impl RenderMode for Game {
         self.ui_manager.render(&self.scene, self.selected_object_id);
     }
 
+    fn set_output(&mut self, output: Viewport) {
+        self.renderer.output = output;
+    }
+
     fn handle_event(&mut self, event: &WindowEvent, time: &mut Time) {
         self.input.handle_event(event);
 
+        // Save the current scene state (F5)
+        if let WindowEvent::Key(glfw::Key::F5, _, Action::Press, _) = event {
+            match self.scene.save(scenes::SAVED) {
+                Ok(()) => println!("Scene saved to {}", scenes::SAVED),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+
+        // Switch between forward and deferred shading (F2)
+        if let WindowEvent::Key(glfw::Key::F2, _, Action::Press, _) = event {
+            let path = match self.renderer.path {
+                RenderPath::Forward => RenderPath::Deferred,
+                RenderPath::Deferred => RenderPath::Forward,
+            };
+            self.set_render_path(path);
+            println!("Render path: {:?}", path);
+            return;
+        }
+
+        // Cycle the tone mapping operator (F3)
+        if let WindowEvent::Key(glfw::Key::F3, _, Action::Press, _) = event {
+            let tone_mapping = &mut self.renderer.tone_mapping;
+            tone_mapping.operator = tone_mapping.operator.next();
+            println!("Tone mapping: {:?}", tone_mapping.operator);
+            return;
+        }
+
+        // Toggle automatic exposure (F4); [ and ] adjust the manual exposure
+        if let WindowEvent::Key(glfw::Key::F4, _, Action::Press, _) = event {
+            let tone_mapping = &mut self.renderer.tone_mapping;
+            tone_mapping.auto_exposure = !tone_mapping.auto_exposure;
+            println!("Auto exposure: {}", tone_mapping.auto_exposure);
+            return;
+        }
+        if let WindowEvent::Key(key, _, Action::Press | Action::Repeat, _) = event {
+            let step = match key {
+                glfw::Key::LeftBracket => Some(1.0 / 1.25),
+                glfw::Key::RightBracket => Some(1.25),
+                _ => None,
+            };
+            if let Some(step) = step {
+                let tone_mapping = &mut self.renderer.tone_mapping;
+                tone_mapping.auto_exposure = false;
+                tone_mapping.exposure *= step;
+                println!("Exposure: {:.2}", tone_mapping.exposure);
+                return;
+            }
+        }
+
+        // Toggle post-processing (F6); F7 reloads the chain from its file
+        if let WindowEvent::Key(glfw::Key::F6, _, Action::Press, _) = event {
+            let chain = &mut self.renderer.post_process;
+            chain.enabled = !chain.enabled;
+            println!("Post-processing: {}", chain.enabled);
+            return;
+        }
+        if let WindowEvent::Key(glfw::Key::F7, _, Action::Press, _) = event {
+            self.set_post_processing(load_post_chain());
+            return;
+        }
+
+        // Toggle ambient occlusion (F8) and its debug view (F9); , and . set its radius
+        if let WindowEvent::Key(glfw::Key::F8, _, Action::Press, _) = event {
+            let ssao = &mut self.renderer.ambient_occlusion;
+            ssao.enabled = !ssao.enabled;
+            println!("Ambient occlusion: {}", ssao.enabled);
+            return;
+        }
+        if let WindowEvent::Key(glfw::Key::F9, _, Action::Press, _) = event {
+            let ssao = &mut self.renderer.ambient_occlusion;
+            ssao.debug_view = !ssao.debug_view;
+            println!("Ambient occlusion view: {}", ssao.debug_view);
+            return;
+        }
+        if let WindowEvent::Key(key, _, Action::Press | Action::Repeat, _) = event {
+            let step = match key {
+                glfw::Key::Comma => Some(1.0 / 1.25),
+                glfw::Key::Period => Some(1.25),
+                _ => None,
+            };
+            if let Some(step) = step {
+                let ssao = &mut self.renderer.ambient_occlusion;
+                ssao.radius *= step;
+                println!("Ambient occlusion radius: {:.2}", ssao.radius);
+                return;
+            }
+        }
+
+        // Cycle the shadow filter of every light (F10)
+        if let WindowEvent::Key(glfw::Key::F10, _, Action::Press, _) = event {
+            let scene = &mut self.scene;
+            let filter = scene.light.shadow.filter.next();
+            scene.light.shadow.filter = filter;
+            for light in &mut scene.point_lights {
+                light.shadow.filter = filter;
+            }
+            for light in &mut scene.spot_lights {
+                light.shadow.filter = filter;
+            }
+            println!("Shadow filter: {:?}", filter);
+            return;
+        }
+
+        // Print the passes the render graph ran last frame, in order (F11)
+        if let WindowEvent::Key(glfw::Key::F11, _, Action::Press, _) = event {
+            let graph = &self.renderer.graph;
+            match graph.error() {
+                Some(e) => println!("Render graph: {}", e),
+                None => println!("Render graph: {}", graph.order().join(" -> ")),
+            }
+            return;
+        }
+
+        // Delete the selected object (Shift keeps its children in place)
+        if let WindowEvent::Key(glfw::Key::Delete, _, Action::Press, mods) = event {
+            if let Some(id) = self.selected_object_id.take() {
+                let policy = if mods.contains(glfw::Modifiers::Shift) {
+                    ChildPolicy::Reparent
+                } else {
+                    ChildPolicy::Despawn
+                };
+                self.scene.remove_object(id, policy);
+            }
+            return;
+        }
+
         if let WindowEvent::MouseButton(glfw::MouseButtonLeft, Action::Press, _) = event {
             let (mx, my) = (self.input.mouse.pos.x, self.input.mouse.pos.y);
 
@@ -472,3 +435,17 @@ impl RenderMode for Game {
         }
     }
 }
+
+/// The post-processing chain from its config file, or the built-in one if it can't be read
+fn load_post_chain() -> PostChain {
+    match PostChain::load(config::POST_PROCESS) {
+        Ok(chain) => {
+            println!("Loaded post-processing chain: {}", config::POST_PROCESS);
+            chain
+        }
+        Err(e) => {
+            eprintln!("{}; using the default chain", e);
+            PostChain::default()
+        }
+    }
+}
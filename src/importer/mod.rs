@@ -1,5 +1,9 @@
+use crate::renderer::vertex::{self, VERTEX_FLOATS};
+use crate::scene::model::ModelMaterial;
 use crate::shaders::Texture;
+use glam::Vec3;
 use image::GenericImageView;
+use std::path::Path;
 
 pub mod formats;
 
@@ -41,12 +45,13 @@ impl AssetImporter {
 
         let scene = Scene::from_file(
             path,
+            // No FlipUVs: `Texture` flips images on load, so the file's UVs apply as is
             vec![
                 PostProcess::Triangulate,
-                PostProcess::FlipUVs,
                 PostProcess::JoinIdenticalVertices,
                 PostProcess::PreTransformVertices,
                 PostProcess::GenerateNormals,
+                PostProcess::CalculateTangentSpace,
                 PostProcess::ValidateDataStructure,
             ],
         )
@@ -55,7 +60,11 @@ impl AssetImporter {
         let mut meshes = Vec::new();
 
         for mesh in &scene.meshes {
-            let mut vertices = Vec::new();
+            // Tangents only exist for meshes with UVs (and normals)
+            let has_tangents = mesh.tangents.len() == mesh.vertices.len()
+                && mesh.bitangents.len() == mesh.vertices.len();
+
+            let mut vertices = Vec::with_capacity(mesh.vertices.len() * VERTEX_FLOATS);
             for i in 0..mesh.vertices.len() {
                 // Position
                 vertices.push(mesh.vertices[i].x);
@@ -72,15 +81,27 @@ impl AssetImporter {
                 }
 
                 // Normals
-                if mesh.normals.len() > i {
-                    vertices.push(mesh.normals[i].x);
-                    vertices.push(mesh.normals[i].y);
-                    vertices.push(mesh.normals[i].z);
+                let normal = if mesh.normals.len() > i {
+                    Vec3::new(mesh.normals[i].x, mesh.normals[i].y, mesh.normals[i].z)
                 } else {
-                    vertices.push(0.0);
-                    vertices.push(0.0);
-                    vertices.push(0.0);
-                }
+                    Vec3::ZERO
+                };
+                vertices.extend_from_slice(&normal.to_array());
+
+                // Tangent and bitangent, made orthonormal to the normal
+                let (tangent, bitangent) = if has_tangents {
+                    let t = &mesh.tangents[i];
+                    let b = &mesh.bitangents[i];
+                    vertex::orthonormal_frame(
+                        normal.normalize_or_zero(),
+                        Vec3::new(t.x, t.y, t.z),
+                        Vec3::new(b.x, b.y, b.z),
+                    )
+                } else {
+                    vertex::orthonormal_frame(normal.normalize_or_zero(), Vec3::ZERO, Vec3::ZERO)
+                };
+                vertices.extend_from_slice(&tangent.to_array());
+                vertices.extend_from_slice(&bitangent.to_array());
             }
 
             let mut indices = Vec::new();
@@ -88,11 +109,158 @@ impl AssetImporter {
                 indices.extend_from_slice(&face.0);
             }
 
-            meshes.push(Mesh::new(&vertices, &indices));
+            let mut gpu_mesh = Mesh::new(&vertices, &indices);
+            gpu_mesh.material_index = mesh.material_index as usize;
+            meshes.push(gpu_mesh);
         }
 
-        println!("Loaded model: {}, meshes: {}", path, meshes.len());
+        let materials: Vec<ModelMaterial> = scene
+            .materials
+            .iter()
+            .map(|material| Self::model_material(material, path))
+            .collect();
+
+        println!(
+            "Loaded model: {}, meshes: {}, materials: {}",
+            path,
+            meshes.len(),
+            materials.len()
+        );
+
+        Ok(crate::scene::model::Model::new(meshes).with_materials(materials))
+    }
+
+    /// Colors and texture paths of an assimp material, with the paths resolved against
+    /// the directory of `model_path`
+    fn model_material(material: &russimp::material::Material, model_path: &str) -> ModelMaterial {
+        use russimp::material::{PropertyTypeInfo, TextureType};
+
+        let color = |values: &[f32], default: Vec3| match values {
+            [r, g, b, ..] => Vec3::new(*r, *g, *b),
+            _ => default,
+        };
+
+        let mut result = ModelMaterial::default();
+        let (mut normal_map, mut height_map) = (None, None);
+        for property in &material.properties {
+            match (property.key.as_str(), &property.data) {
+                ("?mat.name", PropertyTypeInfo::String(name)) => result.name = name.clone(),
+                ("$clr.diffuse", PropertyTypeInfo::FloatArray(c)) => {
+                    result.diffuse = color(c, result.diffuse)
+                }
+                ("$clr.specular", PropertyTypeInfo::FloatArray(c)) => {
+                    result.specular = color(c, result.specular)
+                }
+                ("$clr.emissive", PropertyTypeInfo::FloatArray(c)) => {
+                    result.emissive = color(c, result.emissive)
+                }
+                ("$mat.opacity", PropertyTypeInfo::FloatArray(values)) => {
+                    if let Some(&opacity) = values.first() {
+                        result.opacity = opacity;
+                    }
+                }
+                // Only the first texture of each kind is used
+                ("$tex.file", PropertyTypeInfo::String(file)) if property.index == 0 => {
+                    let file = Self::resolve_texture_path(model_path, file);
+                    match property.semantic {
+                        TextureType::Diffuse | TextureType::BaseColor => result.diffuse_map = file,
+                        TextureType::Specular => result.specular_map = file,
+                        TextureType::Normals | TextureType::NormalCamera => normal_map = file,
+                        TextureType::Height => height_map = file,
+                        TextureType::Emissive | TextureType::EmissionColor => {
+                            result.emissive_map = file
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        // OBJ exporters write normal maps as `map_Bump`, which assimp reports as height.
+        // Real height (bump) maps are left out: the shaders have no use for them.
+        result.normal_map =
+            normal_map.or_else(|| height_map.filter(|path| Self::is_normal_map(path)));
+        result
+    }
+
+    /// Whether the image at `path` holds tangent-space normals rather than heights:
+    /// color channels, with blue (the normals' z, mostly facing out) well above red and green
+    fn is_normal_map(path: &str) -> bool {
+        match image::open(path) {
+            Ok(img) => looks_like_normal_map(&img),
+            Err(e) => {
+                eprintln!("Skipping height map '{}': {}", path, e);
+                false
+            }
+        }
+    }
+
+    /// Path of a texture a model file refers to. Relative paths (with either separator)
+    /// start at the model's directory; embedded textures ("*0") are not supported.
+    fn resolve_texture_path(model_path: &str, file: &str) -> Option<String> {
+        if file.starts_with('*') {
+            eprintln!(
+                "Model '{}': embedded texture {} is not supported",
+                model_path, file
+            );
+            return None;
+        }
+        let mut normalized = file
+            .split(['/', '\\'])
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        if file.starts_with('/') {
+            normalized.insert(0, '/');
+        }
+        let file = Path::new(&normalized);
+        let resolved = match Path::new(model_path).parent() {
+            Some(dir) if file.is_relative() => dir.join(file),
+            _ => file.to_path_buf(),
+        };
+        Some(resolved.to_string_lossy().into_owned())
+    }
+}
+
+/// Grayscale images (also when stored as RGB) are heights; normal maps average to about
+/// (0.5, 0.5, 1.0)
+fn looks_like_normal_map(img: &image::DynamicImage) -> bool {
+    if !img.color().has_color() {
+        return false;
+    }
+    let rgb = img.to_rgb8();
+    let mut sum = [0u64; 3];
+    for pixel in rgb.pixels() {
+        for (total, &c) in sum.iter_mut().zip(&pixel.0) {
+            *total += c as u64;
+        }
+    }
+    let count = (rgb.width() as u64 * rgb.height() as u64).max(1) * 255;
+    let [r, g, b] = sum.map(|total| total as f32 / count as f32);
+    b > r.max(g) + 0.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, GrayImage, Rgb, RgbImage};
+
+    /// 4x4 RGB image of one color
+    fn rgb(color: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb(color)))
+    }
+
+    #[test]
+    fn bluish_rgb_images_are_normal_maps() {
+        assert!(looks_like_normal_map(&rgb([128, 128, 255])));
+        assert!(looks_like_normal_map(&rgb([150, 110, 230])));
+    }
 
-        Ok(crate::scene::model::Model::new(meshes))
+    #[test]
+    fn gray_images_are_height_maps() {
+        let gray = DynamicImage::ImageLuma8(GrayImage::new(4, 4));
+        assert!(!looks_like_normal_map(&gray));
+        assert!(!looks_like_normal_map(&rgb([128, 128, 128])));
+        assert!(!looks_like_normal_map(&rgb([255, 255, 255])));
     }
 }
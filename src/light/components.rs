@@ -1,9 +1,11 @@
 extern crate gl;
+use crate::config::rendering as render_cfg;
 use crate::shaders::Shader;
 use glam::Vec3;
+use serde::{Deserialize, Serialize};
 
 /// Common lighting components that can be reused across light types
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LightProperties {
     pub ambient: f32,
     pub diffuse: f32,
@@ -40,6 +42,11 @@ impl LightProperties {
         self
     }
 
+    /// Brightest any term of this light gets, before attenuation
+    pub fn peak(&self) -> f32 {
+        self.color.max_element() * self.ambient.max(self.diffuse).max(self.specular)
+    }
+
     /// Apply common lighting uniforms to shader
     pub fn apply_to_shader(&self, shader: &Shader, prefix: &str) {
         let ambient_name = if prefix.is_empty() {
@@ -77,7 +84,7 @@ impl LightProperties {
 }
 
 /// Attenuation factors for point and spot lights
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Attenuation {
     pub constant: f32,
     pub linear: f32,
@@ -103,6 +110,24 @@ impl Attenuation {
         }
     }
 
+    /// Distance at which a light of brightness `peak` falls below `LIGHT_VOLUME_CUTOFF`,
+    /// capped at `MAX_LIGHT_RANGE`
+    pub fn range(&self, peak: f32) -> f32 {
+        // Solve constant + linear * d + quadratic * d^2 = peak / cutoff
+        let c = self.constant - peak / render_cfg::LIGHT_VOLUME_CUTOFF;
+        let range = if c >= 0.0 {
+            0.0
+        } else if self.quadratic > 0.0 {
+            (-self.linear + (self.linear * self.linear - 4.0 * self.quadratic * c).sqrt())
+                / (2.0 * self.quadratic)
+        } else if self.linear > 0.0 {
+            -c / self.linear
+        } else {
+            f32::INFINITY
+        };
+        range.min(render_cfg::MAX_LIGHT_RANGE)
+    }
+
     pub fn apply_to_shader(&self, shader: &Shader, prefix: &str) {
         shader.set_float(&format!("{}Constant", prefix), self.constant);
         shader.set_float(&format!("{}Linear", prefix), self.linear);
@@ -111,7 +136,7 @@ impl Attenuation {
 }
 
 /// Cone shape for spotlights
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SpotCone {
     pub cut_off: f32,
     pub outer_cut_off: f32,
@@ -139,3 +164,109 @@ impl SpotCone {
         shader.set_float(&format!("{}OuterCutOff", prefix), self.outer_cut_off);
     }
 }
+
+/// How a light's shadow map is filtered into soft edges
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShadowFilter {
+    /// A 3x3 grid of bilinear depth comparisons, as a `sampler2DShadow` makes them
+    #[default]
+    Hardware,
+    /// Bilinear depth comparisons over a Poisson disk, rotated per pixel
+    Poisson,
+    /// Percentage-closer soft shadows: the penumbra widens with the distance between the
+    /// receiver and its blockers
+    Pcss,
+    /// Variance shadow map: blurred depth and depth squared, bounded by Chebyshev's
+    /// inequality
+    Variance,
+    /// Exponential shadow map: blurred exp(depth)
+    Exponential,
+}
+
+impl ShadowFilter {
+    /// The next filter, wrapping around (for cycling through them)
+    pub fn next(self) -> Self {
+        match self {
+            ShadowFilter::Hardware => ShadowFilter::Poisson,
+            ShadowFilter::Poisson => ShadowFilter::Pcss,
+            ShadowFilter::Pcss => ShadowFilter::Variance,
+            ShadowFilter::Variance => ShadowFilter::Exponential,
+            ShadowFilter::Exponential => ShadowFilter::Hardware,
+        }
+    }
+
+    /// Whether the filter reads blurred moments instead of the depth map itself
+    pub fn uses_moments(self) -> bool {
+        matches!(self, ShadowFilter::Variance | ShadowFilter::Exponential)
+    }
+
+    /// The filter to use where no moments are kept (point light cube maps, or when they
+    /// can't be made): variance and exponential become Poisson
+    pub fn depth_only(self) -> Self {
+        if self.uses_moments() {
+            ShadowFilter::Poisson
+        } else {
+            self
+        }
+    }
+
+    /// Value of the `filterMode` field of the shaders' `ShadowParams`
+    pub fn shader_index(self) -> i32 {
+        match self {
+            ShadowFilter::Hardware => 0,
+            ShadowFilter::Poisson => 1,
+            ShadowFilter::Pcss => 2,
+            ShadowFilter::Variance => 3,
+            ShadowFilter::Exponential => 4,
+        }
+    }
+}
+
+/// Shadow filtering and bias of one light
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    pub filter: ShadowFilter,
+    /// World-space distance the receiver is moved towards the light before the depth test
+    pub bias: f32,
+    /// Shadow map texels the receiver is moved along its normal (more at grazing angles)
+    pub normal_offset: f32,
+    /// Filter radius in shadow map texels (the PCF kernel, or the moments blur)
+    pub radius: f32,
+    /// PCSS only: world-space radius of a point or spot light; for the directional light,
+    /// how much the penumbra widens per unit of distance from its blocker
+    pub light_size: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            filter: ShadowFilter::default(),
+            bias: render_cfg::SHADOW_BIAS,
+            normal_offset: render_cfg::SHADOW_NORMAL_OFFSET,
+            radius: render_cfg::SHADOW_FILTER_RADIUS,
+            light_size: render_cfg::SHADOW_LIGHT_SIZE,
+        }
+    }
+}
+
+impl ShadowSettings {
+    pub fn with_filter(mut self, filter: ShadowFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Bias, normal offset, radius and light size, as packed into the light data texels
+    pub fn params(&self) -> [f32; 4] {
+        [self.bias, self.normal_offset, self.radius, self.light_size]
+    }
+
+    /// Set as the `ShadowParams` struct uniform `name`
+    pub fn apply_to_shader(&self, shader: &Shader, name: &str) {
+        shader.set_int(&format!("{}.filterMode", name), self.filter.shader_index());
+        shader.set_float(&format!("{}.bias", name), self.bias);
+        shader.set_float(&format!("{}.normalOffset", name), self.normal_offset);
+        shader.set_float(&format!("{}.radius", name), self.radius);
+        shader.set_float(&format!("{}.lightSize", name), self.light_size);
+    }
+}
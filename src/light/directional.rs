@@ -1,11 +1,22 @@
-use glam::Vec3;
-use crate::shaders::Shader;
-use super::components::LightProperties;
+use super::components::{LightProperties, ShadowSettings};
 use super::Light;
+use crate::shaders::Shader;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DirectionalLight {
     pub direction: Vec3,
     pub properties: LightProperties,
+    /// Filtering of the shadow cascades
+    #[serde(default)]
+    pub shadow: ShadowSettings,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self::new(Vec3::new(-0.2, -1.0, -0.3), LightProperties::default())
+    }
 }
 
 impl DirectionalLight {
@@ -13,9 +24,15 @@ impl DirectionalLight {
         Self {
             direction: direction.normalize(),
             properties,
+            shadow: ShadowSettings::default(),
         }
     }
 
+    pub fn with_shadow_settings(mut self, shadow: ShadowSettings) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
     pub fn simple(direction: Vec3, ambient: f32, diffuse: f32, specular: f32, shininess: f32) -> Self {
         Self::new(direction, LightProperties::new(ambient, diffuse, specular, shininess))
     }
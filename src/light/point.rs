@@ -1,13 +1,17 @@
-use super::components::{Attenuation, LightProperties};
+use super::components::{Attenuation, LightProperties, ShadowSettings};
 use super::Light;
 use crate::shaders::Shader;
 use glam::Vec3;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PointLight {
     pub position: Vec3,
     pub properties: LightProperties,
     pub attenuation: Attenuation,
+    /// Filtering of the light's cube map, when it gets one
+    #[serde(default)]
+    pub shadow: ShadowSettings,
 }
 
 impl PointLight {
@@ -16,6 +20,7 @@ impl PointLight {
             position,
             properties,
             attenuation: Attenuation::default(),
+            shadow: ShadowSettings::default(),
         }
     }
 
@@ -24,6 +29,11 @@ impl PointLight {
         self
     }
 
+    pub fn with_shadow_settings(mut self, shadow: ShadowSettings) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
     pub fn simple(
         position: Vec3,
         ambient: f32,
@@ -36,9 +46,15 @@ impl PointLight {
             LightProperties::new(ambient, diffuse, specular, shininess),
         )
     }
-    pub fn apply_to_shader_indexed(&self, shader: &Shader, index: usize, view_pos: Vec3) {
-        let prefix = format!("pointLights[{}]", index);
-        // Note: Shader expects "pointLights[i].position"
+
+    /// Distance at which the light falls below `LIGHT_VOLUME_CUTOFF` of its peak
+    pub fn range(&self) -> f32 {
+        self.attenuation.range(self.properties.peak())
+    }
+
+    /// Set the light on a `PointLight` struct uniform called `prefix`
+    pub fn apply_to_shader_as(&self, shader: &Shader, prefix: &str, view_pos: Vec3) {
+        // Shader expects e.g. "light.position"
         shader.set_vec3(
             &format!("{}.position", prefix),
             self.position.x,
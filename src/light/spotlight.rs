@@ -1,15 +1,21 @@
-use super::components::{Attenuation, LightProperties, SpotCone};
+use super::components::{Attenuation, LightProperties, ShadowSettings, SpotCone};
 use super::Light;
 use crate::shaders::Shader;
 use glam::Vec3;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SpotLight {
     pub position: Vec3,
     pub direction: Vec3,
     pub properties: LightProperties,
     pub cone: SpotCone,
     pub attenuation: Attenuation,
+    /// Whether the light gets a shadow map (off unless a scene asks for it)
+    #[serde(default)]
+    pub casts_shadows: bool,
+    #[serde(default)]
+    pub shadow: ShadowSettings,
 }
 
 impl SpotLight {
@@ -20,6 +26,8 @@ impl SpotLight {
             properties,
             cone: SpotCone::default(),
             attenuation: Attenuation::default(),
+            casts_shadows: false,
+            shadow: ShadowSettings::default(),
         }
     }
 
@@ -33,6 +41,16 @@ impl SpotLight {
         self
     }
 
+    pub fn with_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
+
+    pub fn with_shadow_settings(mut self, shadow: ShadowSettings) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
     pub fn simple(
         position: Vec3,
         direction: Vec3,
@@ -48,6 +66,11 @@ impl SpotLight {
         )
     }
 
+    /// Distance at which the light falls below `LIGHT_VOLUME_CUTOFF` of its peak
+    pub fn range(&self) -> f32 {
+        self.attenuation.range(self.properties.peak())
+    }
+
     pub fn apply_to_shader_indexed(&self, shader: &Shader, index: usize) {
         let prefix = format!("spotLights[{}]", index);
         // Position and Direction directly
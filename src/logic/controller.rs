@@ -1,8 +1,61 @@
 use crate::scene::transform::Transform;
 use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
 
 pub trait Controller {
     fn update(&self, transform: &mut Transform, current_time: f32, delta_time: f32);
+
+    /// Serializable parameters of this controller (used by scene files)
+    fn describe(&self) -> ControllerDescription;
+}
+
+/// Serializable form of the built-in controllers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ControllerDescription {
+    Rotation {
+        axis: Vec3,
+        speed: f32,
+    },
+    Orbit {
+        center: Vec3,
+        radius: f32,
+        speed: f32,
+        offset: f32,
+    },
+    Oscillation {
+        base_y: f32,
+        amplitude: f32,
+        speed: f32,
+    },
+    Floating {
+        speed: f32,
+        offset: f32,
+    },
+}
+
+impl ControllerDescription {
+    /// Create the controller described by these parameters
+    pub fn build(&self) -> Box<dyn Controller> {
+        match *self {
+            ControllerDescription::Rotation { axis, speed } => {
+                Box::new(RotationController::new(axis, speed))
+            }
+            ControllerDescription::Orbit {
+                center,
+                radius,
+                speed,
+                offset,
+            } => Box::new(OrbitController::new(center, radius, speed, offset)),
+            ControllerDescription::Oscillation {
+                base_y,
+                amplitude,
+                speed,
+            } => Box::new(OscillationController::new(base_y, amplitude, speed)),
+            ControllerDescription::Floating { speed, offset } => {
+                Box::new(FloatingController::new(speed, offset))
+            }
+        }
+    }
 }
 
 /// A simple rotation controller
@@ -21,6 +74,13 @@ impl Controller for RotationController {
     fn update(&self, transform: &mut Transform, current_time: f32, _delta_time: f32) {
         transform.rotation = Quat::from_axis_angle(self.axis, current_time * self.speed);
     }
+
+    fn describe(&self) -> ControllerDescription {
+        ControllerDescription::Rotation {
+            axis: self.axis,
+            speed: self.speed,
+        }
+    }
 }
 
 /// A controller for circular orbit
@@ -48,6 +108,15 @@ impl Controller for OrbitController {
         transform.position.x = self.center.x + angle.cos() * self.radius;
         transform.position.z = self.center.z + angle.sin() * self.radius;
     }
+
+    fn describe(&self) -> ControllerDescription {
+        ControllerDescription::Orbit {
+            center: self.center,
+            radius: self.radius,
+            speed: self.speed,
+            offset: self.offset,
+        }
+    }
 }
 
 /// A controller for oscillation (Y-axis)
@@ -71,6 +140,14 @@ impl Controller for OscillationController {
     fn update(&self, transform: &mut Transform, current_time: f32, _delta_time: f32) {
         transform.position.y = self.base_y + (current_time * self.speed).sin() * self.amplitude;
     }
+
+    fn describe(&self) -> ControllerDescription {
+        ControllerDescription::Oscillation {
+            base_y: self.base_y,
+            amplitude: self.amplitude,
+            speed: self.speed,
+        }
+    }
 }
 
 /// A more complex floating/orbiting controller for capsules
@@ -95,4 +172,11 @@ impl Controller for FloatingController {
         transform.position = tilt_quat.mul_vec3(orbit_pos);
         transform.rotation = Quat::from_rotation_y(current_time) * Quat::from_rotation_x(tilt);
     }
+
+    fn describe(&self) -> ControllerDescription {
+        ControllerDescription::Floating {
+            speed: self.speed,
+            offset: self.offset,
+        }
+    }
 }
@@ -21,20 +21,123 @@ mod time;
 mod ui;
 mod window;
 
+use app::bench::{self, BenchOptions};
+use app::golden::{self, GoldenOptions};
+use app::headless::{HeadlessApplication, HeadlessOptions};
 use app::Application;
+use assets::paths::scenes;
 use config::window as win_cfg;
 use game::Game;
+use renderer::hdr::ToneMapSettings;
+use renderer::post::PostChain;
+use renderer::target::TargetFormat;
+use renderer::RenderPath;
 use window::GlWindow;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Golden-image regression check: `opengl_renderer golden [--update]`
+    if args.first().map(String::as_str) == Some("golden") {
+        run_golden(&args[1..]);
+        return;
+    }
+
+    // Raycast benchmark, no window needed: `opengl_renderer bench [--objects N]`
+    if args.first().map(String::as_str) == Some("bench") {
+        run_bench(&args[1..]);
+        return;
+    }
+
+    let (headless, positional) = HeadlessOptions::parse(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    // Optional first argument: scene file to load instead of the demo scene
+    let scene_path = positional
+        .first()
+        .cloned()
+        .unwrap_or_else(|| scenes::DEMO.to_string());
+
+    if let Some(options) = headless {
+        run_headless(&scene_path, options);
+        return;
+    }
+
     // Create window and OpenGL context
     let mut window = GlWindow::new(win_cfg::TITLE, win_cfg::WIDTH, win_cfg::HEIGHT);
     window.init_gl();
 
     // Init game state (OpenGL context is ready)
-    let mode = Box::new(Game::new());
+    let mode = Box::new(load_game(&scene_path));
 
     // Start application
     let mut app = Application::new(window, mode);
     app.run();
 }
+
+/// Render the scene offscreen and write the frames to disk (no visible window)
+fn run_headless(scene_path: &str, options: HeadlessOptions) {
+    let mut window = GlWindow::new_hidden(options.width, options.height, options.context_api);
+    window.init_gl();
+
+    let mut game = load_game(scene_path);
+    game.set_ui_visible(options.show_ui);
+    if options.deferred {
+        game.set_render_path(RenderPath::Deferred);
+    }
+    // Float outputs keep the scene's linear values (display effects work on 8-bit color)
+    if options.target_format() == TargetFormat::Rgba32F {
+        game.set_tone_mapping(ToneMapSettings::passthrough());
+        game.set_post_processing(PostChain {
+            enabled: false,
+            ..PostChain::default()
+        });
+    }
+
+    let result =
+        HeadlessApplication::new(window, Box::new(game), options).and_then(|mut app| app.run());
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Load the game and its scene, or report why not and exit
+fn load_game(scene_path: &str) -> Game {
+    Game::from_scene_file(scene_path).unwrap_or_else(|e| {
+        eprintln!("Failed to load scene: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// Render the golden cases and compare (or update) them; exits non-zero on failure
+fn run_golden(args: &[String]) {
+    let options = GoldenOptions::parse(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    match golden::run(&options) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Time BVH raycasts against the linear scan; exits non-zero if their results differ
+fn run_bench(args: &[String]) {
+    let options = BenchOptions::parse(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    if let Err(e) = bench::run(&options) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
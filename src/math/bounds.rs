@@ -0,0 +1,134 @@
+//! Bounding Volumes - Boxes and spheres around mesh data, for culling
+
+use glam::{Mat4, Vec3};
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Smallest box containing every point (a zero-size box at the origin if there are none)
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut points = points.into_iter();
+        let Some(first) = points.next() else {
+            return Self::default();
+        };
+        points.fold(Self::new(first, first), |aabb, p| {
+            Self::new(aabb.min.min(p), aabb.max.max(p))
+        })
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        let closest = sphere.center.clamp(self.min, self.max);
+        closest.distance_squared(sphere.center) <= sphere.radius * sphere.radius
+    }
+
+    /// Box around this box after `matrix` is applied (still axis-aligned, so it may grow)
+    pub fn transformed(&self, matrix: &Mat4) -> Aabb {
+        let center = matrix.transform_point3(self.center());
+        let half = self.half_extents();
+        let abs = |v: glam::Vec4| v.truncate().abs();
+        let extents =
+            abs(matrix.x_axis) * half.x + abs(matrix.y_axis) * half.y + abs(matrix.z_axis) * half.z;
+        Self::new(center - extents, center + extents)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Sphere after `matrix` is applied; non-uniform scale uses the largest axis
+    pub fn transformed(&self, matrix: &Mat4) -> BoundingSphere {
+        let scale = matrix
+            .x_axis
+            .truncate()
+            .length()
+            .max(matrix.y_axis.truncate().length())
+            .max(matrix.z_axis.truncate().length());
+        Self {
+            center: matrix.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// Box and sphere around the same geometry; the sphere is the cheap first test
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl Bounds {
+    /// Bounds of interleaved vertex data whose first three floats are the position
+    pub fn from_vertices(vertices: &[f32], stride: usize) -> Self {
+        let positions = || {
+            vertices
+                .chunks_exact(stride)
+                .map(|v| Vec3::new(v[0], v[1], v[2]))
+        };
+        let aabb = Aabb::from_points(positions());
+        let center = aabb.center();
+        let radius = positions()
+            .map(|p| p.distance_squared(center))
+            .fold(0.0, f32::max)
+            .sqrt();
+
+        Self {
+            aabb,
+            sphere: BoundingSphere { center, radius },
+        }
+    }
+
+    /// Bounds around several meshes (e.g. all meshes of a model)
+    pub fn union(parts: &[Bounds]) -> Self {
+        let Some((first, rest)) = parts.split_first() else {
+            return Self::default();
+        };
+        let aabb = rest
+            .iter()
+            .fold(first.aabb, |aabb, part| aabb.union(&part.aabb));
+        let center = aabb.center();
+        // Enclose every part's sphere; never larger than the box's own sphere
+        let radius = parts
+            .iter()
+            .map(|part| part.sphere.center.distance(center) + part.sphere.radius)
+            .fold(0.0, f32::max)
+            .min(aabb.half_extents().length());
+
+        Self {
+            aabb,
+            sphere: BoundingSphere { center, radius },
+        }
+    }
+
+    pub fn transformed(&self, matrix: &Mat4) -> Bounds {
+        Self {
+            aabb: self.aabb.transformed(matrix),
+            sphere: self.sphere.transformed(matrix),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Frustum - Clip-space planes of a view-projection, for visibility tests
+
+use crate::math::bounds::{Aabb, BoundingSphere, Bounds};
+use glam::{Mat4, Vec3, Vec4};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near, far; `xyz` is the inward normal, `w` the offset
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extract the planes of an OpenGL-style (-1..1 depth) view-projection matrix
+    pub fn from_matrix(view_projection: &Mat4) -> Self {
+        let row = |i| view_projection.row(i);
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(3) + row(2),
+            row(3) - row(2),
+        ]
+        .map(|plane| plane / plane.truncate().length());
+
+        Self { planes }
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+
+    /// Conservative test: a box just outside a corner of the frustum may still pass
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            // Corner furthest along the plane normal
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+
+    pub fn intersects(&self, bounds: &Bounds) -> bool {
+        self.intersects_sphere(&bounds.sphere) && self.intersects_aabb(&bounds.aabb)
+    }
+}
+
+/// Near and far plane distances of an OpenGL perspective projection
+pub fn depth_range(projection: &Mat4) -> (f32, f32) {
+    let (a, b) = (projection.z_axis.z, projection.w_axis.z);
+    (b / (a - 1.0), b / (a + 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 90° camera at the origin looking down -Z, depth 1..100
+    fn frustum() -> Frustum {
+        let projection = Mat4::perspective_rh_gl(90f32.to_radians(), 1.0, 1.0, 100.0);
+        let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        Frustum::from_matrix(&(projection * view))
+    }
+
+    fn cube(center: Vec3, half: f32) -> Bounds {
+        let aabb = Aabb::new(center - Vec3::splat(half), center + Vec3::splat(half));
+        Bounds {
+            aabb,
+            sphere: BoundingSphere {
+                center,
+                radius: half * 3f32.sqrt(),
+            },
+        }
+    }
+
+    #[test]
+    fn bounds_in_view_intersect() {
+        let frustum = frustum();
+        assert!(frustum.intersects(&cube(Vec3::new(0.0, 0.0, -10.0), 1.0)));
+        // Straddling the left plane (x = z at 90°)
+        assert!(frustum.intersects(&cube(Vec3::new(-10.5, 0.0, -10.0), 1.0)));
+        // Straddling the far plane
+        assert!(frustum.intersects(&cube(Vec3::new(0.0, 0.0, -100.5), 1.0)));
+    }
+
+    #[test]
+    fn bounds_outside_a_plane_are_culled() {
+        let frustum = frustum();
+        for center in [
+            Vec3::new(0.0, 0.0, 10.0),    // behind
+            Vec3::new(0.0, 0.0, -0.2),    // before the near plane
+            Vec3::new(0.0, 0.0, -110.0),  // past the far plane
+            Vec3::new(-20.0, 0.0, -10.0), // left
+            Vec3::new(20.0, 0.0, -10.0),  // right
+            Vec3::new(0.0, -20.0, -10.0), // below
+            Vec3::new(0.0, 20.0, -10.0),  // above
+        ] {
+            assert!(!frustum.intersects(&cube(center, 0.5)), "{:?}", center);
+        }
+    }
+
+    #[test]
+    fn box_test_culls_what_the_sphere_lets_through() {
+        let frustum = frustum();
+        // A thin slab just left of the view: its sphere reaches in, the box doesn't
+        let aabb = Aabb::new(Vec3::new(-14.0, -0.1, -10.5), Vec3::new(-10.6, 0.1, -9.5));
+        let bounds = Bounds {
+            aabb,
+            sphere: BoundingSphere {
+                center: aabb.center(),
+                radius: aabb.half_extents().length(),
+            },
+        };
+        assert!(frustum.intersects_sphere(&bounds.sphere));
+        assert!(!frustum.intersects(&bounds));
+    }
+
+    #[test]
+    fn depth_range_recovers_the_planes() {
+        let projection = Mat4::perspective_rh_gl(1.0, 1.5, 0.5, 250.0);
+        let (near, far) = depth_range(&projection);
+        assert!((near - 0.5).abs() < 1e-4);
+        assert!((far - 250.0).abs() < 0.1);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod bounds;
+pub mod frustum;
 pub mod vector2d;
 pub mod vector3d;
 pub mod ray;
@@ -1,4 +1,7 @@
 extern crate gl;
+use crate::math::bounds::Bounds;
+use crate::renderer::instancing::InstanceBuffer;
+use crate::renderer::vertex;
 use gl::types::*;
 use std::ptr;
 use std::f32::consts::PI;
@@ -8,6 +11,8 @@ pub struct Capsule {
     vbo: GLuint,
     ebo: GLuint,
     indices_count: i32,
+    /// Local-space bounds of the generated vertices
+    pub bounds: Bounds,
     pub radius: f32,
     pub height: f32,
 }
@@ -19,6 +24,7 @@ impl Capsule {
             vbo: 0,
             ebo: 0,
             indices_count: 0,
+            bounds: Bounds::default(),
             radius,
             height,
         };
@@ -105,6 +111,9 @@ impl Capsule {
 
         self.indices_count = indices.len() as i32;
 
+        self.bounds = Bounds::from_vertices(&vertices, 8);
+        let vertices = vertex::with_tangents(&vertices, &indices);
+
         unsafe {
             gl::GenVertexArrays(1, &mut self.vao);
             gl::GenBuffers(1, &mut self.vbo);
@@ -128,19 +137,7 @@ impl Capsule {
                 gl::STATIC_DRAW,
             );
 
-            let stride = (8 * std::mem::size_of::<GLfloat>()) as i32;
-            
-            // Position
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
-            gl::EnableVertexAttribArray(0);
-
-            // TexCoord
-            gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE, stride, (3 * std::mem::size_of::<GLfloat>()) as *const _);
-            gl::EnableVertexAttribArray(2);
-             
-            // Normal
-            gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, stride, (5 * std::mem::size_of::<GLfloat>()) as *const _);
-            gl::EnableVertexAttribArray(1);
+            vertex::set_attribute_pointers();
 
             gl::BindVertexArray(0);
         }
@@ -153,6 +150,22 @@ impl Capsule {
             gl::BindVertexArray(0);
         }
     }
+
+    /// Draw `count` instances whose model matrices are in `instances`
+    pub fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            instances.bind_attributes();
+            gl::DrawElementsInstanced(
+                gl::TRIANGLES,
+                self.indices_count,
+                gl::UNSIGNED_INT,
+                ptr::null(),
+                count,
+            );
+            gl::BindVertexArray(0);
+        }
+    }
 }
 
 impl Drop for Capsule {
@@ -1,10 +1,14 @@
 extern crate gl;
+use crate::math::bounds::Bounds;
+use crate::renderer::instancing::InstanceBuffer;
+use crate::renderer::vertex;
 use gl::types::*;
-use std::ptr;
 
 pub struct Cube {
     vao: GLuint,
     vbo: GLuint,
+    /// Local-space bounds of the generated vertices
+    pub bounds: Bounds,
     pub size: f32,
 }
 
@@ -13,6 +17,7 @@ impl Cube {
         let mut cube = Cube {
             vao: 0,
             vbo: 0,
+            bounds: Bounds::default(),
             size,
         };
         cube.init();
@@ -66,6 +71,11 @@ impl Cube {
             -s,  s, -s,  0.0, 1.0,  0.0,  1.0,  0.0
         ];
 
+        self.bounds = Bounds::from_vertices(&vertices, 8);
+        // Unindexed: every three vertices are a triangle
+        let triangles: Vec<u32> = (0..36).collect();
+        let vertices = vertex::with_tangents(&vertices, &triangles);
+
         unsafe {
             gl::GenVertexArrays(1, &mut self.vao);
             gl::GenBuffers(1, &mut self.vbo);
@@ -80,19 +90,7 @@ impl Cube {
                 gl::STATIC_DRAW,
             );
 
-            let stride = (8 * std::mem::size_of::<GLfloat>()) as i32;
-            
-            // Position (0)
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
-            gl::EnableVertexAttribArray(0);
-
-            // TexCoord (2)
-            gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE, stride, (3 * std::mem::size_of::<GLfloat>()) as *const _);
-            gl::EnableVertexAttribArray(2);
-             
-            // Normal (1)
-            gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, stride, (5 * std::mem::size_of::<GLfloat>()) as *const _);
-            gl::EnableVertexAttribArray(1);
+            vertex::set_attribute_pointers();
 
             gl::BindVertexArray(0);
         }
@@ -105,6 +103,16 @@ impl Cube {
             gl::BindVertexArray(0);
         }
     }
+
+    /// Draw `count` instances whose model matrices are in `instances`
+    pub fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            instances.bind_attributes();
+            gl::DrawArraysInstanced(gl::TRIANGLES, 0, 36, count);
+            gl::BindVertexArray(0);
+        }
+    }
 }
 
 impl Drop for Cube {
@@ -1,10 +1,14 @@
 extern crate gl;
+use crate::math::bounds::Bounds;
+use crate::renderer::instancing::InstanceBuffer;
+use crate::renderer::vertex;
 use gl::types::*;
-use std::ptr;
 
 pub struct Plane {
     vao: GLuint,
     vbo: GLuint,
+    /// Local-space bounds of the generated vertices
+    pub bounds: Bounds,
     pub size: f32,
 }
 
@@ -13,6 +17,7 @@ impl Plane {
         let mut plane = Plane {
             vao: 0,
             vbo: 0,
+            bounds: Bounds::default(),
             size,
         };
         plane.init();
@@ -30,6 +35,10 @@ impl Plane {
             half, 0.0, -half, 1.0, 1.0, 0.0, 1.0, 0.0, // Top-Right
         ];
 
+        self.bounds = Bounds::from_vertices(&vertices, 8);
+        // The two triangles of the strip
+        let vertices = vertex::with_tangents(&vertices, &[0, 1, 2, 2, 1, 3]);
+
         unsafe {
             gl::GenVertexArrays(1, &mut self.vao);
             gl::GenBuffers(1, &mut self.vbo);
@@ -44,33 +53,7 @@ impl Plane {
                 gl::STATIC_DRAW,
             );
 
-            let stride = (8 * std::mem::size_of::<GLfloat>()) as i32;
-
-            // Position (0)
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
-            gl::EnableVertexAttribArray(0);
-
-            // TexCoord (2)
-            gl::VertexAttribPointer(
-                2,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                stride,
-                (3 * std::mem::size_of::<GLfloat>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(2);
-
-            // Normal (1)
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                stride,
-                (5 * std::mem::size_of::<GLfloat>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(1);
+            vertex::set_attribute_pointers();
 
             gl::BindVertexArray(0);
         }
@@ -83,6 +66,16 @@ impl Plane {
             gl::BindVertexArray(0);
         }
     }
+
+    /// Draw `count` instances whose model matrices are in `instances`
+    pub fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            instances.bind_attributes();
+            gl::DrawArraysInstanced(gl::TRIANGLE_STRIP, 0, 4, count);
+            gl::BindVertexArray(0);
+        }
+    }
 }
 
 impl Drop for Plane {
@@ -1,4 +1,7 @@
 extern crate gl;
+use crate::math::bounds::Bounds;
+use crate::renderer::instancing::InstanceBuffer;
+use crate::renderer::vertex;
 use gl::types::*;
 use std::ptr;
 use std::f32::consts::PI;
@@ -8,6 +11,8 @@ pub struct Sphere {
     vbo: GLuint,
     ebo: GLuint,
     indices_count: i32,
+    /// Local-space bounds of the generated vertices
+    pub bounds: Bounds,
     pub radius: f32,
 }
 
@@ -18,6 +23,7 @@ impl Sphere {
             vbo: 0,
             ebo: 0,
             indices_count: 0,
+            bounds: Bounds::default(),
             radius,
         };
         sphere.init(sectors, stacks);
@@ -84,6 +90,9 @@ impl Sphere {
 
         self.indices_count = indices.len() as i32;
 
+        self.bounds = Bounds::from_vertices(&vertices, 8);
+        let vertices = vertex::with_tangents(&vertices, &indices);
+
         unsafe {
             gl::GenVertexArrays(1, &mut self.vao);
             gl::GenBuffers(1, &mut self.vbo);
@@ -107,19 +116,7 @@ impl Sphere {
                 gl::STATIC_DRAW,
             );
 
-            let stride = (8 * std::mem::size_of::<GLfloat>()) as i32;
-            
-            // Position
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
-            gl::EnableVertexAttribArray(0);
-
-            // TexCoord
-            gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE, stride, (3 * std::mem::size_of::<GLfloat>()) as *const _);
-            gl::EnableVertexAttribArray(2);
-             
-            // Normal
-            gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, stride, (5 * std::mem::size_of::<GLfloat>()) as *const _);
-            gl::EnableVertexAttribArray(1);
+            vertex::set_attribute_pointers();
 
             gl::BindVertexArray(0);
         }
@@ -132,6 +129,22 @@ impl Sphere {
             gl::BindVertexArray(0);
         }
     }
+
+    /// Draw `count` instances whose model matrices are in `instances`
+    pub fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            instances.bind_attributes();
+            gl::DrawElementsInstanced(
+                gl::TRIANGLES,
+                self.indices_count,
+                gl::UNSIGNED_INT,
+                ptr::null(),
+                count,
+            );
+            gl::BindVertexArray(0);
+        }
+    }
 }
 
 impl Drop for Sphere {
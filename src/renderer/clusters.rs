@@ -0,0 +1,415 @@
+//! Light Clusters - Point and spot lights binned into a 3D grid over the view frustum
+//!
+//! The frustum is split into screen tiles and logarithmic depth slices. Every frame each
+//! light's range sphere is tested against the cluster boxes on the CPU, and the lights
+//! and the per-cluster (offset, count) pairs followed by the flattened light index lists
+//! are uploaded as two texture buffers. A fragment then only loops over the lights of its own cluster.
+
+use crate::config::rendering as render_cfg;
+use crate::config::rendering::texture_units;
+use crate::math::bounds::{Aabb, BoundingSphere};
+use crate::math::frustum;
+use crate::scene::manager::Scene;
+use crate::shaders::Shader;
+use crate::shadow::scheduler::PointShadowScheduler;
+use crate::shadow::spot::SpotShadowMap;
+use glam::{Mat4, UVec3, Vec2, Vec3};
+
+/// `vec4` texels per light in the light data buffer (layout in the lit shaders)
+const TEXELS_PER_LIGHT: usize = 7;
+
+const POINT: f32 = 0.0;
+const SPOT: f32 = 1.0;
+
+/// GL buffer exposed to shaders as a `samplerBuffer`/`usamplerBuffer`
+struct TextureBuffer {
+    buffer: u32,
+    texture: u32,
+}
+
+impl TextureBuffer {
+    fn new(format: gl::types::GLenum) -> Self {
+        let mut buffer = 0;
+        let mut texture = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut buffer);
+            gl::BindBuffer(gl::TEXTURE_BUFFER, buffer);
+            gl::BufferData(gl::TEXTURE_BUFFER, 0, std::ptr::null(), gl::STREAM_DRAW);
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_BUFFER, texture);
+            gl::TexBuffer(gl::TEXTURE_BUFFER, format, buffer);
+            gl::BindTexture(gl::TEXTURE_BUFFER, 0);
+            gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+        }
+        Self { buffer, texture }
+    }
+
+    fn upload<T>(&self, data: &[T]) {
+        unsafe {
+            gl::BindBuffer(gl::TEXTURE_BUFFER, self.buffer);
+            gl::BufferData(
+                gl::TEXTURE_BUFFER,
+                std::mem::size_of_val(data) as isize,
+                data.as_ptr() as *const _,
+                gl::STREAM_DRAW,
+            );
+            gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+        }
+    }
+
+    fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_BUFFER, self.texture);
+        }
+    }
+}
+
+impl Drop for TextureBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+    }
+}
+
+/// The cluster boxes of a projection and the lights binned into each (the CPU side)
+struct ClusterGrid {
+    /// Tiles across, tiles down and depth slices
+    dims: UVec3,
+    near: f32,
+    far: f32,
+    /// View-space box of every cluster, x fastest then y then slice
+    bounds: Vec<Aabb>,
+    /// Projection `bounds` were built for
+    projection: Mat4,
+    /// Light indices per cluster, reused between frames
+    lists: Vec<Vec<u32>>,
+}
+
+impl ClusterGrid {
+    fn new(dims: UVec3) -> Self {
+        Self {
+            dims,
+            near: 0.0,
+            far: 0.0,
+            bounds: Vec::new(),
+            projection: Mat4::ZERO,
+            lists: Vec::new(),
+        }
+    }
+
+    /// Empty every cluster, rebuilding the boxes if `projection` changed
+    fn reset(&mut self, projection: &Mat4) {
+        if *projection != self.projection {
+            self.projection = *projection;
+            (self.near, self.far) = frustum::depth_range(projection);
+            self.bounds = cluster_bounds(projection, self.dims, self.near, self.far);
+            self.lists = vec![Vec::new(); self.bounds.len()];
+        }
+        for list in &mut self.lists {
+            list.clear();
+        }
+    }
+
+    /// Add light `index` to every cluster its view-space range sphere touches
+    fn assign(&mut self, index: u32, center: Vec3, range: f32) {
+        let depth = -center.z;
+        if range <= 0.0 || depth + range < self.near || depth - range > self.far {
+            return;
+        }
+
+        let sphere = BoundingSphere {
+            center,
+            radius: range,
+        };
+        let first = self.slice((depth - range).max(self.near));
+        let last = self.slice((depth + range).min(self.far));
+        let tiles = (self.dims.x * self.dims.y) as usize;
+        for slice in first..=last {
+            let start = slice as usize * tiles;
+            for cluster in start..start + tiles {
+                if self.bounds[cluster].intersects_sphere(&sphere) {
+                    self.lists[cluster].push(index);
+                }
+            }
+        }
+    }
+
+    /// Depth slice containing view depth `depth` (same formula as the lit shaders)
+    fn slice(&self, depth: f32) -> u32 {
+        let slice = ((depth / self.near).ln() * self.slice_scale()).floor();
+        (slice.max(0.0) as u32).min(self.dims.z - 1)
+    }
+
+    fn slice_scale(&self) -> f32 {
+        self.dims.z as f32 / (self.far / self.near).ln()
+    }
+}
+
+pub struct LightClusters {
+    grid: ClusterGrid,
+    /// Size of the framebuffer the tiles divide
+    screen_size: Vec2,
+    light_data: TextureBuffer,
+    /// Offset and count of each cluster's light indices, followed by the indices, in one
+    /// buffer so the lit shaders spend a single texture unit on them
+    light_clusters: TextureBuffer,
+    /// Lights uploaded and cluster entries made by the last update
+    pub light_count: u32,
+    pub assignments: u32,
+}
+
+impl LightClusters {
+    pub fn new() -> Self {
+        Self {
+            grid: ClusterGrid::new(UVec3::new(
+                render_cfg::CLUSTER_TILES_X,
+                render_cfg::CLUSTER_TILES_Y,
+                render_cfg::CLUSTER_SLICES,
+            )),
+            screen_size: Vec2::ONE,
+            light_data: TextureBuffer::new(gl::RGBA32F),
+            light_clusters: TextureBuffer::new(gl::R32UI),
+            light_count: 0,
+            assignments: 0,
+        }
+    }
+
+    /// Bin the scene's point and spot lights (point lights first, so a point light's index
+    /// is its position in `scene.point_lights`) and upload them, with the cube map
+    /// `point_shadows` gave each point light and the layer of each spot light in
+    /// `spot_shadows`
+    pub fn update(
+        &mut self,
+        view: &Mat4,
+        projection: &Mat4,
+        screen_size: Vec2,
+        scene: &Scene,
+        point_shadows: &PointShadowScheduler,
+        spot_shadows: &SpotShadowMap,
+    ) {
+        let (point_lights, spot_lights) = (&scene.point_lights, &scene.spot_lights);
+        self.screen_size = screen_size;
+        self.grid.reset(projection);
+
+        let mut light_data: Vec<[f32; 4]> =
+            Vec::with_capacity((point_lights.len() + spot_lights.len()) * TEXELS_PER_LIGHT);
+        let mut spheres = Vec::with_capacity(point_lights.len() + spot_lights.len());
+        for (index, light) in point_lights.iter().enumerate() {
+            let (p, a) = (&light.properties, &light.attenuation);
+            let range = light.range();
+            // Cube map slot (-1 for none), and the position and range it was rendered from
+            let (slot, origin, shadow_range) = point_shadows
+                .slot_of(index)
+                .map_or((-1.0, Vec3::ZERO, 0.0), |(slot, origin, range)| {
+                    (slot as f32, origin, range)
+                });
+            // Cube maps keep no moments
+            let filter = light.shadow.filter.depth_only().shader_index() as f32;
+            let texel_size = 2.0 / render_cfg::POINT_SHADOW_SIZE as f32;
+            light_data.extend([
+                [light.position.x, light.position.y, light.position.z, POINT],
+                [p.color.x, p.color.y, p.color.z, range],
+                [p.ambient, p.diffuse, p.specular, p.shininess],
+                [a.constant, a.linear, a.quadratic, 0.0],
+                [origin.x, origin.y, origin.z, 0.0],
+                [slot, shadow_range, texel_size, filter],
+                light.shadow.params(),
+            ]);
+            spheres.push((light.position, range));
+        }
+        for (index, light) in spot_lights.iter().enumerate() {
+            let (p, a, d) = (&light.properties, &light.attenuation, light.direction);
+            let range = light.range();
+            // Shadow map layer (-1 for none), its far plane, its texel size one unit from the
+            // light and the filtering it was made for
+            let (layer, shadow_range, texel_size, shadow) = spot_shadows
+                .layer_of(index)
+                .map_or((-1.0, 0.0, 0.0, light.shadow), |(layer, shadow)| {
+                    (layer as f32, shadow.range, shadow.texel_size, shadow.shadow)
+                });
+            light_data.extend([
+                [light.position.x, light.position.y, light.position.z, SPOT],
+                [p.color.x, p.color.y, p.color.z, range],
+                [p.ambient, p.diffuse, p.specular, p.shininess],
+                [a.constant, a.linear, a.quadratic, light.cone.outer_cut_off],
+                [d.x, d.y, d.z, light.cone.cut_off],
+                [
+                    layer,
+                    shadow_range,
+                    texel_size,
+                    shadow.filter.shader_index() as f32,
+                ],
+                shadow.params(),
+            ]);
+            // The cone fits inside the range sphere
+            spheres.push((light.position, range));
+        }
+
+        for (index, (position, range)) in spheres.into_iter().enumerate() {
+            self.grid
+                .assign(index as u32, view.transform_point3(position), range);
+        }
+
+        let lists = &self.grid.lists;
+        let mut clusters: Vec<u32> = Vec::with_capacity(lists.len() * 2);
+        let mut indices: Vec<u32> = Vec::new();
+        for list in lists {
+            // Offsets count past the per-cluster pairs in front of the indices
+            clusters.push((lists.len() * 2 + indices.len()) as u32);
+            clusters.push(list.len() as u32);
+            indices.extend_from_slice(list);
+        }
+        self.assignments = indices.len() as u32;
+        clusters.append(&mut indices);
+
+        self.light_data.upload(&light_data);
+        self.light_clusters.upload(&clusters);
+        self.light_count = (light_data.len() / TEXELS_PER_LIGHT) as u32;
+    }
+
+    /// Bind the buffers and set the cluster uniforms `shader` needs to find its lights
+    pub fn apply_to_shader(&self, shader: &Shader) {
+        self.light_data.bind(texture_units::LIGHT_DATA);
+        self.light_clusters.bind(texture_units::LIGHT_CLUSTERS);
+        shader.set_int("lightData", texture_units::LIGHT_DATA as i32);
+        shader.set_int("lightClusters", texture_units::LIGHT_CLUSTERS as i32);
+
+        let grid = &self.grid;
+        let dims = grid.dims.as_vec3();
+        shader.set_vec3("clusterDims", dims.x, dims.y, dims.z);
+        shader.set_vec2("clusterScreenSize", self.screen_size.x, self.screen_size.y);
+        shader.set_float("clusterNear", grid.near);
+        shader.set_float("clusterSliceScale", grid.slice_scale());
+    }
+}
+
+/// View-space box of every cluster of `projection`, x fastest then y then depth slice
+fn cluster_bounds(projection: &Mat4, dims: UVec3, near: f32, far: f32) -> Vec<Aabb> {
+    let inverse = projection.inverse();
+    // View-space point through an NDC position, scaled to a depth of 1
+    let ray = |x: f32, y: f32| {
+        let p = inverse.project_point3(Vec3::new(x, y, -1.0));
+        p / -p.z
+    };
+    let ndc = |i: u32, count: u32| -1.0 + 2.0 * i as f32 / count as f32;
+    let slice_depth = |k: u32| near * (far / near).powf(k as f32 / dims.z as f32);
+
+    let mut bounds = Vec::with_capacity((dims.x * dims.y * dims.z) as usize);
+    for z in 0..dims.z {
+        let (d0, d1) = (slice_depth(z), slice_depth(z + 1));
+        for y in 0..dims.y {
+            for x in 0..dims.x {
+                let (x0, x1) = (ndc(x, dims.x), ndc(x + 1, dims.x));
+                let (y0, y1) = (ndc(y, dims.y), ndc(y + 1, dims.y));
+                let corners = [ray(x0, y0), ray(x1, y0), ray(x0, y1), ray(x1, y1)];
+                bounds.push(Aabb::from_points(
+                    corners.iter().flat_map(|&r| [r * d0, r * d1]),
+                ));
+            }
+        }
+    }
+    bounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMS: UVec3 = UVec3::new(8, 4, 12);
+
+    fn grid() -> ClusterGrid {
+        let mut grid = ClusterGrid::new(DIMS);
+        let projection = Mat4::perspective_rh_gl(60f32.to_radians(), 16.0 / 9.0, 0.5, 200.0);
+        grid.reset(&projection);
+        grid
+    }
+
+    fn clusters_with(grid: &ClusterGrid, index: u32) -> Vec<usize> {
+        (0..grid.lists.len())
+            .filter(|&cluster| grid.lists[cluster].contains(&index))
+            .collect()
+    }
+
+    #[test]
+    fn slices_are_logarithmic_in_depth() {
+        let grid = grid();
+        assert_eq!(grid.slice(grid.near), 0);
+        assert_eq!(grid.slice(grid.far), DIMS.z - 1);
+        // Outside the depth range clamps to the first and last slice
+        assert_eq!(grid.slice(grid.near * 0.5), 0);
+        assert_eq!(grid.slice(grid.far * 2.0), DIMS.z - 1);
+
+        let ratio = grid.far / grid.near;
+        for k in 0..DIMS.z {
+            let middle = grid.near * ratio.powf((k as f32 + 0.5) / DIMS.z as f32);
+            assert_eq!(grid.slice(middle), k);
+        }
+    }
+
+    #[test]
+    fn cluster_boxes_tile_the_frustum_slices() {
+        let grid = grid();
+        assert_eq!(grid.bounds.len(), (DIMS.x * DIMS.y * DIMS.z) as usize);
+
+        let tiles = (DIMS.x * DIMS.y) as usize;
+        for (cluster, aabb) in grid.bounds.iter().enumerate() {
+            // The box spans exactly its slice's depth range
+            let slice = (cluster / tiles) as u32;
+            assert_eq!(grid.slice(-aabb.max.z * 1.001), slice);
+            assert_eq!(grid.slice(-aabb.min.z * 0.999), slice);
+        }
+        // x fastest: the first row runs left to right
+        assert!(grid.bounds[0].max.x < grid.bounds[1].max.x);
+        assert!(grid.bounds[0].max.y < grid.bounds[DIMS.x as usize].max.y);
+    }
+
+    #[test]
+    fn assignment_matches_a_test_against_every_cluster() {
+        let mut grid = grid();
+        let lights = [
+            (Vec3::new(0.0, 0.0, -10.0), 2.0),
+            (Vec3::new(-8.0, 3.0, -15.0), 6.0),
+            (Vec3::new(40.0, -10.0, -90.0), 25.0),
+            (Vec3::new(0.0, 0.0, -0.2), 1.0),
+            (Vec3::new(5.0, 1.0, -199.0), 3.0),
+        ];
+        for (index, &(center, range)) in lights.iter().enumerate() {
+            grid.assign(index as u32, center, range);
+        }
+
+        for (index, &(center, radius)) in lights.iter().enumerate() {
+            let sphere = BoundingSphere { center, radius };
+            let expected: Vec<usize> = (0..grid.bounds.len())
+                .filter(|&cluster| grid.bounds[cluster].intersects_sphere(&sphere))
+                .collect();
+            assert!(!expected.is_empty());
+            assert_eq!(
+                clusters_with(&grid, index as u32),
+                expected,
+                "light {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn lights_out_of_the_depth_range_are_skipped() {
+        let mut grid = grid();
+        grid.assign(0, Vec3::new(0.0, 0.0, 10.0), 5.0); // behind the camera
+        grid.assign(1, Vec3::new(0.0, 0.0, -300.0), 50.0); // past the far plane
+        grid.assign(2, Vec3::new(0.0, 0.0, -10.0), 0.0); // no range
+        assert!(grid.lists.iter().all(|list| list.is_empty()));
+    }
+
+    #[test]
+    fn reset_empties_the_clusters() {
+        let mut grid = grid();
+        grid.assign(0, Vec3::new(0.0, 0.0, -10.0), 5.0);
+        let projection = grid.projection;
+        grid.reset(&projection);
+        assert!(grid.lists.iter().all(|list| list.is_empty()));
+    }
+}
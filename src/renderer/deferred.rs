@@ -0,0 +1,342 @@
+//! Deferred Shading - G-buffer geometry pass followed by screen-space lighting
+//!
+//! Lit materials with a G-buffer shader write position, normal, albedo and specular once.
+//! A fullscreen pass then adds ambient, the directional light (with cascaded shadows) and
+//! the spot lights, and every point light is drawn as an additive sphere covering its
+//! range, so point lights are bounded by fill rate rather than a shader array.
+
+use super::target::Viewport;
+use crate::assets::paths::{names, shaders};
+use crate::assets::AssetManager;
+use crate::config::rendering as render_cfg;
+use crate::config::rendering::texture_units;
+use crate::math::bounds::BoundingSphere;
+use crate::math::frustum::Frustum;
+use crate::primitives::Sphere;
+use crate::scene::context::RenderContext;
+use crate::shaders::Shader;
+use glam::{Mat4, Vec3};
+use std::rc::Rc;
+
+/// The tessellated volume lies inside the true sphere; scale it up to cover the full range
+const VOLUME_PADDING: f32 = 1.1;
+
+/// Position, normal and material attachments of the geometry pass
+pub struct GBuffer {
+    pub fbo: u32,
+    /// World position (RGBA32F), `w` = 1 where a surface was drawn
+    pub position: u32,
+    /// World normal (RGBA16F), `w` = 1 if the surface receives shadows
+    pub normal: u32,
+    /// Albedo (RGB) and specular intensity (A)
+    pub albedo_spec: u32,
+    depth_rbo: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl GBuffer {
+    pub fn new(width: u32, height: u32) -> Result<Self, String> {
+        let mut gbuffer = Self {
+            fbo: 0,
+            position: 0,
+            normal: 0,
+            albedo_spec: 0,
+            depth_rbo: 0,
+            width,
+            height,
+        };
+
+        let status;
+        unsafe {
+            gl::GenFramebuffers(1, &mut gbuffer.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, gbuffer.fbo);
+
+            gbuffer.position = attach_texture(0, gl::RGBA32F, gl::FLOAT, width, height);
+            gbuffer.normal = attach_texture(1, gl::RGBA16F, gl::FLOAT, width, height);
+            gbuffer.albedo_spec = attach_texture(2, gl::RGBA8, gl::UNSIGNED_BYTE, width, height);
+            let attachments = [
+                gl::COLOR_ATTACHMENT0,
+                gl::COLOR_ATTACHMENT1,
+                gl::COLOR_ATTACHMENT2,
+            ];
+            gl::DrawBuffers(attachments.len() as i32, attachments.as_ptr());
+
+            gl::GenRenderbuffers(1, &mut gbuffer.depth_rbo);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, gbuffer.depth_rbo);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                width as i32,
+                height as i32,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                gbuffer.depth_rbo,
+            );
+
+            status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "G-buffer {}x{} is incomplete: 0x{:X}",
+                width, height, status
+            ));
+        }
+        Ok(gbuffer)
+    }
+
+    /// Bind the attachments to units 0-2 for the lighting shaders
+    pub fn bind_textures(&self, shader: &Shader) {
+        let textures = [
+            ("gPosition", self.position),
+            ("gNormal", self.normal),
+            ("gAlbedoSpec", self.albedo_spec),
+        ];
+        for (unit, (name, texture)) in textures.into_iter().enumerate() {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+            }
+            shader.set_int(name, unit as i32);
+        }
+    }
+
+    /// Copy the scene depth into `output`, so later forward draws are hidden correctly
+    pub fn blit_depth(&self, output: &Viewport) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, output.framebuffer);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                0,
+                0,
+                output.width as i32,
+                output.height as i32,
+                gl::DEPTH_BUFFER_BIT,
+                gl::NEAREST,
+            );
+        }
+        output.bind();
+    }
+}
+
+unsafe fn attach_texture(
+    index: u32,
+    internal_format: gl::types::GLenum,
+    data_type: gl::types::GLenum,
+    width: u32,
+    height: u32,
+) -> u32 {
+    let mut texture = 0;
+    gl::GenTextures(1, &mut texture);
+    gl::BindTexture(gl::TEXTURE_2D, texture);
+    gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        internal_format as i32,
+        width as i32,
+        height as i32,
+        0,
+        gl::RGBA,
+        data_type,
+        std::ptr::null(),
+    );
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+    gl::FramebufferTexture2D(
+        gl::FRAMEBUFFER,
+        gl::COLOR_ATTACHMENT0 + index,
+        gl::TEXTURE_2D,
+        texture,
+        0,
+    );
+    texture
+}
+
+impl Drop for GBuffer {
+    fn drop(&mut self) {
+        let textures = [self.position, self.normal, self.albedo_spec];
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(textures.len() as i32, textures.as_ptr());
+            gl::DeleteRenderbuffers(1, &self.depth_rbo);
+        }
+    }
+}
+
+/// G-buffer and lighting passes of the deferred path
+pub struct DeferredRenderer {
+    lighting_shader: Rc<Shader>,
+    point_shader: Rc<Shader>,
+    /// Created on first use, and again whenever the output size changes
+    gbuffer: Option<GBuffer>,
+    light_volume: Sphere,
+    /// Attribute-less VAO for the fullscreen triangle
+    fullscreen_vao: u32,
+}
+
+impl DeferredRenderer {
+    pub fn new(lighting_shader: Rc<Shader>, point_shader: Rc<Shader>) -> Self {
+        let mut fullscreen_vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut fullscreen_vao);
+        }
+        Self {
+            lighting_shader,
+            point_shader,
+            gbuffer: None,
+            light_volume: Sphere::new(1.0, 16, 12),
+            fullscreen_vao,
+        }
+    }
+
+    /// Load the lighting shaders through `assets`
+    pub fn load(assets: &mut AssetManager) -> Self {
+        let lighting_shader = assets.load_shader(
+            names::SHADER_DEFERRED_LIGHTING,
+            shaders::FULLSCREEN_VERT,
+            shaders::DEFERRED_LIGHTING_FRAG,
+        );
+        let point_shader = assets.load_shader(
+            names::SHADER_DEFERRED_POINT,
+            shaders::LIGHT_VOLUME_VERT,
+            shaders::LIGHT_VOLUME_POINT_FRAG,
+        );
+        Self::new(lighting_shader, point_shader)
+    }
+
+    /// Bind and clear a G-buffer the size of `output` for the geometry pass
+    pub fn begin_geometry_pass(&mut self, output: &Viewport) -> Result<(), String> {
+        let stale = self
+            .gbuffer
+            .as_ref()
+            .is_none_or(|g| g.width != output.width || g.height != output.height);
+        if stale {
+            self.gbuffer = None;
+            self.gbuffer = Some(GBuffer::new(output.width, output.height)?);
+        }
+        let gbuffer = self.gbuffer.as_ref().unwrap();
+
+        let zero = [0.0f32; 4];
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, gbuffer.fbo);
+            gl::Viewport(0, 0, gbuffer.width as i32, gbuffer.height as i32);
+            for attachment in 0..3 {
+                gl::ClearBufferfv(gl::COLOR, attachment, zero.as_ptr());
+            }
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        Ok(())
+    }
+
+    /// Light the G-buffer into the bound `output`. Returns the point light volumes drawn.
+    pub fn light(&self, ctx: &RenderContext, output: &Viewport, frustum: &Frustum) -> u32 {
+        let Some(gbuffer) = &self.gbuffer else {
+            return 0;
+        };
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::FALSE);
+        }
+
+        // Ambient, directional and spot lights over every covered pixel
+        let shader = &self.lighting_shader;
+        shader.use_program();
+        ctx.apply_lighting(shader);
+        shader.set_mat4("view", &ctx.view.to_cols_array());
+        gbuffer.bind_textures(shader);
+        unsafe {
+            gl::BindVertexArray(self.fullscreen_vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+        }
+
+        // Point lights, added through their volumes. Back faces only, so each pixel is
+        // lit once even when the camera is inside a volume.
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+            gl::Enable(gl::CULL_FACE);
+            gl::CullFace(gl::FRONT);
+        }
+        let shader = &self.point_shader;
+        shader.use_program();
+        gbuffer.bind_textures(shader);
+        shader.set_mat4("projection", &ctx.projection.to_cols_array());
+        shader.set_mat4("view", &ctx.view.to_cols_array());
+        shader.set_vec2("screenSize", output.width as f32, output.height as f32);
+        shader.set_int("pointShadowMap", texture_units::POINT_SHADOW_MAPS as i32);
+        shader.set_float("shadowTexel", 2.0 / render_cfg::POINT_SHADOW_SIZE as f32);
+        ctx.apply_occlusion(shader);
+
+        let mut volumes = 0;
+        for (i, light) in ctx.point_lights.iter().enumerate() {
+            let range = light.range();
+            let volume = BoundingSphere {
+                center: light.position,
+                radius: range,
+            };
+            if range <= 0.0 || !frustum.intersects_sphere(&volume) {
+                continue;
+            }
+
+            let model = Mat4::from_translation(light.position)
+                * Mat4::from_scale(Vec3::splat(range * VOLUME_PADDING));
+            shader.set_mat4("model", &model.to_cols_array());
+            light.apply_to_shader_as(shader, "light", ctx.view_pos);
+            shader.set_float("lightRange", range);
+
+            // Only the lights the scheduler gave a complete cube map have shadows
+            match ctx.point_shadows.slot_of(i) {
+                Some((slot, origin, shadow_range)) => {
+                    let shadow_map = &ctx.point_shadow_maps[slot];
+                    shadow_map.bind_cubemap(texture_units::POINT_SHADOW_MAPS);
+                    shader.set_int("hasShadow", 1);
+                    shader.set_vec3("shadowOrigin", origin.x, origin.y, origin.z);
+                    shader.set_float("shadowRange", shadow_range);
+                    // Cube maps keep no moments
+                    let mut shadow = light.shadow;
+                    shadow.filter = shadow.filter.depth_only();
+                    shadow.apply_to_shader(shader, "lightShadow");
+                }
+                None => shader.set_int("hasShadow", 0),
+            }
+
+            self.light_volume.draw();
+            volumes += 1;
+        }
+
+        unsafe {
+            gl::Disable(gl::BLEND);
+            gl::Disable(gl::CULL_FACE);
+            gl::CullFace(gl::BACK);
+            gl::DepthMask(gl::TRUE);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        volumes
+    }
+
+    /// Copy the G-buffer depth into `output` (see `GBuffer::blit_depth`)
+    pub fn blit_depth(&self, output: &Viewport) {
+        if let Some(gbuffer) = &self.gbuffer {
+            gbuffer.blit_depth(output);
+        }
+    }
+}
+
+impl Drop for DeferredRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.fullscreen_vao);
+        }
+    }
+}
@@ -0,0 +1,617 @@
+//! Render Graph - Frame passes ordered and given targets by the resources they declare
+//!
+//! Every frame each pass declares the resources it writes (produces from scratch),
+//! modifies (draws over) and reads. Resources are either imported by the renderer (shadow
+//! maps, the output, ...) or transient targets created by the pass that writes them. The
+//! passes run so that each resource is written first, then modified in the order the
+//! passes were added, then read; passes none of whose writes reach an imported resource
+//! are skipped. Transient targets come from a pool kept between frames, and transients
+//! of the same size and format whose lifetimes don't overlap share one target.
+
+use super::target::{RenderTarget, TargetFormat, Viewport};
+use super::{Frame, Renderer};
+use std::collections::{BTreeSet, HashMap};
+
+/// Resources of the built-in passes
+pub mod resources {
+    /// Imported: the directional light's shadow cascades
+    pub const SHADOW_CASCADES: &str = "shadow_cascades";
+    /// Imported: the point light cube maps
+    pub const POINT_SHADOWS: &str = "point_shadows";
+    /// Imported: the spot light shadow layers
+    pub const SPOT_SHADOWS: &str = "spot_shadows";
+    /// Imported: point and spot lights binned for the lit shaders
+    pub const LIGHT_CLUSTERS: &str = "light_clusters";
+    /// Imported: screen-space ambient occlusion
+    pub const OCCLUSION: &str = "occlusion";
+    /// Transient: the lit scene, color and depth, before tone mapping
+    pub const SCENE_COLOR: &str = "scene_color";
+    /// Transient: the scene after the HDR post effects (bloom), before tone mapping
+    pub const HDR_COLOR: &str = "hdr_color";
+    /// Transient: what the HDR effects alternate with, while more than one runs
+    pub const HDR_SCRATCH: &str = "hdr_scratch";
+    /// Transient: the tone-mapped image, while display effects follow
+    pub const DISPLAY_COLOR: &str = "display_color";
+    /// Transient: what the display effects alternate with, while more than one runs
+    pub const DISPLAY_SCRATCH: &str = "display_scratch";
+    /// Imported: where the final image goes (`Renderer::output`)
+    pub const OUTPUT: &str = "output";
+}
+
+/// Size and format of a transient target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: TargetFormat,
+}
+
+impl TargetDesc {
+    /// A target covering `viewport`
+    pub fn sized_as(viewport: &Viewport, format: TargetFormat) -> Self {
+        Self {
+            width: viewport.width,
+            height: viewport.height,
+            format,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Write,
+    Modify,
+    Read,
+}
+
+/// What a pass declared in `RenderPass::setup`
+#[derive(Debug, Default)]
+pub struct PassBuilder {
+    accesses: Vec<(String, Access)>,
+    creates: Vec<(String, TargetDesc)>,
+}
+
+impl PassBuilder {
+    /// Create the transient target `name`, written by this pass
+    pub fn create(&mut self, name: &str, desc: TargetDesc) {
+        self.creates.push((name.to_string(), desc));
+        self.write(name);
+    }
+
+    /// Produce `name` from scratch (clearing or overwriting all of it)
+    pub fn write(&mut self, name: &str) {
+        self.accesses.push((name.to_string(), Access::Write));
+    }
+
+    /// Draw over `name` once it has been written
+    pub fn modify(&mut self, name: &str) {
+        self.accesses.push((name.to_string(), Access::Modify));
+    }
+
+    /// Sample `name` once it is complete
+    pub fn read(&mut self, name: &str) {
+        self.accesses.push((name.to_string(), Access::Read));
+    }
+}
+
+/// One step of the frame
+pub trait RenderPass {
+    /// Unique name, for `RenderGraph::remove_pass` and errors
+    fn name(&self) -> &str;
+
+    /// Declare what the pass writes and reads this frame. A pass that declares no writes
+    /// (for example because it is turned off) is skipped.
+    fn setup(&mut self, renderer: &Renderer, frame: &Frame, builder: &mut PassBuilder);
+
+    /// Draw the pass. The framebuffer bound on entry is unspecified.
+    fn execute(&mut self, renderer: &mut Renderer, frame: &Frame, resources: &GraphResources);
+}
+
+/// The targets of this frame's resources, handed to the passes
+pub struct GraphResources<'a> {
+    imports: &'a HashMap<String, Option<Viewport>>,
+    targets: HashMap<String, &'a RenderTarget>,
+}
+
+impl GraphResources<'_> {
+    /// The transient target `name`, if a pass created it this frame
+    pub fn target(&self, name: &str) -> Option<&RenderTarget> {
+        self.targets.get(name).copied()
+    }
+
+    /// Framebuffer of `name`: its transient target, or the viewport it was imported with
+    pub fn viewport(&self, name: &str) -> Option<Viewport> {
+        match self.targets.get(name) {
+            Some(target) => Some(target.viewport()),
+            None => self.imports.get(name).copied().flatten(),
+        }
+    }
+}
+
+/// Passes in the order they run, and where their transients live
+#[derive(Debug)]
+struct Plan {
+    order: Vec<usize>,
+    /// Transient name and the pool slot it shares
+    slots: Vec<(String, usize)>,
+    /// Size and format of each pool slot
+    slot_descs: Vec<TargetDesc>,
+}
+
+/// Passes of a frame, run in the order their resource declarations imply
+#[derive(Default)]
+pub struct RenderGraph {
+    passes: Vec<Box<dyn RenderPass>>,
+    /// Resources the renderer provides this frame, with the framebuffer they are drawn
+    /// through, if any
+    imports: HashMap<String, Option<Viewport>>,
+    /// Transient targets, reused across frames while the slots keep their size and format
+    pool: Vec<RenderTarget>,
+    /// Names of the passes the last frame ran, in order
+    order: Vec<String>,
+    /// Last error, reported once rather than every frame
+    error: Option<String>,
+}
+
+impl RenderGraph {
+    /// Add `pass`; where it runs follows from what it declares
+    pub fn add_pass(&mut self, pass: impl RenderPass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    /// Remove the pass named `name`
+    pub fn remove_pass(&mut self, name: &str) -> Option<Box<dyn RenderPass>> {
+        let index = self.passes.iter().position(|p| p.name() == name)?;
+        Some(self.passes.remove(index))
+    }
+
+    /// Provide `name` for this frame, drawn through `viewport` if it is a framebuffer
+    pub fn import(&mut self, name: &str, viewport: Option<Viewport>) {
+        self.imports.insert(name.to_string(), viewport);
+    }
+
+    /// Names of the passes the last frame ran, in order
+    pub fn order(&self) -> &[String] {
+        &self.order
+    }
+
+    /// Why the last frame didn't run, if it didn't
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Set up, order and run the passes for `frame`, then forget this frame's imports.
+    /// Errors (conflicting declarations, targets that can't be allocated) skip the frame.
+    pub fn execute(&mut self, renderer: &mut Renderer, frame: &Frame) {
+        let declared: Vec<PassBuilder> = self
+            .passes
+            .iter_mut()
+            .map(|pass| {
+                let mut builder = PassBuilder::default();
+                pass.setup(renderer, frame, &mut builder);
+                builder
+            })
+            .collect();
+
+        let plan = self.compile(&declared).and_then(|plan| {
+            self.allocate(&plan.slot_descs)?;
+            Ok(plan)
+        });
+        match plan {
+            Ok(plan) => {
+                self.error = None;
+                self.order = plan
+                    .order
+                    .iter()
+                    .map(|&i| self.passes[i].name().to_string())
+                    .collect();
+                let resources = GraphResources {
+                    imports: &self.imports,
+                    targets: plan
+                        .slots
+                        .iter()
+                        .map(|(name, slot)| (name.clone(), &self.pool[*slot]))
+                        .collect(),
+                };
+                for &i in &plan.order {
+                    self.passes[i].execute(renderer, frame, &resources);
+                }
+            }
+            Err(e) => {
+                if self.error.as_ref() != Some(&e) {
+                    eprintln!("Render graph: {}; skipping frames until it is resolved", e);
+                }
+                self.error = Some(e);
+                self.order.clear();
+            }
+        }
+        self.imports.clear();
+    }
+
+    /// Check the declarations, keep the passes that reach an imported resource, order
+    /// them and fit their transients into pool slots
+    fn compile(&self, declared: &[PassBuilder]) -> Result<Plan, String> {
+        let name = |pass: usize| self.passes[pass].name();
+
+        // Transients by creator
+        let mut descs: HashMap<&str, (usize, TargetDesc)> = HashMap::new();
+        for (pass, builder) in declared.iter().enumerate() {
+            for (resource, desc) in &builder.creates {
+                if self.imports.contains_key(resource) {
+                    return Err(format!(
+                        "pass '{}' creates '{}', which is imported",
+                        name(pass),
+                        resource
+                    ));
+                }
+                if let Some((other, _)) = descs.insert(resource, (pass, *desc)) {
+                    return Err(format!(
+                        "'{}' is created by both '{}' and '{}'",
+                        resource,
+                        name(other),
+                        name(pass)
+                    ));
+                }
+            }
+        }
+
+        // Writer, modifiers (in pass order) and readers of every resource
+        #[derive(Default)]
+        struct Users {
+            writer: Option<usize>,
+            modifiers: Vec<usize>,
+            readers: Vec<usize>,
+        }
+        let mut users: HashMap<&str, Users> = HashMap::new();
+        for (pass, builder) in declared.iter().enumerate() {
+            for (resource, access) in &builder.accesses {
+                if !self.imports.contains_key(resource) && !descs.contains_key(resource.as_str()) {
+                    return Err(format!(
+                        "pass '{}' uses '{}', which is neither imported nor created",
+                        name(pass),
+                        resource
+                    ));
+                }
+                let entry = users.entry(resource).or_default();
+                match access {
+                    Access::Write => {
+                        if let Some(other) = entry.writer.filter(|&other| other != pass) {
+                            return Err(format!(
+                                "'{}' is written by both '{}' and '{}'",
+                                resource,
+                                name(other),
+                                name(pass)
+                            ));
+                        }
+                        entry.writer = Some(pass);
+                    }
+                    Access::Modify => entry.modifiers.push(pass),
+                    Access::Read => entry.readers.push(pass),
+                }
+            }
+        }
+
+        // Keep the passes that write into an imported resource, then whatever writes what
+        // a kept pass reads or draws over
+        let mut live = vec![false; declared.len()];
+        let mut pending: Vec<usize> = Vec::new();
+        for (resource, user) in &users {
+            if self.imports.contains_key(*resource) {
+                pending.extend(user.writer.iter().chain(&user.modifiers));
+            }
+        }
+        while let Some(pass) = pending.pop() {
+            if std::mem::replace(&mut live[pass], true) {
+                continue;
+            }
+            for (resource, access) in &declared[pass].accesses {
+                if *access != Access::Write {
+                    let user = &users[resource.as_str()];
+                    pending.extend(user.writer.iter().chain(&user.modifiers));
+                }
+            }
+        }
+
+        // Writer before modifiers before readers, modifiers in pass order
+        let mut edges: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); declared.len()];
+        for user in users.values() {
+            let chain: Vec<usize> = user.writer.iter().chain(&user.modifiers).copied().collect();
+            for pair in chain.windows(2) {
+                edges[pair[0]].insert(pair[1]);
+            }
+            if let Some(&last) = chain.last() {
+                // A pass reading what it draws over is ordered by the drawing
+                for &reader in user.readers.iter().filter(|r| !chain.contains(r)) {
+                    edges[last].insert(reader);
+                }
+            }
+        }
+        let order = topological_order(&edges, &live).map_err(|cycle| {
+            let names: Vec<&str> = cycle.into_iter().map(name).collect();
+            format!("passes {} depend on each other", names.join(", "))
+        })?;
+
+        // Lifetime of each transient (first and last position it is used at), packed into
+        // slots no other transient uses meanwhile
+        let position: HashMap<usize, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(at, &pass)| (pass, at))
+            .collect();
+        let mut lifetimes: Vec<(&str, TargetDesc, usize, usize)> = descs
+            .iter()
+            .filter(|(_, (creator, _))| live[*creator])
+            .map(|(&resource, &(_, desc))| {
+                let user = &users[resource];
+                let uses = user
+                    .writer
+                    .iter()
+                    .chain(&user.modifiers)
+                    .chain(&user.readers);
+                let at: Vec<usize> = uses
+                    .filter_map(|pass| position.get(pass).copied())
+                    .collect();
+                let first = at.iter().copied().min().unwrap_or(0);
+                let last = at.iter().copied().max().unwrap_or(0);
+                (resource, desc, first, last)
+            })
+            .collect();
+        lifetimes.sort_by_key(|&(resource, _, first, _)| (first, resource));
+
+        let mut slots = Vec::new();
+        let mut slot_descs: Vec<TargetDesc> = Vec::new();
+        let mut slot_free_after: Vec<usize> = Vec::new();
+        for (resource, desc, first, last) in lifetimes {
+            let free = (0..slot_descs.len())
+                .find(|&slot| slot_descs[slot] == desc && slot_free_after[slot] < first);
+            let slot = free.unwrap_or_else(|| {
+                slot_descs.push(desc);
+                slot_free_after.push(0);
+                slot_descs.len() - 1
+            });
+            slot_free_after[slot] = last;
+            slots.push((resource.to_string(), slot));
+        }
+
+        Ok(Plan {
+            order,
+            slots,
+            slot_descs,
+        })
+    }
+
+    /// Make the pool match `descs`, keeping the targets that already do
+    fn allocate(&mut self, descs: &[TargetDesc]) -> Result<(), String> {
+        let mut old = std::mem::take(&mut self.pool);
+        for desc in descs {
+            let reused = old.iter().position(|t| {
+                (t.width, t.height, t.format) == (desc.width, desc.height, desc.format)
+            });
+            let target = match reused {
+                Some(index) => old.swap_remove(index),
+                None => RenderTarget::new(desc.width, desc.height, desc.format)?,
+            };
+            self.pool.push(target);
+        }
+        Ok(())
+    }
+}
+
+/// Kahn's algorithm over the `live` passes, the earliest added first among the ready
+/// ones. On a cycle, returns the passes left in it.
+fn topological_order(edges: &[BTreeSet<usize>], live: &[bool]) -> Result<Vec<usize>, Vec<usize>> {
+    let mut incoming = vec![0usize; edges.len()];
+    for (from, targets) in edges.iter().enumerate() {
+        if live[from] {
+            for &to in targets.iter().filter(|&&to| live[to]) {
+                incoming[to] += 1;
+            }
+        }
+    }
+
+    let mut ready: BTreeSet<usize> = (0..edges.len())
+        .filter(|&pass| live[pass] && incoming[pass] == 0)
+        .collect();
+    let mut order = Vec::new();
+    while let Some(pass) = ready.pop_first() {
+        order.push(pass);
+        for &to in edges[pass].iter().filter(|&&to| live[to]) {
+            incoming[to] -= 1;
+            if incoming[to] == 0 {
+                ready.insert(to);
+            }
+        }
+    }
+
+    if order.len() < live.iter().filter(|&&l| l).count() {
+        let stuck = (0..edges.len())
+            .filter(|&pass| live[pass] && incoming[pass] > 0)
+            .collect();
+        return Err(stuck);
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resources::OUTPUT;
+
+    /// A pass only compiled, never set up or run
+    struct Named(&'static str);
+
+    impl RenderPass for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn setup(&mut self, _: &Renderer, _: &Frame, _: &mut PassBuilder) {
+            unreachable!()
+        }
+
+        fn execute(&mut self, _: &mut Renderer, _: &Frame, _: &GraphResources) {
+            unreachable!()
+        }
+    }
+
+    const HDR: TargetDesc = TargetDesc {
+        width: 64,
+        height: 32,
+        format: TargetFormat::Rgba16F,
+    };
+
+    /// Graph of passes named `names`, importing the output, compiled with `declared`
+    /// (one builder per pass)
+    fn compile(
+        names: &[&'static str],
+        declared: &[PassBuilder],
+    ) -> Result<(Vec<&'static str>, Plan), String> {
+        let mut graph = RenderGraph::default();
+        for &name in names {
+            graph.add_pass(Named(name));
+        }
+        graph.import(OUTPUT, None);
+        let plan = graph.compile(declared)?;
+        let order = plan.order.iter().map(|&i| names[i]).collect();
+        Ok((order, plan))
+    }
+
+    fn pass(declare: impl FnOnce(&mut PassBuilder)) -> PassBuilder {
+        let mut builder = PassBuilder::default();
+        declare(&mut builder);
+        builder
+    }
+
+    fn slot_of(plan: &Plan, resource: &str) -> usize {
+        plan.slots
+            .iter()
+            .find(|(name, _)| name == resource)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn writer_runs_before_modifiers_before_readers() {
+        let declared = [
+            pass(|b| {
+                b.read("scene");
+                b.write(OUTPUT);
+            }),
+            pass(|b| b.modify("scene")),
+            pass(|b| b.modify("scene")),
+            pass(|b| b.create("scene", HDR)),
+        ];
+        let (order, _) = compile(&["resolve", "decals", "fog", "scene"], &declared).unwrap();
+        assert_eq!(order, ["scene", "decals", "fog", "resolve"]);
+    }
+
+    #[test]
+    fn passes_that_reach_no_import_are_culled() {
+        let declared = [
+            pass(|b| b.create("scene", HDR)),
+            pass(|b| {
+                b.read("scene");
+                b.create("debug", HDR);
+            }),
+            pass(|b| b.read("debug")),
+            pass(|b| {
+                b.read("scene");
+                b.write(OUTPUT);
+            }),
+        ];
+        let (order, plan) =
+            compile(&["scene", "debug", "debug_view", "resolve"], &declared).unwrap();
+        assert_eq!(order, ["scene", "resolve"]);
+        assert!(plan.slots.iter().all(|(name, _)| name != "debug"));
+    }
+
+    #[test]
+    fn transients_whose_lifetimes_dont_overlap_share_a_slot() {
+        let declared = [
+            pass(|b| b.create("a", HDR)),
+            pass(|b| {
+                b.read("a");
+                b.create("b", HDR);
+            }),
+            pass(|b| {
+                b.read("b");
+                b.create("c", HDR);
+            }),
+            pass(|b| {
+                b.read("c");
+                b.create(
+                    "d",
+                    TargetDesc {
+                        format: TargetFormat::Rgba8,
+                        ..HDR
+                    },
+                );
+            }),
+            pass(|b| {
+                b.read("d");
+                b.write(OUTPUT);
+            }),
+        ];
+        let (_, plan) = compile(&["a", "b", "c", "d", "out"], &declared).unwrap();
+
+        // a is done once b is written, so c can take its slot; b and d can't share either
+        assert_eq!(slot_of(&plan, "a"), slot_of(&plan, "c"));
+        assert_ne!(slot_of(&plan, "a"), slot_of(&plan, "b"));
+        assert_ne!(slot_of(&plan, "b"), slot_of(&plan, "c"));
+        assert_ne!(slot_of(&plan, "d"), slot_of(&plan, "b"));
+        assert_eq!(plan.slot_descs.len(), 3);
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let declared = [
+            pass(|b| {
+                b.read("pong");
+                b.create("ping", HDR);
+            }),
+            pass(|b| {
+                b.read("ping");
+                b.create("pong", HDR);
+            }),
+            pass(|b| {
+                b.read("pong");
+                b.write(OUTPUT);
+            }),
+        ];
+        let error = compile(&["ping", "pong", "out"], &declared).unwrap_err();
+        assert!(error.contains("ping, pong"), "{}", error);
+    }
+
+    #[test]
+    fn conflicting_declarations_are_errors() {
+        let twice = [pass(|b| b.create("a", HDR)), pass(|b| b.create("a", HDR))];
+        assert!(compile(&["x", "y"], &twice).is_err());
+
+        let imported = [pass(|b| b.create(OUTPUT, HDR))];
+        assert!(compile(&["x"], &imported).is_err());
+
+        let unknown = [pass(|b| {
+            b.read("missing");
+            b.write(OUTPUT);
+        })];
+        assert!(compile(&["x"], &unknown).is_err());
+    }
+
+    #[test]
+    fn topological_order_prefers_the_earliest_added() {
+        let edges = vec![
+            BTreeSet::new(),
+            BTreeSet::from([0]),
+            BTreeSet::new(),
+            BTreeSet::from([1]),
+        ];
+        assert_eq!(topological_order(&edges, &[true; 4]), Ok(vec![2, 3, 1, 0]));
+        assert_eq!(
+            topological_order(&edges, &[true, true, false, false]),
+            Ok(vec![1, 0])
+        );
+
+        let cycle = vec![BTreeSet::from([1]), BTreeSet::from([0]), BTreeSet::new()];
+        assert_eq!(topological_order(&cycle, &[true; 3]), Err(vec![0, 1]));
+    }
+}
@@ -0,0 +1,349 @@
+//! HDR - Automatic exposure and the tone mapping resolve
+//!
+//! The main passes render linear radiance into an RGBA16F target (the render graph's scene
+//! color). For automatic exposure,
+//! the log luminance of the frame is drawn into a small mipmapped texture whose last mip
+//! holds the log-average, and a 1x1 exposure value is eased towards `key / average` on the
+//! GPU (ping-ponging between two targets, so nothing is read back). The tone mapping pass
+//! then exposes the scene, applies the curve and encodes gamma into the output.
+
+use super::target::Viewport;
+use crate::assets::paths::{names, shaders};
+use crate::assets::AssetManager;
+use crate::config::rendering as render_cfg;
+use crate::shaders::Shader;
+use std::rc::Rc;
+
+/// Curve compressing exposed scene values into the displayable range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    /// No curve; values above 1.0 clip (or are kept by float outputs)
+    Linear,
+    Reinhard,
+    /// Fit of the ACES filmic reference transform
+    #[default]
+    Aces,
+    /// Hable's Uncharted 2 curve
+    Filmic,
+}
+
+impl ToneMapOperator {
+    /// The next operator, wrapping around (for cycling through them)
+    pub fn next(self) -> Self {
+        match self {
+            ToneMapOperator::Linear => ToneMapOperator::Reinhard,
+            ToneMapOperator::Reinhard => ToneMapOperator::Aces,
+            ToneMapOperator::Aces => ToneMapOperator::Filmic,
+            ToneMapOperator::Filmic => ToneMapOperator::Linear,
+        }
+    }
+
+    fn shader_index(self) -> i32 {
+        match self {
+            ToneMapOperator::Linear => 0,
+            ToneMapOperator::Reinhard => 1,
+            ToneMapOperator::Aces => 2,
+            ToneMapOperator::Filmic => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapSettings {
+    pub operator: ToneMapOperator,
+    /// Exposure multiplier, used while `auto_exposure` is off
+    pub exposure: f32,
+    /// Derive the exposure from the average scene luminance instead
+    pub auto_exposure: bool,
+    /// Middle grey the average luminance is exposed to
+    pub key: f32,
+    /// Range automatic exposure stays within
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    /// How quickly automatic exposure follows the scene (per second)
+    pub adaptation_speed: f32,
+    /// Display gamma the output is encoded for (1.0 keeps it linear)
+    pub gamma: f32,
+}
+
+impl Default for ToneMapSettings {
+    fn default() -> Self {
+        Self {
+            operator: ToneMapOperator::default(),
+            exposure: render_cfg::EXPOSURE,
+            auto_exposure: render_cfg::AUTO_EXPOSURE,
+            key: render_cfg::EXPOSURE_KEY,
+            min_exposure: render_cfg::MIN_EXPOSURE,
+            max_exposure: render_cfg::MAX_EXPOSURE,
+            adaptation_speed: render_cfg::EXPOSURE_ADAPTATION_SPEED,
+            gamma: render_cfg::GAMMA,
+        }
+    }
+}
+
+impl ToneMapSettings {
+    /// Scene values written as they are (for float outputs such as EXR)
+    pub fn passthrough() -> Self {
+        Self {
+            operator: ToneMapOperator::Linear,
+            exposure: 1.0,
+            auto_exposure: false,
+            gamma: 1.0,
+            ..Self::default()
+        }
+    }
+}
+
+/// Single-channel float framebuffer (luminance and exposure)
+struct FloatTarget {
+    fbo: u32,
+    texture: u32,
+    size: u32,
+}
+
+impl FloatTarget {
+    fn new(size: u32, internal_format: gl::types::GLenum, mipmapped: bool) -> Result<Self, String> {
+        let mut target = Self {
+            fbo: 0,
+            texture: 0,
+            size,
+        };
+        let status;
+        unsafe {
+            gl::GenTextures(1, &mut target.texture);
+            gl::BindTexture(gl::TEXTURE_2D, target.texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as i32,
+                size as i32,
+                size as i32,
+                0,
+                gl::RED,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+            let min_filter = if mipmapped {
+                gl::NEAREST_MIPMAP_NEAREST
+            } else {
+                gl::NEAREST
+            };
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            if mipmapped {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+
+            gl::GenFramebuffers(1, &mut target.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                target.texture,
+                0,
+            );
+            status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "Float framebuffer {}x{} is incomplete: 0x{:X}",
+                size, size, status
+            ));
+        }
+        Ok(target)
+    }
+
+    fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.size as i32, self.size as i32);
+        }
+    }
+
+    fn bind_texture(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        }
+    }
+}
+
+impl Drop for FloatTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/// Tone mapping passes of the HDR pipeline
+pub struct HdrPipeline {
+    tonemap_shader: Rc<Shader>,
+    luminance_shader: Rc<Shader>,
+    exposure_shader: Rc<Shader>,
+    /// Created on first use
+    luminance: Option<FloatTarget>,
+    /// Exposure of the previous and the current frame, swapped every frame
+    exposure: Vec<FloatTarget>,
+    current: usize,
+    /// Whether `exposure[current]` holds a measured value to adapt from
+    has_history: bool,
+    /// Attribute-less VAO for the fullscreen triangle
+    fullscreen_vao: u32,
+}
+
+impl HdrPipeline {
+    pub fn new(
+        tonemap_shader: Rc<Shader>,
+        luminance_shader: Rc<Shader>,
+        exposure_shader: Rc<Shader>,
+    ) -> Self {
+        let mut fullscreen_vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut fullscreen_vao);
+        }
+        Self {
+            tonemap_shader,
+            luminance_shader,
+            exposure_shader,
+            luminance: None,
+            exposure: Vec::new(),
+            current: 0,
+            has_history: false,
+            fullscreen_vao,
+        }
+    }
+
+    /// Load the resolve shaders through `assets`
+    pub fn load(assets: &mut AssetManager) -> Self {
+        let tonemap_shader = assets.load_shader(
+            names::SHADER_TONEMAP,
+            shaders::FULLSCREEN_VERT,
+            shaders::TONEMAP_FRAG,
+        );
+        let luminance_shader = assets.load_shader(
+            names::SHADER_LUMINANCE,
+            shaders::FULLSCREEN_VERT,
+            shaders::LUMINANCE_FRAG,
+        );
+        let exposure_shader = assets.load_shader(
+            names::SHADER_EXPOSURE,
+            shaders::FULLSCREEN_VERT,
+            shaders::EXPOSURE_FRAG,
+        );
+        Self::new(tonemap_shader, luminance_shader, exposure_shader)
+    }
+
+    /// Create the luminance and exposure targets the resolve needs
+    pub fn prepare(&mut self) -> Result<(), String> {
+        if self.luminance.is_none() {
+            let size = render_cfg::LUMINANCE_SIZE;
+            self.luminance = Some(FloatTarget::new(size, gl::R16F, true)?);
+            self.exposure = vec![
+                FloatTarget::new(1, gl::R32F, false)?,
+                FloatTarget::new(1, gl::R32F, false)?,
+            ];
+            self.has_history = false;
+        }
+        Ok(())
+    }
+
+    /// Expose and tone map `scene_color` (the scene target, or an HDR post-process result
+    /// of the same size) into `output`. `delta_time` is the time the frame covers, for
+    /// exposure adaptation.
+    pub fn resolve(
+        &mut self,
+        scene_color: u32,
+        output: &Viewport,
+        settings: &ToneMapSettings,
+        delta_time: f32,
+    ) {
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.fullscreen_vao);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, scene_color);
+        }
+
+        if settings.auto_exposure {
+            self.measure_exposure(settings, delta_time);
+        } else {
+            // Adapt from the measured value again once re-enabled
+            self.has_history = false;
+        }
+
+        output.bind();
+        let shader = &self.tonemap_shader;
+        shader.use_program();
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, scene_color);
+        }
+        shader.set_int("hdrColor", 0);
+        if let Some(exposure) = self.exposure.get(self.current) {
+            exposure.bind_texture(1);
+            shader.set_int("exposureMap", 1);
+        }
+        shader.set_int("autoExposure", if settings.auto_exposure { 1 } else { 0 });
+        shader.set_float("exposure", settings.exposure);
+        shader.set_int("toneMapOperator", settings.operator.shader_index());
+        shader.set_float("gamma", settings.gamma);
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+
+    /// Average the scene luminance (scene color bound to unit 0) and write this frame's
+    /// exposure into the other exposure target
+    fn measure_exposure(&mut self, settings: &ToneMapSettings, delta_time: f32) {
+        let Some(luminance) = &self.luminance else {
+            return;
+        };
+
+        luminance.bind();
+        self.luminance_shader.use_program();
+        self.luminance_shader.set_int("hdrColor", 0);
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            luminance.bind_texture(1);
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
+
+        let previous = self.current;
+        self.current = 1 - self.current;
+        self.exposure[self.current].bind();
+        self.exposure[previous].bind_texture(2);
+
+        let shader = &self.exposure_shader;
+        shader.use_program();
+        shader.set_int("luminance", 1);
+        shader.set_float("luminanceLod", (luminance.size as f32).log2());
+        shader.set_int("previousExposure", 2);
+        shader.set_int("hasHistory", if self.has_history { 1 } else { 0 });
+        shader.set_float(
+            "adaptation",
+            1.0 - (-delta_time * settings.adaptation_speed).exp(),
+        );
+        shader.set_float("key", settings.key);
+        shader.set_float("minExposure", settings.min_exposure);
+        shader.set_float("maxExposure", settings.max_exposure);
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+        self.has_history = true;
+    }
+}
+
+impl Drop for HdrPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.fullscreen_vao);
+        }
+    }
+}
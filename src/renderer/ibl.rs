@@ -0,0 +1,561 @@
+//! Image-Based Lighting - Irradiance, prefiltered specular and BRDF maps baked from a cube map
+//!
+//! The diffuse irradiance map is the environment convolved with a cosine lobe. The
+//! prefiltered map holds the environment convolved with the GGX lobe, one mip level per
+//! roughness, and the BRDF lookup table the scale and bias that complete the specular
+//! term (the split-sum approximation). The maps are baked once on the GPU, read back and
+//! cached under `cache::IBL_DIR`, keyed by the source file and the bake settings, so later
+//! startups only upload them.
+
+use crate::assets::paths::{cache, names, shaders};
+use crate::assets::AssetManager;
+use crate::config::rendering as render_cfg;
+use crate::config::rendering::texture_units;
+use crate::primitives::Skybox;
+use crate::shaders::{CubeMap, Shader};
+use glam::{Mat4, Vec3};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, UNIX_EPOCH};
+
+/// Start of every cache file; bump it when the layout or the bake shaders change
+const CACHE_MAGIC: &[u8; 4] = b"IBL1";
+
+/// Sizes and sample counts of a bake (part of the cache key)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BakeSettings {
+    irradiance_size: u32,
+    prefiltered_size: u32,
+    prefiltered_levels: u32,
+    brdf_lut_size: u32,
+    samples: u32,
+}
+
+impl BakeSettings {
+    fn from_config() -> Self {
+        Self {
+            irradiance_size: render_cfg::IBL_IRRADIANCE_SIZE,
+            prefiltered_size: render_cfg::IBL_PREFILTERED_SIZE,
+            prefiltered_levels: render_cfg::IBL_PREFILTERED_LEVELS,
+            brdf_lut_size: render_cfg::IBL_BRDF_LUT_SIZE,
+            samples: render_cfg::IBL_SAMPLES,
+        }
+    }
+
+    fn to_words(self) -> [u32; 5] {
+        [
+            self.irradiance_size,
+            self.prefiltered_size,
+            self.prefiltered_levels,
+            self.brdf_lut_size,
+            self.samples,
+        ]
+    }
+}
+
+/// Baked lighting of one environment cube map
+pub struct Environment {
+    /// RGBA16F cube maps and the RG16F lookup table
+    pub irradiance: u32,
+    pub prefiltered: u32,
+    pub brdf_lut: u32,
+    /// Mip levels of `prefiltered`, from roughness 0 to 1
+    pub prefiltered_levels: u32,
+}
+
+impl Environment {
+    /// Maps for `cubemap`, loaded from `source_path` (the file it was loaded from), read
+    /// from the cache when they were baked before
+    pub fn load_or_bake(
+        assets: &mut AssetManager,
+        cubemap: &CubeMap,
+        source_path: &str,
+    ) -> Result<Self, String> {
+        let settings = BakeSettings::from_config();
+        let cache_path = cache_path(source_path, settings);
+
+        if let Some(path) = &cache_path {
+            if path.exists() {
+                match read_cache(path, settings) {
+                    Ok(environment) => {
+                        println!("Loaded cached environment maps: {}", path.display());
+                        return Ok(environment);
+                    }
+                    Err(e) => eprintln!("{}; baking again", e),
+                }
+            }
+        }
+
+        let start = Instant::now();
+        let environment = bake(assets, cubemap, settings)?;
+        println!(
+            "Baked environment maps for {} in {:.0} ms",
+            source_path,
+            start.elapsed().as_secs_f64() * 1000.0
+        );
+
+        if let Some(path) = &cache_path {
+            match write_cache(path, &environment, settings) {
+                Ok(()) => println!("Cached environment maps: {}", path.display()),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        Ok(environment)
+    }
+
+    /// Bind the maps and set the environment uniforms of the lit shaders
+    pub fn apply_to_shader(&self, shader: &Shader, intensity: f32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + texture_units::IRRADIANCE);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.irradiance);
+            gl::ActiveTexture(gl::TEXTURE0 + texture_units::PREFILTERED);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.prefiltered);
+            gl::ActiveTexture(gl::TEXTURE0 + texture_units::BRDF_LUT);
+            gl::BindTexture(gl::TEXTURE_2D, self.brdf_lut);
+        }
+        set_samplers(shader);
+        shader.set_int("hasEnvironment", 1);
+        shader.set_float("prefilteredMaxLod", (self.prefiltered_levels - 1) as f32);
+        shader.set_float("environmentIntensity", intensity);
+    }
+
+    /// Turn the environment off in a lit shader (its ambient falls back to the flat term)
+    pub fn disable_in_shader(shader: &Shader) {
+        // Still point the samplers at their own units, so the cube samplers never share
+        // a unit with a 2D one
+        set_samplers(shader);
+        shader.set_int("hasEnvironment", 0);
+    }
+}
+
+impl Drop for Environment {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.irradiance);
+            gl::DeleteTextures(1, &self.prefiltered);
+            gl::DeleteTextures(1, &self.brdf_lut);
+        }
+    }
+}
+
+fn set_samplers(shader: &Shader) {
+    shader.set_int("irradianceMap", texture_units::IRRADIANCE as i32);
+    shader.set_int("prefilteredMap", texture_units::PREFILTERED as i32);
+    shader.set_int("brdfLUT", texture_units::BRDF_LUT as i32);
+}
+
+/// Render the three maps from `cubemap`
+fn bake(
+    assets: &mut AssetManager,
+    cubemap: &CubeMap,
+    settings: BakeSettings,
+) -> Result<Environment, String> {
+    let irradiance_shader = assets.load_shader(
+        names::SHADER_IRRADIANCE,
+        shaders::SKYBOX_VERT,
+        shaders::IRRADIANCE_FRAG,
+    );
+    let prefilter_shader = assets.load_shader(
+        names::SHADER_PREFILTER,
+        shaders::SKYBOX_VERT,
+        shaders::PREFILTER_FRAG,
+    );
+    let brdf_shader = assets.load_shader(
+        names::SHADER_BRDF_LUT,
+        shaders::FULLSCREEN_VERT,
+        shaders::BRDF_LUT_FRAG,
+    );
+
+    let environment = Environment {
+        irradiance: create_cube(settings.irradiance_size, 1, None),
+        prefiltered: create_cube(settings.prefiltered_size, settings.prefiltered_levels, None),
+        brdf_lut: create_lut(settings.brdf_lut_size, None),
+        prefiltered_levels: settings.prefiltered_levels,
+    };
+
+    let cube = Skybox::new();
+    let projection = Mat4::perspective_rh_gl(90f32.to_radians(), 1.0, 0.1, 10.0);
+    let mut fbo = 0;
+    let mut fullscreen_vao = 0;
+    unsafe {
+        gl::GenFramebuffers(1, &mut fbo);
+        gl::GenVertexArrays(1, &mut fullscreen_vao);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+        gl::Disable(gl::DEPTH_TEST);
+
+        // Mips of the source, which the convolutions read from to avoid aliasing
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, cubemap.id);
+        gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+        gl::TexParameteri(
+            gl::TEXTURE_CUBE_MAP,
+            gl::TEXTURE_MIN_FILTER,
+            gl::LINEAR_MIPMAP_LINEAR as i32,
+        );
+    }
+
+    // Diffuse irradiance
+    irradiance_shader.use_program();
+    irradiance_shader.set_int("environmentMap", 0);
+    irradiance_shader.set_mat4("projection", &projection.to_cols_array());
+    let faces = render_faces(
+        &irradiance_shader,
+        &cube,
+        environment.irradiance,
+        0,
+        settings.irradiance_size,
+    );
+
+    // Specular, one roughness per mip
+    prefilter_shader.use_program();
+    prefilter_shader.set_int("environmentMap", 0);
+    prefilter_shader.set_int("sampleCount", settings.samples as i32);
+    prefilter_shader.set_mat4("projection", &projection.to_cols_array());
+    let faces = faces.and_then(|()| {
+        for level in 0..settings.prefiltered_levels {
+            let roughness = level as f32 / (settings.prefiltered_levels - 1).max(1) as f32;
+            prefilter_shader.set_float("roughness", roughness);
+            render_faces(
+                &prefilter_shader,
+                &cube,
+                environment.prefiltered,
+                level,
+                settings.prefiltered_size >> level,
+            )?;
+        }
+        Ok(())
+    });
+
+    // BRDF lookup table
+    let status = unsafe {
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            environment.brdf_lut,
+            0,
+        );
+        gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+    };
+    if status == gl::FRAMEBUFFER_COMPLETE {
+        brdf_shader.use_program();
+        brdf_shader.set_int("sampleCount", settings.samples as i32);
+        let size = settings.brdf_lut_size as i32;
+        unsafe {
+            gl::Viewport(0, 0, size, size);
+            gl::BindVertexArray(fullscreen_vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+        }
+    }
+
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::DeleteFramebuffers(1, &fbo);
+        gl::DeleteVertexArrays(1, &fullscreen_vao);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+
+    faces?;
+    if status != gl::FRAMEBUFFER_COMPLETE {
+        return Err(format!(
+            "BRDF lookup table target is incomplete: 0x{:X}",
+            status
+        ));
+    }
+    Ok(environment)
+}
+
+/// Draw the cube once per face of `level` of `texture` (attached to the bound framebuffer),
+/// with `shader` in use
+fn render_faces(
+    shader: &Shader,
+    cube: &Skybox,
+    texture: u32,
+    level: u32,
+    size: u32,
+) -> Result<(), String> {
+    let eye = Vec3::ZERO;
+    let views = [
+        Mat4::look_at_rh(eye, Vec3::X, Vec3::NEG_Y),
+        Mat4::look_at_rh(eye, Vec3::NEG_X, Vec3::NEG_Y),
+        Mat4::look_at_rh(eye, Vec3::Y, Vec3::Z),
+        Mat4::look_at_rh(eye, Vec3::NEG_Y, Vec3::NEG_Z),
+        Mat4::look_at_rh(eye, Vec3::Z, Vec3::NEG_Y),
+        Mat4::look_at_rh(eye, Vec3::NEG_Z, Vec3::NEG_Y),
+    ];
+
+    unsafe {
+        gl::Viewport(0, 0, size as i32, size as i32);
+    }
+    for (face, view) in views.iter().enumerate() {
+        let status = unsafe {
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                texture,
+                level as i32,
+            );
+            gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "Environment map face {} (mip {}) is incomplete: 0x{:X}",
+                face, level, status
+            ));
+        }
+        shader.set_mat4("view", &view.to_cols_array());
+        cube.draw();
+    }
+    Ok(())
+}
+
+/// Half floats in a cube map of `levels` mips, four channels per texel
+fn cube_len(size: u32, levels: u32) -> usize {
+    (0..levels)
+        .map(|level| 6 * 4 * ((size >> level) as usize).pow(2))
+        .sum()
+}
+
+fn lut_len(size: u32) -> usize {
+    2 * (size as usize).pow(2)
+}
+
+/// RGBA16F cube map, filled from `data` (mip by mip, face by face) if given
+fn create_cube(size: u32, levels: u32, data: Option<&[u16]>) -> u32 {
+    let mut texture = 0;
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+        let mut offset = 0;
+        for level in 0..levels {
+            let level_size = size >> level;
+            for face in 0..6 {
+                let pixels = data.map_or(std::ptr::null(), |data| {
+                    data[offset..].as_ptr() as *const std::ffi::c_void
+                });
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    level as i32,
+                    gl::RGBA16F as i32,
+                    level_size as i32,
+                    level_size as i32,
+                    0,
+                    gl::RGBA,
+                    gl::HALF_FLOAT,
+                    pixels,
+                );
+                offset += 4 * (level_size * level_size) as usize;
+            }
+        }
+        let min_filter = if levels > 1 {
+            gl::LINEAR_MIPMAP_LINEAR
+        } else {
+            gl::LINEAR
+        };
+        gl::TexParameteri(
+            gl::TEXTURE_CUBE_MAP,
+            gl::TEXTURE_MIN_FILTER,
+            min_filter as i32,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_CUBE_MAP,
+            gl::TEXTURE_MAG_FILTER,
+            gl::LINEAR as i32,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_CUBE_MAP,
+            gl::TEXTURE_MAX_LEVEL,
+            levels as i32 - 1,
+        );
+        for wrap in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, wrap, gl::CLAMP_TO_EDGE as i32);
+        }
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+    }
+    texture
+}
+
+/// RG16F lookup table, filled from `data` if given
+fn create_lut(size: u32, data: Option<&[u16]>) -> u32 {
+    let mut texture = 0;
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RG16F as i32,
+            size as i32,
+            size as i32,
+            0,
+            gl::RG,
+            gl::HALF_FLOAT,
+            data.map_or(std::ptr::null(), |data| data.as_ptr() as *const _),
+        );
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+    texture
+}
+
+/// Cache file for `source_path` baked with `settings`, or `None` if the source isn't a
+/// readable file (then nothing is cached)
+fn cache_path(source_path: &str, settings: BakeSettings) -> Option<PathBuf> {
+    let metadata = std::fs::metadata(source_path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_secs());
+
+    // FNV-1a, which stays the same across builds (unlike the std hasher)
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    feed(CACHE_MAGIC);
+    feed(source_path.as_bytes());
+    feed(&metadata.len().to_le_bytes());
+    feed(&modified.to_le_bytes());
+    for word in settings.to_words() {
+        feed(&word.to_le_bytes());
+    }
+
+    let stem = Path::new(source_path).file_stem()?.to_string_lossy();
+    Some(Path::new(cache::IBL_DIR).join(format!("{}-{:016x}.ibl", stem, hash)))
+}
+
+/// Write the maps as: magic, the settings, then the half floats of the irradiance map,
+/// the prefiltered map and the lookup table, all little-endian
+fn write_cache(
+    path: &Path,
+    environment: &Environment,
+    settings: BakeSettings,
+) -> Result<(), String> {
+    let mut irradiance = vec![0u16; cube_len(settings.irradiance_size, 1)];
+    let mut prefiltered =
+        vec![0u16; cube_len(settings.prefiltered_size, settings.prefiltered_levels)];
+    let mut lut = vec![0u16; lut_len(settings.brdf_lut_size)];
+    read_cube(
+        environment.irradiance,
+        settings.irradiance_size,
+        1,
+        &mut irradiance,
+    );
+    read_cube(
+        environment.prefiltered,
+        settings.prefiltered_size,
+        settings.prefiltered_levels,
+        &mut prefiltered,
+    );
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, environment.brdf_lut);
+        gl::GetTexImage(
+            gl::TEXTURE_2D,
+            0,
+            gl::RG,
+            gl::HALF_FLOAT,
+            lut.as_mut_ptr() as *mut _,
+        );
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+
+    let mut bytes = Vec::with_capacity(24 + 2 * (irradiance.len() + prefiltered.len() + lut.len()));
+    bytes.extend_from_slice(CACHE_MAGIC);
+    for word in settings.to_words() {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    for value in irradiance.iter().chain(&prefiltered).chain(&lut) {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))?;
+    }
+    std::fs::write(path, bytes).map_err(|e| {
+        format!(
+            "Failed to write environment cache '{}': {}",
+            path.display(),
+            e
+        )
+    })
+}
+
+/// Copy every mip and face of a cube map into `data` (laid out as in `create_cube`)
+fn read_cube(texture: u32, size: u32, levels: u32, data: &mut [u16]) {
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+        let mut offset = 0;
+        for level in 0..levels {
+            let level_size = size >> level;
+            for face in 0..6 {
+                gl::GetTexImage(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    level as i32,
+                    gl::RGBA,
+                    gl::HALF_FLOAT,
+                    data[offset..].as_mut_ptr() as *mut _,
+                );
+                offset += 4 * (level_size * level_size) as usize;
+            }
+        }
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+    }
+}
+
+fn read_cache(path: &Path, settings: BakeSettings) -> Result<Environment, String> {
+    let bytes = std::fs::read(path).map_err(|e| {
+        format!(
+            "Failed to read environment cache '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+
+    let header: Vec<u8> = CACHE_MAGIC
+        .iter()
+        .copied()
+        .chain(
+            settings
+                .to_words()
+                .iter()
+                .flat_map(|word| word.to_le_bytes()),
+        )
+        .collect();
+    let irradiance_len = cube_len(settings.irradiance_size, 1);
+    let prefiltered_len = cube_len(settings.prefiltered_size, settings.prefiltered_levels);
+    let lut_len = lut_len(settings.brdf_lut_size);
+    let expected = header.len() + 2 * (irradiance_len + prefiltered_len + lut_len);
+    if !bytes.starts_with(&header) || bytes.len() != expected {
+        return Err(format!(
+            "Environment cache '{}' doesn't match the current bake settings",
+            path.display()
+        ));
+    }
+
+    let values: Vec<u16> = bytes[header.len()..]
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    let (irradiance, rest) = values.split_at(irradiance_len);
+    let (prefiltered, lut) = rest.split_at(prefiltered_len);
+
+    Ok(Environment {
+        irradiance: create_cube(settings.irradiance_size, 1, Some(irradiance)),
+        prefiltered: create_cube(
+            settings.prefiltered_size,
+            settings.prefiltered_levels,
+            Some(prefiltered),
+        ),
+        brdf_lut: create_lut(settings.brdf_lut_size, Some(lut)),
+        prefiltered_levels: settings.prefiltered_levels,
+    })
+}
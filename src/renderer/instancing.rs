@@ -0,0 +1,133 @@
+//! Instancing - Per-instance model matrices and batching of objects that share GPU data
+//!
+//! Every mesh draw in the scene passes is instanced; a lone object is a batch of one.
+//! The lit and depth vertex shaders read the model matrix from `INSTANCE_ATTRIBUTE`.
+
+use crate::scene::material::Material;
+use crate::scene::object::Renderable;
+use glam::Mat4;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// First attribute location of the per-instance `mat4` (one location per column)
+pub const INSTANCE_ATTRIBUTE: u32 = 3;
+
+/// GPU buffer of model matrices, reused by every instanced draw
+pub struct InstanceBuffer {
+    vbo: u32,
+    /// Matrices the buffer has room for
+    capacity: usize,
+}
+
+impl InstanceBuffer {
+    pub fn new() -> Self {
+        let mut vbo = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut vbo);
+        }
+        Self { vbo, capacity: 0 }
+    }
+
+    /// Replace the contents with `models`, growing the buffer when needed
+    pub fn upload(&mut self, models: &[Mat4]) {
+        let size = std::mem::size_of_val(models) as isize;
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            if models.len() > self.capacity {
+                self.capacity = models.len().next_power_of_two();
+            }
+            // Orphan the old storage so this doesn't wait on draws still reading it
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (self.capacity * std::mem::size_of::<Mat4>()) as isize,
+                std::ptr::null(),
+                gl::STREAM_DRAW,
+            );
+            gl::BufferSubData(gl::ARRAY_BUFFER, 0, size, models.as_ptr() as *const _);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    /// Point the per-instance attributes of the currently bound VAO at this buffer
+    pub fn bind_attributes(&self) {
+        let stride = std::mem::size_of::<Mat4>() as i32;
+        let column = 4 * std::mem::size_of::<f32>();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            for i in 0..4 {
+                let location = INSTANCE_ATTRIBUTE + i;
+                gl::VertexAttribPointer(
+                    location,
+                    4,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    (i as usize * column) as *const _,
+                );
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribDivisor(location, 1);
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+}
+
+impl Drop for InstanceBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vbo);
+        }
+    }
+}
+
+/// Objects drawn with one instanced call
+pub struct Batch {
+    pub renderable: Rc<dyn Renderable>,
+    /// Part of the renderable to draw (see `Renderable::part_count`); `None` draws all
+    pub part: Option<usize>,
+    /// `None` in depth passes, which only need the geometry
+    pub material: Option<Rc<dyn Material>>,
+    pub models: Vec<Mat4>,
+}
+
+/// Groups objects by the `Rc` they share, keeping the order batches were first seen
+#[derive(Default)]
+pub struct Batcher {
+    batches: Vec<Batch>,
+    index: HashMap<(*const (), Option<usize>, *const ()), usize>,
+}
+
+impl Batcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(
+        &mut self,
+        renderable: &Rc<dyn Renderable>,
+        part: Option<usize>,
+        material: Option<&Rc<dyn Material>>,
+        model: Mat4,
+    ) {
+        let key = (
+            Rc::as_ptr(renderable) as *const (),
+            part,
+            material.map_or(std::ptr::null(), |m| Rc::as_ptr(m) as *const ()),
+        );
+        let batches = &mut self.batches;
+        let index = *self.index.entry(key).or_insert_with(|| {
+            batches.push(Batch {
+                renderable: renderable.clone(),
+                part,
+                material: material.cloned(),
+                models: Vec::new(),
+            });
+            batches.len() - 1
+        });
+        self.batches[index].models.push(model);
+    }
+
+    pub fn finish(self) -> Vec<Batch> {
+        self.batches
+    }
+}
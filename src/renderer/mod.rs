@@ -1,12 +1,136 @@
+pub mod clusters;
+pub mod deferred;
+pub mod graph;
+pub mod hdr;
+pub mod ibl;
+pub mod instancing;
+pub mod passes;
+pub mod post;
+pub mod ssao;
+pub mod target;
+pub mod vertex;
+
 use crate::config::{rendering as render_cfg, window as win_cfg};
-use crate::light::{DirectionalLight, PointLight, SpotLight};
+use crate::light::components::ShadowSettings;
+use crate::light::DirectionalLight;
+use crate::math::bounds::Bounds;
+use crate::math::frustum::Frustum;
 use crate::primitives::Skybox;
+use crate::scene::components::{GlobalTransform, MeshMaterials, WorldBounds};
 use crate::scene::context::RenderContext;
 use crate::scene::manager::Scene;
+use crate::scene::material::{BlendMode, Material};
+use crate::scene::object::Renderable;
+use crate::scene::world::World;
 use crate::shaders::{CubeMap, Shader};
+use crate::shadow::cascades::Cascade;
+use crate::shadow::moments::ShadowMoments;
+use crate::shadow::scheduler::{face_frustums, PointShadowScheduler};
+use crate::shadow::spot::SpotShadowMap;
 use crate::shadow::{PointShadowMap, ShadowMap};
+use clusters::LightClusters;
+use deferred::DeferredRenderer;
 use glam::{Mat4, Vec3};
+use graph::resources::{
+    LIGHT_CLUSTERS, OCCLUSION, OUTPUT, POINT_SHADOWS, SHADOW_CASCADES, SPOT_SHADOWS,
+};
+use graph::RenderGraph;
+use hdr::{HdrPipeline, ToneMapSettings};
+use ibl::Environment;
+use instancing::{Batch, Batcher, InstanceBuffer};
+use post::{PostChain, PostProcessor};
+use ssao::{Ssao, SsaoSettings};
 use std::rc::Rc;
+use target::{RenderTarget, Viewport};
+
+/// How the main pass lights the scene
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RenderPath {
+    /// Every object lit in its own shader, by the lights of its clusters
+    #[default]
+    Forward,
+    /// Lit objects written to a G-buffer, then lit per light in screen space; unlit
+    /// objects and materials without a G-buffer shader are still drawn forward
+    Deferred,
+}
+
+/// Scene and camera of the frame being rendered, shared by the render graph's passes
+pub struct Frame<'a> {
+    pub scene: &'a Scene,
+    pub camera_position: Vec3,
+    pub view: Mat4,
+    pub projection: Mat4,
+    /// The camera's view frustum, for culling
+    pub frustum: Frustum,
+}
+
+/// Per-frame object counts, reset at the start of every `render`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Objects drawn / skipped by the camera frustum
+    pub drawn: u32,
+    pub culled: u32,
+    /// Instanced draw calls the drawn objects were batched into
+    pub draw_calls: u32,
+    /// The same, summed over every shadow pass
+    pub shadow_drawn: u32,
+    pub shadow_culled: u32,
+    pub shadow_draw_calls: u32,
+    /// Point light cube map faces re-rendered
+    pub shadow_faces: u32,
+    /// Point light volumes drawn by the deferred path
+    pub light_volumes: u32,
+}
+
+impl RenderStats {
+    fn add_shadow(&mut self, pass: PassCounts) {
+        self.shadow_drawn += pass.drawn;
+        self.shadow_culled += pass.culled;
+        self.shadow_draw_calls += pass.draw_calls;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PassCounts {
+    drawn: u32,
+    culled: u32,
+    draw_calls: u32,
+}
+
+impl PassCounts {
+    fn merge(self, other: PassCounts) -> PassCounts {
+        PassCounts {
+            drawn: self.drawn + other.drawn,
+            culled: self.culled + other.culled,
+            draw_calls: self.draw_calls + other.draw_calls,
+        }
+    }
+}
+
+/// Which objects a main-pass draw covers, and with which shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjectPass {
+    /// Every object, with its material's own shader
+    Forward,
+    /// Deferred-capable objects, with their G-buffer shader
+    GBuffer,
+    /// The rest, drawn forward over the deferred lighting
+    Overlay,
+}
+
+impl ObjectPass {
+    fn includes(self, material: &dyn Material) -> bool {
+        // Blended materials need what is behind them, which the G-buffer can't provide
+        let deferred = material.is_lit()
+            && material.deferred_shader().is_some()
+            && !material.blend_mode().is_transparent();
+        match self {
+            ObjectPass::Forward => true,
+            ObjectPass::GBuffer => deferred,
+            ObjectPass::Overlay => !deferred,
+        }
+    }
+}
 
 pub struct Renderer {
     pub skybox: Skybox,
@@ -14,73 +138,326 @@ pub struct Renderer {
     pub skybox_cubemap: Rc<CubeMap>,
     pub shadow_map: ShadowMap,
     pub point_shadow_maps: Vec<PointShadowMap>,
-    pub light_space_matrix: Mat4,
+    /// Which point lights get `point_shadow_maps`, and which of their faces to refresh
+    pub point_shadows: PointShadowScheduler,
+    /// Depth layers of the shadow-casting spot lights in view
+    pub spot_shadow_map: SpotShadowMap,
+    /// Directional shadow cascades fitted to the camera this frame
+    pub cascades: Vec<Cascade>,
+    /// Point and spot lights binned over the view frustum this frame
+    pub clusters: LightClusters,
+    /// Passes making up the frame (see `passes` for the built-in ones); add or remove
+    /// passes here
+    pub graph: RenderGraph,
     pub frame_count: u64,
+    /// Where the final image goes (the window by default)
+    pub output: Viewport,
+    /// Counts from the last rendered frame
+    pub stats: RenderStats,
+    pub path: RenderPath,
+    /// Exposure and tone mapping applied when resolving the HDR scene
+    pub tone_mapping: ToneMapSettings,
+    /// Seconds covered by the frame being rendered (for exposure adaptation)
+    pub frame_time: f32,
+    /// Scales the image-based lighting of the environment
+    pub environment_intensity: f32,
+    /// Screen-space effects around tone mapping (needs the HDR pipeline)
+    pub post_process: PostChain,
+    /// Screen-space ambient occlusion applied to the lit shaders' ambient terms
+    pub ambient_occlusion: SsaoSettings,
+    /// Passes of the deferred path; without them it renders forward
+    deferred: Option<DeferredRenderer>,
+    /// Exposure and tone mapping passes; without them the scene renders into an 8-bit
+    /// target copied to `output`
+    pub hdr: Option<HdrPipeline>,
+    /// Shaders running `post_process`
+    pub post: Option<PostProcessor>,
+    /// Irradiance, prefiltered and BRDF maps of the skybox; without them the lit shaders
+    /// use a flat ambient term
+    environment: Option<Environment>,
+    /// Depth prepass and occlusion passes running `ambient_occlusion`
+    pub ssao: Option<Ssao>,
+    /// Moments and blur passes of variance and exponential shadows; without them those
+    /// filters fall back to Poisson PCF
+    shadow_moments: Option<ShadowMoments>,
+    /// Model matrices of the batch being drawn
+    instances: InstanceBuffer,
 }
 
 impl Renderer {
-    pub fn new(skybox_shader: Rc<Shader>, skybox_cubemap: Rc<CubeMap>) -> Self {
+    /// Renderer with `point_shadow_slots` point shadow cube maps (at most
+    /// `MAX_POINT_SHADOWS`; as many as the lit shaders were compiled for)
+    pub fn new(
+        skybox_shader: Rc<Shader>,
+        skybox_cubemap: Rc<CubeMap>,
+        point_shadow_slots: usize,
+    ) -> Self {
         let shadow_map = ShadowMap::new(render_cfg::SHADOW_MAP_SIZE, render_cfg::SHADOW_MAP_SIZE);
 
         let mut point_shadow_maps = Vec::new();
-        for _ in 0..render_cfg::MAX_POINT_LIGHTS {
+        for _ in 0..point_shadow_slots {
             point_shadow_maps.push(PointShadowMap::new(render_cfg::POINT_SHADOW_SIZE));
         }
 
+        let mut graph = RenderGraph::default();
+        passes::add_default_passes(&mut graph);
+
         Self {
             skybox: Skybox::new(),
             skybox_shader,
             skybox_cubemap,
             shadow_map,
             point_shadow_maps,
-            light_space_matrix: Mat4::IDENTITY,
+            point_shadows: PointShadowScheduler::new(point_shadow_slots),
+            spot_shadow_map: SpotShadowMap::new(render_cfg::SPOT_SHADOW_SIZE),
+            cascades: Vec::new(),
+            clusters: LightClusters::new(),
+            graph,
             frame_count: 0,
+            output: Viewport::window(win_cfg::WIDTH, win_cfg::HEIGHT),
+            stats: RenderStats::default(),
+            path: RenderPath::default(),
+            tone_mapping: ToneMapSettings::default(),
+            frame_time: 0.0,
+            environment_intensity: render_cfg::IBL_INTENSITY,
+            post_process: PostChain::default(),
+            ambient_occlusion: SsaoSettings::default(),
+            deferred: None,
+            hdr: None,
+            post: None,
+            environment: None,
+            ssao: None,
+            shadow_moments: None,
+            instances: InstanceBuffer::new(),
         }
     }
 
-    pub fn render(
-        &mut self,
-        scene: &Scene,
-        camera: &crate::camera::OrbitCamera,
-        light: &DirectionalLight,
-        point_lights: &[PointLight],
-        spot_lights: &[SpotLight],
-    ) {
-        // Shadow Passes
-        self.render_shadow_pass(scene, light);
-        self.render_point_shadow_pass(scene, point_lights);
+    pub fn render(&mut self, scene: &Scene, camera: &crate::camera::OrbitCamera) {
+        self.stats = RenderStats::default();
 
-        // Main Render Setup
-        let aspect = win_cfg::WIDTH as f32 / win_cfg::HEIGHT as f32;
-        let projection = camera.projection_matrix(aspect);
+        let projection = camera.projection_matrix(self.output.aspect());
         let view = camera.view_matrix();
+        let frame = Frame {
+            scene,
+            camera_position: camera.position,
+            view,
+            projection,
+            frustum: Frustum::from_matrix(&(projection * view)),
+        };
 
-        unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        // The passes get the renderer, so the graph steps out of it while they run
+        let mut graph = std::mem::take(&mut self.graph);
+        for resource in [
+            SHADOW_CASCADES,
+            POINT_SHADOWS,
+            SPOT_SHADOWS,
+            LIGHT_CLUSTERS,
+            OCCLUSION,
+        ] {
+            graph.import(resource, None);
         }
+        graph.import(OUTPUT, Some(self.output));
+        graph.execute(self, &frame);
+        self.graph = graph;
 
-        // Render Skybox
-        self.render_skybox(&projection, &view);
+        self.frame_count += 1;
+    }
 
-        // Render Scene Objects
+    /// Draw the lit scene and the skybox into `target`, which this clears
+    pub fn render_scene(&mut self, frame: &Frame, target: &Viewport) {
+        let (scene, frustum) = (frame.scene, &frame.frustum);
+        let (projection, view) = (frame.projection, frame.view);
+
+        // Lights, shadows and matrices shared by the main passes
         let context = RenderContext {
             projection,
             view,
-            view_pos: camera.position,
-            light,
-            point_lights,
-            spot_lights,
+            view_pos: frame.camera_position,
+            light: &scene.light,
+            point_lights: &scene.point_lights,
+            clusters: &self.clusters,
             shadow_map: &self.shadow_map,
             point_shadow_maps: &self.point_shadow_maps,
-            far_plane: render_cfg::SHADOW_FAR_PLANE,
-            light_space_matrix: self.light_space_matrix,
+            point_shadows: &self.point_shadows,
+            spot_shadow_map: &self.spot_shadow_map,
+            cascades: &self.cascades,
+            cascade_blend: self.shadow_map.settings.blend,
+            environment: self.environment.as_ref(),
+            environment_intensity: self.environment_intensity,
+            ssao: self
+                .ssao
+                .as_ref()
+                .filter(|_| self.ambient_occlusion.enabled),
+        };
+
+        let deferred = match (self.path, self.deferred.as_mut()) {
+            (RenderPath::Deferred, Some(deferred)) => match deferred.begin_geometry_pass(target) {
+                Ok(()) => Some(&*deferred),
+                Err(e) => {
+                    eprintln!("{}; falling back to forward rendering", e);
+                    self.path = RenderPath::Forward;
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        let pass = if let Some(deferred) = deferred {
+            // Geometry Pass
+            let geometry = render_objects(
+                &scene.world,
+                &context,
+                frustum,
+                &mut self.instances,
+                ObjectPass::GBuffer,
+            );
+
+            // Lighting Passes
+            target.bind();
+            unsafe {
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            }
+            self.stats.light_volumes = deferred.light(&context, target, frustum);
+
+            // Skybox and forward-only objects, depth tested against the G-buffer
+            deferred.blit_depth(target);
+            self.render_skybox(&projection, &view);
+            let overlay = render_objects(
+                &scene.world,
+                &context,
+                frustum,
+                &mut self.instances,
+                ObjectPass::Overlay,
+            );
+            geometry.merge(overlay)
+        } else {
+            // Main Render Setup
+            target.bind();
+            unsafe {
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            }
+
+            // Render Skybox
+            self.render_skybox(&projection, &view);
+
+            render_objects(
+                &scene.world,
+                &context,
+                frustum,
+                &mut self.instances,
+                ObjectPass::Forward,
+            )
         };
+        self.stats.drawn = pass.drawn;
+        self.stats.culled = pass.culled;
+        self.stats.draw_calls = pass.draw_calls;
+    }
 
-        for obj in &scene.objects {
-            obj.render(&context);
+    /// Enabled effects of `post_process` on scene radiance (`hdr`) or on the display image;
+    /// none without the HDR pipeline and post-processor
+    pub fn post_effect_count(&self, hdr: bool) -> usize {
+        if self.hdr.is_none() || self.post.is_none() {
+            return 0;
         }
+        self.post_process.active(hdr).count()
+    }
 
-        self.frame_count += 1;
+    /// Run the HDR effects of `post_process` over `scene` into `result`, alternating with
+    /// `scratch` when more than one runs
+    pub fn apply_hdr_effects(
+        &mut self,
+        scene: &RenderTarget,
+        result: &RenderTarget,
+        scratch: Option<&RenderTarget>,
+    ) {
+        // The passes after this one read `result` even if the effects don't run
+        let Some(post) = self.post.as_mut() else {
+            scene.blit_color(&result.viewport());
+            return;
+        };
+        if let Err(e) = post.apply_hdr(&self.post_process, scene, result, scratch) {
+            eprintln!("{}; disabling post-processing", e);
+            self.post = None;
+            scene.blit_color(&result.viewport());
+        }
+    }
+
+    /// Expose and tone map `scene` into `target` with the HDR pipeline, or copy it there
+    /// without it
+    pub fn tone_map(&mut self, scene: &RenderTarget, target: &Viewport) {
+        let Some(hdr) = self.hdr.as_mut() else {
+            scene.blit_color(target);
+            return;
+        };
+        if let Err(e) = hdr.prepare() {
+            // The next frames render display values straight away
+            eprintln!("{}; rendering without HDR", e);
+            self.hdr = None;
+            scene.blit_color(target);
+            return;
+        }
+        hdr.resolve(
+            scene.color_texture,
+            target,
+            &self.tone_mapping,
+            self.frame_time,
+        );
+    }
+
+    /// Run the display effects of `post_process` over the tone-mapped `input` into `output`,
+    /// alternating with `scratch` when more than one runs. `depth` is the scene depth.
+    pub fn apply_display_effects(
+        &mut self,
+        input: &RenderTarget,
+        scratch: Option<&RenderTarget>,
+        output: &Viewport,
+        depth: u32,
+    ) {
+        let Some(post) = self.post.as_mut() else {
+            input.blit_color(output);
+            return;
+        };
+        if let Err(e) = post.apply_display(&self.post_process, input, scratch, output, depth) {
+            eprintln!("{}; disabling post-processing", e);
+            self.post = None;
+            input.blit_color(output);
+        }
+    }
+
+    /// Enable the deferred path (selected through `path`)
+    pub fn with_deferred(mut self, deferred: DeferredRenderer) -> Self {
+        self.deferred = Some(deferred);
+        self
+    }
+
+    /// Render into a float target and tone map it into `output` (see `tone_mapping`)
+    pub fn with_hdr(mut self, hdr: HdrPipeline) -> Self {
+        self.hdr = Some(hdr);
+        self
+    }
+
+    /// Run `post_process` around tone mapping (only with `with_hdr`)
+    pub fn with_post_processing(mut self, post: PostProcessor) -> Self {
+        self.post = Some(post);
+        self
+    }
+
+    /// Light with the environment's baked maps (see `ibl::Environment::load_or_bake`)
+    pub fn with_environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Compute ambient occlusion from the camera (see `ambient_occlusion`)
+    pub fn with_ssao(mut self, ssao: Ssao) -> Self {
+        self.ssao = Some(ssao);
+        self
+    }
+
+    /// Filter shadow maps into moments for variance and exponential shadows
+    pub fn with_shadow_moments(mut self, moments: ShadowMoments) -> Self {
+        self.shadow_moments = Some(moments);
+        self
     }
 
     fn render_skybox(&self, projection: &Mat4, view: &Mat4) {
@@ -109,36 +486,421 @@ impl Renderer {
         }
     }
 
-    fn render_shadow_pass(&mut self, scene: &Scene, light: &DirectionalLight) {
-        self.light_space_matrix =
-            self.shadow_map
-                .light_space_matrix(light.direction, Vec3::ZERO, 35.0);
+    /// Draw the depth of the opaque and cutout objects in `frustum` from the camera, and
+    /// compute their occlusion at the size of `target`
+    pub fn render_ssao(
+        &mut self,
+        scene: &Scene,
+        target: &Viewport,
+        view: &Mat4,
+        projection: &Mat4,
+        frustum: &Frustum,
+    ) {
+        if !self.ambient_occlusion.enabled {
+            return;
+        }
+        let Some(ssao) = self.ssao.as_mut() else {
+            return;
+        };
+
+        match ssao.begin_depth_pass(target, &(*projection * *view)) {
+            Ok(shader) => {
+                render_depth(&scene.world, shader, &mut self.instances, |bounds| {
+                    frustum.intersects(bounds)
+                });
+            }
+            Err(e) => {
+                eprintln!("{}; disabling ambient occlusion", e);
+                self.ssao = None;
+                return;
+            }
+        }
+        ssao.compute(projection, &self.ambient_occlusion);
+        target.bind();
+    }
+
+    fn render_shadow_pass(
+        &mut self,
+        scene: &Scene,
+        light: &DirectionalLight,
+        view: &Mat4,
+        projection: &Mat4,
+    ) {
+        self.cascades = self.shadow_map.cascades(light.direction, view, projection);
 
         self.shadow_map.begin_pass();
-        self.shadow_map
-            .set_light_space_matrix(&self.light_space_matrix);
+        for (layer, cascade) in self.cascades.iter().enumerate() {
+            self.shadow_map.begin_cascade(layer, cascade);
+
+            // Anything outside the cascade's box can't cast into its layer
+            let light_frustum = Frustum::from_matrix(&cascade.light_space_matrix);
+            let pass = render_depth(
+                &scene.world,
+                &self.shadow_map.shader,
+                &mut self.instances,
+                |bounds| light_frustum.intersects(bounds),
+            );
+            self.stats.add_shadow(pass);
+        }
+        self.shadow_map.end_pass(&self.output);
+
+        // Variance and exponential filters read blurred moments of the cascades
+        self.shadow_map.shadow = light.shadow;
+        let layers = self.cascades.len();
+        let shadow_map = &mut self.shadow_map;
+        let shadow = filter_moments(&mut self.shadow_moments, light.shadow, |moments| {
+            shadow_map.filter_moments(moments, layers)
+        });
+        self.shadow_map.shadow = shadow;
+        self.output.bind();
+    }
+
+    /// Re-render the point light cube map faces the scheduler picks for this frame
+    fn render_point_shadow_pass(
+        &mut self,
+        scene: &Scene,
+        camera_position: Vec3,
+        projection: &Mat4,
+        frustum: &Frustum,
+    ) {
+        let updates = self.point_shadows.schedule(
+            &scene.point_lights,
+            &scene.world,
+            camera_position,
+            projection,
+            frustum,
+        );
+
+        for update in updates {
+            let Some(psm) = self.point_shadow_maps.get(update.slot) else {
+                continue;
+            };
+            psm.begin_pass(update.position, update.range, update.faces);
+            // Casters within the range, seen by one of the faces drawn
+            let faces = face_frustums(update.position, update.range);
+            let drawn: Vec<&Frustum> = faces
+                .iter()
+                .enumerate()
+                .filter(|(face, _)| update.faces & (1 << face) != 0)
+                .map(|(_, frustum)| frustum)
+                .collect();
+            let pass = render_depth(&scene.world, &psm.shader, &mut self.instances, |bounds| {
+                bounds.sphere.center.distance(update.position)
+                    <= update.range + bounds.sphere.radius
+                    && drawn.iter().any(|frustum| frustum.intersects(bounds))
+            });
+            self.stats.add_shadow(pass);
+            self.stats.shadow_faces += update.faces.count_ones();
+            psm.end_pass(&self.output);
+        }
+    }
+
+    /// Render a depth layer for each shadow-casting spot light reaching into `frustum`
+    fn render_spot_shadow_pass(&mut self, scene: &Scene, frustum: &Frustum) {
+        self.spot_shadow_map.assign(&scene.spot_lights, frustum);
+        if self.spot_shadow_map.shadows.is_empty() {
+            return;
+        }
+
+        self.spot_shadow_map.begin_pass();
+        for (layer, shadow) in self.spot_shadow_map.shadows.iter().enumerate() {
+            self.spot_shadow_map.begin_layer(layer);
+
+            // Only casters inside the light's cone reach its layer
+            let light_frustum = Frustum::from_matrix(&shadow.light_space_matrix);
+            let pass = render_depth(
+                &scene.world,
+                &self.spot_shadow_map.shader,
+                &mut self.instances,
+                |bounds| light_frustum.intersects(bounds),
+            );
+            self.stats.add_shadow(pass);
+        }
+        self.spot_shadow_map.end_pass(&self.output);
+
+        for layer in 0..self.spot_shadow_map.shadows.len() {
+            let settings = self.spot_shadow_map.shadows[layer].shadow;
+            let spot_shadow_map = &mut self.spot_shadow_map;
+            let shadow = filter_moments(&mut self.shadow_moments, settings, |moments| {
+                spot_shadow_map.filter_moments(moments, layer)
+            });
+            self.spot_shadow_map.shadows[layer].shadow = shadow;
+        }
+        self.output.bind();
+    }
+}
 
-        for obj in &scene.objects {
-            obj.render_depth(&self.shadow_map.shader);
+/// Point shadow slots the current context has fragment texture units for (GL 3.3
+/// guarantees 16, which leaves one next to the lit shaders' other samplers)
+pub fn available_point_shadow_slots() -> usize {
+    let mut units = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAX_TEXTURE_IMAGE_UNITS, &mut units);
+    }
+    render_cfg::texture_units::point_shadow_slots(units.max(0) as u32)
+}
+
+/// Run `filter` with the moments passes if `settings` needs moments, and return the
+/// settings the shadow map can be sampled with: without the passes, or once they fail
+/// (and are dropped), moment filters fall back to `ShadowFilter::depth_only`
+fn filter_moments(
+    passes: &mut Option<ShadowMoments>,
+    settings: ShadowSettings,
+    filter: impl FnOnce(&ShadowMoments) -> Result<(), String>,
+) -> ShadowSettings {
+    if !settings.filter.uses_moments() {
+        return settings;
+    }
+    let fallback = settings.with_filter(settings.filter.depth_only());
+    let Some(moments) = passes.as_ref() else {
+        return fallback;
+    };
+    match filter(moments) {
+        Ok(()) => settings,
+        Err(e) => {
+            eprintln!("{}; filtering shadows without moments", e);
+            *passes = None;
+            fallback
         }
-        self.shadow_map.end_pass(win_cfg::WIDTH, win_cfg::HEIGHT);
     }
+}
 
-    fn render_point_shadow_pass(&mut self, scene: &Scene, point_lights: &[PointLight]) {
-        let far_plane = render_cfg::SHADOW_FAR_PLANE;
-        let light_idx = (self.frame_count % 2) as usize;
+/// Draw the entities `pass` covers that are inside `frustum`: opaque and cutout ones with
+/// one instanced draw per mesh/material pair, then transparent ones back to front
+fn render_objects(
+    world: &World,
+    ctx: &RenderContext,
+    frustum: &Frustum,
+    instances: &mut InstanceBuffer,
+    pass: ObjectPass,
+) -> PassCounts {
+    let mut counts = PassCounts::default();
+    let mut batcher = Batcher::new();
+    // Distance to the camera (squared) and a batch of one
+    let mut transparent: Vec<(f32, Batch)> = Vec::new();
+    world.query::<(
+        &GlobalTransform,
+        &Rc<dyn Renderable>,
+        &Rc<dyn Material>,
+        Option<&MeshMaterials>,
+        Option<&WorldBounds>,
+    )>(|_, (global, renderable, material, parts, bounds)| {
+        let included = match parts {
+            Some(parts) => parts.0.iter().any(|m| pass.includes(m.as_ref())),
+            None => pass.includes(material.as_ref()),
+        };
+        if !included {
+            return;
+        }
+        if bounds.is_some_and(|b| !frustum.intersects(&b.0)) {
+            counts.culled += 1;
+            return;
+        }
+        counts.drawn += 1;
 
-        for i in 0..2 {
-            let idx = light_idx + i * 2;
-            if let Some(pl) = point_lights.get(idx) {
-                if let Some(psm) = self.point_shadow_maps.get(idx) {
-                    psm.begin_pass(pl.position, far_plane);
-                    for obj in &scene.objects {
-                        obj.render_depth(&psm.shader);
+        let center = bounds.map_or(global.position(), |b| b.0.sphere.center);
+        let mut add = |part: Option<usize>, material: &Rc<dyn Material>| {
+            if material.blend_mode().is_transparent() {
+                let batch = Batch {
+                    renderable: renderable.clone(),
+                    part,
+                    material: Some(material.clone()),
+                    models: vec![global.0],
+                };
+                transparent.push((center.distance_squared(ctx.view_pos), batch));
+            } else {
+                batcher.add(renderable, part, Some(material), global.0);
+            }
+        };
+        match parts {
+            // Each part is drawn with its own material; parts of other passes are skipped
+            Some(parts) => {
+                for (part, material) in parts.0.iter().enumerate() {
+                    if pass.includes(material.as_ref()) {
+                        add(Some(part), material);
                     }
-                    psm.end_pass(win_cfg::WIDTH, win_cfg::HEIGHT);
                 }
             }
+            None => add(None, material),
         }
+    });
+
+    for batch in batcher.finish() {
+        draw_material_batch(&batch, ctx, instances, pass);
+        counts.draw_calls += 1;
+    }
+
+    // Transparent objects test against the opaque depth but don't write it, and are drawn
+    // one by one so the far-to-near order holds. The target's alpha is left as it was.
+    if !transparent.is_empty() {
+        transparent.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::DepthMask(gl::FALSE);
+        }
+        for (_, batch) in &transparent {
+            let additive = batch
+                .material
+                .as_ref()
+                .is_some_and(|m| m.blend_mode() == BlendMode::Additive);
+            let destination = if additive {
+                gl::ONE
+            } else {
+                gl::ONE_MINUS_SRC_ALPHA
+            };
+            unsafe {
+                gl::BlendFuncSeparate(gl::SRC_ALPHA, destination, gl::ZERO, gl::ONE);
+            }
+            draw_material_batch(batch, ctx, instances, pass);
+            counts.draw_calls += 1;
+        }
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            gl::Disable(gl::BLEND);
+        }
+    }
+    counts
+}
+
+/// Set up the batch's material for `pass` and draw it
+fn draw_material_batch(
+    batch: &Batch,
+    ctx: &RenderContext,
+    instances: &mut InstanceBuffer,
+    pass: ObjectPass,
+) {
+    let Some(material) = &batch.material else {
+        return;
+    };
+    let shader = match (pass, material.deferred_shader()) {
+        (ObjectPass::GBuffer, Some(shader)) => {
+            shader.use_program();
+            material.set_uniforms(shader);
+            shader
+        }
+        _ => {
+            material.apply();
+            material.shader()
+        }
+    };
+
+    // Matrices
+    shader.set_mat4("projection", &ctx.projection.to_cols_array());
+    shader.set_mat4("view", &ctx.view.to_cols_array());
+
+    // Toggles
+    shader.set_int("u_UseLighting", if material.is_lit() { 1 } else { 0 });
+    shader.set_int(
+        "u_UseShadows",
+        if material.receive_shadows() { 1 } else { 0 },
+    );
+    shader.set_float("alphaCutoff", material.blend_mode().alpha_cutoff());
+
+    // Lighting (the G-buffer only needs the view position, for two-sided normals)
+    if pass == ObjectPass::GBuffer {
+        shader.set_vec3("viewPos", ctx.view_pos.x, ctx.view_pos.y, ctx.view_pos.z);
+    } else if material.is_lit() {
+        ctx.apply_lighting(shader);
+        // The occlusion is that of the opaque surfaces behind
+        if material.blend_mode().is_transparent() {
+            Ssao::disable_in_shader(shader);
+        }
+    }
+
+    draw_batch(batch, instances);
+}
+
+/// Draw every shadow caster for which `visible` holds into the currently bound depth
+/// pass, one instanced draw per mesh (and cutout material). Entities without world bounds
+/// yet are always drawn.
+fn render_depth(
+    world: &World,
+    shader: &Shader,
+    instances: &mut InstanceBuffer,
+    visible: impl Fn(&Bounds) -> bool,
+) -> PassCounts {
+    let mut counts = PassCounts::default();
+    let mut batcher = Batcher::new();
+    world.query::<(
+        &GlobalTransform,
+        &Rc<dyn Renderable>,
+        Option<&Rc<dyn Material>>,
+        Option<&MeshMaterials>,
+        Option<&WorldBounds>,
+    )>(|_, (global, renderable, material, parts, bounds)| {
+        if bounds.is_some_and(|b| !visible(&b.0)) {
+            counts.culled += 1;
+            return;
+        }
+        let model = global.0;
+        let cast = match (parts, material) {
+            // Parts are batched apart only when some are cut out or cast nothing
+            (Some(parts), _) if parts.0.iter().any(|m| m.blend_mode() != BlendMode::Opaque) => {
+                let mut cast = false;
+                for (part, material) in parts.0.iter().enumerate() {
+                    cast |=
+                        add_shadow_caster(&mut batcher, renderable, Some(part), material, model);
+                }
+                cast
+            }
+            (None, Some(material)) => {
+                add_shadow_caster(&mut batcher, renderable, None, material, model)
+            }
+            _ => {
+                batcher.add(renderable, None, None, model);
+                true
+            }
+        };
+        if cast {
+            counts.drawn += 1;
+        }
+    });
+
+    for batch in batcher.finish() {
+        // Cutout batches discard what the lit shaders discard: alpha map times opacity
+        let (cutoff, opacity, alpha_map) = match &batch.material {
+            Some(m) => (m.blend_mode().alpha_cutoff(), m.opacity(), m.alpha_map()),
+            None => (0.0, 1.0, None),
+        };
+        shader.set_float("alphaCutoff", cutoff);
+        shader.set_float("opacity", opacity);
+        match alpha_map {
+            Some((texture, uv_scale)) => {
+                texture.bind(0);
+                shader.set_int("alphaMap", 0);
+                shader.set_int("hasAlphaMap", 1);
+                shader.set_vec2("u_UVScale", uv_scale.x, uv_scale.y);
+            }
+            None => shader.set_int("hasAlphaMap", 0),
+        }
+        draw_batch(&batch, instances);
+        counts.draw_calls += 1;
+    }
+    counts
+}
+
+/// Batch one shadow caster: opaque materials by mesh alone, cutout ones with their
+/// material for the alpha test. Transparent materials cast no shadow (returns false).
+fn add_shadow_caster(
+    batcher: &mut Batcher,
+    renderable: &Rc<dyn Renderable>,
+    part: Option<usize>,
+    material: &Rc<dyn Material>,
+    model: Mat4,
+) -> bool {
+    match material.blend_mode() {
+        BlendMode::Opaque => batcher.add(renderable, part, None, model),
+        BlendMode::Cutout { .. } => batcher.add(renderable, part, Some(material), model),
+        BlendMode::Blend | BlendMode::Additive => return false,
+    }
+    true
+}
+
+fn draw_batch(batch: &Batch, instances: &mut InstanceBuffer) {
+    instances.upload(&batch.models);
+    let count = batch.models.len() as i32;
+    match batch.part {
+        Some(part) => batch.renderable.draw_part_instanced(part, instances, count),
+        None => batch.renderable.draw_instanced(instances, count),
     }
 }
@@ -0,0 +1,337 @@
+//! Built-in Passes - The renderer's frame as render graph passes
+//!
+//! Shadows, ambient occlusion, light binning, the lit scene, the HDR effects, tone mapping,
+//! the display effects and the ambient occlusion debug view. Each wraps the `Renderer`
+//! method that draws it; the graph orders them by the resources they declare.
+
+use super::graph::resources::*;
+use super::graph::{GraphResources, PassBuilder, RenderGraph, RenderPass, TargetDesc};
+use super::target::TargetFormat;
+use super::{Frame, Renderer};
+use glam::Vec2;
+
+/// Add the built-in passes to `graph`
+pub fn add_default_passes(graph: &mut RenderGraph) {
+    graph.add_pass(DirectionalShadowPass);
+    graph.add_pass(PointShadowPass);
+    graph.add_pass(SpotShadowPass);
+    graph.add_pass(AmbientOcclusionPass);
+    graph.add_pass(LightClusterPass);
+    graph.add_pass(ScenePass);
+    graph.add_pass(HdrEffectsPass);
+    graph.add_pass(ToneMapPass);
+    graph.add_pass(DisplayEffectsPass);
+    graph.add_pass(OcclusionDebugPass);
+}
+
+/// Cascaded shadow map of the directional light
+pub struct DirectionalShadowPass;
+
+impl DirectionalShadowPass {
+    pub const NAME: &'static str = "directional_shadows";
+}
+
+impl RenderPass for DirectionalShadowPass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, _renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        builder.write(SHADOW_CASCADES);
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, frame: &Frame, _resources: &GraphResources) {
+        let scene = frame.scene;
+        renderer.render_shadow_pass(scene, &scene.light, &frame.view, &frame.projection);
+    }
+}
+
+/// The point light cube map faces scheduled this frame
+pub struct PointShadowPass;
+
+impl PointShadowPass {
+    pub const NAME: &'static str = "point_shadows";
+}
+
+impl RenderPass for PointShadowPass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, _renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        builder.write(POINT_SHADOWS);
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, frame: &Frame, _resources: &GraphResources) {
+        renderer.render_point_shadow_pass(
+            frame.scene,
+            frame.camera_position,
+            &frame.projection,
+            &frame.frustum,
+        );
+    }
+}
+
+/// A depth layer per shadow-casting spot light in view
+pub struct SpotShadowPass;
+
+impl SpotShadowPass {
+    pub const NAME: &'static str = "spot_shadows";
+}
+
+impl RenderPass for SpotShadowPass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, _renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        builder.write(SPOT_SHADOWS);
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, frame: &Frame, _resources: &GraphResources) {
+        renderer.render_spot_shadow_pass(frame.scene, &frame.frustum);
+    }
+}
+
+/// Depth prepass and screen-space ambient occlusion, while turned on
+pub struct AmbientOcclusionPass;
+
+impl AmbientOcclusionPass {
+    pub const NAME: &'static str = "ambient_occlusion";
+}
+
+impl RenderPass for AmbientOcclusionPass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        if renderer.ambient_occlusion.enabled && renderer.ssao.is_some() {
+            builder.write(OCCLUSION);
+        }
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, frame: &Frame, _resources: &GraphResources) {
+        // At the size of the scene target, which covers the output
+        let target = renderer.output;
+        renderer.render_ssao(
+            frame.scene,
+            &target,
+            &frame.view,
+            &frame.projection,
+            &frame.frustum,
+        );
+    }
+}
+
+/// Point and spot lights binned over the view, with the shadows they were given
+pub struct LightClusterPass;
+
+impl LightClusterPass {
+    pub const NAME: &'static str = "light_clusters";
+}
+
+impl RenderPass for LightClusterPass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, _renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        // The shadow passes hand out the cube maps and layers the lights are sent with
+        builder.read(POINT_SHADOWS);
+        builder.read(SPOT_SHADOWS);
+        builder.write(LIGHT_CLUSTERS);
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, frame: &Frame, _resources: &GraphResources) {
+        let output = renderer.output;
+        renderer.clusters.update(
+            &frame.view,
+            &frame.projection,
+            Vec2::new(output.width as f32, output.height as f32),
+            frame.scene,
+            &renderer.point_shadows,
+            &renderer.spot_shadow_map,
+        );
+    }
+}
+
+/// The lit scene, forward or deferred (see `Renderer::path`), with the skybox
+pub struct ScenePass;
+
+impl ScenePass {
+    pub const NAME: &'static str = "scene";
+}
+
+impl RenderPass for ScenePass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        for shadows in [SHADOW_CASCADES, POINT_SHADOWS, SPOT_SHADOWS] {
+            builder.read(shadows);
+        }
+        builder.read(LIGHT_CLUSTERS);
+        if renderer.ambient_occlusion.enabled {
+            builder.read(OCCLUSION);
+        }
+        // Linear radiance for tone mapping, or display values to copy out
+        let format = if renderer.hdr.is_some() {
+            TargetFormat::Rgba16F
+        } else {
+            TargetFormat::Rgba8
+        };
+        builder.create(SCENE_COLOR, TargetDesc::sized_as(&renderer.output, format));
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, frame: &Frame, resources: &GraphResources) {
+        if let Some(target) = resources.viewport(SCENE_COLOR) {
+            renderer.render_scene(frame, &target);
+        }
+    }
+}
+
+/// The post-processing effects on scene radiance (bloom), while any are enabled
+pub struct HdrEffectsPass;
+
+impl HdrEffectsPass {
+    pub const NAME: &'static str = "hdr_effects";
+}
+
+impl RenderPass for HdrEffectsPass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        let effects = renderer.post_effect_count(true);
+        if effects == 0 {
+            return;
+        }
+        let desc = TargetDesc::sized_as(&renderer.output, TargetFormat::Rgba16F);
+        builder.read(SCENE_COLOR);
+        builder.create(HDR_COLOR, desc);
+        if effects > 1 {
+            builder.create(HDR_SCRATCH, desc);
+        }
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, _frame: &Frame, resources: &GraphResources) {
+        let targets = (resources.target(SCENE_COLOR), resources.target(HDR_COLOR));
+        if let (Some(scene), Some(result)) = targets {
+            renderer.apply_hdr_effects(scene, result, resources.target(HDR_SCRATCH));
+        }
+    }
+}
+
+/// Exposure and tone mapping into the output, or into the display image the display
+/// effects start from; a plain copy without the HDR pipeline
+pub struct ToneMapPass;
+
+impl ToneMapPass {
+    pub const NAME: &'static str = "tone_mapping";
+}
+
+impl RenderPass for ToneMapPass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        if renderer.post_effect_count(true) > 0 {
+            builder.read(HDR_COLOR);
+        } else {
+            builder.read(SCENE_COLOR);
+        }
+        if renderer.post_effect_count(false) > 0 {
+            let desc = TargetDesc::sized_as(&renderer.output, TargetFormat::Rgba8);
+            builder.create(DISPLAY_COLOR, desc);
+        } else {
+            builder.write(OUTPUT);
+        }
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, _frame: &Frame, resources: &GraphResources) {
+        // Whichever of each this pass declared is the one the graph provides
+        let scene = resources
+            .target(HDR_COLOR)
+            .or_else(|| resources.target(SCENE_COLOR));
+        let target = resources
+            .viewport(DISPLAY_COLOR)
+            .or_else(|| resources.viewport(OUTPUT));
+        if let (Some(scene), Some(target)) = (scene, target) {
+            renderer.tone_map(scene, &target);
+        }
+    }
+}
+
+/// The post-processing effects on the tone-mapped image (FXAA, vignette, ...) into the
+/// output, while any are enabled
+pub struct DisplayEffectsPass;
+
+impl DisplayEffectsPass {
+    pub const NAME: &'static str = "display_effects";
+}
+
+impl RenderPass for DisplayEffectsPass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        let effects = renderer.post_effect_count(false);
+        if effects == 0 {
+            return;
+        }
+        // Effects sample the scene depth as well
+        builder.read(SCENE_COLOR);
+        builder.read(DISPLAY_COLOR);
+        if effects > 1 {
+            // Intermediate images alternate between the scratch target and the input
+            let desc = TargetDesc::sized_as(&renderer.output, TargetFormat::Rgba8);
+            builder.create(DISPLAY_SCRATCH, desc);
+            builder.modify(DISPLAY_COLOR);
+        }
+        builder.write(OUTPUT);
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, _frame: &Frame, resources: &GraphResources) {
+        let targets = (
+            resources.target(SCENE_COLOR),
+            resources.target(DISPLAY_COLOR),
+            resources.viewport(OUTPUT),
+        );
+        if let (Some(scene), Some(input), Some(output)) = targets {
+            let scratch = resources.target(DISPLAY_SCRATCH);
+            renderer.apply_display_effects(input, scratch, &output, scene.depth_texture);
+        }
+    }
+}
+
+/// The raw occlusion in place of the image, while its debug view is on
+pub struct OcclusionDebugPass;
+
+impl OcclusionDebugPass {
+    pub const NAME: &'static str = "occlusion_debug";
+}
+
+impl RenderPass for OcclusionDebugPass {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn setup(&mut self, renderer: &Renderer, _frame: &Frame, builder: &mut PassBuilder) {
+        let settings = &renderer.ambient_occlusion;
+        if settings.enabled && settings.debug_view && renderer.ssao.is_some() {
+            builder.read(OCCLUSION);
+            builder.modify(OUTPUT);
+        }
+    }
+
+    fn execute(&mut self, renderer: &mut Renderer, _frame: &Frame, resources: &GraphResources) {
+        if let (Some(ssao), Some(output)) = (&renderer.ssao, resources.viewport(OUTPUT)) {
+            ssao.draw_debug(&output);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Bloom mip chain - progressive downsample of the bright parts, then tent upsample back
+
+use super::Bloom;
+use crate::shaders::Shader;
+
+/// One half-resolution level of the chain
+struct BloomMip {
+    fbo: u32,
+    texture: u32,
+    width: u32,
+    height: u32,
+}
+
+impl BloomMip {
+    fn new(width: u32, height: u32) -> Result<Self, String> {
+        let mut mip = Self {
+            fbo: 0,
+            texture: 0,
+            width,
+            height,
+        };
+        let status;
+        unsafe {
+            gl::GenTextures(1, &mut mip.texture);
+            gl::BindTexture(gl::TEXTURE_2D, mip.texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::R11F_G11F_B10F as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGB,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+
+            gl::GenFramebuffers(1, &mut mip.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, mip.fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                mip.texture,
+                0,
+            );
+            status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "Bloom mip {}x{} is incomplete: 0x{:X}",
+                width, height, status
+            ));
+        }
+        Ok(mip)
+    }
+
+    fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.width as i32, self.height as i32);
+        }
+    }
+}
+
+impl Drop for BloomMip {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/// Mips of the blurred highlights, rebuilt when the source size or level count changes
+#[derive(Default)]
+pub(super) struct BloomChain {
+    mips: Vec<BloomMip>,
+    /// Source size and requested levels the mips were built for
+    source: (u32, u32, u32),
+}
+
+impl BloomChain {
+    fn ensure(&mut self, width: u32, height: u32, levels: u32) -> Result<(), String> {
+        if self.source == (width, height, levels) && !self.mips.is_empty() {
+            return Ok(());
+        }
+        self.mips.clear();
+        let (mut w, mut h) = (width / 2, height / 2);
+        while self.mips.len() < levels.max(1) as usize && w >= 2 && h >= 2 {
+            self.mips.push(BloomMip::new(w, h)?);
+            w /= 2;
+            h /= 2;
+        }
+        self.source = (width, height, levels);
+        Ok(())
+    }
+
+    /// Blur the highlights of `source` (a `width` x `height` texture) and return the
+    /// half-resolution texture holding the result. Expects the fullscreen VAO bound.
+    pub(super) fn render(
+        &mut self,
+        downsample: &Shader,
+        upsample: &Shader,
+        source: u32,
+        (width, height): (u32, u32),
+        settings: &Bloom,
+    ) -> Result<u32, String> {
+        self.ensure(width, height, settings.levels)?;
+
+        // Downsample, thresholding on the way into the first mip
+        downsample.use_program();
+        downsample.set_int("source", 0);
+        downsample.set_float("threshold", settings.threshold);
+        downsample.set_float("knee", settings.knee);
+        let mut input = (source, width, height);
+        for (i, mip) in self.mips.iter().enumerate() {
+            mip.bind();
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0);
+                gl::BindTexture(gl::TEXTURE_2D, input.0);
+            }
+            downsample.set_vec2("texelSize", 1.0 / input.1 as f32, 1.0 / input.2 as f32);
+            downsample.set_int("prefilter", if i == 0 { 1 } else { 0 });
+            unsafe {
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+            input = (mip.texture, mip.width, mip.height);
+        }
+
+        // Upsample, adding each level onto the next larger one
+        upsample.use_program();
+        upsample.set_int("source", 0);
+        upsample.set_float("radius", settings.radius);
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+        }
+        for pair in self.mips.windows(2).rev() {
+            let (larger, smaller) = (&pair[0], &pair[1]);
+            larger.bind();
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0);
+                gl::BindTexture(gl::TEXTURE_2D, smaller.texture);
+            }
+            upsample.set_vec2(
+                "texelSize",
+                1.0 / smaller.width as f32,
+                1.0 / smaller.height as f32,
+            );
+            unsafe {
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+        }
+        unsafe {
+            gl::Disable(gl::BLEND);
+        }
+
+        self.mips
+            .first()
+            .map(|mip| mip.texture)
+            .ok_or_else(|| format!("Bloom source {}x{} is too small", width, height))
+    }
+}
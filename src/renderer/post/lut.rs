@@ -0,0 +1,72 @@
+//! Color grading LUT - 3D lookup table loaded from a horizontal strip image
+
+/// 3D texture of `size`^3 graded colors, indexed by the input color
+pub(super) struct Lut {
+    pub texture: u32,
+    pub size: u32,
+}
+
+impl Lut {
+    /// Load a strip of `size` square slices side by side (`size * size` x `size` pixels):
+    /// red grows to the right within a slice, green downwards, blue from slice to slice
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let img = image::open(path)
+            .map_err(|e| format!("Failed to load LUT '{}': {}", path, e))?
+            .to_rgb8();
+        let size = img.height();
+        if size < 2 || img.width() != size * size {
+            return Err(format!(
+                "LUT '{}' is {}x{}; expected a strip of N slices of NxN (N*N x N)",
+                path,
+                img.width(),
+                size
+            ));
+        }
+
+        // Red fastest, then green, then blue, as glTexImage3D expects
+        let mut data = Vec::with_capacity((size * size * size * 3) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.extend_from_slice(&img.get_pixel(b * size + r, g).0);
+                }
+            }
+        }
+
+        let mut texture = 0;
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_3D, texture);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage3D(
+                gl::TEXTURE_3D,
+                0,
+                gl::RGB8 as i32,
+                size as i32,
+                size as i32,
+                size as i32,
+                0,
+                gl::RGB,
+                gl::UNSIGNED_BYTE,
+                data.as_ptr() as *const _,
+            );
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            for wrap in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+                gl::TexParameteri(gl::TEXTURE_3D, wrap, gl::CLAMP_TO_EDGE as i32);
+            }
+            gl::BindTexture(gl::TEXTURE_3D, 0);
+        }
+
+        println!("Loaded LUT: {} ({}^3)", path, size);
+        Ok(Self { texture, size })
+    }
+}
+
+impl Drop for Lut {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
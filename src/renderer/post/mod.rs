@@ -0,0 +1,486 @@
+//! Post-Processing - Ordered chain of screen-space effects around the tone mapping resolve
+//!
+//! Each enabled effect reads the previous pass's color (plus the scene depth) and writes
+//! the next one, alternating between two targets the render graph provides. Effects on
+//! scene radiance (bloom) run on the HDR image before tone mapping; the others (FXAA,
+//! vignette, chromatic aberration, color grading) run on the display image after it, the
+//! last one writing the output. The chain is declared in code (`PostChain::default`) or
+//! loaded from a RON file.
+
+mod bloom;
+mod lut;
+
+use super::target::{RenderTarget, Viewport};
+use crate::assets::paths::{names, shaders};
+use crate::assets::AssetManager;
+use crate::shaders::Shader;
+use bloom::BloomChain;
+use lut::Lut;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Texture units of the post-process shaders
+const COLOR_UNIT: u32 = 0;
+const EFFECT_UNIT: u32 = 1;
+const DEPTH_UNIT: u32 = 2;
+
+fn default_true() -> bool {
+    true
+}
+
+/// Adds a blurred glow around highlights brighter than `threshold`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bloom {
+    /// Scene brightness where the glow starts
+    pub threshold: f32,
+    /// Width of the soft transition below the threshold
+    pub knee: f32,
+    pub intensity: f32,
+    /// Upsample filter radius, in texels of the smaller mip
+    pub radius: f32,
+    /// Half-resolution blur levels (wider glow with more)
+    pub levels: u32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.15,
+            radius: 1.0,
+            levels: 6,
+        }
+    }
+}
+
+/// Fast approximate anti-aliasing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fxaa {
+    /// Longest edge blur, in pixels
+    pub span_max: f32,
+    /// Shortens the blur on low-contrast edges
+    pub reduce_mul: f32,
+    pub reduce_min: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Self {
+            span_max: 8.0,
+            reduce_mul: 1.0 / 8.0,
+            reduce_min: 1.0 / 128.0,
+        }
+    }
+}
+
+/// Darkens the image towards the corners
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Vignette {
+    /// Darkening at full strength (0 = none, 1 = black)
+    pub intensity: f32,
+    /// Distance from the center, in screen heights, where full strength is reached
+    pub radius: f32,
+    /// Width of the falloff inside `radius`
+    pub smoothness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.35,
+            radius: 0.95,
+            smoothness: 0.6,
+        }
+    }
+}
+
+/// Splits red and blue towards the screen edges, like a cheap lens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChromaticAberration {
+    /// Channel offset at the corners, in UV units
+    pub intensity: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self { intensity: 0.006 }
+    }
+}
+
+/// Remaps display colors through a 3D lookup table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorGrading {
+    /// Strip image of N slices of NxN (see `Lut::from_file`)
+    pub lut: String,
+    /// Blend between the ungraded (0) and graded (1) colors
+    pub strength: f32,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            lut: crate::assets::paths::luts::WARM.to_string(),
+            strength: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PostEffect {
+    Bloom(Bloom),
+    Fxaa(Fxaa),
+    Vignette(Vignette),
+    ChromaticAberration(ChromaticAberration),
+    ColorGrading(ColorGrading),
+}
+
+impl PostEffect {
+    /// Whether the effect works on scene radiance, before tone mapping
+    fn is_hdr(&self) -> bool {
+        matches!(self, PostEffect::Bloom(_))
+    }
+}
+
+/// One entry of the chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostPass {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub effect: PostEffect,
+}
+
+impl PostPass {
+    pub fn new(effect: PostEffect) -> Self {
+        Self {
+            enabled: true,
+            effect,
+        }
+    }
+
+    pub fn disabled(effect: PostEffect) -> Self {
+        Self {
+            enabled: false,
+            effect,
+        }
+    }
+}
+
+/// The effects to apply, in order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostChain {
+    /// Switches the whole chain
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub passes: Vec<PostPass>,
+}
+
+impl Default for PostChain {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            passes: vec![
+                PostPass::new(PostEffect::Bloom(Bloom::default())),
+                PostPass::new(PostEffect::Fxaa(Fxaa::default())),
+                PostPass::disabled(PostEffect::ChromaticAberration(
+                    ChromaticAberration::default(),
+                )),
+                PostPass::new(PostEffect::Vignette(Vignette::default())),
+                PostPass::disabled(PostEffect::ColorGrading(ColorGrading::default())),
+            ],
+        }
+    }
+}
+
+impl PostChain {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read post-process chain '{}': {}", path, e))?;
+        ron::from_str(&source)
+            .map_err(|e| format!("Failed to parse post-process chain '{}': {}", path, e))
+    }
+
+    /// The enabled effects on scene radiance (`hdr`) or on the display image, in order
+    pub fn active(&self, hdr: bool) -> impl Iterator<Item = &PostEffect> {
+        self.passes
+            .iter()
+            .filter(move |pass| self.enabled && pass.enabled && pass.effect.is_hdr() == hdr)
+            .map(|pass| &pass.effect)
+    }
+}
+
+/// Shaders, bloom mips and LUTs that run a `PostChain`; the images between effects are
+/// render graph transients (see `passes`)
+pub struct PostProcessor {
+    bloom_downsample: Rc<Shader>,
+    bloom_upsample: Rc<Shader>,
+    bloom_composite: Rc<Shader>,
+    fxaa: Rc<Shader>,
+    vignette: Rc<Shader>,
+    chromatic_aberration: Rc<Shader>,
+    color_grading: Rc<Shader>,
+    bloom: BloomChain,
+    /// Loaded LUTs by path; `None` for ones that failed (reported once)
+    luts: HashMap<String, Option<Lut>>,
+    /// Attribute-less VAO for the fullscreen triangle
+    fullscreen_vao: u32,
+}
+
+impl PostProcessor {
+    /// Load the effect shaders through `assets`
+    pub fn load(assets: &mut AssetManager) -> Self {
+        let mut load = |name, frag| assets.load_shader(name, shaders::FULLSCREEN_VERT, frag);
+        let bloom_downsample = load(
+            names::SHADER_BLOOM_DOWNSAMPLE,
+            shaders::BLOOM_DOWNSAMPLE_FRAG,
+        );
+        let bloom_upsample = load(names::SHADER_BLOOM_UPSAMPLE, shaders::BLOOM_UPSAMPLE_FRAG);
+        let bloom_composite = load(names::SHADER_BLOOM_COMPOSITE, shaders::BLOOM_COMPOSITE_FRAG);
+        let fxaa = load(names::SHADER_FXAA, shaders::FXAA_FRAG);
+        let vignette = load(names::SHADER_VIGNETTE, shaders::VIGNETTE_FRAG);
+        let chromatic_aberration = load(
+            names::SHADER_CHROMATIC_ABERRATION,
+            shaders::CHROMATIC_ABERRATION_FRAG,
+        );
+        let color_grading = load(names::SHADER_COLOR_GRADING, shaders::COLOR_GRADING_FRAG);
+
+        let mut fullscreen_vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut fullscreen_vao);
+        }
+        Self {
+            bloom_downsample,
+            bloom_upsample,
+            bloom_composite,
+            fxaa,
+            vignette,
+            chromatic_aberration,
+            color_grading,
+            bloom: BloomChain::default(),
+            luts: HashMap::new(),
+            fullscreen_vao,
+        }
+    }
+
+    /// Run the HDR effects of `chain` over `scene` into `result`. Intermediate images go to
+    /// `scratch`, which is needed when more than one effect runs.
+    pub fn apply_hdr(
+        &mut self,
+        chain: &PostChain,
+        scene: &RenderTarget,
+        result: &RenderTarget,
+        scratch: Option<&RenderTarget>,
+    ) -> Result<(), String> {
+        let effects: Vec<&Bloom> = chain
+            .active(true)
+            .filter_map(|effect| match effect {
+                PostEffect::Bloom(settings) => Some(settings),
+                _ => None,
+            })
+            .collect();
+        if effects.len() > 1 && scratch.is_none() {
+            return Err("HDR effects need a scratch target to alternate with".to_string());
+        }
+        let mut color = scene.color_texture;
+
+        self.begin();
+        for (i, settings) in effects.iter().enumerate() {
+            // Counted back from the last effect, which writes `result`, so that none reads
+            // the target it writes
+            let target = if (effects.len() - 1 - i).is_multiple_of(2) {
+                result
+            } else {
+                scratch.unwrap()
+            };
+            let bloom = match self.bloom.render(
+                &self.bloom_downsample,
+                &self.bloom_upsample,
+                color,
+                (scene.width, scene.height),
+                settings,
+            ) {
+                Ok(bloom) => bloom,
+                Err(e) => {
+                    self.end();
+                    return Err(e);
+                }
+            };
+
+            target.viewport().bind();
+            let shader = &self.bloom_composite;
+            shader.use_program();
+            bind_input(shader, color, scene.depth_texture);
+            bind_texture(EFFECT_UNIT, gl::TEXTURE_2D, bloom);
+            shader.set_int("bloom", EFFECT_UNIT as i32);
+            shader.set_float("intensity", settings.intensity);
+            draw_fullscreen();
+            color = target.color_texture;
+        }
+        self.end();
+        Ok(())
+    }
+
+    /// Run the display effects of `chain` over the tone-mapped `input` into `output`.
+    /// Intermediate images alternate between `scratch` and `input`, so `scratch` is needed
+    /// when more than one effect runs. `depth` is the scene depth texture.
+    pub fn apply_display(
+        &mut self,
+        chain: &PostChain,
+        input: &RenderTarget,
+        scratch: Option<&RenderTarget>,
+        output: &Viewport,
+        depth: u32,
+    ) -> Result<(), String> {
+        let effects: Vec<&PostEffect> = chain
+            .active(false)
+            .filter(|effect| self.is_ready(effect))
+            .collect();
+        if effects.len() > 1 && scratch.is_none() {
+            return Err("Display effects need a scratch target to alternate with".to_string());
+        }
+        let texel_size = (1.0 / output.width as f32, 1.0 / output.height as f32);
+        let mut color = input.color_texture;
+
+        self.begin();
+        for (i, effect) in effects.iter().enumerate() {
+            // The last effect writes the output
+            let target = if i + 1 == effects.len() {
+                output.bind();
+                None
+            } else {
+                let target = if i.is_multiple_of(2) {
+                    scratch.unwrap()
+                } else {
+                    input
+                };
+                target.viewport().bind();
+                Some(target.color_texture)
+            };
+
+            let shader = match effect {
+                PostEffect::Fxaa(fxaa) => {
+                    let shader = &self.fxaa;
+                    shader.use_program();
+                    shader.set_float("spanMax", fxaa.span_max);
+                    shader.set_float("reduceMul", fxaa.reduce_mul);
+                    shader.set_float("reduceMin", fxaa.reduce_min);
+                    shader
+                }
+                PostEffect::Vignette(vignette) => {
+                    let shader = &self.vignette;
+                    shader.use_program();
+                    shader.set_float("intensity", vignette.intensity);
+                    shader.set_float("radius", vignette.radius);
+                    shader.set_float("smoothness", vignette.smoothness);
+                    shader
+                }
+                PostEffect::ChromaticAberration(aberration) => {
+                    let shader = &self.chromatic_aberration;
+                    shader.use_program();
+                    shader.set_float("intensity", aberration.intensity);
+                    shader
+                }
+                PostEffect::ColorGrading(grading) => {
+                    let lut = self.luts[&grading.lut].as_ref().unwrap();
+                    let shader = &self.color_grading;
+                    shader.use_program();
+                    bind_texture(EFFECT_UNIT, gl::TEXTURE_3D, lut.texture);
+                    shader.set_int("lut", EFFECT_UNIT as i32);
+                    shader.set_float("lutSize", lut.size as f32);
+                    shader.set_float("strength", grading.strength);
+                    shader
+                }
+                PostEffect::Bloom(_) => continue,
+            };
+            bind_input(shader, color, depth);
+            shader.set_vec2("texelSize", texel_size.0, texel_size.1);
+            draw_fullscreen();
+
+            if let Some(written) = target {
+                color = written;
+            }
+        }
+        if effects.is_empty() {
+            // Everything was skipped: still present the tone-mapped image
+            self.blit(color, output);
+        }
+        self.end();
+        Ok(())
+    }
+
+    /// Whether `effect` has what it needs to run (loading a LUT on first use)
+    fn is_ready(&mut self, effect: &PostEffect) -> bool {
+        match effect {
+            PostEffect::ColorGrading(grading) => self
+                .luts
+                .entry(grading.lut.clone())
+                .or_insert_with(|| {
+                    Lut::from_file(&grading.lut)
+                        .map_err(|e| eprintln!("{}; skipping color grading", e))
+                        .ok()
+                })
+                .is_some(),
+            _ => true,
+        }
+    }
+
+    /// Copy `color` into `output` unchanged
+    fn blit(&self, color: u32, output: &Viewport) {
+        output.bind();
+        let shader = &self.chromatic_aberration;
+        shader.use_program();
+        shader.set_float("intensity", 0.0);
+        bind_input(shader, color, 0);
+        draw_fullscreen();
+    }
+
+    fn begin(&self) {
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.fullscreen_vao);
+        }
+    }
+
+    fn end(&self) {
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
+impl Drop for PostProcessor {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.fullscreen_vao);
+        }
+    }
+}
+
+/// Bind the previous pass's color and the scene depth as `sceneColor` / `sceneDepth`
+fn bind_input(shader: &Shader, color: u32, depth: u32) {
+    bind_texture(COLOR_UNIT, gl::TEXTURE_2D, color);
+    bind_texture(DEPTH_UNIT, gl::TEXTURE_2D, depth);
+    shader.set_int("sceneColor", COLOR_UNIT as i32);
+    shader.set_int("sceneDepth", DEPTH_UNIT as i32);
+}
+
+fn bind_texture(unit: u32, target: gl::types::GLenum, texture: u32) {
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0 + unit);
+        gl::BindTexture(target, texture);
+    }
+}
+
+fn draw_fullscreen() {
+    unsafe {
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+    }
+}
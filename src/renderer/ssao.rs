@@ -0,0 +1,446 @@
+//! SSAO - Screen-space ambient occlusion of the opaque scene
+//!
+//! Before the main passes, opaque and cutout objects are drawn from the camera into a
+//! depth-only target. The occlusion pass reconstructs each pixel's view-space position from
+//! that depth and its normal from the neighbouring depths, then tests a hemisphere kernel
+//! around it (rotated per pixel by a tiled noise texture) against the depth buffer. A
+//! depth-aware blur over the noise tile removes the rotation pattern, and the lit shaders
+//! multiply their ambient terms by the result.
+
+use super::target::Viewport;
+use crate::assets::paths::{names, shaders};
+use crate::assets::AssetManager;
+use crate::config::rendering as render_cfg;
+use crate::config::rendering::texture_units;
+use crate::shaders::Shader;
+use glam::{Mat4, Vec3};
+use std::rc::Rc;
+
+/// Side of the square rotation tile (and of the blur that hides it)
+const NOISE_SIZE: usize = 4;
+
+/// Ordered dither of the tile, so neighbouring pixels get distant rotations
+const BAYER_4X4: [[u32; NOISE_SIZE]; NOISE_SIZE] =
+    [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsaoSettings {
+    pub enabled: bool,
+    /// World-space radius of the sampled hemisphere
+    pub radius: f32,
+    /// Depth difference below which a sample doesn't count as occluded
+    pub bias: f32,
+    /// Kernel samples per pixel (clamped to `SSAO_MAX_SAMPLES`)
+    pub samples: u32,
+    /// Exponent applied to the occlusion (above 1 darkens it)
+    pub intensity: f32,
+    /// Show the raw, unblurred occlusion instead of the scene
+    pub debug_view: bool,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: render_cfg::SSAO_ENABLED,
+            radius: render_cfg::SSAO_RADIUS,
+            bias: render_cfg::SSAO_BIAS,
+            samples: render_cfg::SSAO_SAMPLES,
+            intensity: render_cfg::SSAO_INTENSITY,
+            debug_view: false,
+        }
+    }
+}
+
+/// Prepass depth and the raw and blurred occlusion, the size of the scene target
+struct SsaoTargets {
+    depth_fbo: u32,
+    depth: u32,
+    /// Occlusion before and after the blur (R8), each with its own framebuffer
+    occlusion_fbos: [u32; 2],
+    occlusion: [u32; 2],
+    width: u32,
+    height: u32,
+}
+
+impl SsaoTargets {
+    fn new(width: u32, height: u32) -> Result<Self, String> {
+        let mut targets = Self {
+            depth_fbo: 0,
+            depth: 0,
+            occlusion_fbos: [0; 2],
+            occlusion: [0; 2],
+            width,
+            height,
+        };
+
+        let mut status = [0; 3];
+        unsafe {
+            gl::GenFramebuffers(1, &mut targets.depth_fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, targets.depth_fbo);
+            targets.depth = screen_texture(
+                width,
+                height,
+                gl::DEPTH_COMPONENT24,
+                gl::DEPTH_COMPONENT,
+                gl::UNSIGNED_INT,
+                gl::NEAREST,
+            );
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::TEXTURE_2D,
+                targets.depth,
+                0,
+            );
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            status[0] = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+
+            for i in 0..2 {
+                gl::GenFramebuffers(1, &mut targets.occlusion_fbos[i]);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, targets.occlusion_fbos[i]);
+                targets.occlusion[i] = screen_texture(
+                    width,
+                    height,
+                    gl::R8,
+                    gl::RED,
+                    gl::UNSIGNED_BYTE,
+                    gl::LINEAR,
+                );
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_2D,
+                    targets.occlusion[i],
+                    0,
+                );
+                status[i + 1] = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        if let Some(status) = status.iter().find(|&&s| s != gl::FRAMEBUFFER_COMPLETE) {
+            return Err(format!(
+                "SSAO framebuffer {}x{} is incomplete: 0x{:X}",
+                width, height, status
+            ));
+        }
+        Ok(targets)
+    }
+
+    fn bind_occlusion(&self, index: usize) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.occlusion_fbos[index]);
+            gl::Viewport(0, 0, self.width as i32, self.height as i32);
+        }
+    }
+}
+
+impl Drop for SsaoTargets {
+    fn drop(&mut self) {
+        let fbos = [
+            self.depth_fbo,
+            self.occlusion_fbos[0],
+            self.occlusion_fbos[1],
+        ];
+        let textures = [self.depth, self.occlusion[0], self.occlusion[1]];
+        unsafe {
+            gl::DeleteFramebuffers(fbos.len() as i32, fbos.as_ptr());
+            gl::DeleteTextures(textures.len() as i32, textures.as_ptr());
+        }
+    }
+}
+
+/// Depth prepass, occlusion and blur passes
+pub struct Ssao {
+    depth_shader: Rc<Shader>,
+    occlusion_shader: Rc<Shader>,
+    blur_shader: Rc<Shader>,
+    debug_shader: Rc<Shader>,
+    /// Created on first use, and again whenever the scene target size changes
+    targets: Option<SsaoTargets>,
+    /// Whether `targets` holds this frame's occlusion
+    computed: bool,
+    /// Rotations around the normal, tiled over the screen (RG16F, NOISE_SIZE squared)
+    noise: u32,
+    /// Hemisphere samples around +Z, rebuilt when the sample count changes
+    kernel: Vec<Vec3>,
+    /// Attribute-less VAO for the fullscreen triangle
+    fullscreen_vao: u32,
+}
+
+impl Ssao {
+    pub fn new(
+        depth_shader: Rc<Shader>,
+        occlusion_shader: Rc<Shader>,
+        blur_shader: Rc<Shader>,
+        debug_shader: Rc<Shader>,
+    ) -> Self {
+        let mut fullscreen_vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut fullscreen_vao);
+        }
+        Self {
+            depth_shader,
+            occlusion_shader,
+            blur_shader,
+            debug_shader,
+            targets: None,
+            computed: false,
+            noise: noise_texture(),
+            kernel: Vec::new(),
+            fullscreen_vao,
+        }
+    }
+
+    /// Load the prepass and occlusion shaders through `assets`
+    pub fn load(assets: &mut AssetManager) -> Self {
+        let depth_shader = assets.load_shader(
+            names::SHADER_SSAO_DEPTH,
+            shaders::SHADOW_DEPTH_VERT,
+            shaders::SHADOW_DEPTH_FRAG,
+        );
+        let occlusion_shader = assets.load_shader(
+            names::SHADER_SSAO,
+            shaders::FULLSCREEN_VERT,
+            shaders::SSAO_FRAG,
+        );
+        let blur_shader = assets.load_shader(
+            names::SHADER_SSAO_BLUR,
+            shaders::FULLSCREEN_VERT,
+            shaders::SSAO_BLUR_FRAG,
+        );
+        let debug_shader = assets.load_shader(
+            names::SHADER_SSAO_DEBUG,
+            shaders::FULLSCREEN_VERT,
+            shaders::SSAO_DEBUG_FRAG,
+        );
+        Self::new(depth_shader, occlusion_shader, blur_shader, debug_shader)
+    }
+
+    /// Bind and clear a depth target the size of `output` for the prepass, and return the
+    /// depth shader, set up with the camera's `view_projection`
+    pub fn begin_depth_pass(
+        &mut self,
+        output: &Viewport,
+        view_projection: &Mat4,
+    ) -> Result<&Shader, String> {
+        self.computed = false;
+        let stale = self
+            .targets
+            .as_ref()
+            .is_none_or(|t| t.width != output.width || t.height != output.height);
+        if stale {
+            self.targets = None;
+            self.targets = Some(SsaoTargets::new(output.width, output.height)?);
+        }
+        let targets = self.targets.as_ref().unwrap();
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, targets.depth_fbo);
+            gl::Viewport(0, 0, targets.width as i32, targets.height as i32);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        self.depth_shader.use_program();
+        self.depth_shader
+            .set_mat4("lightSpaceMatrix", &view_projection.to_cols_array());
+        Ok(&self.depth_shader)
+    }
+
+    /// Compute and blur the occlusion of the prepass depth, seen through `projection`
+    pub fn compute(&mut self, projection: &Mat4, settings: &SsaoSettings) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        let samples = settings.samples.clamp(1, render_cfg::SSAO_MAX_SAMPLES) as usize;
+        if self.kernel.len() != samples {
+            self.kernel = hemisphere_kernel(samples);
+        }
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.fullscreen_vao);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, targets.depth);
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, self.noise);
+        }
+
+        // Raw occlusion
+        targets.bind_occlusion(0);
+        let shader = &self.occlusion_shader;
+        shader.use_program();
+        shader.set_int("depthMap", 0);
+        shader.set_int("noiseMap", 1);
+        for (i, sample) in self.kernel.iter().enumerate() {
+            shader.set_vec3(&format!("samples[{}]", i), sample.x, sample.y, sample.z);
+        }
+        shader.set_int("sampleCount", samples as i32);
+        shader.set_float("radius", settings.radius);
+        shader.set_float("bias", settings.bias);
+        shader.set_float("intensity", settings.intensity);
+        shader.set_mat4("projection", &projection.to_cols_array());
+        shader.set_mat4("invProjection", &projection.inverse().to_cols_array());
+        shader.set_vec2(
+            "noiseScale",
+            targets.width as f32 / NOISE_SIZE as f32,
+            targets.height as f32 / NOISE_SIZE as f32,
+        );
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+
+        // Blur over the noise tile, without crossing depth edges
+        targets.bind_occlusion(1);
+        let shader = &self.blur_shader;
+        shader.use_program();
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, targets.occlusion[0]);
+        }
+        shader.set_int("depthMap", 0);
+        shader.set_int("occlusionMap", 1);
+        shader.set_int("blurSize", NOISE_SIZE as i32);
+        shader.set_float("radius", settings.radius);
+        shader.set_mat4("invProjection", &projection.inverse().to_cols_array());
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        self.computed = true;
+    }
+
+    /// Bind this frame's occlusion for a lit shader's ambient terms
+    pub fn apply_to_shader(&self, shader: &Shader) {
+        let occlusion = match &self.targets {
+            Some(targets) if self.computed => targets.occlusion[1],
+            _ => return Self::disable_in_shader(shader),
+        };
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + texture_units::OCCLUSION);
+            gl::BindTexture(gl::TEXTURE_2D, occlusion);
+        }
+        shader.set_int("ssaoMap", texture_units::OCCLUSION as i32);
+        shader.set_int("hasSsao", 1);
+    }
+
+    /// Leave a lit shader's ambient terms unoccluded
+    pub fn disable_in_shader(shader: &Shader) {
+        shader.set_int("ssaoMap", texture_units::OCCLUSION as i32);
+        shader.set_int("hasSsao", 0);
+    }
+
+    /// Draw the raw occlusion of this frame over `output`
+    pub fn draw_debug(&self, output: &Viewport) {
+        let Some(targets) = self.targets.as_ref().filter(|_| self.computed) else {
+            return;
+        };
+        output.bind();
+        self.debug_shader.use_program();
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, targets.occlusion[0]);
+        }
+        self.debug_shader.set_int("occlusionMap", 0);
+        unsafe {
+            gl::BindVertexArray(self.fullscreen_vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
+impl Drop for Ssao {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.noise);
+            gl::DeleteVertexArrays(1, &self.fullscreen_vao);
+        }
+    }
+}
+
+/// Clamped, unmipmapped texture for a screen-sized attachment
+unsafe fn screen_texture(
+    width: u32,
+    height: u32,
+    internal_format: gl::types::GLenum,
+    format: gl::types::GLenum,
+    data_type: gl::types::GLenum,
+    filter: gl::types::GLenum,
+) -> u32 {
+    let mut texture = 0;
+    gl::GenTextures(1, &mut texture);
+    gl::BindTexture(gl::TEXTURE_2D, texture);
+    gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        internal_format as i32,
+        width as i32,
+        height as i32,
+        0,
+        format,
+        data_type,
+        std::ptr::null(),
+    );
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+    texture
+}
+
+/// Unit rotations in the tangent plane, dithered over the tile and repeated over the screen
+fn noise_texture() -> u32 {
+    let cells = (NOISE_SIZE * NOISE_SIZE) as f32;
+    let mut data = Vec::with_capacity(NOISE_SIZE * NOISE_SIZE * 2);
+    for row in BAYER_4X4 {
+        for rank in row {
+            let angle = std::f32::consts::TAU * (rank as f32 + 0.5) / cells;
+            data.push(angle.cos());
+            data.push(angle.sin());
+        }
+    }
+
+    let mut texture = 0;
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RG16F as i32,
+            NOISE_SIZE as i32,
+            NOISE_SIZE as i32,
+            0,
+            gl::RG,
+            gl::FLOAT,
+            data.as_ptr() as *const _,
+        );
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+    }
+    texture
+}
+
+/// `count` samples in the unit hemisphere around +Z: cosine-weighted directions on a
+/// golden-angle spiral, at distances packed towards the center (where occluders matter
+/// most) and decorrelated from the direction by a radical inverse
+fn hemisphere_kernel(count: usize) -> Vec<Vec3> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+    (0..count)
+        .map(|i| {
+            let t = (i as f32 + 0.5) / count as f32;
+            let phi = i as f32 * golden_angle;
+            let r = t.sqrt();
+            let direction = Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - t).sqrt());
+
+            let u = (i as u32).reverse_bits() as f32 / 4_294_967_296.0;
+            direction * (0.1 + 0.9 * u * u)
+        })
+        .collect()
+}
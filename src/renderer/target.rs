@@ -0,0 +1,226 @@
+//! Render Target - Offscreen framebuffers and the output the renderer draws into
+
+use image::{Rgba32FImage, RgbaImage};
+
+/// Framebuffer the final image goes to (0 = the window's default framebuffer)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub framebuffer: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn window(width: u32, height: u32) -> Self {
+        Self {
+            framebuffer: 0,
+            width,
+            height,
+        }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+
+    /// Bind the framebuffer and set the GL viewport to cover it
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::Viewport(0, 0, self.width as i32, self.height as i32);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetFormat {
+    /// 8-bit per channel, for PNG output
+    Rgba8,
+    /// 32-bit float per channel, for EXR output (values above 1.0 are kept)
+    Rgba32F,
+    /// 16-bit float per channel, for the HDR scene color
+    Rgba16F,
+}
+
+/// Offscreen color + depth framebuffer (both attachments can be sampled)
+pub struct RenderTarget {
+    pub fbo: u32,
+    pub color_texture: u32,
+    /// Depth (24-bit) and stencil
+    pub depth_texture: u32,
+    pub width: u32,
+    pub height: u32,
+    pub format: TargetFormat,
+}
+
+impl RenderTarget {
+    pub fn new(width: u32, height: u32, format: TargetFormat) -> Result<Self, String> {
+        let (internal_format, data_type) = match format {
+            TargetFormat::Rgba8 => (gl::RGBA8, gl::UNSIGNED_BYTE),
+            TargetFormat::Rgba32F => (gl::RGBA32F, gl::FLOAT),
+            TargetFormat::Rgba16F => (gl::RGBA16F, gl::FLOAT),
+        };
+
+        let mut fbo = 0;
+        let mut color_texture = 0;
+        let mut depth_texture = 0;
+        let status;
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+
+            gl::GenTextures(1, &mut color_texture);
+            gl::BindTexture(gl::TEXTURE_2D, color_texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                data_type,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            // Screen-space passes sample around the edges
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                color_texture,
+                0,
+            );
+
+            gl::GenTextures(1, &mut depth_texture);
+            gl::BindTexture(gl::TEXTURE_2D, depth_texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::DEPTH24_STENCIL8 as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::DEPTH_STENCIL,
+                gl::UNSIGNED_INT_24_8,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::TEXTURE_2D,
+                depth_texture,
+                0,
+            );
+
+            status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        let target = Self {
+            fbo,
+            color_texture,
+            depth_texture,
+            width,
+            height,
+            format,
+        };
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "Offscreen framebuffer {}x{} ({:?}) is incomplete: 0x{:X}",
+                width, height, format, status
+            ));
+        }
+        Ok(target)
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        Viewport {
+            framebuffer: self.fbo,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Copy the color attachment into `output`, scaled to its size
+    pub fn blit_color(&self, output: &Viewport) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, output.framebuffer);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                0,
+                0,
+                output.width as i32,
+                output.height as i32,
+                gl::COLOR_BUFFER_BIT,
+                gl::LINEAR,
+            );
+        }
+        output.bind();
+    }
+
+    /// Read the color attachment back as 8-bit RGBA (top row first)
+    pub fn read_rgba8(&self) -> RgbaImage {
+        let mut pixels = vec![0u8; (self.width * self.height * 4) as usize];
+        self.read_pixels(gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+        flip_rows(&mut pixels, (self.width * 4) as usize);
+        RgbaImage::from_raw(self.width, self.height, pixels).unwrap()
+    }
+
+    /// Read the color attachment back as float RGBA (top row first)
+    pub fn read_rgba32f(&self) -> Rgba32FImage {
+        let mut pixels = vec![0f32; (self.width * self.height * 4) as usize];
+        self.read_pixels(gl::FLOAT, pixels.as_mut_ptr() as *mut _);
+        flip_rows(&mut pixels, (self.width * 4) as usize);
+        Rgba32FImage::from_raw(self.width, self.height, pixels).unwrap()
+    }
+
+    fn read_pixels(&self, data_type: gl::types::GLenum, out: *mut std::ffi::c_void) {
+        unsafe {
+            gl::Finish();
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                gl::RGBA,
+                data_type,
+                out,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+    }
+}
+
+/// GL returns the bottom row first; images expect the top row first
+fn flip_rows<T>(pixels: &mut [T], row_len: usize) {
+    let rows = pixels.len() / row_len;
+    for y in 0..rows / 2 {
+        let (top, bottom) = pixels.split_at_mut((rows - 1 - y) * row_len);
+        top[y * row_len..(y + 1) * row_len].swap_with_slice(&mut bottom[..row_len]);
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            let textures = [self.color_texture, self.depth_texture];
+            gl::DeleteTextures(textures.len() as i32, textures.as_ptr());
+        }
+    }
+}
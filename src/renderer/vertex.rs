@@ -0,0 +1,118 @@
+//! Vertex Layout - Interleaved per-vertex attributes shared by meshes and primitives
+//!
+//! Every lit vertex is position, UV, normal, tangent and bitangent (14 floats). The
+//! tangent frame comes from the importer when it has one, and is otherwise computed from
+//! the triangles' UVs; vertices without usable UVs get an arbitrary frame around the
+//! normal, so a normal map still applies without distorting the lighting.
+
+use glam::{Vec2, Vec3};
+
+/// Floats per vertex
+pub const VERTEX_FLOATS: usize = 14;
+
+/// Attribute locations in the lit vertex shader (3-6 hold the per-instance model matrix)
+pub const POSITION_ATTRIBUTE: u32 = 0;
+pub const NORMAL_ATTRIBUTE: u32 = 1;
+pub const TEX_COORD_ATTRIBUTE: u32 = 2;
+pub const TANGENT_ATTRIBUTE: u32 = 7;
+pub const BITANGENT_ATTRIBUTE: u32 = 8;
+
+/// Floats per vertex of the position/UV/normal data the primitives generate
+const BASE_FLOATS: usize = 8;
+
+/// Point the vertex attributes of the bound VAO at the bound `ARRAY_BUFFER`
+pub fn set_attribute_pointers() {
+    let float = std::mem::size_of::<f32>();
+    let stride = (VERTEX_FLOATS * float) as i32;
+    let attributes = [
+        (POSITION_ATTRIBUTE, 3, 0),
+        (TEX_COORD_ATTRIBUTE, 2, 3),
+        (NORMAL_ATTRIBUTE, 3, 5),
+        (TANGENT_ATTRIBUTE, 3, 8),
+        (BITANGENT_ATTRIBUTE, 3, 11),
+    ];
+    unsafe {
+        for (location, size, offset) in attributes {
+            gl::VertexAttribPointer(
+                location,
+                size,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (offset * float) as *const _,
+            );
+            gl::EnableVertexAttribArray(location);
+        }
+    }
+}
+
+/// Expand position/UV/normal vertices (8 floats) to the full layout, with tangents
+/// computed over the triangles in `indices`
+pub fn with_tangents(vertices: &[f32], indices: &[u32]) -> Vec<f32> {
+    let mut full = Vec::with_capacity(vertices.len() / BASE_FLOATS * VERTEX_FLOATS);
+    for vertex in vertices.chunks_exact(BASE_FLOATS) {
+        full.extend_from_slice(vertex);
+        full.extend_from_slice(&[0.0; VERTEX_FLOATS - BASE_FLOATS]);
+    }
+    compute_tangents(&mut full, indices);
+    full
+}
+
+/// Fill the tangents and bitangents of full-layout `vertices` from the UVs of the
+/// triangles in `indices`
+pub fn compute_tangents(vertices: &mut [f32], indices: &[u32]) {
+    let count = vertices.len() / VERTEX_FLOATS;
+    let position = |v: &[f32], i: usize| Vec3::from_slice(&v[i * VERTEX_FLOATS..]);
+    let uv = |v: &[f32], i: usize| Vec2::from_slice(&v[i * VERTEX_FLOATS + 3..]);
+
+    // Sum the UV gradients of the triangles around each vertex
+    let mut tangents = vec![Vec3::ZERO; count];
+    let mut bitangents = vec![Vec3::ZERO; count];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| triangle[k] as usize);
+        if a >= count || b >= count || c >= count {
+            continue;
+        }
+        let dp1 = position(vertices, b) - position(vertices, a);
+        let dp2 = position(vertices, c) - position(vertices, a);
+        let duv1 = uv(vertices, b) - uv(vertices, a);
+        let duv2 = uv(vertices, c) - uv(vertices, a);
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        if det.abs() < 1e-12 {
+            continue;
+        }
+        let tangent = (dp1 * duv2.y - dp2 * duv1.y) / det;
+        let bitangent = (dp2 * duv1.x - dp1 * duv2.x) / det;
+        for i in [a, b, c] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    for (i, vertex) in vertices.chunks_exact_mut(VERTEX_FLOATS).enumerate() {
+        let normal = Vec3::from_slice(&vertex[5..]).normalize_or_zero();
+        let (tangent, bitangent) = orthonormal_frame(normal, tangents[i], bitangents[i]);
+        vertex[8..11].copy_from_slice(&tangent.to_array());
+        vertex[11..14].copy_from_slice(&bitangent.to_array());
+    }
+}
+
+/// Tangent and bitangent perpendicular to `normal`, following `tangent` and the
+/// handedness of `bitangent` when they are usable, and an arbitrary frame otherwise
+pub fn orthonormal_frame(normal: Vec3, tangent: Vec3, bitangent: Vec3) -> (Vec3, Vec3) {
+    let normal = if normal == Vec3::ZERO {
+        Vec3::Y
+    } else {
+        normal
+    };
+    let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+    if tangent == Vec3::ZERO {
+        let (tangent, bitangent) = normal.any_orthonormal_pair();
+        return (tangent, bitangent);
+    }
+    let mut generated = normal.cross(tangent);
+    if generated.dot(bitangent) < 0.0 {
+        generated = -generated;
+    }
+    (tangent, generated)
+}
@@ -0,0 +1,378 @@
+//! BVH - Bounding volume hierarchy over world-space collider boxes
+//!
+//! Built top-down by splitting at the median centroid along the widest axis. While the
+//! set of objects stays the same, moved objects only refit the boxes above them; the tree
+//! is rebuilt when objects come or go, or when refitting has made it too loose.
+
+use crate::math::bounds::Aabb;
+use crate::math::ray::Ray;
+use crate::scene::handle::ObjectId;
+use std::collections::HashMap;
+
+/// Rebuild once the root box has grown this much (by surface area) through refits
+const REBUILD_GROWTH: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy)]
+enum NodeKind {
+    Leaf(ObjectId),
+    /// Child node indices; children always come after their parent
+    Branch(usize, usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    aabb: Aabb,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    /// Root first
+    nodes: Vec<Node>,
+    leaf_of: HashMap<ObjectId, usize>,
+    built_area: f32,
+}
+
+impl Bvh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a fresh tree over `items`
+    pub fn build(items: &[(ObjectId, Aabb)]) -> Self {
+        let mut bvh = Self::new();
+        bvh.rebuild(items);
+        bvh
+    }
+
+    /// Bring the tree up to date with the current boxes: refit if the same objects are
+    /// present, rebuild otherwise. Returns true if the tree was rebuilt.
+    pub fn update(&mut self, items: &[(ObjectId, Aabb)]) -> bool {
+        let same_objects = items.len() == self.leaf_of.len()
+            && items.iter().all(|(id, _)| self.leaf_of.contains_key(id));
+        if !same_objects {
+            self.rebuild(items);
+            return true;
+        }
+
+        let mut changed = false;
+        for (id, aabb) in items {
+            let node = &mut self.nodes[self.leaf_of[id]];
+            if node.aabb != *aabb {
+                node.aabb = *aabb;
+                changed = true;
+            }
+        }
+        if !changed {
+            return false;
+        }
+
+        self.refit();
+        if surface_area(&self.nodes[0].aabb) > self.built_area * REBUILD_GROWTH {
+            self.rebuild(items);
+            return true;
+        }
+        false
+    }
+
+    fn rebuild(&mut self, items: &[(ObjectId, Aabb)]) {
+        self.nodes.clear();
+        self.leaf_of.clear();
+
+        let mut items = items.to_vec();
+        if !items.is_empty() {
+            self.build_node(&mut items);
+        }
+        self.built_area = self.nodes.first().map_or(0.0, |n| surface_area(&n.aabb));
+    }
+
+    fn build_node(&mut self, items: &mut [(ObjectId, Aabb)]) -> usize {
+        let index = self.nodes.len();
+        let aabb = items[1..]
+            .iter()
+            .fold(items[0].1, |aabb, (_, item)| aabb.union(item));
+
+        if let [(id, _)] = items {
+            self.nodes.push(Node {
+                aabb,
+                kind: NodeKind::Leaf(*id),
+            });
+            self.leaf_of.insert(*id, index);
+            return index;
+        }
+
+        // Split at the median centroid along the axis the centroids spread furthest
+        let centroids = Aabb::from_points(items.iter().map(|(_, b)| b.center()));
+        let extent = centroids.max - centroids.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |(_, a), (_, b)| {
+            a.center()[axis].total_cmp(&b.center()[axis])
+        });
+
+        // Reserve the slot so children come after the parent
+        self.nodes.push(Node {
+            aabb,
+            kind: NodeKind::Branch(0, 0),
+        });
+        let (left_items, right_items) = items.split_at_mut(mid);
+        let left = self.build_node(left_items);
+        let right = self.build_node(right_items);
+        self.nodes[index].kind = NodeKind::Branch(left, right);
+        index
+    }
+
+    /// Recompute every branch box from its children, bottom-up
+    fn refit(&mut self) {
+        for index in (0..self.nodes.len()).rev() {
+            if let NodeKind::Branch(left, right) = self.nodes[index].kind {
+                self.nodes[index].aabb = self.nodes[left].aabb.union(&self.nodes[right].aabb);
+            }
+        }
+    }
+
+    /// Closest hit along `ray`. `hit_test` does the exact test for a candidate and returns
+    /// its distance; subtrees further away than the best hit so far are skipped.
+    pub fn cast_ray(
+        &self,
+        ray: &Ray,
+        mut hit_test: impl FnMut(ObjectId) -> Option<f32>,
+    ) -> Option<(ObjectId, f32)> {
+        let mut best: Option<(ObjectId, f32)> = None;
+        let mut stack: Vec<(usize, f32)> = Vec::new();
+        if let Some(t) = self.enter(0, ray) {
+            stack.push((0, t));
+        }
+
+        while let Some((index, entry)) = stack.pop() {
+            if best.is_some_and(|(_, dist)| entry >= dist) {
+                continue;
+            }
+            match self.nodes[index].kind {
+                NodeKind::Leaf(id) => {
+                    if let Some(dist) = hit_test(id) {
+                        if best.is_none_or(|(_, best_dist)| dist < best_dist) {
+                            best = Some((id, dist));
+                        }
+                    }
+                }
+                NodeKind::Branch(left, right) => {
+                    let mut near = (left, self.enter(left, ray));
+                    let mut far = (right, self.enter(right, ray));
+                    if far.1.is_some_and(|f| near.1.is_none_or(|n| f < n)) {
+                        std::mem::swap(&mut near, &mut far);
+                    }
+                    // Push the far child first so the near one is visited first
+                    for (child, t) in [far, near] {
+                        if let Some(t) = t {
+                            stack.push((child, t));
+                        }
+                    }
+                }
+            }
+        }
+
+        best
+    }
+
+    /// Every object whose box `ray` passes through, for the caller to test exactly
+    pub fn ray_candidates(&self, ray: &Ray, mut visit: impl FnMut(ObjectId)) {
+        self.visit(
+            |aabb| ray.intersect_aabb(aabb.min, aabb.max).is_some(),
+            &mut visit,
+        );
+    }
+
+    /// Every object whose box overlaps `region`
+    pub fn overlapping(&self, region: &Aabb, mut visit: impl FnMut(ObjectId)) {
+        self.visit(
+            |aabb| aabb.min.cmple(region.max).all() && aabb.max.cmpge(region.min).all(),
+            &mut visit,
+        );
+    }
+
+    fn visit(&self, enters: impl Fn(&Aabb) -> bool, visit: &mut impl FnMut(ObjectId)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !enters(&node.aabb) {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf(id) => visit(id),
+                NodeKind::Branch(left, right) => stack.extend([right, left]),
+            }
+        }
+    }
+
+    /// Distance at which `ray` enters node `index`, if it does
+    fn enter(&self, index: usize, ray: &Ray) -> Option<f32> {
+        let aabb = &self.nodes.get(index)?.aabb;
+        ray.intersect_aabb(aabb.min, aabb.max)
+    }
+}
+
+fn surface_area(aabb: &Aabb) -> f32 {
+    let d = aabb.max - aabb.min;
+    2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::handle::SlotMap;
+    use glam::Vec3;
+    use std::collections::BTreeSet;
+
+    /// Deterministic xorshift, so failures reproduce
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0 as f32 / u32::MAX as f32
+        }
+
+        fn vec3(&mut self, range: f32) -> Vec3 {
+            Vec3::new(self.next(), self.next(), self.next()) * 2.0 * range - range
+        }
+    }
+
+    fn scatter(rng: &mut Rng, ids: &mut SlotMap<()>, count: usize) -> Vec<(ObjectId, Aabb)> {
+        (0..count)
+            .map(|_| {
+                let center = rng.vec3(50.0);
+                let half = Vec3::splat(0.5) + rng.vec3(1.0).abs();
+                (ids.insert(()), Aabb::new(center - half, center + half))
+            })
+            .collect()
+    }
+
+    fn rays(rng: &mut Rng, count: usize) -> Vec<Ray> {
+        (0..count)
+            .map(|_| Ray::new(rng.vec3(60.0), rng.vec3(1.0) + Vec3::splat(1e-3)))
+            .collect()
+    }
+
+    fn box_distance(items: &[(ObjectId, Aabb)], id: ObjectId, ray: &Ray) -> Option<f32> {
+        let (_, aabb) = items.iter().find(|(item, _)| *item == id)?;
+        ray.intersect_aabb(aabb.min, aabb.max)
+    }
+
+    fn linear_nearest(items: &[(ObjectId, Aabb)], ray: &Ray) -> Option<f32> {
+        items
+            .iter()
+            .filter_map(|(_, aabb)| ray.intersect_aabb(aabb.min, aabb.max))
+            .min_by(f32::total_cmp)
+    }
+
+    /// The BVH answers every query the way a scan over `items` does
+    fn assert_matches_linear(bvh: &Bvh, items: &[(ObjectId, Aabb)], rng: &mut Rng) {
+        for ray in rays(rng, 200) {
+            let nearest = bvh.cast_ray(&ray, |id| box_distance(items, id, &ray));
+            assert_eq!(nearest.map(|(_, t)| t), linear_nearest(items, &ray));
+
+            let mut candidates = BTreeSet::new();
+            bvh.ray_candidates(&ray, |id| {
+                candidates.insert(id);
+            });
+            let expected: BTreeSet<ObjectId> = items
+                .iter()
+                .filter(|(_, aabb)| ray.intersect_aabb(aabb.min, aabb.max).is_some())
+                .map(|(id, _)| *id)
+                .collect();
+            assert_eq!(candidates, expected);
+        }
+
+        let region = Aabb::new(Vec3::splat(-20.0), Vec3::new(10.0, 30.0, 5.0));
+        let mut overlapping = BTreeSet::new();
+        bvh.overlapping(&region, |id| {
+            overlapping.insert(id);
+        });
+        let expected: BTreeSet<ObjectId> = items
+            .iter()
+            .filter(|(_, b)| b.min.cmple(region.max).all() && b.max.cmpge(region.min).all())
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(overlapping, expected);
+    }
+
+    #[test]
+    fn built_tree_matches_linear_scan() {
+        let mut rng = Rng(0x9e3779b9);
+        let items = scatter(&mut rng, &mut SlotMap::new(), 300);
+        assert_matches_linear(&Bvh::build(&items), &items, &mut rng);
+    }
+
+    #[test]
+    fn small_moves_refit_without_rebuilding() {
+        let mut rng = Rng(12345);
+        let mut items = scatter(&mut rng, &mut SlotMap::new(), 200);
+        let mut bvh = Bvh::build(&items);
+
+        for (_, aabb) in items.iter_mut().step_by(7) {
+            let offset = rng.vec3(2.0);
+            *aabb = Aabb::new(aabb.min + offset, aabb.max + offset);
+        }
+        assert!(!bvh.update(&items));
+        assert_matches_linear(&bvh, &items, &mut rng);
+
+        // Nothing moved: nothing to do
+        assert!(!bvh.update(&items));
+    }
+
+    #[test]
+    fn refit_that_loosens_the_tree_rebuilds() {
+        let mut rng = Rng(777);
+        let mut items = scatter(&mut rng, &mut SlotMap::new(), 100);
+        let mut bvh = Bvh::build(&items);
+
+        let far = Vec3::splat(500.0);
+        items[0].1 = Aabb::new(items[0].1.min + far, items[0].1.max + far);
+        assert!(bvh.update(&items));
+        assert_matches_linear(&bvh, &items, &mut rng);
+    }
+
+    #[test]
+    fn added_and_removed_objects_rebuild() {
+        let mut rng = Rng(4242);
+        let mut ids = SlotMap::new();
+        let mut items = scatter(&mut rng, &mut ids, 150);
+        let mut bvh = Bvh::build(&items);
+
+        let removed = items.swap_remove(10).0;
+        ids.remove(removed);
+        assert!(bvh.update(&items));
+        assert_matches_linear(&bvh, &items, &mut rng);
+
+        let mut seen = false;
+        bvh.overlapping(&Aabb::new(Vec3::splat(-100.0), Vec3::splat(100.0)), |id| {
+            seen |= id == removed;
+        });
+        assert!(!seen);
+
+        // The first new object reuses the removed one's slot
+        items.extend(scatter(&mut rng, &mut ids, 5));
+        assert!(bvh.update(&items));
+        assert_matches_linear(&bvh, &items, &mut rng);
+    }
+
+    #[test]
+    fn empty_tree_finds_nothing() {
+        let bvh = Bvh::build(&[]);
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+        assert!(bvh.cast_ray(&ray, |_| Some(1.0)).is_none());
+        bvh.ray_candidates(&ray, |_| panic!("no objects to visit"));
+    }
+}
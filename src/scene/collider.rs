@@ -1,20 +1,26 @@
+use crate::math::bounds::Aabb;
 use crate::math::ray::Ray;
-use crate::scene::transform::Transform;
-use glam::Vec3;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ColliderShape {
     Sphere { radius: f32 },
     Box { min: Vec3, max: Vec3 },
     // Add Capsule later
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Collider {
     pub shape: ColliderShape,
+    #[serde(default = "default_enabled")]
     pub enabled: bool,
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
 impl Collider {
     pub fn new_sphere(radius: f32) -> Self {
         Self {
@@ -36,15 +42,24 @@ impl Collider {
         Self::new_box(Vec3::splat(-half), Vec3::splat(half))
     }
 
-    /// Check for intersection with a ray.
+    /// Local-space box around the shape
+    pub fn local_aabb(&self) -> Aabb {
+        match self.shape {
+            ColliderShape::Sphere { radius } => {
+                Aabb::new(Vec3::splat(-radius), Vec3::splat(radius))
+            }
+            ColliderShape::Box { min, max } => Aabb::new(min, max),
+        }
+    }
+
+    /// Check for intersection with a ray, given the object's world matrix.
     /// Returns the distance along the ray (t) if intersected.
-    pub fn intersect(&self, ray: &Ray, transform: &Transform) -> Option<f32> {
+    pub fn intersect(&self, ray: &Ray, world_matrix: &Mat4) -> Option<f32> {
         if !self.enabled {
             return None;
         }
 
-        let model_matrix = transform.to_matrix();
-        let inverse_model = model_matrix.inverse();
+        let inverse_model = world_matrix.inverse();
 
         let local_origin = inverse_model.transform_point3(ray.origin);
         let local_direction = inverse_model.transform_vector3(ray.direction);
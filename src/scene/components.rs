@@ -0,0 +1,67 @@
+//! Components - World data that has no engine type of its own
+//!
+//! Engine types are attached to entities as they are: `Transform` (local),
+//! `Collider`, `Rc<dyn Renderable>`, `Rc<dyn Material>` and `Box<dyn Controller>`.
+
+use crate::math::bounds::Bounds;
+use crate::scene::material::Material;
+use crate::scene::world::Entity;
+use glam::{Mat4, Vec3};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Display name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Name(pub String);
+
+/// Cached local-to-world matrix, refreshed by `systems::propagate_transforms`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobalTransform(pub Mat4);
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self(Mat4::IDENTITY)
+    }
+}
+
+impl GlobalTransform {
+    pub fn position(&self) -> Vec3 {
+        self.0.w_axis.truncate()
+    }
+}
+
+/// Mesh bounds in world space, refreshed by `systems::update_world_bounds`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldBounds(pub Bounds);
+
+/// Free-form labels used to find objects, e.g. "wall"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags(pub Vec<String>);
+
+impl Tags {
+    pub fn contains(&self, tag: &str) -> bool {
+        self.0.iter().any(|t| t == tag)
+    }
+}
+
+/// Hierarchy links, maintained by `Scene::set_parent`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parent(pub Entity);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Children(pub Vec<Entity>);
+
+/// Scene file mesh/material names an entity was built from (needed to save it back)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetNames {
+    pub mesh: String,
+    /// Empty when the object uses its model's own materials
+    pub material: String,
+    /// Model material name -> scene material name
+    pub mesh_materials: BTreeMap<String, String>,
+}
+
+/// One material per part of the entity's renderable (see `Renderable::part_count`),
+/// drawn in place of its single `Rc<dyn Material>`
+#[derive(Clone)]
+pub struct MeshMaterials(pub Vec<Rc<dyn Material>>);
@@ -1,5 +1,12 @@
-use crate::light::{DirectionalLight, Light, PointLight, SpotLight};
+use crate::config::rendering::texture_units;
+use crate::light::{DirectionalLight, Light, PointLight};
+use crate::renderer::clusters::LightClusters;
+use crate::renderer::ibl::Environment;
+use crate::renderer::ssao::Ssao;
 use crate::shaders::Shader;
+use crate::shadow::cascades::Cascade;
+use crate::shadow::scheduler::PointShadowScheduler;
+use crate::shadow::spot::SpotShadowMap;
 use crate::shadow::ShadowMap;
 use glam::{Mat4, Vec3};
 
@@ -8,52 +15,76 @@ pub struct RenderContext<'a> {
     pub view: Mat4,
     pub view_pos: Vec3,
     pub light: &'a DirectionalLight,
+    /// Point lights of the scene (the deferred path draws one volume each)
     pub point_lights: &'a [PointLight],
-    pub spot_lights: &'a [SpotLight],
+    /// Point and spot lights binned for the lit shaders
+    pub clusters: &'a LightClusters,
     pub shadow_map: &'a ShadowMap,
+    /// Cube maps of the shadow-casting point lights, by scheduler slot
     pub point_shadow_maps: &'a [crate::shadow::PointShadowMap],
-    pub far_plane: f32,
-    pub light_space_matrix: Mat4,
+    /// Which light each cube map belongs to
+    pub point_shadows: &'a PointShadowScheduler,
+    /// Depth layers of the shadow-casting spot lights this frame
+    pub spot_shadow_map: &'a SpotShadowMap,
+    pub cascades: &'a [Cascade],
+    /// Fraction of each cascade blended into the next
+    pub cascade_blend: f32,
+    /// Baked lighting of the skybox, for ambient light and reflections
+    pub environment: Option<&'a Environment>,
+    pub environment_intensity: f32,
+    /// Screen-space ambient occlusion of this frame, darkening the ambient terms
+    pub ssao: Option<&'a Ssao>,
 }
 
 impl<'a> RenderContext<'a> {
     pub fn apply_lighting(&self, shader: &Shader) {
         self.light.apply_to_shader(shader, self.view_pos);
-        let mut point_light_count = 0;
-        for (i, pl) in self.point_lights.iter().enumerate() {
-            if i >= crate::config::rendering::MAX_POINT_LIGHTS {
-                break;
-            }
-            pl.apply_to_shader_indexed(shader, i, self.view_pos);
-            point_light_count += 1;
-        }
-        shader.set_int("nrPointLights", point_light_count as i32);
-
-        let mut spot_light_count = 0;
-        for (i, sl) in self.spot_lights.iter().enumerate() {
-            if i >= crate::config::rendering::MAX_SPOT_LIGHTS {
-                break;
-            }
-            sl.apply_to_shader_indexed(shader, i);
-            spot_light_count += 1;
-        }
-        shader.set_int("nrSpotLights", spot_light_count as i32);
 
-        // We also need to set lightSpaceMatrix and bind shadow map
-        shader.set_mat4("lightSpaceMatrix", &self.light_space_matrix.to_cols_array());
+        // Point and spot lights, binned per cluster
+        self.clusters.apply_to_shader(shader);
 
-        // Assuming shadow map is always bound to unit 5
-        self.shadow_map.bind_shadow_map(5);
-        shader.set_int("shadowMap", 5);
+        // Directional shadow cascades
+        let cascades = self.cascades;
+        shader.set_int("cascadeCount", cascades.len() as i32);
+        let matrices: Vec<[f32; 16]> = cascades
+            .iter()
+            .map(|c| c.light_space_matrix.to_cols_array())
+            .collect();
+        shader.set_mat4_array("lightSpaceMatrices", &matrices);
+        let splits: Vec<f32> = cascades.iter().map(|c| c.split_far).collect();
+        shader.set_float_array("cascadeSplits", &splits);
+        let texel_sizes: Vec<f32> = cascades.iter().map(|c| c.texel_size).collect();
+        shader.set_float_array("cascadeTexelSizes", &texel_sizes);
+        shader.set_float("cascadeBlend", self.cascade_blend);
 
+        self.shadow_map
+            .bind_shadow_map(texture_units::CASCADE_DEPTH);
+        shader.set_int("shadowMap", texture_units::CASCADE_DEPTH as i32);
+        self.shadow_map.apply_filtering(shader);
+
+        let mut depth_units = Vec::with_capacity(self.point_shadow_maps.len());
         for (i, psm) in self.point_shadow_maps.iter().enumerate() {
-            if i >= crate::config::rendering::MAX_POINT_LIGHTS {
-                break;
-            }
-            psm.bind_cubemap(6 + i as u32);
-            shader.set_int(&format!("pointShadowMaps[{}]", i), (6 + i) as i32);
+            let unit = texture_units::POINT_SHADOW_MAPS + i as u32;
+            psm.bind_cubemap(unit);
+            depth_units.push(unit as i32);
+        }
+        shader.set_int_array("pointShadowMaps", &depth_units);
+        self.spot_shadow_map.apply_to_shader(shader);
+
+        // Image-based ambient light and reflections
+        match self.environment {
+            Some(environment) => environment.apply_to_shader(shader, self.environment_intensity),
+            None => Environment::disable_in_shader(shader),
         }
-        shader.set_float("farPlane", self.far_plane);
+        self.apply_occlusion(shader);
         shader.set_vec3("viewPos", self.view_pos.x, self.view_pos.y, self.view_pos.z);
     }
+
+    /// Bind the ambient occlusion, or turn it off in `shader`
+    pub fn apply_occlusion(&self, shader: &Shader) {
+        match self.ssao {
+            Some(ssao) => ssao.apply_to_shader(shader),
+            None => Ssao::disable_in_shader(shader),
+        }
+    }
 }
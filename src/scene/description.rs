@@ -0,0 +1,370 @@
+//! Scene Description - Serializable (RON) form of a scene
+//!
+//! Meshes and materials are declared once by name and referenced from objects,
+//! so a description maps 1:1 onto the shared `Rc` resources built at load time.
+
+use crate::assets::paths::{names, shaders};
+use crate::assets::AssetManager;
+use crate::light::{DirectionalLight, PointLight, SpotLight};
+use crate::logic::controller::ControllerDescription;
+use crate::primitives::{Capsule, Cube, Plane, Sphere};
+use crate::scene::collider::Collider;
+use crate::scene::material::{
+    BlendMode, ColoredMaterial, Material, PbrMaps, PbrMaterial, TexturedMaterial,
+};
+use crate::scene::material_factory::MaterialFactory;
+use crate::scene::object::Renderable;
+use crate::scene::transform::Transform;
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_white() -> Vec3 {
+    Vec3::ONE
+}
+
+fn default_roughness() -> f32 {
+    0.5
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub meshes: Vec<MeshDescription>,
+    #[serde(default)]
+    pub materials: Vec<MaterialDescription>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+    #[serde(default)]
+    pub lights: LightsDescription,
+}
+
+/// Named mesh that objects refer to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeshDescription {
+    pub name: String,
+    pub source: MeshSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MeshSource {
+    Cube {
+        size: f32,
+    },
+    Sphere {
+        radius: f32,
+        sectors: u32,
+        stacks: u32,
+    },
+    Capsule {
+        radius: f32,
+        height: f32,
+        sectors: u32,
+        cap_stacks: u32,
+        cylinder_stacks: u32,
+    },
+    Plane {
+        size: f32,
+    },
+    /// Model file loaded through the asset importer (cached by path)
+    Model {
+        path: String,
+    },
+}
+
+impl MeshSource {
+    pub fn build(&self, assets: &mut AssetManager) -> Result<Rc<dyn Renderable>, String> {
+        Ok(match self {
+            MeshSource::Cube { size } => Rc::new(Cube::new(*size)),
+            MeshSource::Sphere {
+                radius,
+                sectors,
+                stacks,
+            } => Rc::new(Sphere::new(*radius, *sectors, *stacks)),
+            MeshSource::Capsule {
+                radius,
+                height,
+                sectors,
+                cap_stacks,
+                cylinder_stacks,
+            } => Rc::new(Capsule::new(
+                *radius,
+                *height,
+                *sectors,
+                *cap_stacks,
+                *cylinder_stacks,
+            )),
+            MeshSource::Plane { size } => Rc::new(Plane::new(*size)),
+            MeshSource::Model { path } => match assets.get_model(path) {
+                Some(model) => model,
+                None => assets.load_model(path, path)?,
+            },
+        })
+    }
+}
+
+/// Named material that objects refer to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaterialDescription {
+    pub name: String,
+    pub source: MaterialSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MaterialSource {
+    Colored {
+        color: Vec3,
+        #[serde(default = "default_one")]
+        opacity: f32,
+        #[serde(default)]
+        blend_mode: BlendMode,
+        #[serde(default = "default_true")]
+        lit: bool,
+        #[serde(default = "default_true")]
+        receive_shadows: bool,
+    },
+    Textured {
+        texture: String,
+        /// UV tiling; `None` samples the texture once across the mesh
+        #[serde(default)]
+        uv_scale: Option<Vec2>,
+        /// Tangent-space normal map, tiled like the texture
+        #[serde(default)]
+        normal_map: Option<String>,
+        #[serde(default = "default_one")]
+        normal_scale: f32,
+        /// Multiplies the texture's alpha
+        #[serde(default = "default_one")]
+        opacity: f32,
+        #[serde(default)]
+        blend_mode: BlendMode,
+        #[serde(default = "default_true")]
+        lit: bool,
+        #[serde(default = "default_true")]
+        receive_shadows: bool,
+    },
+    /// Metallic-roughness material; each map given is multiplied with its factor
+    Pbr {
+        #[serde(default = "default_white")]
+        base_color: Vec3,
+        #[serde(default)]
+        metallic: f32,
+        #[serde(default = "default_roughness")]
+        roughness: f32,
+        #[serde(default)]
+        emissive: Vec3,
+        #[serde(default)]
+        base_color_map: Option<String>,
+        /// Roughness in G, metallic in B
+        #[serde(default)]
+        metallic_roughness_map: Option<String>,
+        #[serde(default)]
+        normal_map: Option<String>,
+        #[serde(default = "default_one")]
+        normal_scale: f32,
+        #[serde(default)]
+        occlusion_map: Option<String>,
+        #[serde(default = "default_one")]
+        occlusion_strength: f32,
+        #[serde(default)]
+        emissive_map: Option<String>,
+        #[serde(default)]
+        uv_scale: Option<Vec2>,
+        /// Multiplies the base color map's alpha
+        #[serde(default = "default_one")]
+        opacity: f32,
+        #[serde(default)]
+        blend_mode: BlendMode,
+        #[serde(default = "default_true")]
+        receive_shadows: bool,
+    },
+}
+
+impl MaterialSource {
+    pub fn build(
+        &self,
+        factory: &MaterialFactory,
+        assets: &mut AssetManager,
+    ) -> Result<Rc<dyn Material>, String> {
+        Ok(match self {
+            MaterialSource::Colored {
+                color,
+                opacity,
+                blend_mode,
+                lit,
+                receive_shadows,
+            } => Rc::new(ColoredMaterial {
+                opacity: *opacity,
+                blend_mode: *blend_mode,
+                ..factory.colored_material(*color, *lit, *receive_shadows)
+            }),
+            MaterialSource::Textured {
+                texture,
+                uv_scale,
+                normal_map,
+                normal_scale,
+                opacity,
+                blend_mode,
+                lit,
+                receive_shadows,
+            } => {
+                let texture = match assets.get_texture(texture, true) {
+                    Some(tex) => tex,
+                    None => assets.load_texture(texture, texture)?,
+                };
+                let normal_map = normal_map
+                    .as_ref()
+                    .map(|path| match assets.get_texture(path, false) {
+                        Some(tex) => Ok(tex),
+                        None => assets.load_linear_texture(path, path),
+                    })
+                    .transpose()?;
+                Rc::new(TexturedMaterial {
+                    normal_map,
+                    normal_scale: *normal_scale,
+                    opacity: *opacity,
+                    blend_mode: *blend_mode,
+                    ..factory.textured_material(texture, *uv_scale, *lit, *receive_shadows)
+                })
+            }
+            MaterialSource::Pbr {
+                base_color,
+                metallic,
+                roughness,
+                emissive,
+                base_color_map,
+                metallic_roughness_map,
+                normal_map,
+                normal_scale,
+                occlusion_map,
+                occlusion_strength,
+                emissive_map,
+                uv_scale,
+                opacity,
+                blend_mode,
+                receive_shadows,
+            } => {
+                // Color maps are sRGB, the others hold linear data
+                let mut load = |path: &Option<String>, srgb: bool| {
+                    path.as_ref()
+                        .map(|path| match assets.get_texture(path, srgb) {
+                            Some(tex) => Ok(tex),
+                            None if srgb => assets.load_texture(path, path),
+                            None => assets.load_linear_texture(path, path),
+                        })
+                        .transpose()
+                };
+                let maps = PbrMaps {
+                    base_color: load(base_color_map, true)?,
+                    metallic_roughness: load(metallic_roughness_map, false)?,
+                    normal: load(normal_map, false)?,
+                    occlusion: load(occlusion_map, false)?,
+                    emissive: load(emissive_map, true)?,
+                };
+                Rc::new(PbrMaterial {
+                    base_color: *base_color,
+                    metallic: *metallic,
+                    roughness: *roughness,
+                    emissive: *emissive,
+                    normal_scale: *normal_scale,
+                    occlusion_strength: *occlusion_strength,
+                    maps,
+                    uv_scale: uv_scale.unwrap_or(Vec2::ONE),
+                    opacity: *opacity,
+                    blend_mode: *blend_mode,
+                    receive_shadows: *receive_shadows,
+                    ..factory.pbr_material()
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectDescription {
+    pub name: String,
+    /// Name of the parent object; `transform` is then relative to it
+    #[serde(default)]
+    pub parent: Option<String>,
+    /// Free-form labels, e.g. "wall"
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Named groups the object belongs to; members keep declaration order
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub transform: Transform,
+    pub mesh: String,
+    /// Material of the whole object; leave it out to use a model mesh's own materials
+    #[serde(default)]
+    pub material: String,
+    /// Model material name -> material to draw those meshes with instead
+    #[serde(default)]
+    pub mesh_materials: BTreeMap<String, String>,
+    #[serde(default)]
+    pub collider: Option<Collider>,
+    #[serde(default)]
+    pub controller: Option<ControllerDescription>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LightsDescription {
+    #[serde(default)]
+    pub directional: DirectionalLight,
+    #[serde(default)]
+    pub point: Vec<PointLight>,
+    #[serde(default)]
+    pub spot: Vec<SpotLight>,
+}
+
+/// Material factory backed by the lit, G-buffer and PBR shaders (loaded on first use)
+pub fn lit_material_factory(assets: &mut AssetManager) -> MaterialFactory {
+    let colored = match assets.get_shader(names::SHADER_COLORED) {
+        Some(shader) => shader,
+        None => assets.load_shader(
+            names::SHADER_COLORED,
+            shaders::LIT_VERT,
+            shaders::LIT_COLOR_FRAG,
+        ),
+    };
+    let textured = match assets.get_shader(names::SHADER_TEXTURED) {
+        Some(shader) => shader,
+        None => assets.load_shader(
+            names::SHADER_TEXTURED,
+            shaders::LIT_VERT,
+            shaders::LIT_TEXTURED_FRAG,
+        ),
+    };
+    let gbuffer_colored = match assets.get_shader(names::SHADER_GBUFFER_COLORED) {
+        Some(shader) => shader,
+        None => assets.load_shader(
+            names::SHADER_GBUFFER_COLORED,
+            shaders::LIT_VERT,
+            shaders::GBUFFER_COLOR_FRAG,
+        ),
+    };
+    let gbuffer_textured = match assets.get_shader(names::SHADER_GBUFFER_TEXTURED) {
+        Some(shader) => shader,
+        None => assets.load_shader(
+            names::SHADER_GBUFFER_TEXTURED,
+            shaders::LIT_VERT,
+            shaders::GBUFFER_TEXTURED_FRAG,
+        ),
+    };
+    let pbr = match assets.get_shader(names::SHADER_PBR) {
+        Some(shader) => shader,
+        None => assets.load_shader(names::SHADER_PBR, shaders::LIT_VERT, shaders::LIT_PBR_FRAG),
+    };
+    MaterialFactory::new(colored, textured)
+        .with_deferred(gbuffer_colored, gbuffer_textured)
+        .with_pbr(pbr)
+}
@@ -0,0 +1,208 @@
+//! Generational handles - O(1) object lookup that rejects stale ids
+//!
+//! A slot keeps its index across removals but bumps its generation, so a handle
+//! to a removed object never resolves to whatever reuses the slot later.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId {
+    index: u32,
+    generation: u32,
+}
+
+impl ObjectId {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Dense slot storage addressed by `ObjectId`
+pub struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> SlotMap<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) -> ObjectId {
+        self.len += 1;
+
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.value = Some(value);
+            return ObjectId {
+                index,
+                generation: slot.generation,
+            };
+        }
+
+        let index = self.slots.len() as u32;
+        self.slots.push(Slot {
+            generation: 0,
+            value: Some(value),
+        });
+        ObjectId {
+            index,
+            generation: 0,
+        }
+    }
+
+    /// Remove the value; the slot's generation is bumped so `id` goes stale
+    pub fn remove(&mut self, id: ObjectId) -> Option<T> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<&T> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut T> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    /// Live entries in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectId, &T)> {
+        self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            slot.value.as_ref().map(|value| {
+                (
+                    ObjectId {
+                        index: i as u32,
+                        generation: slot.generation,
+                    },
+                    value,
+                )
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (ObjectId, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(i, slot)| {
+            let generation = slot.generation;
+            slot.value.as_mut().map(|value| {
+                (
+                    ObjectId {
+                        index: i as u32,
+                        generation,
+                    },
+                    value,
+                )
+            })
+        })
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().filter_map(|slot| slot.value.as_mut())
+    }
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_slot_is_reused_with_a_new_generation() {
+        let mut map = SlotMap::new();
+        let a = map.insert("a");
+        let b = map.insert("b");
+        assert_eq!(map.remove(a), Some("a"));
+
+        let c = map.insert("c");
+        assert_eq!(c.index(), a.index());
+        assert_ne!(c, a);
+        assert_eq!(map.get(c), Some(&"c"));
+        assert_eq!(map.get(b), Some(&"b"));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn stale_ids_resolve_to_nothing() {
+        let mut map = SlotMap::new();
+        let a = map.insert(1);
+        map.remove(a);
+        let b = map.insert(2);
+
+        assert_eq!(map.get(a), None);
+        assert_eq!(map.get_mut(a), None);
+        assert_eq!(map.remove(a), None);
+        assert_eq!(map.get(b), Some(&2));
+    }
+
+    #[test]
+    fn removing_twice_only_frees_the_slot_once() {
+        let mut map = SlotMap::new();
+        let a = map.insert(1);
+        assert_eq!(map.remove(a), Some(1));
+        assert_eq!(map.remove(a), None);
+        assert!(map.is_empty());
+
+        // One free slot, so the second insert has to grow the storage
+        let b = map.insert(2);
+        let c = map.insert(3);
+        assert_ne!(b.index(), c.index());
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn iteration_skips_removed_slots() {
+        let mut map = SlotMap::new();
+        let ids: Vec<ObjectId> = (0..4).map(|i| map.insert(i)).collect();
+        map.remove(ids[1]);
+        map.remove(ids[2]);
+
+        let live: Vec<(ObjectId, i32)> = map.iter().map(|(id, &v)| (id, v)).collect();
+        assert_eq!(live, vec![(ids[0], 0), (ids[3], 3)]);
+    }
+}
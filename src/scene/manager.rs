@@ -1,84 +1,1129 @@
+use crate::assets::AssetManager;
+use crate::light::{DirectionalLight, PointLight, SpotLight};
+use crate::logic::Controller;
+use crate::math::bounds::Aabb;
 use crate::math::ray::Ray;
-use crate::scene::object::SceneObject3D;
+use crate::scene::bvh::Bvh;
+use crate::scene::collider::Collider;
+use crate::scene::components::{AssetNames, Children, GlobalTransform, Name, Parent, Tags};
+use crate::scene::description::{
+    lit_material_factory, LightsDescription, MaterialDescription, MeshDescription, MeshSource,
+    ObjectDescription, SceneDescription,
+};
+use crate::scene::handle::ObjectId;
+use crate::scene::material::Material;
+use crate::scene::material_factory::MaterialFactory;
+use crate::scene::object::{Renderable, SceneObject3D};
+use crate::scene::systems;
+use crate::scene::transform::Transform;
+use crate::scene::world::World;
+use glam::Mat4;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+/// Material of an object and, when its parts differ, the material of each part
+type ObjectMaterials = (Rc<dyn Material>, Option<Vec<Rc<dyn Material>>>);
+
+/// What happens to the children of a removed object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildPolicy {
+    /// Remove the whole subtree along with the object
+    Despawn,
+    /// Attach the children to the removed object's parent, keeping their world transforms
+    Reparent,
+}
 
 pub struct Scene {
-    pub objects: Vec<SceneObject3D>,
-    // Special object IDs for animation/logic (keeping them for convenience)
-    pub green_cube_id: usize,
-    pub red_cube_id: usize,
-    pub orbiting_sphere_ids: Vec<usize>,
-    pub capsule_ids: Vec<usize>,
-    pub statue_ids: Vec<usize>,
-    pub xwing_id: usize,
+    pub world: World,
+
+    // Lights
+    pub light: DirectionalLight,
+    pub point_lights: Vec<PointLight>,
+    pub spot_lights: Vec<SpotLight>,
+
+    // Mesh/material definitions referenced by objects (kept for saving)
+    pub meshes: Vec<MeshDescription>,
+    pub materials: Vec<MaterialDescription>,
+
+    // Named groups of objects, members in insertion order (sorted by name for saving)
+    groups: BTreeMap<String, Vec<ObjectId>>,
+
+    // Collider hierarchy for ray and region queries, refreshed with the world transforms
+    bvh: Bvh,
 }
 
 impl Scene {
     pub fn new() -> Self {
         Self {
-            objects: Vec::new(),
-            green_cube_id: 0,
-            red_cube_id: 0,
-            orbiting_sphere_ids: Vec::new(),
-            capsule_ids: Vec::new(),
-            statue_ids: Vec::new(),
-            xwing_id: 0,
+            world: World::new(),
+            light: DirectionalLight::default(),
+            point_lights: Vec::new(),
+            spot_lights: Vec::new(),
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            groups: BTreeMap::new(),
+            bvh: Bvh::new(),
+        }
+    }
+
+    /// Load a scene from a RON scene file
+    pub fn load(path: &str, assets: &mut AssetManager) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scene '{}': {}", path, e))?;
+        let description: SceneDescription = ron::from_str(&source)
+            .map_err(|e| format!("Failed to parse scene '{}': {}", path, e))?;
+
+        println!(
+            "Loaded scene: {}, objects: {}",
+            path,
+            description.objects.len()
+        );
+
+        Self::from_description(description, assets)
+    }
+
+    /// Write the scene to a RON scene file
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let description = self.to_description()?;
+        let source = ron::ser::to_string_pretty(&description, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("Failed to serialize scene '{}': {}", path, e))?;
+        std::fs::write(path, source).map_err(|e| format!("Failed to write scene '{}': {}", path, e))
+    }
+
+    /// Build meshes, materials and objects from a description
+    pub fn from_description(
+        description: SceneDescription,
+        assets: &mut AssetManager,
+    ) -> Result<Self, String> {
+        let factory = lit_material_factory(assets);
+
+        let mut meshes: HashMap<&str, Rc<dyn Renderable>> = HashMap::new();
+        for mesh in &description.meshes {
+            let built = mesh
+                .source
+                .build(assets)
+                .map_err(|e| format!("Mesh '{}': {}", mesh.name, e))?;
+            meshes.insert(&mesh.name, built);
+        }
+
+        let mut materials: HashMap<&str, Rc<dyn Material>> = HashMap::new();
+        for material in &description.materials {
+            let built = material
+                .source
+                .build(&factory, assets)
+                .map_err(|e| format!("Material '{}': {}", material.name, e))?;
+            materials.insert(&material.name, built);
+        }
+
+        Self::assemble(&description, &meshes, |desc| {
+            let model_path = description
+                .meshes
+                .iter()
+                .find(|m| m.name == desc.mesh)
+                .and_then(|m| match &m.source {
+                    MeshSource::Model { path } => Some(path.as_str()),
+                    _ => None,
+                });
+            let mesh_materials =
+                object_mesh_materials(desc, model_path, &materials, &factory, assets)?;
+            let material = if desc.material.is_empty() {
+                match mesh_materials.as_ref().and_then(|parts| parts.first()) {
+                    Some(material) => material.clone(),
+                    None => factory.textured(assets.white_texture()),
+                }
+            } else {
+                find_material(&materials, desc, &desc.material)?
+            };
+            Ok((material, mesh_materials))
+        })
+    }
+
+    /// Spawn the objects of a description whose meshes are built, with the material and
+    /// per-mesh materials `object_materials` gives each, then resolve the hierarchy and
+    /// copy the lights and mesh/material declarations
+    fn assemble(
+        description: &SceneDescription,
+        meshes: &HashMap<&str, Rc<dyn Renderable>>,
+        mut object_materials: impl FnMut(&ObjectDescription) -> Result<ObjectMaterials, String>,
+    ) -> Result<Self, String> {
+        let mut scene = Scene::new();
+        let mut ids = Vec::with_capacity(description.objects.len());
+
+        for desc in &description.objects {
+            let mesh = meshes.get(desc.mesh.as_str()).ok_or_else(|| {
+                format!(
+                    "Object '{}' references unknown mesh '{}'",
+                    desc.name, desc.mesh
+                )
+            })?;
+            let (material, mesh_materials) = object_materials(desc)?;
+
+            let mut obj = SceneObject3D::new(mesh.clone(), material)
+                .with_name(&desc.name)
+                .with_asset_names(&desc.mesh, &desc.material);
+            obj.mesh_materials = mesh_materials;
+            if let Some(names) = obj.asset_names.as_mut() {
+                names.mesh_materials = desc.mesh_materials.clone();
+            }
+            obj.transform = desc.transform;
+            obj.tags = desc.tags.clone();
+            obj.collider = desc.collider.clone();
+            obj.controller = desc.controller.as_ref().map(|c| c.build());
+
+            let id = scene.add_object(obj);
+            for group in &desc.groups {
+                scene.add_to_group(id, group);
+            }
+            ids.push(id);
         }
+
+        // Resolve hierarchy once every object exists (parents may be declared later)
+        for (&child_id, desc) in ids.iter().zip(&description.objects) {
+            if let Some(parent_name) = &desc.parent {
+                let parent_id = ids
+                    .iter()
+                    .zip(&description.objects)
+                    .find(|(_, d)| &d.name == parent_name)
+                    .map(|(&id, _)| id)
+                    .ok_or_else(|| {
+                        format!(
+                            "Object '{}' references unknown parent '{}'",
+                            desc.name, parent_name
+                        )
+                    })?;
+                scene.set_parent(child_id, Some(parent_id), false)?;
+            }
+        }
+        scene.update_world_transforms();
+
+        scene.light = description.lights.directional.clone();
+        scene.point_lights = description.lights.point.clone();
+        scene.spot_lights = description.lights.spot.clone();
+        scene.meshes = description.meshes.clone();
+        scene.materials = description.materials.clone();
+
+        Ok(scene)
     }
 
-    pub fn add_object(&mut self, obj: SceneObject3D) -> usize {
-        let id = obj.id;
-        self.objects.push(obj);
+    /// Capture the current state as a description.
+    /// Fails if an object was not built from a named mesh and material.
+    pub fn to_description(&self) -> Result<SceneDescription, String> {
+        let mut objects = Vec::with_capacity(self.world.len());
+        let mut error = None;
+
+        self.world.query::<(
+            &Name,
+            &Transform,
+            Option<&Tags>,
+            Option<&AssetNames>,
+            Option<&Parent>,
+            Option<&Collider>,
+            Option<&Box<dyn Controller>>,
+        )>(
+            |id, (name, transform, tags, assets, parent, collider, controller)| {
+                let Some(assets) = assets else {
+                    error.get_or_insert_with(|| {
+                        format!(
+                            "Object '{}' has no mesh/material names and cannot be saved",
+                            name.0
+                        )
+                    });
+                    return;
+                };
+
+                let parent = parent
+                    .and_then(|p| self.world.get::<Name>(p.0))
+                    .map(|n| n.0.clone());
+
+                objects.push(ObjectDescription {
+                    name: name.0.clone(),
+                    parent,
+                    tags: tags.map(|t| t.0.clone()).unwrap_or_default(),
+                    groups: self.groups_of(id),
+                    transform: *transform,
+                    mesh: assets.mesh.clone(),
+                    material: assets.material.clone(),
+                    mesh_materials: assets.mesh_materials.clone(),
+                    collider: collider.cloned(),
+                    controller: controller.map(|c| c.describe()),
+                });
+            },
+        );
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        Ok(SceneDescription {
+            meshes: self.meshes.clone(),
+            materials: self.materials.clone(),
+            objects,
+            lights: LightsDescription {
+                directional: self.light.clone(),
+                point: self.point_lights.clone(),
+                spot: self.spot_lights.clone(),
+            },
+        })
+    }
+
+    /// Spawn an object's components; unnamed objects are called "Object <index>"
+    pub fn add_object(&mut self, obj: SceneObject3D) -> ObjectId {
+        let named = !obj.name.is_empty();
+        let id = obj.spawn(&mut self.world);
+        if !named {
+            self.world
+                .insert(id, Name(format!("Object {}", id.index())));
+        }
         id
     }
 
-    pub fn get_object_mut(&mut self, id: usize) -> Option<&mut SceneObject3D> {
-        self.objects.iter_mut().find(|obj| obj.id == id)
+    /// Name of a live object
+    pub fn name_of(&self, id: ObjectId) -> Option<String> {
+        self.world.get::<Name>(id).map(|n| n.0.clone())
+    }
+
+    /// First object with the given name
+    pub fn find_by_name(&self, name: &str) -> Option<ObjectId> {
+        let mut found = None;
+        self.world.query::<&Name>(|id, n| {
+            if found.is_none() && n.0 == name {
+                found = Some(id);
+            }
+        });
+        found
+    }
+
+    /// Every object carrying `tag`, in slot order
+    pub fn find_with_tag(&self, tag: &str) -> Vec<ObjectId> {
+        let mut found = Vec::new();
+        self.world.query::<&Tags>(|id, tags| {
+            if tags.contains(tag) {
+                found.push(id);
+            }
+        });
+        found
+    }
+
+    pub fn has_tag(&self, id: ObjectId, tag: &str) -> bool {
+        self.world
+            .get::<Tags>(id)
+            .is_some_and(|tags| tags.contains(tag))
+    }
+
+    pub fn add_tag(&mut self, id: ObjectId, tag: &str) {
+        if self.has_tag(id, tag) {
+            return;
+        }
+        match self.world.get_mut::<Tags>(id) {
+            Some(tags) => tags.0.push(tag.to_string()),
+            None => {
+                self.world.insert(id, Tags(vec![tag.to_string()]));
+            }
+        }
+    }
+
+    pub fn remove_tag(&mut self, id: ObjectId, tag: &str) {
+        if let Some(tags) = self.world.get_mut::<Tags>(id) {
+            tags.0.retain(|t| t != tag);
+        }
+    }
+
+    /// Members of a named group in the order they were added (empty if unknown)
+    pub fn group(&self, name: &str) -> &[ObjectId] {
+        self.groups
+            .get(name)
+            .map_or(&[], |members| members.as_slice())
+    }
+
+    /// Append a live object to a group, creating the group on first use
+    pub fn add_to_group(&mut self, id: ObjectId, name: &str) {
+        if !self.world.is_alive(id) {
+            return;
+        }
+        let members = self.groups.entry(name.to_string()).or_default();
+        if !members.contains(&id) {
+            members.push(id);
+        }
+    }
+
+    pub fn remove_from_group(&mut self, id: ObjectId, name: &str) {
+        if let Some(members) = self.groups.get_mut(name) {
+            members.retain(|&member| member != id);
+        }
     }
 
-    pub fn cast_ray(&self, ray: &Ray) -> Option<usize> {
-        let mut min_dist = f32::MAX;
-        let mut hit_id = None;
+    /// Names of every group containing `id`
+    pub fn groups_of(&self, id: ObjectId) -> Vec<String> {
+        self.groups
+            .iter()
+            .filter(|(_, members)| members.contains(&id))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    fn parent_of(&self, id: ObjectId) -> Option<ObjectId> {
+        self.world.get::<Parent>(id).map(|p| p.0)
+    }
+
+    fn children_of(&self, id: ObjectId) -> Vec<ObjectId> {
+        self.world
+            .get::<Children>(id)
+            .map(|c| c.0.clone())
+            .unwrap_or_default()
+    }
+
+    /// Attach `child` to `parent` (or detach it with `None`).
+    /// With `keep_world` the local transform is recomputed so the object stays in place,
+    /// otherwise the local transform is kept and the object moves with its new parent.
+    pub fn set_parent(
+        &mut self,
+        child: ObjectId,
+        parent: Option<ObjectId>,
+        keep_world: bool,
+    ) -> Result<(), String> {
+        if !self.world.is_alive(child) {
+            return Err(format!("Unknown object {}", child));
+        }
+        if let Some(parent_id) = parent {
+            if !self.world.is_alive(parent_id) {
+                return Err(format!("Unknown parent object {}", parent_id));
+            }
+            if parent_id == child || self.is_ancestor(child, parent_id) {
+                return Err(format!(
+                    "Cannot parent object {} to {}: would create a cycle",
+                    child, parent_id
+                ));
+            }
+        }
+
+        if keep_world {
+            self.update_world_transforms();
+        }
+
+        // Unlink from the old parent
+        if let Some(old_id) = self.world.remove::<Parent>(child).map(|p| p.0) {
+            if let Some(old) = self.world.get_mut::<Children>(old_id) {
+                old.0.retain(|&c| c != child);
+            }
+        }
 
-        for obj in &self.objects {
-            if let Some(dist) = obj
-                .collider
-                .as_ref()
-                .and_then(|c| c.intersect(ray, &obj.transform))
-            {
-                if dist < min_dist {
-                    min_dist = dist;
-                    hit_id = Some(obj.id);
+        // Link to the new parent
+        let parent_world = match parent {
+            Some(parent_id) => {
+                match self.world.get_mut::<Children>(parent_id) {
+                    Some(children) => children.0.push(child),
+                    None => {
+                        self.world.insert(parent_id, Children(vec![child]));
+                    }
                 }
+                self.world.insert(child, Parent(parent_id));
+                self.world
+                    .get::<GlobalTransform>(parent_id)
+                    .map_or(Mat4::IDENTITY, |g| g.0)
             }
+            None => Mat4::IDENTITY,
+        };
+
+        if keep_world {
+            let child_world = self
+                .world
+                .get::<GlobalTransform>(child)
+                .map_or(Mat4::IDENTITY, |g| g.0);
+            self.world.insert(
+                child,
+                Transform::from_matrix(&(parent_world.inverse() * child_world)),
+            );
         }
 
-        hit_id
+        self.update_world_transforms();
+        Ok(())
     }
 
-    pub fn check_intersection(&self, ray: &Ray) {
-        let mut min_dist = f32::MAX;
-        let mut hit_object: Option<(String, usize)> = None;
-
-        for obj in &self.objects {
-            if let Some(dist) = obj
-                .collider
-                .as_ref()
-                .and_then(|c| c.intersect(ray, &obj.transform))
-            {
-                if dist < min_dist {
-                    min_dist = dist;
-                    hit_object = Some((obj.name.clone(), obj.id));
+    /// True if `ancestor` is somewhere above `id` in the hierarchy
+    pub fn is_ancestor(&self, ancestor: ObjectId, id: ObjectId) -> bool {
+        let mut current = self.parent_of(id);
+        while let Some(parent_id) = current {
+            if parent_id == ancestor {
+                return true;
+            }
+            current = self.parent_of(parent_id);
+        }
+        false
+    }
+
+    /// Remove an object; its children are despawned or re-attached according to `policy`.
+    /// Returns the ids of every removed object.
+    pub fn remove_object(&mut self, id: ObjectId, policy: ChildPolicy) -> Vec<ObjectId> {
+        if !self.world.is_alive(id) {
+            return Vec::new();
+        }
+        let parent = self.parent_of(id);
+        let children = self.children_of(id);
+
+        let mut removed = vec![id];
+        match policy {
+            ChildPolicy::Despawn => {
+                let mut stack = children;
+                while let Some(child) = stack.pop() {
+                    stack.extend(self.children_of(child));
+                    removed.push(child);
+                }
+            }
+            ChildPolicy::Reparent => {
+                for child in children {
+                    let _ = self.set_parent(child, parent, true);
                 }
             }
         }
 
-        if let Some((name, id)) = hit_object {
-            println!(
+        if let Some(parent_id) = parent {
+            if let Some(p) = self.world.get_mut::<Children>(parent_id) {
+                p.0.retain(|&c| c != id);
+            }
+        }
+
+        for members in self.groups.values_mut() {
+            members.retain(|member| !removed.contains(member));
+        }
+
+        for &removed_id in &removed {
+            if let Some(name) = self.name_of(removed_id) {
+                println!("Destroying object: {}", name);
+            }
+            self.world.despawn(removed_id);
+        }
+
+        removed
+    }
+
+    /// Recompute cached world matrices top-down from every root object, then the
+    /// world-space bounds and the collider BVH that depend on them
+    pub fn update_world_transforms(&mut self) {
+        systems::propagate_transforms(&mut self.world);
+        systems::update_world_bounds(&mut self.world);
+        self.bvh.update(&systems::collider_bounds(&self.world));
+    }
+
+    pub fn cast_ray(&self, ray: &Ray) -> Option<ObjectId> {
+        self.nearest_hit(ray).map(|(id, _)| id)
+    }
+
+    pub fn check_intersection(&self, ray: &Ray) {
+        match self.nearest_hit(ray) {
+            Some((id, dist)) => println!(
                 "Raycast Hit: '{}' (ID: {}) at distance {:.2}",
-                name, id, min_dist
-            );
-        } else {
-            println!("Raycast Miss");
+                self.name_of(id).unwrap_or_default(),
+                id,
+                dist
+            ),
+            None => println!("Raycast Miss"),
+        }
+    }
+
+    /// Closest object hit by `ray`, with the distance along it
+    pub fn nearest_hit(&self, ray: &Ray) -> Option<(ObjectId, f32)> {
+        self.bvh.cast_ray(ray, |id| self.hit_distance(id, ray))
+    }
+
+    /// Every object hit by `ray`, nearest first
+    pub fn all_hits(&self, ray: &Ray) -> Vec<(ObjectId, f32)> {
+        let mut hits = Vec::new();
+        self.bvh.ray_candidates(ray, |id| {
+            if let Some(dist) = self.hit_distance(id, ray) {
+                hits.push((id, dist));
+            }
+        });
+        hits.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        hits
+    }
+
+    /// Objects whose world-space collider box overlaps `region`
+    pub fn objects_in(&self, region: &Aabb) -> Vec<ObjectId> {
+        let mut found = Vec::new();
+        self.bvh.overlapping(region, |id| {
+            if self.world.is_alive(id) {
+                found.push(id);
+            }
+        });
+        found
+    }
+
+    fn hit_distance(&self, id: ObjectId, ray: &Ray) -> Option<f32> {
+        let collider = self.world.get::<Collider>(id)?;
+        let global = self.world.get::<GlobalTransform>(id)?;
+        collider.intersect(ray, &global.0)
+    }
+}
+
+fn find_material(
+    materials: &HashMap<&str, Rc<dyn Material>>,
+    desc: &ObjectDescription,
+    name: &str,
+) -> Result<Rc<dyn Material>, String> {
+    materials.get(name).cloned().ok_or_else(|| {
+        format!(
+            "Object '{}' references unknown material '{}'",
+            desc.name, name
+        )
+    })
+}
+
+/// Per-mesh materials of an object: its model's own when it names no material, or its
+/// material otherwise, with the `mesh_materials` overrides applied by model material name.
+/// `None` when one material covers the whole object.
+fn object_mesh_materials(
+    desc: &ObjectDescription,
+    model_path: Option<&str>,
+    materials: &HashMap<&str, Rc<dyn Material>>,
+    factory: &MaterialFactory,
+    assets: &mut AssetManager,
+) -> Result<Option<Vec<Rc<dyn Material>>>, String> {
+    if !desc.material.is_empty() && desc.mesh_materials.is_empty() {
+        return Ok(None);
+    }
+    let (path, model) = model_path
+        .and_then(|path| Some((path, assets.get_model(path)?)))
+        .ok_or_else(|| {
+            format!(
+                "Object '{}' needs a material: mesh '{}' is not a model file",
+                desc.name, desc.mesh
+            )
+        })?;
+
+    let mut parts = if desc.material.is_empty() {
+        assets.model_materials(path, factory).unwrap_or_default()
+    } else {
+        vec![find_material(materials, desc, &desc.material)?; model.meshes.len()]
+    };
+
+    let names: Vec<&str> = model
+        .mesh_materials()
+        .into_iter()
+        .map(|m| m.map_or("", |m| m.name.as_str()))
+        .collect();
+    for (model_material, scene_material) in &desc.mesh_materials {
+        if !names.contains(&model_material.as_str()) {
+            return Err(format!(
+                "Object '{}' overrides material '{}', which mesh '{}' does not use",
+                desc.name, model_material, desc.mesh
+            ));
+        }
+        let material = find_material(materials, desc, scene_material)?;
+        for (part, name) in parts.iter_mut().zip(&names) {
+            if name == model_material {
+                *part = material.clone();
+            }
+        }
+    }
+    Ok(Some(parts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::components::{
+        Attenuation, LightProperties, ShadowFilter, ShadowSettings, SpotCone,
+    };
+    use crate::logic::controller::ControllerDescription;
+    use crate::math::bounds::Bounds;
+    use crate::renderer::instancing::InstanceBuffer;
+    use crate::scene::description::MaterialSource;
+    use crate::scene::material::BlendMode;
+    use crate::shaders::Shader;
+    use glam::{Quat, Vec2, Vec3};
+
+    /// Mesh that draws nothing, so scenes can be assembled without a GL context
+    struct NoMesh;
+
+    impl Renderable for NoMesh {
+        fn draw(&self) {}
+        fn draw_instanced(&self, _instances: &InstanceBuffer, _count: i32) {}
+        fn bounds(&self) -> Bounds {
+            Bounds::default()
+        }
+    }
+
+    /// Material that is never drawn
+    struct NoMaterial;
+
+    impl Material for NoMaterial {
+        fn shader(&self) -> &Rc<Shader> {
+            unreachable!("test scenes are not drawn")
+        }
+        fn set_uniforms(&self, _shader: &Shader) {}
+    }
+
+    fn mesh(name: &str, source: MeshSource) -> MeshDescription {
+        MeshDescription {
+            name: name.to_string(),
+            source,
+        }
+    }
+
+    fn material(name: &str, source: MaterialSource) -> MaterialDescription {
+        MaterialDescription {
+            name: name.to_string(),
+            source,
+        }
+    }
+
+    fn object(name: &str, mesh: &str, material: &str) -> ObjectDescription {
+        ObjectDescription {
+            name: name.to_string(),
+            parent: None,
+            tags: Vec::new(),
+            groups: Vec::new(),
+            transform: Transform::default(),
+            mesh: mesh.to_string(),
+            material: material.to_string(),
+            mesh_materials: BTreeMap::new(),
+            collider: None,
+            controller: None,
+        }
+    }
+
+    /// A description using every mesh source, material kind, controller and light kind,
+    /// with a hierarchy (a child declared before its parent), tags and groups. Groups are
+    /// listed by name, as saving lists them.
+    fn rich_description() -> SceneDescription {
+        let meshes = vec![
+            mesh("box", MeshSource::Cube { size: 2.0 }),
+            mesh(
+                "ball",
+                MeshSource::Sphere {
+                    radius: 0.5,
+                    sectors: 24,
+                    stacks: 12,
+                },
+            ),
+            mesh(
+                "pill",
+                MeshSource::Capsule {
+                    radius: 0.25,
+                    height: 1.5,
+                    sectors: 16,
+                    cap_stacks: 4,
+                    cylinder_stacks: 2,
+                },
+            ),
+            mesh("floor", MeshSource::Plane { size: 20.0 }),
+            mesh(
+                "statue",
+                MeshSource::Model {
+                    path: "assets/models/statue.glb".to_string(),
+                },
+            ),
+        ];
+        let materials = vec![
+            material(
+                "red",
+                MaterialSource::Colored {
+                    color: Vec3::new(1.0, 0.1, 0.1),
+                    opacity: 0.5,
+                    blend_mode: BlendMode::Blend,
+                    lit: false,
+                    receive_shadows: true,
+                },
+            ),
+            material(
+                "grass",
+                MaterialSource::Textured {
+                    texture: "textures/grass.jpg".to_string(),
+                    uv_scale: Some(Vec2::new(8.0, 8.0)),
+                    normal_map: Some("textures/grass_normal.png".to_string()),
+                    normal_scale: 0.75,
+                    opacity: 1.0,
+                    blend_mode: BlendMode::Cutout { threshold: 0.4 },
+                    lit: true,
+                    receive_shadows: false,
+                },
+            ),
+            material(
+                "marble",
+                MaterialSource::Pbr {
+                    base_color: Vec3::new(0.9, 0.9, 0.85),
+                    metallic: 0.1,
+                    roughness: 0.3,
+                    emissive: Vec3::new(0.0, 0.2, 0.0),
+                    base_color_map: Some("textures/marble.png".to_string()),
+                    metallic_roughness_map: Some("textures/marble_mr.png".to_string()),
+                    normal_map: None,
+                    normal_scale: 1.0,
+                    occlusion_map: Some("textures/marble_ao.png".to_string()),
+                    occlusion_strength: 0.6,
+                    emissive_map: None,
+                    uv_scale: None,
+                    opacity: 1.0,
+                    blend_mode: BlendMode::Opaque,
+                    receive_shadows: true,
+                },
+            ),
+        ];
+
+        let mut moon = object("moon", "ball", "red");
+        moon.parent = Some("planet".to_string());
+        moon.tags = vec!["sky".to_string(), "round".to_string()];
+        moon.groups = vec!["animated".to_string()];
+        moon.transform = Transform::from_position(Vec3::new(3.0, 0.0, 0.0));
+        moon.collider = Some(Collider::new_sphere(0.5));
+        moon.controller = Some(ControllerDescription::Orbit {
+            center: Vec3::ZERO,
+            radius: 3.0,
+            speed: 0.5,
+            offset: 1.0,
+        });
+
+        let mut planet = object("planet", "box", "marble");
+        planet.tags = vec!["sky".to_string()];
+        planet.groups = vec!["animated".to_string(), "landmarks".to_string()];
+        planet.transform = Transform::new(
+            Vec3::new(0.0, 5.0, -2.0),
+            Quat::from_rotation_y(0.7),
+            Vec3::new(1.0, 2.0, 1.0),
+        );
+        planet.controller = Some(ControllerDescription::Rotation {
+            axis: Vec3::Y,
+            speed: 0.25,
+        });
+
+        let mut ground = object("ground", "floor", "grass");
+        ground.tags = vec!["floor".to_string()];
+        ground.collider = Some(Collider::new_box(
+            Vec3::new(-10.0, -0.1, -10.0),
+            Vec3::new(10.0, 0.0, 10.0),
+        ));
+
+        let mut buoy = object("buoy", "pill", "red");
+        buoy.parent = Some("ground".to_string());
+        buoy.controller = Some(ControllerDescription::Oscillation {
+            base_y: 0.5,
+            amplitude: 0.2,
+            speed: 2.0,
+        });
+
+        let mut statue = object("statue", "statue", "");
+        statue.groups = vec!["landmarks".to_string()];
+        statue.mesh_materials = BTreeMap::from([("Stone".to_string(), "marble".to_string())]);
+        statue.controller = Some(ControllerDescription::Floating {
+            speed: 1.0,
+            offset: 0.3,
+        });
+
+        let shadow = |filter| ShadowSettings::default().with_filter(filter);
+        let mut directional = DirectionalLight::new(
+            Vec3::new(0.3, -1.0, 0.2),
+            LightProperties::new(0.05, 0.8, 0.5, 64.0),
+        );
+        directional.shadow = shadow(ShadowFilter::Pcss);
+        let point = PointLight::new(
+            Vec3::new(1.0, 3.0, 1.0),
+            LightProperties::new(0.0, 1.0, 0.5, 16.0),
+        )
+        .with_attenuation(Attenuation::new(1.0, 0.14, 0.07))
+        .with_shadow_settings(shadow(ShadowFilter::Poisson));
+        let spot = SpotLight::new(
+            Vec3::new(0.0, 4.0, 0.0),
+            Vec3::NEG_Y,
+            LightProperties::default(),
+        )
+        .with_cone(SpotCone::from_degrees(15.0, 25.0))
+        .with_attenuation(Attenuation::new(1.0, 0.07, 0.017))
+        .with_shadows(true)
+        .with_shadow_settings(shadow(ShadowFilter::Variance));
+
+        SceneDescription {
+            meshes,
+            materials,
+            objects: vec![moon, planet, ground, buoy, statue],
+            lights: LightsDescription {
+                directional,
+                point: vec![point],
+                spot: vec![spot],
+            },
+        }
+    }
+
+    /// Scene built from `description` with placeholder meshes and materials
+    fn assemble(description: &SceneDescription) -> Result<Scene, String> {
+        let meshes: HashMap<&str, Rc<dyn Renderable>> = description
+            .meshes
+            .iter()
+            .map(|m| (m.name.as_str(), Rc::new(NoMesh) as Rc<dyn Renderable>))
+            .collect();
+        Scene::assemble(description, &meshes, |_| {
+            Ok((Rc::new(NoMaterial) as Rc<dyn Material>, None))
+        })
+    }
+
+    #[test]
+    fn descriptions_round_trip_through_ron() {
+        let description = rich_description();
+        let source =
+            ron::ser::to_string_pretty(&description, ron::ser::PrettyConfig::default()).unwrap();
+        let parsed: SceneDescription = ron::from_str(&source).unwrap();
+        assert_eq!(parsed, description);
+    }
+
+    #[test]
+    fn assembled_scenes_describe_themselves_as_loaded() {
+        let description = rich_description();
+        let scene = assemble(&description).unwrap();
+        assert_eq!(scene.to_description().unwrap(), description);
+    }
+
+    #[test]
+    fn unknown_meshes_and_parents_are_errors() {
+        let mut description = rich_description();
+        description.objects[0].mesh = "missing".to_string();
+        let error = assemble(&description).err().unwrap();
+        assert!(error.contains("unknown mesh 'missing'"), "{}", error);
+
+        let mut description = rich_description();
+        description.objects[0].parent = Some("missing".to_string());
+        let error = assemble(&description).err().unwrap();
+        assert!(error.contains("unknown parent 'missing'"), "{}", error);
+    }
+
+    /// Object named `name` at `transform`, spawned straight into the world
+    fn spawn(scene: &mut Scene, name: &str, transform: Transform) -> ObjectId {
+        let id = scene.world.spawn();
+        scene.world.insert(id, Name(name.to_string()));
+        scene.world.insert(id, transform);
+        scene
+            .world
+            .insert(id, GlobalTransform(transform.to_matrix()));
+        id
+    }
+
+    fn world_matrix(scene: &Scene, id: ObjectId) -> Mat4 {
+        scene.world.get::<GlobalTransform>(id).unwrap().0
+    }
+
+    /// Root, its child and grandchild: the root rotated and uniformly scaled, each below
+    /// offset from its parent
+    fn chain() -> (Scene, [ObjectId; 3]) {
+        let mut scene = Scene::new();
+        let root = spawn(
+            &mut scene,
+            "root",
+            Transform::new(
+                Vec3::new(1.0, 2.0, 3.0),
+                Quat::from_rotation_z(0.6) * Quat::from_rotation_x(0.3),
+                Vec3::splat(2.0),
+            ),
+        );
+        let child = spawn(&mut scene, "child", Transform::from_position(Vec3::X));
+        let grandchild = spawn(&mut scene, "grandchild", Transform::from_position(Vec3::Y));
+        scene.set_parent(child, Some(root), false).unwrap();
+        scene.set_parent(grandchild, Some(child), false).unwrap();
+        (scene, [root, child, grandchild])
+    }
+
+    #[test]
+    fn keep_world_reparenting_leaves_the_object_in_place() {
+        let mut scene = Scene::new();
+        let parent = spawn(
+            &mut scene,
+            "parent",
+            Transform::new(
+                Vec3::new(-4.0, 1.0, 2.0),
+                Quat::from_rotation_y(1.1) * Quat::from_rotation_x(-0.4),
+                Vec3::splat(3.0),
+            ),
+        );
+        let child = spawn(
+            &mut scene,
+            "child",
+            Transform::new(
+                Vec3::new(2.0, 0.5, -1.0),
+                Quat::from_rotation_z(0.25),
+                Vec3::splat(0.5),
+            ),
+        );
+        let before = world_matrix(&scene, child);
+
+        scene.set_parent(child, Some(parent), true).unwrap();
+        assert!(world_matrix(&scene, child).abs_diff_eq(before, 1e-4));
+        assert_ne!(
+            *scene.world.get::<Transform>(child).unwrap(),
+            Transform::default()
+        );
+
+        // And back out to the root
+        scene.set_parent(child, None, true).unwrap();
+        assert!(world_matrix(&scene, child).abs_diff_eq(before, 1e-4));
+    }
+
+    #[test]
+    fn parenting_without_keep_world_moves_the_object_with_its_parent() {
+        let (scene, [root, child, _]) = chain();
+        let expected = world_matrix(&scene, root) * Mat4::from_translation(Vec3::X);
+        assert!(world_matrix(&scene, child).abs_diff_eq(expected, 1e-5));
+    }
+
+    #[test]
+    fn parenting_under_a_descendant_is_rejected() {
+        let (mut scene, [root, child, grandchild]) = chain();
+        assert!(scene.set_parent(root, Some(grandchild), false).is_err());
+        assert!(scene.set_parent(root, Some(root), true).is_err());
+        assert!(scene.set_parent(child, Some(grandchild), true).is_err());
+
+        // The hierarchy is untouched
+        assert_eq!(scene.parent_of(root), None);
+        assert_eq!(scene.parent_of(grandchild), Some(child));
+        assert!(scene.is_ancestor(root, grandchild));
+    }
+
+    #[test]
+    fn despawning_removes_the_whole_subtree() {
+        let (mut scene, [root, child, grandchild]) = chain();
+        let other = spawn(&mut scene, "other", Transform::default());
+
+        let mut removed = scene.remove_object(child, ChildPolicy::Despawn);
+        removed.sort_by_key(|id| id.index());
+        assert_eq!(removed, vec![child, grandchild]);
+        assert!(!scene.world.is_alive(child));
+        assert!(!scene.world.is_alive(grandchild));
+        assert!(scene.world.is_alive(root) && scene.world.is_alive(other));
+        assert!(scene.children_of(root).is_empty());
+    }
+
+    #[test]
+    fn reparenting_moves_children_to_the_grandparent_in_place() {
+        let (mut scene, [root, child, grandchild]) = chain();
+        let before = world_matrix(&scene, grandchild);
+
+        assert_eq!(
+            scene.remove_object(child, ChildPolicy::Reparent),
+            vec![child]
+        );
+        assert_eq!(scene.parent_of(grandchild), Some(root));
+        assert_eq!(scene.children_of(root), vec![grandchild]);
+        assert!(world_matrix(&scene, grandchild).abs_diff_eq(before, 1e-4));
+    }
+
+    #[test]
+    fn handles_of_removed_objects_go_stale() {
+        let (mut scene, [root, child, _]) = chain();
+        scene.remove_object(child, ChildPolicy::Despawn);
+
+        // A new object may take the slot, but not the old handle
+        let newcomer = spawn(&mut scene, "newcomer", Transform::default());
+        assert_ne!(newcomer, child);
+        assert!(!scene.world.is_alive(child));
+        assert!(scene.world.get::<Name>(child).is_none());
+        assert_eq!(scene.name_of(child), None);
+        assert!(scene.set_parent(child, Some(root), false).is_err());
+        assert!(scene.set_parent(newcomer, Some(child), false).is_err());
+        assert!(scene.remove_object(child, ChildPolicy::Despawn).is_empty());
+    }
+
+    /// Saveable object named `name` with `tags`, drawn with placeholders
+    fn add(scene: &mut Scene, name: &str, tags: &[&str]) -> ObjectId {
+        let mut obj = SceneObject3D::new(Rc::new(NoMesh), Rc::new(NoMaterial))
+            .with_name(name)
+            .with_asset_names("box", "red");
+        obj.tags = tags.iter().map(|t| t.to_string()).collect();
+        scene.add_object(obj)
+    }
+
+    fn names(scene: &Scene, ids: &[ObjectId]) -> Vec<String> {
+        ids.iter().map(|&id| scene.name_of(id).unwrap()).collect()
+    }
+
+    #[test]
+    fn objects_are_found_by_name_and_tag() {
+        let mut scene = Scene::new();
+        let wall = add(&mut scene, "wall", &["static", "solid"]);
+        let crate_ = add(&mut scene, "crate", &["solid"]);
+        let ghost = add(&mut scene, "ghost", &[]);
+        let unnamed = add(&mut scene, "", &[]);
+
+        assert_eq!(scene.find_by_name("crate"), Some(crate_));
+        assert_eq!(scene.find_by_name("nobody"), None);
+        assert_eq!(
+            scene.find_by_name(&format!("Object {}", unnamed.index())),
+            Some(unnamed)
+        );
+        assert_eq!(scene.find_with_tag("solid"), vec![wall, crate_]);
+        assert_eq!(scene.find_with_tag("static"), vec![wall]);
+        assert!(scene.find_with_tag("flying").is_empty());
+
+        scene.add_tag(ghost, "solid");
+        scene.add_tag(ghost, "solid");
+        scene.remove_tag(wall, "solid");
+        assert_eq!(scene.find_with_tag("solid"), vec![crate_, ghost]);
+        assert_eq!(
+            *scene.world.get::<Tags>(ghost).unwrap(),
+            Tags(vec!["solid".into()])
+        );
+        assert!(scene.has_tag(wall, "static") && !scene.has_tag(wall, "solid"));
+
+        scene.remove_object(crate_, ChildPolicy::Despawn);
+        assert_eq!(scene.find_by_name("crate"), None);
+        assert_eq!(scene.find_with_tag("solid"), vec![ghost]);
+    }
+
+    #[test]
+    fn groups_keep_members_in_the_order_they_were_added() {
+        let mut scene = Scene::new();
+        let a = add(&mut scene, "a", &[]);
+        let b = add(&mut scene, "b", &[]);
+        let c = add(&mut scene, "c", &[]);
+
+        scene.add_to_group(c, "enemies");
+        scene.add_to_group(a, "enemies");
+        scene.add_to_group(a, "enemies");
+        scene.add_to_group(b, "pickups");
+        assert_eq!(scene.group("enemies"), &[c, a]);
+        assert_eq!(scene.groups_of(a), vec!["enemies".to_string()]);
+        assert!(scene.group("nobody").is_empty());
+
+        scene.remove_from_group(c, "enemies");
+        scene.remove_from_group(b, "enemies");
+        assert_eq!(scene.group("enemies"), &[a]);
+        assert!(scene.groups_of(c).is_empty());
+    }
+
+    #[test]
+    fn removed_objects_leave_their_groups() {
+        let (mut scene, [root, child, grandchild]) = chain();
+        for id in [root, child, grandchild] {
+            scene.add_to_group(id, "all");
         }
+        scene.add_to_group(grandchild, "leaves");
+
+        scene.remove_object(child, ChildPolicy::Despawn);
+        assert_eq!(scene.group("all"), &[root]);
+        assert!(scene.group("leaves").is_empty());
+
+        // Dead handles are not added back
+        scene.add_to_group(child, "all");
+        assert_eq!(scene.group("all"), &[root]);
+    }
+
+    #[test]
+    fn groups_survive_saving_and_loading() {
+        let mut scene = Scene::new();
+        let a = add(&mut scene, "a", &[]);
+        let b = add(&mut scene, "b", &[]);
+        let c = add(&mut scene, "c", &[]);
+        scene.add_to_group(a, "enemies");
+        scene.add_to_group(c, "enemies");
+        scene.add_to_group(b, "pickups");
+        scene.add_to_group(c, "pickups");
+        scene.meshes = vec![mesh("box", MeshSource::Cube { size: 1.0 })];
+
+        let saved = ron::ser::to_string_pretty(
+            &scene.to_description().unwrap(),
+            ron::ser::PrettyConfig::default(),
+        )
+        .unwrap();
+        let loaded = assemble(&ron::from_str(&saved).unwrap()).unwrap();
+
+        assert_eq!(names(&loaded, loaded.group("enemies")), ["a", "c"]);
+        assert_eq!(names(&loaded, loaded.group("pickups")), ["b", "c"]);
+        let c = loaded.find_by_name("c").unwrap();
+        assert_eq!(loaded.groups_of(c), ["enemies", "pickups"]);
     }
 }
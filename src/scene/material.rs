@@ -1,21 +1,87 @@
 use crate::shaders::{Shader, Texture};
 use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 
+/// How a material's alpha combines with what is behind it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum BlendMode {
+    #[default]
+    Opaque,
+    /// Alpha test: fragments below `threshold` are discarded, the rest drawn opaque.
+    /// Shadows are cut out the same way.
+    Cutout { threshold: f32 },
+    /// Mixed over the scene by alpha, drawn back to front after the opaque objects
+    Blend,
+    /// Added onto the scene (scaled by alpha), drawn with the blended objects
+    Additive,
+}
+
+impl BlendMode {
+    /// Drawn after the opaque objects without writing depth (and casting no shadows)
+    pub fn is_transparent(self) -> bool {
+        matches!(self, BlendMode::Blend | BlendMode::Additive)
+    }
+
+    /// Alpha the shaders discard below (0 keeps every fragment)
+    pub fn alpha_cutoff(self) -> f32 {
+        match self {
+            BlendMode::Cutout { threshold } => threshold,
+            _ => 0.0,
+        }
+    }
+}
+
 pub trait Material {
     fn shader(&self) -> &Rc<Shader>;
-    fn apply(&self);
+    /// Set the material's own uniforms (color, textures) on `shader`
+    fn set_uniforms(&self, shader: &Shader);
+    fn apply(&self) {
+        self.shader().use_program();
+        self.set_uniforms(self.shader());
+    }
+    /// Shader that writes this material into the G-buffer; `None` keeps it forward-rendered
+    fn deferred_shader(&self) -> Option<&Rc<Shader>> {
+        None
+    }
     fn is_lit(&self) -> bool {
         true
     }
     fn receive_shadows(&self) -> bool {
         true
     }
+    fn blend_mode(&self) -> BlendMode {
+        BlendMode::Opaque
+    }
+    /// Alpha multiplied into the texture alpha before the cutout test
+    fn opacity(&self) -> f32 {
+        1.0
+    }
+    /// Texture whose alpha cuts out a `BlendMode::Cutout` material in the shadow passes,
+    /// with the UV scale it is sampled at
+    fn alpha_map(&self) -> Option<(&Rc<Texture>, Vec2)> {
+        None
+    }
+}
+
+/// Colors are picked in sRGB, but lit in linear space
+fn srgb_to_linear(color: Vec3) -> Vec3 {
+    color.map(|c| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
 }
 
 pub struct ColoredMaterial {
     pub shader: Rc<Shader>,
+    pub deferred_shader: Option<Rc<Shader>>,
     pub color: Vec3,
+    /// Alpha of the surface (see `blend_mode`)
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
     pub is_lit: bool,
     pub receive_shadows: bool,
 }
@@ -31,10 +97,14 @@ impl Material for ColoredMaterial {
         &self.shader
     }
 
-    fn apply(&self) {
-        self.shader.use_program();
-        self.shader
-            .set_vec3("objectColor", self.color.x, self.color.y, self.color.z);
+    fn set_uniforms(&self, shader: &Shader) {
+        let color = srgb_to_linear(self.color);
+        shader.set_vec3("objectColor", color.x, color.y, color.z);
+        shader.set_float("opacity", self.opacity);
+    }
+
+    fn deferred_shader(&self) -> Option<&Rc<Shader>> {
+        self.deferred_shader.as_ref()
     }
 
     fn is_lit(&self) -> bool {
@@ -43,11 +113,35 @@ impl Material for ColoredMaterial {
     fn receive_shadows(&self) -> bool {
         self.receive_shadows
     }
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+    fn opacity(&self) -> f32 {
+        self.opacity
+    }
 }
 
 pub struct TexturedMaterial {
     pub shader: Rc<Shader>,
+    pub deferred_shader: Option<Rc<Shader>>,
     pub texture: Rc<Texture>,
+    /// Tangent-space normals, tiled like `texture`; load it as linear
+    pub normal_map: Option<Rc<Texture>>,
+    /// Strength of the normal map's tilt
+    pub normal_scale: f32,
+    /// Linear tint of `texture`
+    pub color: Vec3,
+    /// Scale of the specular highlight and reflections, per channel
+    pub specular: Vec3,
+    /// Multiplies `specular`; load it as linear
+    pub specular_map: Option<Rc<Texture>>,
+    /// Emitted light, added after lighting (forward only, not in the G-buffer)
+    pub emissive: Vec3,
+    /// Multiplies `emissive`; load it as sRGB
+    pub emissive_map: Option<Rc<Texture>>,
+    /// Multiplies the texture's alpha (see `blend_mode`)
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
     pub is_lit: bool,
     pub is_repeated: bool,
     pub uv_scale: Vec2,
@@ -59,15 +153,47 @@ impl Material for TexturedMaterial {
         &self.shader
     }
 
-    fn apply(&self) {
-        self.shader.use_program();
+    fn set_uniforms(&self, shader: &Shader) {
         self.texture.bind(0);
-        self.shader.set_int("u_Texture", 0);
+        shader.set_int("u_Texture", 0);
         // Send repeat flag and scale
-        self.shader
-            .set_int("u_IsRepeated", if self.is_repeated { 1 } else { 0 });
-        self.shader
-            .set_vec2("u_UVScale", self.uv_scale.x, self.uv_scale.y); // Manual UV scaling
+        shader.set_int("u_IsRepeated", if self.is_repeated { 1 } else { 0 });
+        shader.set_vec2("u_UVScale", self.uv_scale.x, self.uv_scale.y); // Manual UV scaling
+
+        shader.set_int("normalMap", 1);
+        match &self.normal_map {
+            Some(normal_map) => {
+                normal_map.bind(1);
+                shader.set_int("hasNormalMap", 1);
+                shader.set_float("normalScale", self.normal_scale);
+            }
+            None => shader.set_int("hasNormalMap", 0),
+        }
+
+        shader.set_float("opacity", self.opacity);
+
+        let (c, s, e) = (self.color, self.specular, self.emissive);
+        shader.set_vec3("diffuseColor", c.x, c.y, c.z);
+        shader.set_vec3("specularColor", s.x, s.y, s.z);
+        shader.set_vec3("emissiveColor", e.x, e.y, e.z);
+        let maps = [
+            (&self.specular_map, 2, "specularMap", "hasSpecularMap"),
+            (&self.emissive_map, 3, "emissiveMap", "hasEmissiveMap"),
+        ];
+        for (map, unit, sampler, flag) in maps {
+            shader.set_int(sampler, unit);
+            match map {
+                Some(texture) => {
+                    texture.bind(unit as u32);
+                    shader.set_int(flag, 1);
+                }
+                None => shader.set_int(flag, 0),
+            }
+        }
+    }
+
+    fn deferred_shader(&self) -> Option<&Rc<Shader>> {
+        self.deferred_shader.as_ref()
     }
 
     fn is_lit(&self) -> bool {
@@ -76,4 +202,113 @@ impl Material for TexturedMaterial {
     fn receive_shadows(&self) -> bool {
         self.receive_shadows
     }
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+    fn opacity(&self) -> f32 {
+        self.opacity
+    }
+    fn alpha_map(&self) -> Option<(&Rc<Texture>, Vec2)> {
+        let uv_scale = if self.is_repeated {
+            self.uv_scale
+        } else {
+            Vec2::ONE
+        };
+        Some((&self.texture, uv_scale))
+    }
+}
+
+/// Texture maps of a `PbrMaterial`; each one present is multiplied with its factor
+#[derive(Clone, Default)]
+pub struct PbrMaps {
+    /// Base color (RGB) and alpha (A); load it as sRGB
+    pub base_color: Option<Rc<Texture>>,
+    /// Roughness in G, metallic in B (glTF layout)
+    pub metallic_roughness: Option<Rc<Texture>>,
+    /// Tangent-space normals
+    pub normal: Option<Rc<Texture>>,
+    /// Ambient occlusion in R
+    pub occlusion: Option<Rc<Texture>>,
+    /// Emissive color; load it as sRGB
+    pub emissive: Option<Rc<Texture>>,
+}
+
+/// Metallic-roughness material lit by the Cook-Torrance shader. It has no G-buffer
+/// variant, so it stays forward-rendered on the deferred path.
+pub struct PbrMaterial {
+    pub shader: Rc<Shader>,
+    /// Linear base color (unlike `ColoredMaterial::color`, which is sRGB)
+    pub base_color: Vec3,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Emitted light, added after lighting
+    pub emissive: Vec3,
+    /// Strength of the normal map's tilt
+    pub normal_scale: f32,
+    /// How much of the occlusion map applies (0 = none)
+    pub occlusion_strength: f32,
+    pub maps: PbrMaps,
+    /// UV tiling of all maps
+    pub uv_scale: Vec2,
+    /// Multiplies the base color map's alpha (see `blend_mode`)
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    pub receive_shadows: bool,
+}
+
+impl Material for PbrMaterial {
+    fn shader(&self) -> &Rc<Shader> {
+        &self.shader
+    }
+
+    fn set_uniforms(&self, shader: &Shader) {
+        let (c, e) = (self.base_color, self.emissive);
+        shader.set_vec3("baseColorFactor", c.x, c.y, c.z);
+        shader.set_float("metallicFactor", self.metallic);
+        shader.set_float("roughnessFactor", self.roughness);
+        shader.set_vec3("emissiveFactor", e.x, e.y, e.z);
+        shader.set_float("normalScale", self.normal_scale);
+        shader.set_float("occlusionStrength", self.occlusion_strength);
+        shader.set_vec2("u_UVScale", self.uv_scale.x, self.uv_scale.y);
+        shader.set_float("opacity", self.opacity);
+
+        // One unit per map, below `texture_units::MATERIAL_MAPS`
+        let maps = [
+            (&self.maps.base_color, "baseColorMap", "hasBaseColorMap"),
+            (
+                &self.maps.metallic_roughness,
+                "metallicRoughnessMap",
+                "hasMetallicRoughnessMap",
+            ),
+            (&self.maps.normal, "normalMap", "hasNormalMap"),
+            (&self.maps.occlusion, "occlusionMap", "hasOcclusionMap"),
+            (&self.maps.emissive, "emissiveMap", "hasEmissiveMap"),
+        ];
+        for (unit, (map, sampler, flag)) in maps.into_iter().enumerate() {
+            match map {
+                Some(texture) => {
+                    texture.bind(unit as u32);
+                    shader.set_int(sampler, unit as i32);
+                    shader.set_int(flag, 1);
+                }
+                None => shader.set_int(flag, 0),
+            }
+        }
+    }
+
+    fn receive_shadows(&self) -> bool {
+        self.receive_shadows
+    }
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+    fn opacity(&self) -> f32 {
+        self.opacity
+    }
+    fn alpha_map(&self) -> Option<(&Rc<Texture>, Vec2)> {
+        self.maps
+            .base_color
+            .as_ref()
+            .map(|texture| (texture, self.uv_scale))
+    }
 }
@@ -1,6 +1,8 @@
 //! Material Factory - Centralized material creation
 
-use super::material::{ColoredMaterial, Material, TexturedMaterial};
+use super::material::{
+    BlendMode, ColoredMaterial, Material, PbrMaps, PbrMaterial, TexturedMaterial,
+};
 use crate::shaders::{Shader, Texture};
 use glam::{Vec2, Vec3};
 use std::rc::Rc;
@@ -9,6 +11,10 @@ use std::rc::Rc;
 pub struct MaterialFactory {
     colored_shader: Rc<Shader>,
     textured_shader: Rc<Shader>,
+    /// G-buffer variants of the two shaders, for the deferred path
+    deferred_shaders: Option<(Rc<Shader>, Rc<Shader>)>,
+    /// Cook-Torrance shader for the `pbr*` constructors
+    pbr_shader: Option<Rc<Shader>>,
 }
 
 impl MaterialFactory {
@@ -17,14 +23,35 @@ impl MaterialFactory {
         Self {
             colored_shader,
             textured_shader,
+            deferred_shaders: None,
+            pbr_shader: None,
         }
     }
 
+    /// Let lit materials be drawn by the deferred path, through these G-buffer shaders
+    pub fn with_deferred(
+        mut self,
+        colored_shader: Rc<Shader>,
+        textured_shader: Rc<Shader>,
+    ) -> Self {
+        self.deferred_shaders = Some((colored_shader, textured_shader));
+        self
+    }
+
+    /// Enable the `pbr*` constructors, drawn with this metallic-roughness shader
+    pub fn with_pbr(mut self, pbr_shader: Rc<Shader>) -> Self {
+        self.pbr_shader = Some(pbr_shader);
+        self
+    }
+
     /// Create a basic colored material with default lighting
     pub fn colored(&self, color: Vec3) -> Rc<dyn Material> {
         Rc::new(ColoredMaterial {
             shader: self.colored_shader.clone(),
+            deferred_shader: self.deferred_shaders.as_ref().map(|(c, _)| c.clone()),
             color,
+            opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
             is_lit: true,
             receive_shadows: true,
         })
@@ -34,7 +61,10 @@ impl MaterialFactory {
     pub fn colored_unlit(&self, color: Vec3) -> Rc<dyn Material> {
         Rc::new(ColoredMaterial {
             shader: self.colored_shader.clone(),
+            deferred_shader: self.deferred_shaders.as_ref().map(|(c, _)| c.clone()),
             color,
+            opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
             is_lit: false,
             receive_shadows: false,
         })
@@ -44,17 +74,58 @@ impl MaterialFactory {
     pub fn colored_no_shadow(&self, color: Vec3) -> Rc<dyn Material> {
         Rc::new(ColoredMaterial {
             shader: self.colored_shader.clone(),
+            deferred_shader: self.deferred_shaders.as_ref().map(|(c, _)| c.clone()),
             color,
+            opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
             is_lit: true,
             receive_shadows: false,
         })
     }
 
+    /// Create a colored material with explicit lighting/shadow flags
+    pub fn colored_with(
+        &self,
+        color: Vec3,
+        is_lit: bool,
+        receive_shadows: bool,
+    ) -> Rc<dyn Material> {
+        Rc::new(self.colored_material(color, is_lit, receive_shadows))
+    }
+
+    /// Opaque colored material, to adjust before wrapping in an `Rc`
+    pub fn colored_material(
+        &self,
+        color: Vec3,
+        is_lit: bool,
+        receive_shadows: bool,
+    ) -> ColoredMaterial {
+        ColoredMaterial {
+            shader: self.colored_shader.clone(),
+            deferred_shader: self.deferred_shaders.as_ref().map(|(c, _)| c.clone()),
+            color,
+            opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
+            is_lit,
+            receive_shadows,
+        }
+    }
+
     /// Create a basic textured material with default settings
     pub fn textured(&self, texture: Rc<Texture>) -> Rc<dyn Material> {
         Rc::new(TexturedMaterial {
             shader: self.textured_shader.clone(),
+            deferred_shader: self.deferred_shaders.as_ref().map(|(_, t)| t.clone()),
             texture,
+            normal_map: None,
+            normal_scale: 1.0,
+            color: Vec3::ONE,
+            specular: Vec3::ONE,
+            specular_map: None,
+            emissive: Vec3::ZERO,
+            emissive_map: None,
+            opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
             is_lit: true,
             is_repeated: false,
             uv_scale: Vec2::ONE,
@@ -66,7 +137,17 @@ impl MaterialFactory {
     pub fn textured_tiled(&self, texture: Rc<Texture>, uv_scale: Vec2) -> Rc<dyn Material> {
         Rc::new(TexturedMaterial {
             shader: self.textured_shader.clone(),
+            deferred_shader: self.deferred_shaders.as_ref().map(|(_, t)| t.clone()),
             texture,
+            normal_map: None,
+            normal_scale: 1.0,
+            color: Vec3::ONE,
+            specular: Vec3::ONE,
+            specular_map: None,
+            emissive: Vec3::ZERO,
+            emissive_map: None,
+            opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
             is_lit: true,
             is_repeated: true,
             uv_scale,
@@ -74,11 +155,60 @@ impl MaterialFactory {
         })
     }
 
+    /// Create a textured material with optional tiling and explicit lighting/shadow flags
+    pub fn textured_with(
+        &self,
+        texture: Rc<Texture>,
+        uv_scale: Option<Vec2>,
+        is_lit: bool,
+        receive_shadows: bool,
+    ) -> Rc<dyn Material> {
+        Rc::new(self.textured_material(texture, uv_scale, is_lit, receive_shadows))
+    }
+
+    /// Textured material without extra maps, to adjust before wrapping in an `Rc`
+    pub fn textured_material(
+        &self,
+        texture: Rc<Texture>,
+        uv_scale: Option<Vec2>,
+        is_lit: bool,
+        receive_shadows: bool,
+    ) -> TexturedMaterial {
+        TexturedMaterial {
+            shader: self.textured_shader.clone(),
+            deferred_shader: self.deferred_shaders.as_ref().map(|(_, t)| t.clone()),
+            texture,
+            normal_map: None,
+            normal_scale: 1.0,
+            color: Vec3::ONE,
+            specular: Vec3::ONE,
+            specular_map: None,
+            emissive: Vec3::ZERO,
+            emissive_map: None,
+            opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
+            is_lit,
+            is_repeated: uv_scale.is_some(),
+            uv_scale: uv_scale.unwrap_or(Vec2::ONE),
+            receive_shadows,
+        }
+    }
+
     /// Create an unlit textured material
     pub fn textured_unlit(&self, texture: Rc<Texture>) -> Rc<dyn Material> {
         Rc::new(TexturedMaterial {
             shader: self.textured_shader.clone(),
+            deferred_shader: self.deferred_shaders.as_ref().map(|(_, t)| t.clone()),
             texture,
+            normal_map: None,
+            normal_scale: 1.0,
+            color: Vec3::ONE,
+            specular: Vec3::ONE,
+            specular_map: None,
+            emissive: Vec3::ZERO,
+            emissive_map: None,
+            opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
             is_lit: false,
             is_repeated: false,
             uv_scale: Vec2::ONE,
@@ -86,6 +216,63 @@ impl MaterialFactory {
         })
     }
 
+    /// Untextured PBR material with default settings, to adjust before wrapping in an `Rc`.
+    /// Panics if the factory was built without `with_pbr`.
+    pub fn pbr_material(&self) -> PbrMaterial {
+        PbrMaterial {
+            shader: self
+                .pbr_shader
+                .clone()
+                .expect("MaterialFactory has no PBR shader (see with_pbr)"),
+            base_color: Vec3::ONE,
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: Vec3::ZERO,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            maps: PbrMaps::default(),
+            uv_scale: Vec2::ONE,
+            opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
+            receive_shadows: true,
+        }
+    }
+
+    /// Create a PBR material from constant factors
+    pub fn pbr(&self, base_color: Vec3, metallic: f32, roughness: f32) -> Rc<dyn Material> {
+        Rc::new(PbrMaterial {
+            base_color,
+            metallic,
+            roughness,
+            ..self.pbr_material()
+        })
+    }
+
+    /// Create a PBR material driven by its maps (all factors 1), with optional tiling
+    pub fn pbr_textured(&self, maps: PbrMaps, uv_scale: Option<Vec2>) -> Rc<dyn Material> {
+        Rc::new(PbrMaterial {
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: if maps.emissive.is_some() {
+                Vec3::ONE
+            } else {
+                Vec3::ZERO
+            },
+            maps,
+            uv_scale: uv_scale.unwrap_or(Vec2::ONE),
+            ..self.pbr_material()
+        })
+    }
+
+    /// Create a glowing PBR material
+    pub fn pbr_emissive(&self, base_color: Vec3, emissive: Vec3) -> Rc<dyn Material> {
+        Rc::new(PbrMaterial {
+            base_color,
+            emissive,
+            ..self.pbr_material()
+        })
+    }
+
     pub fn red(&self) -> Rc<dyn Material> {
         self.colored(Vec3::new(1.0, 0.0, 0.0))
     }
@@ -1,8 +1,14 @@
+pub mod bvh;
 pub mod collider;
+pub mod components;
 pub mod context;
+pub mod description;
+pub mod handle;
 pub mod manager;
 pub mod material;
 pub mod material_factory;
 pub mod model;
 pub mod object;
+pub mod systems;
 pub mod transform;
+pub mod world;
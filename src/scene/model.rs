@@ -1,15 +1,24 @@
 extern crate gl;
+use crate::math::bounds::Bounds;
+use crate::renderer::instancing::InstanceBuffer;
+use crate::renderer::vertex::{self, VERTEX_FLOATS};
 use crate::scene::object::Renderable;
 use gl::types::*;
+use glam::Vec3;
 
 pub struct Mesh {
     vao: GLuint,
     vbo: GLuint,
     ebo: GLuint,
     indices_count: i32,
+    /// Local-space bounds, computed from the vertex data on load
+    pub bounds: Bounds,
+    /// Index into the owning `Model::materials`
+    pub material_index: usize,
 }
 
 impl Mesh {
+    /// Upload interleaved vertices in the `renderer::vertex` layout
     pub fn new(vertices: &[f32], indices: &[u32]) -> Self {
         let mut vao = 0;
         let mut vbo = 0;
@@ -38,33 +47,7 @@ impl Mesh {
                 gl::STATIC_DRAW,
             );
 
-            let stride = (8 * std::mem::size_of::<f32>()) as i32;
-
-            // Position
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
-            gl::EnableVertexAttribArray(0);
-
-            // Texture Coords
-            gl::VertexAttribPointer(
-                2,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                stride,
-                (3 * std::mem::size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(2);
-
-            // Normals
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                stride,
-                (5 * std::mem::size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(1);
+            vertex::set_attribute_pointers();
 
             gl::BindVertexArray(0);
         }
@@ -74,6 +57,8 @@ impl Mesh {
             vbo,
             ebo,
             indices_count: indices.len() as i32,
+            bounds: Bounds::from_vertices(vertices, VERTEX_FLOATS),
+            material_index: 0,
         }
     }
 
@@ -89,6 +74,22 @@ impl Mesh {
             gl::BindVertexArray(0);
         }
     }
+
+    /// Draw `count` instances whose model matrices are in `instances`
+    pub fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            instances.bind_attributes();
+            gl::DrawElementsInstanced(
+                gl::TRIANGLES,
+                self.indices_count,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+                count,
+            );
+            gl::BindVertexArray(0);
+        }
+    }
 }
 
 impl Drop for Mesh {
@@ -101,13 +102,67 @@ impl Drop for Mesh {
     }
 }
 
+/// Material a model file gives its meshes, as plain data. Colors are linear factors;
+/// map paths are resolved relative to the model file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelMaterial {
+    pub name: String,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub emissive: Vec3,
+    /// OBJ `d`: below 1 the material is blended
+    pub opacity: f32,
+    pub diffuse_map: Option<String>,
+    pub specular_map: Option<String>,
+    pub normal_map: Option<String>,
+    pub emissive_map: Option<String>,
+}
+
+impl Default for ModelMaterial {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            diffuse: Vec3::ONE,
+            specular: Vec3::ONE,
+            emissive: Vec3::ZERO,
+            opacity: 1.0,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+            emissive_map: None,
+        }
+    }
+}
+
 pub struct Model {
     pub meshes: Vec<Mesh>,
+    /// Materials the meshes refer to by `Mesh::material_index`
+    pub materials: Vec<ModelMaterial>,
+    /// Union of the mesh bounds
+    pub bounds: Bounds,
 }
 
 impl Model {
     pub fn new(meshes: Vec<Mesh>) -> Self {
-        Self { meshes }
+        let parts: Vec<Bounds> = meshes.iter().map(|mesh| mesh.bounds).collect();
+        Self {
+            bounds: Bounds::union(&parts),
+            meshes,
+            materials: Vec::new(),
+        }
+    }
+
+    pub fn with_materials(mut self, materials: Vec<ModelMaterial>) -> Self {
+        self.materials = materials;
+        self
+    }
+
+    /// Material of each mesh, in mesh order (`None` where the file gave it none)
+    pub fn mesh_materials(&self) -> Vec<Option<&ModelMaterial>> {
+        self.meshes
+            .iter()
+            .map(|mesh| self.materials.get(mesh.material_index))
+            .collect()
     }
 }
 
@@ -117,4 +172,24 @@ impl Renderable for Model {
             mesh.draw();
         }
     }
+
+    fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        for mesh in &self.meshes {
+            mesh.draw_instanced(instances, count);
+        }
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    fn part_count(&self) -> usize {
+        self.meshes.len()
+    }
+
+    fn draw_part_instanced(&self, part: usize, instances: &InstanceBuffer, count: i32) {
+        if let Some(mesh) = self.meshes.get(part) {
+            mesh.draw_instanced(instances, count);
+        }
+    }
 }
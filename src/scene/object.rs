@@ -1,16 +1,29 @@
+use crate::math::bounds::Bounds;
 use crate::primitives::{Capsule, Cube, Plane, Sphere};
+use crate::renderer::instancing::InstanceBuffer;
 use crate::scene::collider::Collider;
 use crate::scene::transform::{Transform, Transform2D};
 use crate::shaders::Shader;
 // use crate::shapes::{Rectangle, Circle, Triangle}; // Unused/Incompatible shapes for now
 
 use std::rc::Rc;
-use std::sync::atomic::{AtomicUsize, Ordering};
-
-static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
 
 pub trait Renderable {
     fn draw(&self);
+    /// Draw `count` copies with per-instance model matrices from `instances`
+    fn draw_instanced(&self, instances: &InstanceBuffer, count: i32);
+    /// Local-space bounding volumes, used for culling
+    fn bounds(&self) -> Bounds;
+    /// Separately drawable parts (a model's meshes), each of which can take its own material
+    fn part_count(&self) -> usize {
+        1
+    }
+    /// Draw `count` instances of one part
+    fn draw_part_instanced(&self, part: usize, instances: &InstanceBuffer, count: i32) {
+        if part == 0 {
+            self.draw_instanced(instances, count);
+        }
+    }
 }
 
 // 3D Primitive Implementation
@@ -18,61 +31,119 @@ impl Renderable for Cube {
     fn draw(&self) {
         self.draw();
     }
+    fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        self.draw_instanced(instances, count);
+    }
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
 }
 impl Renderable for Sphere {
     fn draw(&self) {
         self.draw();
     }
+    fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        self.draw_instanced(instances, count);
+    }
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
 }
 impl Renderable for Capsule {
     fn draw(&self) {
         self.draw();
     }
+    fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        self.draw_instanced(instances, count);
+    }
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
 }
 impl Renderable for Plane {
     fn draw(&self) {
         self.draw();
     }
+    fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        self.draw_instanced(instances, count);
+    }
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
 }
 
 impl<T: Renderable + ?Sized> Renderable for Rc<T> {
     fn draw(&self) {
         (**self).draw();
     }
+    fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        (**self).draw_instanced(instances, count);
+    }
+    fn bounds(&self) -> Bounds {
+        (**self).bounds()
+    }
+    fn part_count(&self) -> usize {
+        (**self).part_count()
+    }
+    fn draw_part_instanced(&self, part: usize, instances: &InstanceBuffer, count: i32) {
+        (**self).draw_part_instanced(part, instances, count);
+    }
 }
 
 impl<T: Renderable + ?Sized> Renderable for Box<T> {
     fn draw(&self) {
         (**self).draw();
     }
+    fn draw_instanced(&self, instances: &InstanceBuffer, count: i32) {
+        (**self).draw_instanced(instances, count);
+    }
+    fn bounds(&self) -> Bounds {
+        (**self).bounds()
+    }
+    fn part_count(&self) -> usize {
+        (**self).part_count()
+    }
+    fn draw_part_instanced(&self, part: usize, instances: &InstanceBuffer, count: i32) {
+        (**self).draw_part_instanced(part, instances, count);
+    }
 }
 
 use crate::logic::Controller;
+use crate::scene::components::{AssetNames, GlobalTransform, MeshMaterials, Name, Tags};
 use crate::scene::material::Material;
+use crate::scene::world::{Entity, World};
 
+/// Builder for the usual components of a rendered object.
+/// `spawn` moves them into a `World`; further components can be inserted afterwards.
 pub struct SceneObject3D {
-    pub id: usize,
+    /// Display name; `Scene::add_object` fills in "Object <index>" if left empty
     pub name: String,
+    /// Local transform (relative to the parent, or the world for root objects)
     pub transform: Transform,
-    pub renderable: Box<dyn Renderable>,
+    pub tags: Vec<String>,
+    pub renderable: Rc<dyn Renderable>,
     pub material: Rc<dyn Material>,
+    /// Per-part materials (see `Renderable::part_count`); `None` draws every part with
+    /// `material`
+    pub mesh_materials: Option<Vec<Rc<dyn Material>>>,
     pub collider: Option<Collider>,
     pub controller: Option<Box<dyn Controller>>,
+    /// Scene file mesh/material names this object was built from
+    pub asset_names: Option<AssetNames>,
 }
 
-use crate::scene::context::RenderContext;
-
 impl SceneObject3D {
-    pub fn new(renderable: Box<dyn Renderable>, material: Rc<dyn Material>) -> Self {
-        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    pub fn new(renderable: Rc<dyn Renderable>, material: Rc<dyn Material>) -> Self {
         Self {
-            id,
-            name: format!("Object {}", id),
+            name: String::new(),
             transform: Transform::default(),
+            tags: Vec::new(),
             renderable,
             material,
+            mesh_materials: None,
             collider: None,
             controller: None,
+            asset_names: None,
         }
     }
 
@@ -81,6 +152,11 @@ impl SceneObject3D {
         self
     }
 
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
     pub fn with_collider(mut self, collider: Collider) -> Self {
         self.collider = Some(collider);
         self
@@ -91,53 +167,58 @@ impl SceneObject3D {
         self
     }
 
-    pub fn update(&mut self, current_time: f32, delta_time: f32) {
-        if let Some(ref controller) = self.controller {
-            controller.update(&mut self.transform, current_time, delta_time);
-        }
+    /// Draw each part of the renderable with its own material, e.g. the materials
+    /// `AssetManager::model_materials` built from a model file
+    pub fn with_mesh_materials(mut self, materials: Vec<Rc<dyn Material>>) -> Self {
+        self.mesh_materials = Some(materials);
+        self
     }
 
-    pub fn render(&self, ctx: &RenderContext) {
-        self.material.apply();
-        let shader = self.material.shader();
-
-        // Matrices
-        shader.set_mat4("projection", &ctx.projection.to_cols_array());
-        shader.set_mat4("view", &ctx.view.to_cols_array());
-        shader.set_mat4("model", &self.transform.to_matrix().to_cols_array());
-        shader.set_vec3(
-            "u_Scale",
-            self.transform.scale.x,
-            self.transform.scale.y,
-            self.transform.scale.z,
-        );
-
-        // Toggles
-        shader.set_int("u_UseLighting", if self.material.is_lit() { 1 } else { 0 });
-        shader.set_int(
-            "u_UseShadows",
-            if self.material.receive_shadows() {
-                1
-            } else {
-                0
-            },
-        );
-
-        // Lighting
-        if self.material.is_lit() {
-            ctx.apply_lighting(shader);
+    /// Override the material of one part, keeping the others
+    pub fn with_mesh_material(mut self, part: usize, material: Rc<dyn Material>) -> Self {
+        let count = self.renderable.part_count();
+        let base = &self.material;
+        let materials = self
+            .mesh_materials
+            .get_or_insert_with(|| vec![base.clone(); count]);
+        if let Some(slot) = materials.get_mut(part) {
+            *slot = material;
         }
-
-        self.renderable.draw();
+        self
     }
 
-    pub fn render_depth(&self, shader: &Shader) {
-        shader.set_mat4("model", &self.transform.to_matrix().to_cols_array());
-        self.renderable.draw();
+    /// Record the scene file mesh/material names so the object can be saved back
+    pub fn with_asset_names(mut self, mesh: &str, material: &str) -> Self {
+        self.asset_names = Some(AssetNames {
+            mesh: mesh.to_string(),
+            material: material.to_string(),
+            mesh_materials: Default::default(),
+        });
+        self
     }
 
-    pub fn destroy(&mut self) {
-        println!("Destroying object: {}", self.name);
+    /// Create an entity carrying these components
+    pub fn spawn(self, world: &mut World) -> Entity {
+        let entity = world.spawn();
+        world.insert(entity, Name(self.name));
+        world.insert(entity, self.transform);
+        world.insert(entity, Tags(self.tags));
+        world.insert(entity, GlobalTransform(self.transform.to_matrix()));
+        world.insert(entity, self.renderable);
+        world.insert(entity, self.material);
+        if let Some(materials) = self.mesh_materials {
+            world.insert(entity, MeshMaterials(materials));
+        }
+        if let Some(collider) = self.collider {
+            world.insert(entity, collider);
+        }
+        if let Some(controller) = self.controller {
+            world.insert(entity, controller);
+        }
+        if let Some(asset_names) = self.asset_names {
+            world.insert(entity, asset_names);
+        }
+        entity
     }
 }
 
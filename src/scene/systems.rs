@@ -0,0 +1,61 @@
+//! Systems - Per-frame logic that runs over world queries
+
+use crate::logic::Controller;
+use crate::math::bounds::Aabb;
+use crate::scene::collider::Collider;
+use crate::scene::components::{Children, GlobalTransform, Parent, WorldBounds};
+use crate::scene::object::Renderable;
+use crate::scene::transform::Transform;
+use crate::scene::world::{Entity, World};
+use glam::Mat4;
+use std::rc::Rc;
+
+/// Advance every controller, writing into its entity's local transform
+pub fn run_controllers(world: &World, current_time: f32, delta_time: f32) {
+    world.query::<(&Box<dyn Controller>, &mut Transform)>(|_, (controller, transform)| {
+        controller.update(transform, current_time, delta_time);
+    });
+}
+
+/// Recompute `GlobalTransform` top-down from every root entity
+pub fn propagate_transforms(world: &mut World) {
+    let mut stack: Vec<(Entity, Mat4)> = Vec::new();
+    world.query::<(&Transform, Option<&Parent>)>(|entity, (_, parent)| {
+        if parent.is_none() {
+            stack.push((entity, Mat4::IDENTITY));
+        }
+    });
+
+    while let Some((entity, parent_world)) = stack.pop() {
+        let Some(local) = world.get::<Transform>(entity).map(|t| t.to_matrix()) else {
+            continue;
+        };
+        let global = parent_world * local;
+        world.insert(entity, GlobalTransform(global));
+
+        if let Some(children) = world.get::<Children>(entity) {
+            stack.extend(children.0.iter().map(|&child| (child, global)));
+        }
+    }
+}
+
+/// Move each mesh's local bounds into world space; run after `propagate_transforms`
+pub fn update_world_bounds(world: &mut World) {
+    let mut bounds = Vec::new();
+    world.query::<(&GlobalTransform, &Rc<dyn Renderable>)>(|entity, (global, renderable)| {
+        bounds.push((entity, renderable.bounds().transformed(&global.0)));
+    });
+
+    for (entity, world_bounds) in bounds {
+        world.insert(entity, WorldBounds(world_bounds));
+    }
+}
+
+/// World-space box around every collider, as input for the scene's BVH
+pub fn collider_bounds(world: &World) -> Vec<(Entity, Aabb)> {
+    let mut bounds = Vec::new();
+    world.query::<(&GlobalTransform, &Collider)>(|entity, (global, collider)| {
+        bounds.push((entity, collider.local_aabb().transformed(&global.0)));
+    });
+    bounds
+}
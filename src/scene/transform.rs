@@ -1,6 +1,8 @@
 use glam::{Mat4, Quat, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Transform {
     pub position: Vec3,
     pub rotation: Quat,
@@ -33,6 +35,16 @@ impl Transform {
         }
     }
 
+    /// Decompose an affine matrix (no shear) back into a transform
+    pub fn from_matrix(matrix: &Mat4) -> Self {
+        let (scale, rotation, position) = matrix.to_scale_rotation_translation();
+        Self {
+            position,
+            rotation,
+            scale,
+        }
+    }
+
     pub fn to_matrix(&self) -> Mat4 {
         Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
     }
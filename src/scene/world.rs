@@ -0,0 +1,356 @@
+//! World - Entity/component storage
+//!
+//! Entities are generational `ObjectId`s; components of each type live in their own
+//! storage, keyed by `TypeId`, so any `'static` type can be attached to an entity
+//! without touching the world itself.
+
+use crate::scene::handle::{ObjectId, SlotMap};
+use std::any::{Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+
+pub type Entity = ObjectId;
+
+/// Components of one type, indexed by entity slot
+pub struct Storage<T> {
+    slots: Vec<Option<(Entity, T)>>,
+}
+
+impl<T> Storage<T> {
+    fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.slots.get(entity.index() as usize) {
+            Some(Some((owner, value))) if *owner == entity => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index() as usize) {
+            Some(Some((owner, value))) if *owner == entity => Some(value),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        let index = entity.index() as usize;
+        if self.slots.len() <= index {
+            self.slots.resize_with(index + 1, || None);
+        }
+        match self.slots[index].replace((entity, value)) {
+            Some((owner, old)) if owner == entity => Some(old),
+            _ => None,
+        }
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slots.get_mut(entity.index() as usize)?;
+        match slot {
+            Some((owner, _)) if *owner == entity => slot.take().map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+/// Type-erased storage so the world can drop a despawned entity's components
+trait AnyStorage {
+    fn remove_entity(&mut self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> AnyStorage for RefCell<Storage<T>> {
+    fn remove_entity(&mut self, entity: Entity) {
+        self.get_mut().remove(entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+pub struct World {
+    entities: SlotMap<()>,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self {
+            entities: SlotMap::new(),
+            storages: HashMap::new(),
+        }
+    }
+
+    /// Create an entity with no components
+    pub fn spawn(&mut self) -> Entity {
+        self.entities.insert(())
+    }
+
+    /// Remove an entity and all of its components; stale ids are ignored
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if self.entities.remove(entity).is_none() {
+            return false;
+        }
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(entity);
+        }
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.get(entity).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Live entities in slot order
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.iter().map(|(entity, _)| entity)
+    }
+
+    /// Attach a component, replacing (and returning) any previous one of the same type
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(Storage::<T>::new())))
+            .as_any_mut()
+            .downcast_mut::<RefCell<Storage<T>>>()
+            .unwrap()
+            .get_mut()
+            .insert(entity, component)
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.storage_cell_mut::<T>()?.get_mut().remove(entity)
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<Ref<'_, T>> {
+        let storage = self.storage_cell::<T>()?.borrow();
+        Ref::filter_map(storage, |s| s.get(entity)).ok()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.storage_cell_mut::<T>()?.get_mut().get_mut(entity)
+    }
+
+    /// Shared access to every component of one type
+    pub fn read<T: 'static>(&self) -> Option<Ref<'_, Storage<T>>> {
+        self.storage_cell::<T>().map(|cell| cell.borrow())
+    }
+
+    /// Exclusive access to every component of one type.
+    /// Panics if the storage is already borrowed (e.g. by an enclosing query).
+    pub fn write<T: 'static>(&self) -> Option<RefMut<'_, Storage<T>>> {
+        self.storage_cell::<T>().map(|cell| cell.borrow_mut())
+    }
+
+    /// Run `f` for every entity that matches the component set `Q`,
+    /// e.g. `world.query::<(&Transform, &mut Collider)>(|entity, (t, c)| ...)`.
+    /// Requesting the same component type twice with `&mut` panics.
+    pub fn query<Q: Query>(&self, mut f: impl FnMut(Entity, Q::Item<'_>)) {
+        let Some(mut state) = Q::borrow(self) else {
+            return;
+        };
+        for entity in self.entities() {
+            if let Some(item) = Q::fetch(&mut state, entity) {
+                f(entity, item);
+            }
+        }
+    }
+
+    fn storage_cell<T: 'static>(&self) -> Option<&RefCell<Storage<T>>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .and_then(|s| s.as_any().downcast_ref::<RefCell<Storage<T>>>())
+    }
+
+    fn storage_cell_mut<T: 'static>(&mut self) -> Option<&mut RefCell<Storage<T>>> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|s| s.as_any_mut().downcast_mut::<RefCell<Storage<T>>>())
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A set of components fetched together for one entity
+pub trait Query {
+    /// Storage borrows held for the duration of the query
+    type State<'w>;
+    type Item<'s>;
+
+    /// `None` if a required component type has never been inserted
+    fn borrow(world: &World) -> Option<Self::State<'_>>;
+    fn fetch<'s>(state: &'s mut Self::State<'_>, entity: Entity) -> Option<Self::Item<'s>>;
+}
+
+impl<T: 'static> Query for &T {
+    type State<'w> = Ref<'w, Storage<T>>;
+    type Item<'s> = &'s T;
+
+    fn borrow(world: &World) -> Option<Self::State<'_>> {
+        world.read::<T>()
+    }
+
+    fn fetch<'s>(state: &'s mut Self::State<'_>, entity: Entity) -> Option<Self::Item<'s>> {
+        state.get(entity)
+    }
+}
+
+impl<T: 'static> Query for &mut T {
+    type State<'w> = RefMut<'w, Storage<T>>;
+    type Item<'s> = &'s mut T;
+
+    fn borrow(world: &World) -> Option<Self::State<'_>> {
+        world.write::<T>()
+    }
+
+    fn fetch<'s>(state: &'s mut Self::State<'_>, entity: Entity) -> Option<Self::Item<'s>> {
+        state.get_mut(entity)
+    }
+}
+
+/// Optional component: matches every entity, yielding `None` where it is missing
+impl<T: 'static> Query for Option<&T> {
+    type State<'w> = Option<Ref<'w, Storage<T>>>;
+    type Item<'s> = Option<&'s T>;
+
+    fn borrow(world: &World) -> Option<Self::State<'_>> {
+        Some(world.read::<T>())
+    }
+
+    fn fetch<'s>(state: &'s mut Self::State<'_>, entity: Entity) -> Option<Self::Item<'s>> {
+        Some(state.as_ref().and_then(|s| s.get(entity)))
+    }
+}
+
+macro_rules! impl_query_tuple {
+    ($($name:ident),+) => {
+        #[allow(non_snake_case)]
+        impl<$($name: Query),+> Query for ($($name,)+) {
+            type State<'w> = ($($name::State<'w>,)+);
+            type Item<'s> = ($($name::Item<'s>,)+);
+
+            fn borrow(world: &World) -> Option<Self::State<'_>> {
+                Some(($($name::borrow(world)?,)+))
+            }
+
+            fn fetch<'s>(state: &'s mut Self::State<'_>, entity: Entity) -> Option<Self::Item<'s>> {
+                let ($($name,)+) = state;
+                Some(($($name::fetch($name, entity)?,)+))
+            }
+        }
+    };
+}
+
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
+impl_query_tuple!(A, B, C, D, E, F, G);
+impl_query_tuple!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    /// Entities with: position + velocity, position only, velocity + name
+    fn world() -> (World, [Entity; 3]) {
+        let mut world = World::new();
+        let moving = world.spawn();
+        world.insert(moving, Position(1));
+        world.insert(moving, Velocity(2));
+        let still = world.spawn();
+        world.insert(still, Position(10));
+        let named = world.spawn();
+        world.insert(named, Velocity(5));
+        world.insert(named, Name("named"));
+        (world, [moving, still, named])
+    }
+
+    #[test]
+    fn query_matches_entities_with_every_component() {
+        let (world, [moving, ..]) = world();
+        let mut matched = Vec::new();
+        world.query::<(&Position, &Velocity)>(|entity, (p, v)| matched.push((entity, p.0, v.0)));
+        assert_eq!(matched, vec![(moving, 1, 2)]);
+    }
+
+    #[test]
+    fn query_writes_through_mutable_components() {
+        let (world, [moving, still, _]) = world();
+        world.query::<(&mut Position, &Velocity)>(|_, (p, v)| p.0 += v.0);
+        assert_eq!(*world.get::<Position>(moving).unwrap(), Position(3));
+        assert_eq!(*world.get::<Position>(still).unwrap(), Position(10));
+    }
+
+    #[test]
+    fn optional_components_match_every_entity() {
+        let (world, [moving, still, _]) = world();
+        let mut matched = Vec::new();
+        world.query::<(&Position, Option<&Velocity>)>(|entity, (_, v)| {
+            matched.push((entity, v.map(|v| v.0)))
+        });
+        assert_eq!(matched, vec![(moving, Some(2)), (still, None)]);
+    }
+
+    #[test]
+    fn query_of_a_never_inserted_type_matches_nothing() {
+        let (world, _) = world();
+        let mut count = 0;
+        world.query::<(&Position, &u64)>(|_, _| count += 1);
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn despawned_entities_drop_out_of_queries() {
+        let (mut world, [moving, still, _]) = world();
+        assert!(world.despawn(moving));
+        assert!(!world.despawn(moving));
+
+        // The slot is reused, but the old entity's components are gone
+        let reused = world.spawn();
+        assert_eq!(reused.index(), moving.index());
+        assert!(world.get::<Position>(reused).is_none());
+
+        let mut matched = Vec::new();
+        world.query::<&Position>(|entity, _| matched.push(entity));
+        assert_eq!(matched, vec![still]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn aliasing_mutable_query_panics() {
+        let (world, _) = world();
+        world.query::<(&mut Position, &mut Position)>(|_, _| {});
+    }
+}
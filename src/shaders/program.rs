@@ -136,6 +136,39 @@ impl Program {
             gl::UniformMatrix4fv(self.get_uniform_location(name), 1, gl::FALSE, mat.as_ptr());
         }
     }
+
+    // Array setters: `name` is the array itself, filled from element 0 in one call
+
+    pub fn set_int_array(&self, name: &str, values: &[i32]) {
+        unsafe {
+            gl::Uniform1iv(
+                self.get_uniform_location(name),
+                values.len() as i32,
+                values.as_ptr(),
+            );
+        }
+    }
+
+    pub fn set_float_array(&self, name: &str, values: &[f32]) {
+        unsafe {
+            gl::Uniform1fv(
+                self.get_uniform_location(name),
+                values.len() as i32,
+                values.as_ptr(),
+            );
+        }
+    }
+
+    pub fn set_mat4_array(&self, name: &str, mats: &[[f32; 16]]) {
+        unsafe {
+            gl::UniformMatrix4fv(
+                self.get_uniform_location(name),
+                mats.len() as i32,
+                gl::FALSE,
+                mats.as_ptr() as *const f32,
+            );
+        }
+    }
 }
 
 impl Drop for Program {
@@ -7,10 +7,23 @@ pub struct Texture {
     pub id: GLuint,
     pub width: u32,
     pub height: u32,
+    /// Whether the image had an alpha channel
+    pub has_alpha: bool,
 }
 
 impl Texture {
     pub fn new(width: u32, height: u32, data: &[u8], format: GLenum) -> Texture {
+        Texture::with_internal_format(width, height, data, format, format)
+    }
+
+    /// Like `new`, but stored as `internal_format` (e.g. `SRGB8` for color data)
+    pub fn with_internal_format(
+        width: u32,
+        height: u32,
+        data: &[u8],
+        internal_format: GLenum,
+        format: GLenum,
+    ) -> Texture {
         let mut id = 0;
         unsafe {
             gl::GenTextures(1, &mut id);
@@ -28,7 +41,7 @@ impl Texture {
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                format as i32,
+                internal_format as i32,
                 width as i32,
                 height as i32,
                 0,
@@ -38,10 +51,25 @@ impl Texture {
             );
         }
 
-        Texture { id, width, height }
+        Texture {
+            id,
+            width,
+            height,
+            has_alpha: format == gl::RGBA,
+        }
     }
 
+    /// Load an image holding linear data (normals, roughness, occlusion)
     pub fn from_file(path: &str) -> Result<Texture, String> {
+        Texture::load(path, false)
+    }
+
+    /// Load a color image; the GPU converts it from sRGB to linear when sampled
+    pub fn from_file_srgb(path: &str) -> Result<Texture, String> {
+        Texture::load(path, true)
+    }
+
+    fn load(path: &str, srgb: bool) -> Result<Texture, String> {
         println!("Loading texture from: {}", path);
         let img = image::open(path).map_err(|e| e.to_string())?;
         let img = img.flipv(); // OpenGL expects (0,0) at bottom-left
@@ -52,13 +80,25 @@ impl Texture {
         let (data, format) = match img.color() {
             image::ColorType::Rgb8 => (img.to_rgb8().into_raw(), gl::RGB),
             image::ColorType::Rgba8 => (img.to_rgba8().into_raw(), gl::RGBA),
+            color if color.has_alpha() => (img.to_rgba8().into_raw(), gl::RGBA),
             _ => {
                 // Default to RGB if possible
                 (img.to_rgb8().into_raw(), gl::RGB)
             }
         };
+        let internal_format = match (srgb, format) {
+            (false, _) => format,
+            (true, gl::RGBA) => gl::SRGB8_ALPHA8,
+            (true, _) => gl::SRGB8,
+        };
 
-        Ok(Texture::new(width, height, &data, format))
+        Ok(Texture::with_internal_format(
+            width,
+            height,
+            &data,
+            internal_format,
+            format,
+        ))
     }
 
     pub fn bind(&self, unit: u32) {
@@ -69,6 +109,7 @@ impl Texture {
     }
 }
 
+/// Color cube map (skybox), stored as sRGB
 #[derive(Debug, Clone)]
 pub struct CubeMap {
     pub id: GLuint,
@@ -86,16 +127,17 @@ impl CubeMap {
                 let width = img.width();
                 let height = img.height();
 
-                let (data, format) = match img.color() {
-                    image::ColorType::Rgb8 => (img.to_rgb8().into_raw(), gl::RGB),
-                    image::ColorType::Rgba8 => (img.to_rgba8().into_raw(), gl::RGBA),
-                    _ => (img.to_rgb8().into_raw(), gl::RGB),
+                let (data, format, internal_format) = match img.color() {
+                    image::ColorType::Rgba8 => {
+                        (img.to_rgba8().into_raw(), gl::RGBA, gl::SRGB8_ALPHA8)
+                    }
+                    _ => (img.to_rgb8().into_raw(), gl::RGB, gl::SRGB8),
                 };
 
                 gl::TexImage2D(
                     gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
                     0,
-                    format as i32,
+                    internal_format as i32,
                     width as i32,
                     height as i32,
                     0,
@@ -174,7 +216,7 @@ impl CubeMap {
                 gl::TexImage2D(
                     gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
                     0,
-                    gl::RGBA as i32,
+                    gl::SRGB8_ALPHA8 as i32,
                     face_width as i32,
                     face_height as i32,
                     0,
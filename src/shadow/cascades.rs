@@ -0,0 +1,208 @@
+//! Shadow Cascades - Splitting the camera frustum into directional shadow map slices
+//!
+//! Each cascade covers a depth range of the view frustum with its own light-space
+//! orthographic projection, so texel density follows the camera instead of a fixed box.
+//! Cascades are fitted to a bounding sphere of their frustum slice (a size that doesn't
+//! change as the camera rotates) and snapped to whole texels (so edges don't shimmer).
+
+use crate::config::rendering as render_cfg;
+use crate::math::frustum;
+use glam::{Mat4, Vec3, Vec4};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CascadeSettings {
+    /// Number of cascades, 1..=`MAX_SHADOW_CASCADES`
+    pub count: usize,
+    /// Split scheme: 0.0 gives equal depth ranges, 1.0 logarithmic ones (each cascade
+    /// covers the same depth ratio), values in between blend the two
+    pub split_lambda: f32,
+    /// Distance from the camera beyond which nothing receives directional shadows
+    pub shadow_distance: f32,
+    /// How far in front of a cascade (towards the light) casters are still captured
+    pub caster_distance: f32,
+    /// Fraction of each cascade's depth range blended into the next one
+    pub blend: f32,
+}
+
+impl Default for CascadeSettings {
+    fn default() -> Self {
+        Self {
+            count: render_cfg::SHADOW_CASCADES,
+            split_lambda: render_cfg::SHADOW_SPLIT_LAMBDA,
+            shadow_distance: render_cfg::SHADOW_DISTANCE,
+            caster_distance: render_cfg::SHADOW_CASTER_DISTANCE,
+            blend: render_cfg::SHADOW_CASCADE_BLEND,
+        }
+    }
+}
+
+/// One slice of the view frustum and the light projection that covers it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cascade {
+    pub light_space_matrix: Mat4,
+    /// View-space depth where this cascade ends
+    pub split_far: f32,
+    /// World-space size of one shadow map texel, for normal-offset biasing
+    pub texel_size: f32,
+}
+
+impl CascadeSettings {
+    /// Far depth of each cascade between the camera's `near` and `far` planes
+    pub fn split_distances(&self, near: f32, far: f32) -> Vec<f32> {
+        let far = far.min(self.shadow_distance).max(near);
+        let count = self.count.clamp(1, render_cfg::MAX_SHADOW_CASCADES);
+        (1..=count)
+            .map(|i| {
+                let p = i as f32 / count as f32;
+                let uniform = near + (far - near) * p;
+                let logarithmic = near * (far / near).powf(p);
+                let lambda = self.split_lambda.clamp(0.0, 1.0);
+                lambda * logarithmic + (1.0 - lambda) * uniform
+            })
+            .collect()
+    }
+
+    /// Fit every cascade to the camera described by `view` and `projection` (an
+    /// OpenGL perspective projection), for a light shining along `light_dir`
+    pub fn fit(
+        &self,
+        light_dir: Vec3,
+        view: &Mat4,
+        projection: &Mat4,
+        resolution: u32,
+    ) -> Vec<Cascade> {
+        let (near, far) = frustum::depth_range(projection);
+
+        // Frustum corners on the near and far planes, in world space
+        let inverse = (*projection * *view).inverse();
+        let corner = |x: f32, y: f32, z: f32| {
+            let p = inverse * Vec4::new(x, y, z, 1.0);
+            p.truncate() / p.w
+        };
+        let rays: Vec<(Vec3, Vec3)> = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+            .iter()
+            .map(|&(x, y)| (corner(x, y, -1.0), corner(x, y, 1.0)))
+            .collect();
+        // Corners are linear in view depth along each edge of the frustum
+        let slice_corners = |depth: f32| {
+            let t = (depth - near) / (far - near);
+            rays.iter()
+                .map(move |&(n, f)| n.lerp(f, t))
+                .collect::<Vec<Vec3>>()
+        };
+
+        let light_dir = light_dir.normalize();
+        let up = if light_dir.cross(Vec3::Y).length_squared() < 1e-6 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+
+        let mut split_near = near;
+        self.split_distances(near, far)
+            .into_iter()
+            .map(|split_far| {
+                let mut corners = slice_corners(split_near);
+                corners.extend(slice_corners(split_far));
+                split_near = split_far;
+
+                let center = corners.iter().copied().sum::<Vec3>() / corners.len() as f32;
+                let radius = corners
+                    .iter()
+                    .map(|c| c.distance(center))
+                    .fold(0.0, f32::max);
+                // Quantize so the projection size stays fixed while the camera moves
+                let radius = (radius * 16.0).ceil() / 16.0;
+
+                let eye = center - light_dir * (radius + self.caster_distance);
+                let light_view = Mat4::look_at_rh(eye, center, up);
+                let mut light_projection = Mat4::orthographic_rh_gl(
+                    -radius,
+                    radius,
+                    -radius,
+                    radius,
+                    0.0,
+                    2.0 * radius + self.caster_distance,
+                );
+
+                // Snap the projection to whole texels
+                let half_resolution = resolution as f32 / 2.0;
+                let origin = (light_projection * light_view).transform_point3(Vec3::ZERO);
+                let texel = origin.truncate() * half_resolution;
+                let offset = (texel.round() - texel) / half_resolution;
+                light_projection.w_axis.x += offset.x;
+                light_projection.w_axis.y += offset.y;
+
+                Cascade {
+                    light_space_matrix: light_projection * light_view,
+                    split_far,
+                    texel_size: 2.0 * radius / resolution as f32,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(count: usize, split_lambda: f32) -> CascadeSettings {
+        CascadeSettings {
+            count,
+            split_lambda,
+            shadow_distance: 100.0,
+            ..CascadeSettings::default()
+        }
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn uniform_splits_are_evenly_spaced() {
+        let splits = settings(4, 0.0).split_distances(1.0, 1000.0);
+        assert_close(&splits, &[25.75, 50.5, 75.25, 100.0]);
+    }
+
+    #[test]
+    fn logarithmic_splits_keep_a_constant_ratio() {
+        let splits = settings(4, 1.0).split_distances(0.1, 1000.0);
+        // (100 / 0.1)^(1/4) ≈ 5.623 per cascade
+        assert_close(&splits, &[0.5623, 3.1623, 17.7828, 100.0]);
+    }
+
+    #[test]
+    fn lambda_blends_the_two_schemes() {
+        let uniform = settings(3, 0.0).split_distances(0.5, 60.0);
+        let logarithmic = settings(3, 1.0).split_distances(0.5, 60.0);
+        let blended = settings(3, 0.25).split_distances(0.5, 60.0);
+        for i in 0..3 {
+            let expected = 0.25 * logarithmic[i] + 0.75 * uniform[i];
+            assert!((blended[i] - expected).abs() < 1e-4);
+        }
+        // Splits grow and end at the camera's far plane when it is nearer than the
+        // shadow distance
+        assert!(blended.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((blended[2] - 60.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn cascade_count_is_clamped() {
+        assert_eq!(settings(0, 0.5).split_distances(0.1, 50.0).len(), 1);
+        assert_eq!(
+            settings(99, 0.5).split_distances(0.1, 50.0).len(),
+            render_cfg::MAX_SHADOW_CASCADES
+        );
+    }
+
+    #[test]
+    fn shadow_distance_inside_the_near_plane_collapses_the_splits() {
+        let splits = settings(2, 0.5).split_distances(200.0, 1000.0);
+        assert_close(&splits, &[200.0, 200.0]);
+    }
+}
@@ -1,16 +1,35 @@
 extern crate gl;
+pub mod cascades;
+pub mod moments;
+pub mod scheduler;
+pub mod spot;
+
 use crate::assets::paths::shaders as shader_paths;
+use crate::config::rendering as render_cfg;
+use crate::config::rendering::texture_units;
+use crate::light::components::ShadowSettings;
+use crate::renderer::target::Viewport;
 use crate::shaders::Shader;
+use cascades::{Cascade, CascadeSettings};
 use gl::types::*;
 use glam::{Mat4, Vec3};
+use moments::{MomentMaps, ShadowMoments};
 use std::ptr;
 
+/// Directional light shadow map with one depth layer per cascade
 pub struct ShadowMap {
     pub fbo: GLuint,
+    /// `TEXTURE_2D_ARRAY` with `MAX_SHADOW_CASCADES` layers
     pub depth_texture: GLuint,
     pub width: u32,
     pub height: u32,
     pub shader: Shader,
+    pub settings: CascadeSettings,
+    /// Filtering of this frame's cascades: the directional light's, without moments when
+    /// none could be made
+    pub shadow: ShadowSettings,
+    /// Blurred moments of the cascades, made on first use
+    moments: Option<MomentMaps>,
 }
 
 impl ShadowMap {
@@ -28,48 +47,51 @@ impl ShadowMap {
             // Create framebuffer
             gl::GenFramebuffers(1, &mut fbo);
 
-            // Create depth texture
+            // Create depth texture array, one layer per cascade
             gl::GenTextures(1, &mut depth_texture);
-            gl::BindTexture(gl::TEXTURE_2D, depth_texture);
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, depth_texture);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
                 0,
                 gl::DEPTH_COMPONENT as i32,
                 width as i32,
                 height as i32,
+                render_cfg::MAX_SHADOW_CASCADES as i32,
                 0,
                 gl::DEPTH_COMPONENT,
                 gl::FLOAT,
                 std::ptr::null(),
             );
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
             gl::TexParameteri(
-                gl::TEXTURE_2D,
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_MIN_FILTER,
+                gl::NEAREST as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_MAG_FILTER,
+                gl::NEAREST as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
                 gl::TEXTURE_WRAP_S,
                 gl::CLAMP_TO_BORDER as i32,
             );
             gl::TexParameteri(
-                gl::TEXTURE_2D,
+                gl::TEXTURE_2D_ARRAY,
                 gl::TEXTURE_WRAP_T,
                 gl::CLAMP_TO_BORDER as i32,
             );
             let border_color: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
             gl::TexParameterfv(
-                gl::TEXTURE_2D,
+                gl::TEXTURE_2D_ARRAY,
                 gl::TEXTURE_BORDER_COLOR,
                 border_color.as_ptr(),
             );
 
-            // Attach depth texture to framebuffer
+            // Layers are attached per cascade in `begin_cascade`
             gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
-            gl::FramebufferTexture2D(
-                gl::FRAMEBUFFER,
-                gl::DEPTH_ATTACHMENT,
-                gl::TEXTURE_2D,
-                depth_texture,
-                0,
-            );
+            gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, depth_texture, 0, 0);
             gl::DrawBuffer(gl::NONE);
             gl::ReadBuffer(gl::NONE);
             gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
@@ -81,27 +103,16 @@ impl ShadowMap {
             width,
             height,
             shader,
+            settings: CascadeSettings::default(),
+            shadow: ShadowSettings::default(),
+            moments: None,
         }
     }
 
-    /// Calculate light space matrix for directional light
-    pub fn light_space_matrix(
-        &self,
-        light_dir: Vec3,
-        scene_center: Vec3,
-        scene_radius: f32,
-    ) -> Mat4 {
-        let light_pos = scene_center - light_dir.normalize() * scene_radius * 2.0;
-        let light_view = Mat4::look_at_rh(light_pos, scene_center, Vec3::Y);
-        let light_projection = Mat4::orthographic_rh_gl(
-            -scene_radius,
-            scene_radius,
-            -scene_radius,
-            scene_radius,
-            0.1,
-            scene_radius * 4.0,
-        );
-        light_projection * light_view
+    /// Fit the cascades to the camera for a light shining along `light_dir`
+    pub fn cascades(&self, light_dir: Vec3, view: &Mat4, projection: &Mat4) -> Vec<Cascade> {
+        self.settings
+            .fit(light_dir, view, projection, self.width.min(self.height))
     }
 
     /// Begin shadow pass - render to depth buffer
@@ -109,40 +120,67 @@ impl ShadowMap {
         unsafe {
             gl::Viewport(0, 0, self.width as i32, self.height as i32);
             gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
-            gl::Clear(gl::DEPTH_BUFFER_BIT);
             gl::Enable(gl::CULL_FACE);
             gl::CullFace(gl::FRONT); // Prevent shadow acne by rendering back faces for shadows
         }
         self.shader.use_program();
     }
 
-    /// End shadow pass - restore default framebuffer
-    pub fn end_pass(&self, screen_width: u32, screen_height: u32) {
+    /// Direct the following depth draws into `cascade`'s layer
+    pub fn begin_cascade(&self, layer: usize, cascade: &Cascade) {
+        unsafe {
+            gl::FramebufferTextureLayer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                self.depth_texture,
+                0,
+                layer as i32,
+            );
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        self.shader.set_mat4(
+            "lightSpaceMatrix",
+            &cascade.light_space_matrix.to_cols_array(),
+        );
+    }
+
+    /// End shadow pass - restore the renderer's output framebuffer
+    pub fn end_pass(&self, output: &Viewport) {
         unsafe {
             gl::Disable(gl::CULL_FACE);
             gl::CullFace(gl::BACK);
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
-            gl::Viewport(0, 0, screen_width as i32, screen_height as i32);
         }
+        output.bind();
     }
 
-    /// Bind shadow map texture for sampling in lit shaders
+    /// Bind the cascade array for sampling in lit shaders
     pub fn bind_shadow_map(&self, unit: u32) {
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0 + unit);
-            gl::BindTexture(gl::TEXTURE_2D, self.depth_texture);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.depth_texture);
         }
     }
 
-    /// Set light space matrix in shader
-    pub fn set_light_space_matrix(&self, matrix: &Mat4) {
-        self.shader
-            .set_mat4("lightSpaceMatrix", &matrix.to_cols_array());
+    /// Turn the first `layers` cascades into moments for `shadow`'s filter
+    pub fn filter_moments(&mut self, pass: &ShadowMoments, layers: usize) -> Result<(), String> {
+        if self.moments.is_none() {
+            let size = self.width.min(self.height) / render_cfg::SHADOW_MOMENTS_DOWNSCALE.max(1);
+            self.moments = Some(MomentMaps::new(size, render_cfg::MAX_SHADOW_CASCADES)?);
+        }
+        let maps = self.moments.as_ref().unwrap();
+        for layer in 0..layers {
+            pass.filter(maps, self.depth_texture, layer, &self.shadow, None);
+        }
+        Ok(())
     }
 
-    /// Set model matrix in depth shader
-    pub fn set_model(&self, model: &Mat4) {
-        self.shader.set_mat4("model", &model.to_cols_array());
+    /// Bind the cascades' moments and set the light's filtering
+    pub fn apply_filtering(&self, shader: &Shader) {
+        if let Some(moments) = &self.moments {
+            moments.bind(texture_units::CASCADE_MOMENTS);
+        }
+        shader.set_int("shadowMoments", texture_units::CASCADE_MOMENTS as i32);
+        self.shadow.apply_to_shader(shader, "dirShadow");
     }
 }
 
@@ -225,44 +263,62 @@ impl PointShadowMap {
         }
     }
 
-    pub fn begin_pass(&self, light_pos: Vec3, far_plane: f32) {
-        let shadow_proj = Mat4::perspective_rh_gl(90.0f32.to_radians(), 1.0, 1.0, far_plane);
-        let shadow_transforms = [
+    /// View-projection of each cube face (+X, -X, +Y, -Y, +Z, -Z) of a light at
+    /// `light_pos` reaching `range`
+    pub fn face_matrices(light_pos: Vec3, range: f32) -> [Mat4; 6] {
+        let near = near_plane(range);
+        let shadow_proj = Mat4::perspective_rh_gl(90.0f32.to_radians(), 1.0, near, range);
+        [
             shadow_proj * Mat4::look_at_rh(light_pos, light_pos + Vec3::X, -Vec3::Y),
             shadow_proj * Mat4::look_at_rh(light_pos, light_pos - Vec3::X, -Vec3::Y),
             shadow_proj * Mat4::look_at_rh(light_pos, light_pos + Vec3::Y, Vec3::Z),
             shadow_proj * Mat4::look_at_rh(light_pos, light_pos - Vec3::Y, -Vec3::Z),
             shadow_proj * Mat4::look_at_rh(light_pos, light_pos + Vec3::Z, -Vec3::Y),
             shadow_proj * Mat4::look_at_rh(light_pos, light_pos - Vec3::Z, -Vec3::Y),
-        ];
+        ]
+    }
+
+    /// Clear the faces in the `faces` bit mask and set up the depth shader to draw only
+    /// into them; the other faces keep their depth
+    pub fn begin_pass(&self, light_pos: Vec3, range: f32, faces: u8) {
+        let shadow_transforms = Self::face_matrices(light_pos, range);
 
         unsafe {
             gl::Viewport(0, 0, self.resolution as i32, self.resolution as i32);
             gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
-            gl::Clear(gl::DEPTH_BUFFER_BIT);
+            // A layered attachment clears every face, so clear them one by one
+            for face in (0..6).filter(|face| faces & (1 << face) != 0) {
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::DEPTH_ATTACHMENT,
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    self.depth_cubemap,
+                    0,
+                );
+                gl::Clear(gl::DEPTH_BUFFER_BIT);
+            }
+            gl::FramebufferTexture(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, self.depth_cubemap, 0);
             gl::Enable(gl::CULL_FACE);
             gl::CullFace(gl::FRONT); // Render back faces to prevent acne
         }
 
         self.shader.use_program();
-        for i in 0..6 {
-            self.shader.set_mat4(
-                &format!("shadowMatrices[{}]", i),
-                &shadow_transforms[i].to_cols_array(),
-            );
-        }
-        self.shader.set_float("far_plane", far_plane);
+        self.shader.set_mat4_array(
+            "shadowMatrices",
+            &shadow_transforms.map(|m| m.to_cols_array()),
+        );
+        self.shader.set_int("faceMask", faces as i32);
+        self.shader.set_float("far_plane", range);
         self.shader
             .set_vec3("lightPos", light_pos.x, light_pos.y, light_pos.z);
     }
 
-    pub fn end_pass(&self, screen_width: u32, screen_height: u32) {
+    pub fn end_pass(&self, output: &Viewport) {
         unsafe {
             gl::Disable(gl::CULL_FACE);
             gl::CullFace(gl::BACK);
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
-            gl::Viewport(0, 0, screen_width as i32, screen_height as i32);
         }
+        output.bind();
     }
 
     pub fn bind_cubemap(&self, unit: u32) {
@@ -290,3 +346,9 @@ impl Drop for ShadowMap {
         }
     }
 }
+
+/// Near plane of a perspective shadow map reaching `range`: casters closer than it are
+/// clipped, so it stays small for short ranges
+fn near_plane(range: f32) -> f32 {
+    (range * 0.05).min(1.0)
+}
@@ -0,0 +1,267 @@
+//! Shadow Moments - Blurred depth moments for variance and exponential shadow maps
+//!
+//! After a shadow map layer is rendered, a fullscreen pass turns its depth into moments at
+//! a lower resolution: depth and depth squared for variance maps, exp(c * depth) for
+//! exponential ones. Perspective depth is linearized first, so the moments are spread
+//! evenly over the light's range. A separable Gaussian blur then softens them into the
+//! matching layer of a float texture array, which the lit shaders filter bilinearly.
+
+use crate::assets::paths::{names, shaders};
+use crate::assets::AssetManager;
+use crate::config::rendering as render_cfg;
+use crate::light::components::{ShadowFilter, ShadowSettings};
+use crate::shaders::Shader;
+use gl::types::*;
+use std::rc::Rc;
+
+/// Widest blur, in taps on each side
+const MAX_BLUR_TAPS: i32 = 8;
+
+/// Filtered moments of the layers of one shadow map, and the scratch targets they are
+/// made in
+pub struct MomentMaps {
+    fbo: GLuint,
+    /// `TEXTURE_2D_ARRAY` (RG32F), one layer per shadow map layer
+    pub texture: GLuint,
+    /// Unblurred and half-blurred moments of the layer being filtered (RG32F)
+    scratch: [GLuint; 2],
+    pub size: u32,
+}
+
+impl MomentMaps {
+    /// Moments of a `size` squared map with `layers` layers
+    pub fn new(size: u32, layers: usize) -> Result<Self, String> {
+        let mut maps = Self {
+            fbo: 0,
+            texture: 0,
+            scratch: [0; 2],
+            size,
+        };
+
+        let status;
+        unsafe {
+            gl::GenTextures(1, &mut maps.texture);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, maps.texture);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                gl::RG32F as i32,
+                size as i32,
+                size as i32,
+                layers as i32,
+                0,
+                gl::RG,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+            set_filtering(gl::TEXTURE_2D_ARRAY);
+
+            for scratch in &mut maps.scratch {
+                gl::GenTextures(1, scratch);
+                gl::BindTexture(gl::TEXTURE_2D, *scratch);
+                gl::TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    gl::RG32F as i32,
+                    size as i32,
+                    size as i32,
+                    0,
+                    gl::RG,
+                    gl::FLOAT,
+                    std::ptr::null(),
+                );
+                set_filtering(gl::TEXTURE_2D);
+            }
+
+            gl::GenFramebuffers(1, &mut maps.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, maps.fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                maps.scratch[0],
+                0,
+            );
+            status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "Shadow moments framebuffer {}x{} is incomplete: 0x{:X}",
+                size, size, status
+            ));
+        }
+        Ok(maps)
+    }
+
+    /// Bind the moments for sampling in lit shaders
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.texture);
+        }
+    }
+}
+
+impl Drop for MomentMaps {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.texture);
+            gl::DeleteTextures(self.scratch.len() as i32, self.scratch.as_ptr());
+        }
+    }
+}
+
+/// Moments and blur passes, shared by every shadow map that keeps moments
+pub struct ShadowMoments {
+    moments_shader: Rc<Shader>,
+    blur_shader: Rc<Shader>,
+    /// Attribute-less VAO for the fullscreen triangle
+    fullscreen_vao: GLuint,
+}
+
+impl ShadowMoments {
+    pub fn new(moments_shader: Rc<Shader>, blur_shader: Rc<Shader>) -> Self {
+        let mut fullscreen_vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut fullscreen_vao);
+        }
+        Self {
+            moments_shader,
+            blur_shader,
+            fullscreen_vao,
+        }
+    }
+
+    /// Load the moments and blur shaders through `assets`
+    pub fn load(assets: &mut AssetManager) -> Self {
+        let moments_shader = assets.load_shader(
+            names::SHADER_SHADOW_MOMENTS,
+            shaders::FULLSCREEN_VERT,
+            shaders::SHADOW_MOMENTS_FRAG,
+        );
+        let blur_shader = assets.load_shader(
+            names::SHADER_SHADOW_BLUR,
+            shaders::FULLSCREEN_VERT,
+            shaders::SHADOW_BLUR_FRAG,
+        );
+        Self::new(moments_shader, blur_shader)
+    }
+
+    /// Turn `layer` of `depth_texture` (a depth `TEXTURE_2D_ARRAY`) into blurred moments in
+    /// the same layer of `maps`, for `settings`' filter and radius. `perspective` holds the
+    /// near and far planes of a perspective map, whose depth is linearized first.
+    /// Leaves the moments framebuffer bound.
+    pub fn filter(
+        &self,
+        maps: &MomentMaps,
+        depth_texture: GLuint,
+        layer: usize,
+        settings: &ShadowSettings,
+        perspective: Option<(f32, f32)>,
+    ) {
+        let downscale = render_cfg::SHADOW_MOMENTS_DOWNSCALE.max(1);
+        let taps = ((settings.radius / downscale as f32).round() as i32).clamp(1, MAX_BLUR_TAPS);
+        let texel = 1.0 / maps.size as f32;
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, maps.fbo);
+            gl::Viewport(0, 0, maps.size as i32, maps.size as i32);
+            gl::BindVertexArray(self.fullscreen_vao);
+        }
+
+        // Moments of the depth, averaged over the texels each moment texel covers
+        attach(maps.scratch[0], None);
+        let shader = &self.moments_shader;
+        shader.use_program();
+        bind_input(gl::TEXTURE_2D_ARRAY, depth_texture);
+        shader.set_int("depthMap", 0);
+        shader.set_int("layer", layer as i32);
+        shader.set_int("downscale", downscale as i32);
+        shader.set_int(
+            "exponential",
+            (settings.filter == ShadowFilter::Exponential) as i32,
+        );
+        let (near, far) = perspective.unwrap_or((0.0, 1.0));
+        shader.set_int("perspective", perspective.is_some() as i32);
+        shader.set_float("nearPlane", near);
+        shader.set_float("farPlane", far);
+        draw();
+
+        // Blur across, then down into the layer
+        let shader = &self.blur_shader;
+        shader.use_program();
+        shader.set_int("momentsMap", 0);
+        shader.set_int("taps", taps);
+        let passes = [
+            (maps.scratch[0], maps.scratch[1], None, (texel, 0.0)),
+            (maps.scratch[1], maps.texture, Some(layer), (0.0, texel)),
+        ];
+        for (source, target, target_layer, (x, y)) in passes {
+            attach(target, target_layer);
+            bind_input(gl::TEXTURE_2D, source);
+            shader.set_vec2("direction", x, y);
+            draw();
+        }
+
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
+impl Drop for ShadowMoments {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.fullscreen_vao);
+        }
+    }
+}
+
+/// Bilinear, clamped and unmipmapped
+unsafe fn set_filtering(target: GLenum) {
+    gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+    gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+    gl::TexParameteri(target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+    gl::TexParameteri(target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+}
+
+/// Render into the 2D `texture`, or into `layer` of the array `texture`
+fn attach(texture: GLuint, layer: Option<usize>) {
+    unsafe {
+        match layer {
+            Some(layer) => gl::FramebufferTextureLayer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                texture,
+                0,
+                layer as i32,
+            ),
+            None => gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture,
+                0,
+            ),
+        }
+    }
+}
+
+/// Bind the texture a pass reads to unit 0
+fn bind_input(kind: GLenum, texture: GLuint) {
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(kind, texture);
+    }
+}
+
+fn draw() {
+    unsafe {
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+    }
+}
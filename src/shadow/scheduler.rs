@@ -0,0 +1,415 @@
+//! Point Shadow Scheduler - Which point light cube map faces to re-render each frame
+//!
+//! The cube maps are a pool of slots handed to the point lights that matter most on
+//! screen, ranked by how much of the view their range sphere covers and how close they are
+//! to the camera. A slot only keeps its light's depth up to date: all six faces are marked
+//! when the light moves, changes range or takes the slot over, and the faces that see a
+//! caster are marked when it moves, appears or disappears within the range. Static slots
+//! are skipped. Marked slots are rendered most urgent first (moving lights before
+//! catch-up, raised every frame they wait) until the per-frame face budget is used up.
+
+use super::PointShadowMap;
+use crate::config::rendering as render_cfg;
+use crate::light::PointLight;
+use crate::math::bounds::{BoundingSphere, Bounds};
+use crate::math::frustum::Frustum;
+use crate::scene::components::WorldBounds;
+use crate::scene::object::Renderable;
+use crate::scene::world::{Entity, World};
+use glam::{Mat4, Vec3};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Every face of a cube map, one bit each in +X, -X, +Y, -Y, +Z, -Z order
+pub const ALL_FACES: u8 = 0b11_1111;
+
+/// Urgency multiplier of slots whose light or casters moved this frame
+const MOTION_BOOST: f32 = 2.0;
+
+/// Faces of one cube map to render this frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaceUpdate {
+    pub slot: usize,
+    /// Index into the scene's point lights
+    pub light: usize,
+    pub position: Vec3,
+    /// Far plane of the cube map (the light's range)
+    pub range: f32,
+    /// Bit mask of the faces, see `ALL_FACES`
+    pub faces: u8,
+}
+
+/// One cube map and what it currently holds
+#[derive(Debug, Clone, Copy, Default)]
+struct ShadowSlot {
+    light: Option<usize>,
+    /// Light position and range the cube map was rendered with
+    position: Vec3,
+    range: f32,
+    /// Faces that no longer match the scene
+    pending: u8,
+    /// Whether all six faces hold the current light's depth
+    ready: bool,
+    /// Frames the pending faces have waited for the budget
+    waiting: u32,
+}
+
+pub struct PointShadowScheduler {
+    /// Cube map faces rendered per frame at most
+    pub face_budget: u32,
+    slots: Vec<ShadowSlot>,
+    /// World bounds of the casters last frame, to find the ones that moved
+    casters: HashMap<Entity, Bounds>,
+}
+
+impl PointShadowScheduler {
+    /// Scheduler for `slots` cube maps
+    pub fn new(slots: usize) -> Self {
+        Self {
+            face_budget: render_cfg::POINT_SHADOW_FACE_BUDGET,
+            slots: vec![ShadowSlot::default(); slots],
+            casters: HashMap::new(),
+        }
+    }
+
+    /// Cube map slot of light `light`, once its cube map is complete, with the position
+    /// and range it was rendered from (the light may have moved since, with the update
+    /// put off by the face budget)
+    pub fn slot_of(&self, light: usize) -> Option<(usize, Vec3, f32)> {
+        let slot = self
+            .slots
+            .iter()
+            .position(|s| s.ready && s.light == Some(light))?;
+        Some((slot, self.slots[slot].position, self.slots[slot].range))
+    }
+
+    /// Hand out the slots, mark what changed since last frame and pick the faces to
+    /// render within the budget. The updates returned are taken as done.
+    pub fn schedule(
+        &mut self,
+        lights: &[PointLight],
+        world: &World,
+        camera_position: Vec3,
+        projection: &Mat4,
+        frustum: &Frustum,
+    ) -> Vec<FaceUpdate> {
+        let importance: Vec<f32> = lights
+            .iter()
+            .map(|light| importance(light, camera_position, projection, frustum))
+            .collect();
+        self.assign(&importance);
+
+        // Lights that moved or changed range need all six faces again
+        let mut moved = vec![false; self.slots.len()];
+        for (slot, moved) in self.slots.iter_mut().zip(moved.iter_mut()) {
+            let Some(light) = slot.light.map(|i| &lights[i]) else {
+                continue;
+            };
+            if light.position != slot.position || light.range() != slot.range {
+                slot.pending = ALL_FACES;
+                *moved = true;
+            }
+        }
+
+        // Casters that moved, came or went, at both their old and new places
+        let mut casters = HashMap::with_capacity(self.casters.len());
+        let mut changed = Vec::new();
+        world.query::<(&Rc<dyn Renderable>, &WorldBounds)>(|entity, (_, bounds)| {
+            let previous = self.casters.remove(&entity);
+            if previous != Some(bounds.0) {
+                changed.push(bounds.0);
+                changed.extend(previous);
+            }
+            casters.insert(entity, bounds.0);
+        });
+        // The ones left over are gone
+        changed.extend(self.casters.values().copied());
+        self.casters = casters;
+
+        for (slot, moved) in self.slots.iter_mut().zip(moved.iter_mut()) {
+            if slot.light.is_none() || slot.pending == ALL_FACES {
+                continue;
+            }
+            let (position, range) = (slot.position, slot.range);
+            let in_range =
+                |b: &&Bounds| b.sphere.center.distance(position) <= range + b.sphere.radius;
+            let faces = face_frustums(position, range);
+            for bounds in changed.iter().filter(in_range) {
+                for (face, frustum) in faces.iter().enumerate() {
+                    if frustum.intersects(bounds) {
+                        slot.pending |= 1 << face;
+                        *moved = true;
+                    }
+                }
+            }
+        }
+
+        // Most urgent first, within the budget
+        let mut order: Vec<(usize, f32)> = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| {
+                let light = slot.light.filter(|_| slot.pending != 0)?;
+                let boost = if moved[i] { MOTION_BOOST } else { 1.0 };
+                Some((i, importance[light] * boost * (1 + slot.waiting) as f32))
+            })
+            .collect();
+        order.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let mut updates = Vec::new();
+        let mut budget = self.face_budget;
+        for (i, _) in order {
+            let slot = &mut self.slots[i];
+            let faces = slot.pending.count_ones();
+            if faces > budget && !updates.is_empty() {
+                slot.waiting += 1;
+                continue;
+            }
+            budget = budget.saturating_sub(faces);
+
+            let light = slot.light.unwrap();
+            slot.position = lights[light].position;
+            slot.range = lights[light].range();
+            updates.push(FaceUpdate {
+                slot: i,
+                light,
+                position: slot.position,
+                range: slot.range,
+                faces: slot.pending,
+            });
+            slot.pending = 0;
+            slot.ready = true;
+            slot.waiting = 0;
+        }
+        updates
+    }
+
+    /// Keep each slot with its light while that light stays visible, and give free or
+    /// clearly less important slots to the most important lights without one
+    fn assign(&mut self, importance: &[f32]) {
+        for slot in &mut self.slots {
+            if slot
+                .light
+                .is_some_and(|i| importance.get(i).is_none_or(|&v| v <= 0.0))
+            {
+                *slot = ShadowSlot::default();
+            }
+        }
+
+        let mut candidates: Vec<usize> = (0..importance.len())
+            .filter(|&i| importance[i] > 0.0 && self.slots.iter().all(|s| s.light != Some(i)))
+            .collect();
+        candidates.sort_by(|&a, &b| importance[b].total_cmp(&importance[a]));
+
+        for light in candidates {
+            let free = self.slots.iter().position(|s| s.light.is_none());
+            let slot = free.or_else(|| {
+                let (weakest, held) = self
+                    .slots
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, s)| Some((i, importance[s.light?])))
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+                let margin = render_cfg::POINT_SHADOW_SWAP_MARGIN;
+                (importance[light] > held * margin).then_some(weakest)
+            });
+            let Some(slot) = slot else {
+                // The remaining candidates are even less important
+                break;
+            };
+            self.slots[slot] = ShadowSlot {
+                light: Some(light),
+                pending: ALL_FACES,
+                ..ShadowSlot::default()
+            };
+        }
+    }
+}
+
+/// How much a light's shadows can show: the fraction of the screen its range sphere
+/// covers (all of it with the camera inside), less the further it is from the camera.
+/// Zero for lights outside the view.
+fn importance(
+    light: &PointLight,
+    camera_position: Vec3,
+    projection: &Mat4,
+    frustum: &Frustum,
+) -> f32 {
+    let range = light.range();
+    let sphere = BoundingSphere {
+        center: light.position,
+        radius: range,
+    };
+    if range <= 0.0 || !frustum.intersects_sphere(&sphere) {
+        return 0.0;
+    }
+
+    let distance = camera_position.distance(light.position);
+    let coverage = if distance <= range {
+        1.0
+    } else {
+        // Ellipse of the projected sphere, over the 2x2 NDC square
+        let (rx, ry) = (
+            range / distance * projection.x_axis.x,
+            range / distance * projection.y_axis.y,
+        );
+        (std::f32::consts::PI * rx * ry / 4.0).min(1.0)
+    };
+    coverage / (1.0 + distance / range)
+}
+
+/// View frustum of each cube map face of a light at `position` reaching `range`
+pub fn face_frustums(position: Vec3, range: f32) -> [Frustum; 6] {
+    PointShadowMap::face_matrices(position, range).map(|m| Frustum::from_matrix(&m))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Slot of each light, `None` for the empty ones
+    fn held(scheduler: &PointShadowScheduler) -> Vec<Option<usize>> {
+        scheduler.slots.iter().map(|s| s.light).collect()
+    }
+
+    #[test]
+    fn free_slots_go_to_the_most_important_lights() {
+        let mut scheduler = PointShadowScheduler::new(2);
+        scheduler.assign(&[0.1, 0.5, 0.0, 0.3]);
+        assert_eq!(held(&scheduler), [Some(1), Some(3)]);
+        assert!(scheduler
+            .slots
+            .iter()
+            .all(|s| s.pending == ALL_FACES && !s.ready));
+    }
+
+    #[test]
+    fn slots_are_taken_over_only_past_the_swap_margin() {
+        let mut scheduler = PointShadowScheduler::new(1);
+        scheduler.assign(&[0.4]);
+        assert_eq!(held(&scheduler), [Some(0)]);
+
+        let held_importance = 0.4 * render_cfg::POINT_SHADOW_SWAP_MARGIN;
+        scheduler.assign(&[0.4, held_importance * 0.9]);
+        assert_eq!(held(&scheduler), [Some(0)]);
+
+        scheduler.assign(&[0.4, held_importance * 1.1]);
+        assert_eq!(held(&scheduler), [Some(1)]);
+        assert_eq!(scheduler.slots[0].pending, ALL_FACES);
+    }
+
+    #[test]
+    fn lights_that_leave_the_view_or_scene_free_their_slots() {
+        let mut scheduler = PointShadowScheduler::new(2);
+        scheduler.assign(&[0.4, 0.2]);
+        scheduler.assign(&[0.0]);
+        assert_eq!(held(&scheduler), [None, None]);
+    }
+
+    /// Camera at the origin looking down -Z, and point lights at `positions`
+    struct View {
+        projection: Mat4,
+        frustum: Frustum,
+        lights: Vec<PointLight>,
+    }
+
+    impl View {
+        fn new(positions: &[Vec3]) -> Self {
+            let projection = Mat4::perspective_rh_gl(60f32.to_radians(), 16.0 / 9.0, 0.1, 100.0);
+            let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+            Self {
+                projection,
+                frustum: Frustum::from_matrix(&(projection * view)),
+                lights: positions
+                    .iter()
+                    .map(|&p| PointLight::simple(p, 0.1, 0.8, 1.0, 32.0))
+                    .collect(),
+            }
+        }
+
+        fn schedule(&self, scheduler: &mut PointShadowScheduler) -> Vec<FaceUpdate> {
+            let world = World::new();
+            scheduler.schedule(
+                &self.lights,
+                &world,
+                Vec3::ZERO,
+                &self.projection,
+                &self.frustum,
+            )
+        }
+    }
+
+    fn lights_of(updates: &[FaceUpdate]) -> Vec<usize> {
+        updates.iter().map(|u| u.light).collect()
+    }
+
+    /// Where the cube map of `light` was rendered from
+    fn origin(scheduler: &PointShadowScheduler, light: usize) -> Option<Vec3> {
+        scheduler.slot_of(light).map(|(_, position, _)| position)
+    }
+
+    #[test]
+    fn the_face_budget_puts_off_the_less_important_lights() {
+        let view = View::new(&[
+            Vec3::new(-3.0, 0.0, -20.0),
+            Vec3::new(0.0, 0.0, -5.0),
+            Vec3::new(3.0, 0.0, -10.0),
+        ]);
+        let mut scheduler = PointShadowScheduler::new(3);
+        scheduler.face_budget = 12;
+
+        // Two full cube maps fit, nearest first; the furthest light waits
+        let updates = view.schedule(&mut scheduler);
+        assert_eq!(lights_of(&updates), [1, 2]);
+        assert!(updates.iter().all(|u| u.faces == ALL_FACES));
+        assert!(scheduler.slot_of(1).is_some() && scheduler.slot_of(2).is_some());
+        assert_eq!(scheduler.slot_of(0), None);
+
+        // Then its turn comes, and nothing is left to do after that
+        assert_eq!(lights_of(&view.schedule(&mut scheduler)), [0]);
+        assert!(scheduler.slot_of(0).is_some());
+        assert!(view.schedule(&mut scheduler).is_empty());
+    }
+
+    #[test]
+    fn the_most_urgent_update_is_made_even_over_budget() {
+        let view = View::new(&[Vec3::new(0.0, 0.0, -5.0), Vec3::new(3.0, 0.0, -10.0)]);
+        let mut scheduler = PointShadowScheduler::new(2);
+        scheduler.face_budget = 4;
+
+        assert_eq!(lights_of(&view.schedule(&mut scheduler)), [0]);
+        assert_eq!(lights_of(&view.schedule(&mut scheduler)), [1]);
+    }
+
+    #[test]
+    fn moving_a_light_rerenders_its_cube_map_from_the_new_position() {
+        let mut view = View::new(&[Vec3::new(0.0, 0.0, -5.0), Vec3::new(3.0, 0.0, -10.0)]);
+        let mut scheduler = PointShadowScheduler::new(2);
+        view.schedule(&mut scheduler);
+
+        let moved = Vec3::new(4.0, 1.0, -10.0);
+        view.lights[1].position = moved;
+        let updates = view.schedule(&mut scheduler);
+        assert_eq!(lights_of(&updates), [1]);
+        assert_eq!((updates[0].faces, updates[0].position), (ALL_FACES, moved));
+        assert_eq!(origin(&scheduler, 1), Some(moved));
+    }
+
+    #[test]
+    fn put_off_slots_keep_the_position_they_were_rendered_from() {
+        let start = [Vec3::new(0.0, 0.0, -5.0), Vec3::new(3.0, 0.0, -10.0)];
+        let mut view = View::new(&start);
+        let mut scheduler = PointShadowScheduler::new(2);
+        scheduler.face_budget = 6;
+        view.schedule(&mut scheduler);
+        view.schedule(&mut scheduler);
+
+        for light in &mut view.lights {
+            light.position.y += 1.0;
+        }
+        assert_eq!(lights_of(&view.schedule(&mut scheduler)), [0]);
+        assert_eq!(origin(&scheduler, 0), Some(view.lights[0].position));
+        assert_eq!(origin(&scheduler, 1), Some(start[1]));
+    }
+}
@@ -0,0 +1,268 @@
+//! Spot Light Shadows - One perspective depth layer per shadow-casting spot light
+//!
+//! Each frame the layers go to the spot lights marked `casts_shadows` whose range reaches
+//! into the view, in scene order. A layer's projection covers the light's outer cone out
+//! to its attenuation range, and is filtered like the directional cascades with the
+//! light's own `ShadowSettings`.
+
+use super::moments::{MomentMaps, ShadowMoments};
+use super::near_plane;
+use crate::assets::paths::shaders as shader_paths;
+use crate::config::rendering as render_cfg;
+use crate::config::rendering::texture_units;
+use crate::light::components::ShadowSettings;
+use crate::light::SpotLight;
+use crate::math::bounds::BoundingSphere;
+use crate::math::frustum::Frustum;
+use crate::renderer::target::Viewport;
+use crate::shaders::Shader;
+use gl::types::*;
+use glam::{Mat4, Vec3};
+
+/// Extra angle around the outer cone, so the filter at its edge stays on the map
+const CONE_MARGIN: f32 = 0.05;
+
+/// Widest projection a layer covers; wider cones are cut off at it
+const MAX_FOV: f32 = 170.0 * std::f32::consts::PI / 180.0;
+
+/// A spot light's layer this frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotShadow {
+    /// Index into the scene's spot lights
+    pub light: usize,
+    pub light_space_matrix: Mat4,
+    /// World size of a shadow map texel one unit in front of the light
+    pub texel_size: f32,
+    /// Far plane of the layer (the light's range)
+    pub range: f32,
+    /// The light's filtering, see `SpotShadowMap::filter_moments`
+    pub shadow: ShadowSettings,
+}
+
+/// Spot light shadow maps, one layer per light given a shadow this frame
+pub struct SpotShadowMap {
+    pub fbo: GLuint,
+    /// `TEXTURE_2D_ARRAY` with `MAX_SPOT_SHADOWS` layers
+    pub depth_texture: GLuint,
+    pub resolution: u32,
+    pub shader: Shader,
+    /// The lights the layers belong to, by layer
+    pub shadows: Vec<SpotShadow>,
+    /// Blurred moments of the layers, made on first use
+    moments: Option<MomentMaps>,
+}
+
+impl SpotShadowMap {
+    pub fn new(resolution: u32) -> Self {
+        let shader = Shader::from_files(
+            shader_paths::SHADOW_DEPTH_VERT,
+            shader_paths::SHADOW_DEPTH_FRAG,
+        )
+        .expect("Failed to create spot shadow depth shader");
+
+        let mut fbo = 0;
+        let mut depth_texture = 0;
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+
+            gl::GenTextures(1, &mut depth_texture);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, depth_texture);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                gl::DEPTH_COMPONENT as i32,
+                resolution as i32,
+                resolution as i32,
+                render_cfg::MAX_SPOT_SHADOWS as i32,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_MIN_FILTER,
+                gl::NEAREST as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_MAG_FILTER,
+                gl::NEAREST as i32,
+            );
+            // Outside the cone counts as lit
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_BORDER as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_BORDER as i32,
+            );
+            let border_color: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+            gl::TexParameterfv(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_BORDER_COLOR,
+                border_color.as_ptr(),
+            );
+
+            // Layers are attached per light in `begin_layer`
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, depth_texture, 0, 0);
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        SpotShadowMap {
+            fbo,
+            depth_texture,
+            resolution,
+            shader,
+            shadows: Vec::new(),
+            moments: None,
+        }
+    }
+
+    /// Give the layers to the shadow-casting spot lights whose range reaches into
+    /// `frustum`, in scene order, and fit their projections
+    pub fn assign(&mut self, lights: &[SpotLight], frustum: &Frustum) {
+        self.shadows.clear();
+        for (index, light) in lights.iter().enumerate() {
+            if self.shadows.len() == render_cfg::MAX_SPOT_SHADOWS {
+                break;
+            }
+            let range = light.range();
+            let sphere = BoundingSphere {
+                center: light.position,
+                radius: range,
+            };
+            if !light.casts_shadows || range <= 0.0 || !frustum.intersects_sphere(&sphere) {
+                continue;
+            }
+            let fov = cone_fov(light);
+            self.shadows.push(SpotShadow {
+                light: index,
+                light_space_matrix: light_space_matrix(light, fov, range),
+                texel_size: 2.0 * (fov * 0.5).tan() / self.resolution as f32,
+                range,
+                shadow: light.shadow,
+            });
+        }
+    }
+
+    /// Layer and shadow of spot light `light`, if it has one this frame
+    pub fn layer_of(&self, light: usize) -> Option<(usize, &SpotShadow)> {
+        self.shadows
+            .iter()
+            .enumerate()
+            .find(|(_, shadow)| shadow.light == light)
+    }
+
+    /// Begin shadow pass - render to depth buffer
+    pub fn begin_pass(&self) {
+        unsafe {
+            gl::Viewport(0, 0, self.resolution as i32, self.resolution as i32);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Enable(gl::CULL_FACE);
+            gl::CullFace(gl::FRONT); // Render back faces to prevent acne
+        }
+        self.shader.use_program();
+    }
+
+    /// Direct the following depth draws into the layer of `self.shadows[layer]`
+    pub fn begin_layer(&self, layer: usize) {
+        unsafe {
+            gl::FramebufferTextureLayer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                self.depth_texture,
+                0,
+                layer as i32,
+            );
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        self.shader.set_mat4(
+            "lightSpaceMatrix",
+            &self.shadows[layer].light_space_matrix.to_cols_array(),
+        );
+    }
+
+    /// End shadow pass - restore the renderer's output framebuffer
+    pub fn end_pass(&self, output: &Viewport) {
+        unsafe {
+            gl::Disable(gl::CULL_FACE);
+            gl::CullFace(gl::BACK);
+        }
+        output.bind();
+    }
+
+    /// Turn `layer` into moments for its light's filter. Lights whose moments can't be
+    /// made should fall back to `ShadowFilter::depth_only`.
+    pub fn filter_moments(&mut self, pass: &ShadowMoments, layer: usize) -> Result<(), String> {
+        if self.moments.is_none() {
+            let size = self.resolution / render_cfg::SHADOW_MOMENTS_DOWNSCALE.max(1);
+            self.moments = Some(MomentMaps::new(size, render_cfg::MAX_SPOT_SHADOWS)?);
+        }
+        let shadow = &self.shadows[layer];
+        let perspective = (near_plane(shadow.range), shadow.range);
+        let maps = self.moments.as_ref().unwrap();
+        pass.filter(
+            maps,
+            self.depth_texture,
+            layer,
+            &shadow.shadow,
+            Some(perspective),
+        );
+        Ok(())
+    }
+
+    /// Bind the layers, their moments and matrices for sampling in lit shaders
+    pub fn apply_to_shader(&self, shader: &Shader) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + texture_units::SPOT_DEPTH);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.depth_texture);
+        }
+        shader.set_int("spotShadowMaps", texture_units::SPOT_DEPTH as i32);
+        if let Some(moments) = &self.moments {
+            moments.bind(texture_units::SPOT_MOMENTS);
+        }
+        shader.set_int("spotShadowMoments", texture_units::SPOT_MOMENTS as i32);
+        let matrices: Vec<[f32; 16]> = self
+            .shadows
+            .iter()
+            .map(|shadow| shadow.light_space_matrix.to_cols_array())
+            .collect();
+        shader.set_mat4_array("spotLightSpaceMatrices", &matrices);
+    }
+}
+
+impl Drop for SpotShadowMap {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.depth_texture);
+        }
+    }
+}
+
+/// Vertical (and horizontal) field of view covering `light`'s outer cone
+fn cone_fov(light: &SpotLight) -> f32 {
+    let half_angle = light.cone.outer_cut_off.clamp(-1.0, 1.0).acos();
+    (2.0 * (half_angle + CONE_MARGIN)).min(MAX_FOV)
+}
+
+/// Perspective view from `light` down its direction, out to `range`
+fn light_space_matrix(light: &SpotLight, fov: f32, range: f32) -> Mat4 {
+    let direction = light.direction.normalize_or(Vec3::NEG_Y);
+    // Any up vector that isn't along the direction
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+    Mat4::perspective_rh_gl(fov, 1.0, near_plane(range), range)
+        * Mat4::look_at_rh(light.position, light.position + direction, up)
+}
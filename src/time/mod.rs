@@ -31,6 +31,16 @@ impl Time {
         }
     }
 
+    /// Step by a fixed amount instead of reading the clock (offscreen rendering)
+    pub fn advance(&mut self, delta_time: f32) {
+        if self.is_paused {
+            self.delta_time = 0.0;
+        } else {
+            self.delta_time = delta_time;
+            self.elapsed_time += delta_time;
+        }
+    }
+
     pub fn toggle_pause(&mut self) {
         self.is_paused = !self.is_paused;
     }
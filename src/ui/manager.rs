@@ -1,4 +1,6 @@
+use crate::scene::handle::ObjectId;
 use crate::scene::manager::Scene;
+use crate::scene::transform::Transform;
 use crate::shaders::Shader;
 use crate::ui::{inspector::Inspector, TextRenderer};
 use std::rc::Rc;
@@ -18,17 +20,19 @@ impl UIManager {
         }
     }
 
-    pub fn render(&self, scene: &Scene, selected_object_id: Option<usize>) {
+    pub fn render(&self, scene: &Scene, selected_object_id: Option<ObjectId>) {
         // Inspector
         if let Some(id) = selected_object_id {
-            if let Some(obj) = scene.objects.iter().find(|o| o.id == id) {
+            if let (Some(name), Some(transform)) =
+                (scene.name_of(id), scene.world.get::<Transform>(id))
+            {
                 self.inspector.draw(
                     &self.text_renderer,
                     &self.ui_rect_shader,
                     1280.0,
                     720.0,
-                    &obj.name,
-                    obj.transform.position,
+                    &name,
+                    transform.position,
                 );
             }
         }
@@ -1,7 +1,7 @@
 use crate::config::window as win_cfg;
 use glfw::{
-    fail_on_errors, Action, Context, Glfw, GlfwReceiver, Key, PWindow, SwapInterval, WindowEvent,
-    WindowMode,
+    fail_on_errors, Action, Context, ContextCreationApi, Glfw, GlfwReceiver, Key, PWindow,
+    SwapInterval, WindowEvent, WindowMode,
 };
 
 pub struct GlWindow {
@@ -13,6 +13,22 @@ pub struct GlWindow {
 /// New GLWindow
 impl GlWindow {
     pub fn new(title: &str, width: u32, height: u32) -> GlWindow {
+        Self::create(title, width, height, true, ContextCreationApi::Native)
+    }
+
+    /// Invisible window whose context is only used to render offscreen.
+    /// `Egl`/`OsMesa` let Mesa create the context (e.g. llvmpipe software rendering).
+    pub fn new_hidden(width: u32, height: u32, context_api: ContextCreationApi) -> GlWindow {
+        Self::create(win_cfg::TITLE, width, height, false, context_api)
+    }
+
+    fn create(
+        title: &str,
+        width: u32,
+        height: u32,
+        visible: bool,
+        context_api: ContextCreationApi,
+    ) -> GlWindow {
         let mut glfw = glfw::init(fail_on_errors!()).unwrap_or_else(|e| {
             panic!("Failed to initialize GLFW: {:?}", e);
         });
@@ -21,10 +37,12 @@ impl GlWindow {
         glfw.window_hint(glfw::WindowHint::OpenGlProfile(
             glfw::OpenGlProfileHint::Compat,
         ));
+        glfw.window_hint(glfw::WindowHint::Visible(visible));
+        glfw.window_hint(glfw::WindowHint::ContextCreationApi(context_api));
 
         let (mut window, events) = glfw
             .create_window(width, height, title, WindowMode::Windowed)
-            .expect("Failed to create GLFW window.");
+            .unwrap_or_else(|| panic!("Failed to create GLFW window ({:?} context).", context_api));
 
         window.make_current();
         window.set_key_polling(true);
@@ -32,8 +50,8 @@ impl GlWindow {
         window.set_mouse_button_polling(true);
         window.set_scroll_polling(true);
 
-        // VSync
-        glfw.set_swap_interval(if win_cfg::VSYNC {
+        // VSync (pointless when nothing is presented)
+        glfw.set_swap_interval(if win_cfg::VSYNC && visible {
             SwapInterval::Sync(1)
         } else {
             SwapInterval::None
@@ -58,6 +76,8 @@ impl GlWindow {
 
         unsafe {
             gl::Enable(gl::DEPTH_TEST);
+            // Filter across cube map faces (blurry environment mips show seams otherwise)
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
             gl::ClearColor(0.2, 0.3, 0.3, 1.0);
 
             let (width, height) = self.window.get_size();